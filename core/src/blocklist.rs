@@ -0,0 +1,79 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! How blocked image hashes are stored: their raw bytes, back to back
+
+use image_hasher::{ImageHash, InvalidBytesError};
+
+/// Bytes per hash side, so each stored hash is this many bytes
+pub const HASH_BYTES: u8 = 8;
+
+pub fn encode<'a>(hashes: impl IntoIterator<Item = &'a ImageHash>) -> Vec<u8> {
+    hashes
+        .into_iter()
+        .flat_map(|x| x.as_bytes().iter().copied())
+        .collect()
+}
+
+/// Any trailing partial hash is ignored
+pub fn decode(raw: &[u8]) -> Result<Vec<ImageHash>, InvalidBytesError> {
+    raw.chunks_exact(HASH_BYTES.into())
+        .map(ImageHash::from_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(bytes: [u8; 8]) -> ImageHash {
+        ImageHash::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn hashes_are_stored_back_to_back() {
+        let hashes = [hash([0, 1, 2, 3, 4, 5, 6, 7]), hash([255; 8])];
+        let encoded = encode(&hashes);
+        assert_eq!(encoded.len(), 16);
+        assert_eq!(&encoded[..8], &[0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(&encoded[8..], &[255; 8]);
+    }
+
+    #[test]
+    fn hashes_round_trip() {
+        let hashes = vec![hash([9, 8, 7, 6, 5, 4, 3, 2]), hash([1; 8]), hash([0; 8])];
+        assert_eq!(decode(&encode(&hashes)).unwrap(), hashes);
+    }
+
+    #[test]
+    fn empty_lists_round_trip() {
+        assert!(encode(&[]).is_empty());
+        assert!(decode(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn partial_hashes_are_ignored() {
+        let mut encoded = encode(&[hash([3; 8])]);
+        encoded.extend([1, 2, 3]);
+        assert_eq!(decode(&encoded).unwrap(), [hash([3; 8])]);
+    }
+
+    #[test]
+    fn order_and_duplicates_are_kept() {
+        let hashes = vec![hash([2; 8]), hash([1; 8]), hash([2; 8])];
+        assert_eq!(decode(&encode(&hashes)).unwrap(), hashes);
+    }
+}
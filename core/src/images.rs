@@ -0,0 +1,233 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Where filtered images come from, and how to fetch them
+
+use std::{borrow::Cow, fmt::Display};
+
+/// The Discord-side types an image can come from
+pub trait ImageSources {
+    type EmojiId: Copy + Display;
+    type Sticker;
+    type Reaction;
+
+    /// CDN URL of the sticker, if it has one
+    fn sticker_url(sticker: &Self::Sticker) -> Option<String>;
+    fn is_lottie(sticker: &Self::Sticker) -> bool;
+    /// The custom emoji reacted with, if it isn't a unicode emoji
+    fn custom_emoji(reaction: &Self::Reaction) -> Option<Self::EmojiId>;
+}
+
+pub enum ResolveUrl<'a, P: ImageSources> {
+    Direct(&'a str),
+    Attachment(&'a str),
+    Embed(&'a str),
+    Emoji(P::EmojiId),
+    Sticker(&'a P::Sticker),
+    Reaction(&'a P::Reaction),
+    Icon(&'a str),
+    Banner(&'a str),
+}
+
+// Derived impls would needlessly require `P: Copy`
+impl<P: ImageSources> Clone for ResolveUrl<'_, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P: ImageSources> Copy for ResolveUrl<'_, P> {}
+
+impl<'a, P: ImageSources> ResolveUrl<'a, P> {
+    /// CDN URL of the image, if it has one
+    pub fn resolve(&self) -> Option<Cow<'a, str>> {
+        match self {
+            Self::Emoji(id) => Some(Cow::Owned(format!(
+                "https://cdn.discordapp.com/emojis/{id}"
+            ))),
+            Self::Sticker(sticker) => P::sticker_url(sticker).map(Cow::Owned),
+            Self::Reaction(reaction) => {
+                P::custom_emoji(reaction).and_then(|id| Self::Emoji(id).resolve())
+            }
+            Self::Direct(text)
+            | Self::Attachment(text)
+            | Self::Embed(text)
+            | Self::Icon(text)
+            | Self::Banner(text) => Some(Cow::Borrowed(text)),
+        }
+    }
+
+    /// Every kind of image, indexed by [`Self::index`]
+    pub const KINDS: [&'static str; 8] = [
+        "link",
+        "attachment",
+        "embed",
+        "emoji",
+        "sticker",
+        "reaction",
+        "server icon",
+        "server banner",
+    ];
+
+    pub const fn index(&self) -> usize {
+        match self {
+            Self::Direct(_) => 0,
+            Self::Attachment(_) => 1,
+            Self::Embed(_) => 2,
+            Self::Emoji(_) => 3,
+            Self::Sticker(_) => 4,
+            Self::Reaction(_) => 5,
+            Self::Icon(_) => 6,
+            Self::Banner(_) => 7,
+        }
+    }
+
+    /// What kind of image this is, for notices
+    pub const fn kind(&self) -> &'static str {
+        Self::KINDS[self.index()]
+    }
+
+    pub fn is_lottie(&self) -> bool {
+        matches!(self, Self::Sticker(x) if P::is_lottie(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Mock {}
+
+    struct Sticker {
+        url: Option<&'static str>,
+        lottie: bool,
+    }
+
+    enum Reaction {
+        Custom(u64),
+        Unicode,
+    }
+
+    impl ImageSources for Mock {
+        type EmojiId = u64;
+        type Sticker = Sticker;
+        type Reaction = Reaction;
+
+        fn sticker_url(sticker: &Sticker) -> Option<String> {
+            sticker.url.map(ToOwned::to_owned)
+        }
+
+        fn is_lottie(sticker: &Sticker) -> bool {
+            sticker.lottie
+        }
+
+        fn custom_emoji(reaction: &Reaction) -> Option<u64> {
+            match reaction {
+                Reaction::Custom(x) => Some(*x),
+                Reaction::Unicode => None,
+            }
+        }
+    }
+
+    type Url<'a> = ResolveUrl<'a, Mock>;
+
+    const STICKER: Sticker = Sticker {
+        url: Some("https://media.discordapp.net/stickers/5.png"),
+        lottie: false,
+    };
+    const LOTTIE: Sticker = Sticker {
+        url: None,
+        lottie: true,
+    };
+
+    fn every_kind() -> [Url<'static>; 8] {
+        [
+            Url::Direct("https://example.com/a.png"),
+            Url::Attachment("https://cdn.discordapp.com/attachments/1/2/a.png"),
+            Url::Embed("https://example.com/b.png"),
+            Url::Emoji(3),
+            Url::Sticker(&STICKER),
+            Url::Reaction(&Reaction::Custom(4)),
+            Url::Icon("https://cdn.discordapp.com/icons/1/a.png"),
+            Url::Banner("https://cdn.discordapp.com/banners/1/a.png"),
+        ]
+    }
+
+    #[test]
+    fn text_sources_resolve_to_themselves() {
+        for text in ["https://example.com/a.png", "not even a url"] {
+            for url in [
+                Url::Direct(text),
+                Url::Attachment(text),
+                Url::Embed(text),
+                Url::Icon(text),
+                Url::Banner(text),
+            ] {
+                assert!(matches!(url.resolve(), Some(Cow::Borrowed(x)) if x == text));
+            }
+        }
+    }
+
+    #[test]
+    fn emoji_resolve_to_cdn() {
+        assert_eq!(
+            Url::Emoji(1_117_940_624_453_140_480).resolve().as_deref(),
+            Some("https://cdn.discordapp.com/emojis/1117940624453140480")
+        );
+    }
+
+    #[test]
+    fn custom_reactions_resolve_as_emoji() {
+        assert_eq!(
+            Url::Reaction(&Reaction::Custom(7)).resolve(),
+            Url::Emoji(7).resolve()
+        );
+    }
+
+    #[test]
+    fn unicode_reactions_have_no_url() {
+        assert_eq!(Url::Reaction(&Reaction::Unicode).resolve(), None);
+    }
+
+    #[test]
+    fn stickers_use_their_url() {
+        assert_eq!(
+            Url::Sticker(&STICKER).resolve().as_deref(),
+            Some("https://media.discordapp.net/stickers/5.png")
+        );
+        assert_eq!(Url::Sticker(&LOTTIE).resolve(), None);
+    }
+
+    #[test]
+    fn only_lottie_stickers_are_lottie() {
+        assert!(Url::Sticker(&LOTTIE).is_lottie());
+        assert!(!Url::Sticker(&STICKER).is_lottie());
+        assert!(!Url::Direct("https://example.com/a.json").is_lottie());
+    }
+
+    #[test]
+    fn every_kind_has_its_own_index() {
+        let indices: Vec<_> = every_kind().iter().map(Url::index).collect();
+        assert_eq!(indices, (0..Url::KINDS.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn kinds_are_named() {
+        let kinds: Vec<_> = every_kind().iter().map(Url::kind).collect();
+        assert_eq!(kinds, Url::KINDS);
+        assert_eq!(Url::Icon("").kind(), "server icon");
+    }
+}
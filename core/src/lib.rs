@@ -0,0 +1,28 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The parts of the bot that don't need Discord: blocklist storage, profanity decisions, trigger
+//! names and image URL resolution
+//!
+//! Anything tied to a Discord library reaches in through small traits, implemented by the bot.
+
+pub mod blocklist;
+pub mod images;
+pub mod profanity;
+pub mod repack;
+pub mod triggers;
+
+pub use repack::ContainBytes;
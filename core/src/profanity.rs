@@ -0,0 +1,212 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Deciding whether text should be filtered, given the server's censor lists
+
+use rustrict::{Censor, Replacements, Trie, Type};
+
+/// Scan `text` with the given lists
+///
+/// rustrict only accepts `'static` lists, so callers are expected to leak them.
+pub fn analyze(text: &str, trie: &'static Trie, replacements: &'static Replacements) -> Type {
+    Censor::new(text.to_lowercase().chars().filter_map(|x|
+        // Convert dashes and newlines to spaces to trigger false positive detection
+        if x == '\n' || x == '-' {Some(' ')}
+        // Remove asterisks to stop self-censor detection for markdown bolding
+        else if x == '*' {None}
+        // Replace regional_indicator characters with their ASCII equivalents
+        else if ('\u{1f1e6}'..='\u{1f1ff}').contains(&x) {Some(char::from(b'a' + (u32::from(x) - 0x1f1e6) as u8))}
+        // Keep other characters unchanged
+        else {Some(x)})
+    )
+    .with_trie(trie)
+    .with_replacements(replacements)
+    .with_ignore_false_positives(false)
+    .analyze()
+}
+
+pub fn is_objectionable(scan_types: Type) -> bool {
+    (scan_types.is(Type::PROFANE) & !scan_types.is(Type::EVASIVE))
+        | (scan_types.is(Type::SEXUAL) & !scan_types.is(Type::EVASIVE))
+        | scan_types.is(Type::PROFANE & Type::MODERATE_OR_HIGHER & Type::EVASIVE)
+        | scan_types.is(Type::PROFANE & Type::MODERATE_OR_HIGHER & Type::EVASIVE)
+}
+
+pub const TYPE_CATEGORIES: [(&str, Type); 6] = [
+    ("profane", Type::PROFANE),
+    ("offensive", Type::OFFENSIVE),
+    ("sexual", Type::SEXUAL),
+    ("mean", Type::MEAN),
+    ("evasive", Type::EVASIVE),
+    ("spam", Type::SPAM),
+];
+pub const TYPE_SEVERITIES: [(&str, Type); 3] = [
+    ("severe", Type::SEVERE),
+    ("moderate", Type::MODERATE),
+    ("mild", Type::MILD),
+];
+
+/// Comma-separated list of the categories in `scan_types`, each with its highest severity
+pub fn type_names(scan_types: Type) -> String {
+    let names = TYPE_CATEGORIES
+        .iter()
+        .filter_map(|(category, category_type)| {
+            TYPE_SEVERITIES
+                .iter()
+                .find(|x| scan_types.is(*category_type & x.1))
+                .map(|(severity, _)| format!("{severity} {category}"))
+        })
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        "none".to_owned()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Highest severity in `scan_types`, for mod-facing messages
+pub fn severity(scan_types: Type) -> &'static str {
+    TYPE_SEVERITIES
+        .iter()
+        .find(|x| scan_types.is(x.1))
+        .map_or("mild", |x| x.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lists(blocked: &[&str], allowed: &[&str]) -> (&'static Trie, &'static Replacements) {
+        let mut trie = Trie::new();
+        for i in allowed {
+            trie.set(i, Type::SAFE);
+        }
+        for i in blocked {
+            trie.set(i, Type::PROFANE & Type::SEVERE);
+        }
+        (
+            Box::leak(Box::new(trie)),
+            Box::leak(Box::new(Replacements::new())),
+        )
+    }
+
+    fn flags(text: &str, blocked: &[&str]) -> bool {
+        let (trie, replacements) = lists(blocked, &[]);
+        is_objectionable(analyze(text, trie, replacements))
+    }
+
+    #[test]
+    fn clean_text_passes() {
+        assert!(!flags("hello there, how are you?", &["frobnicate"]));
+        assert!(!flags("", &["frobnicate"]));
+    }
+
+    #[test]
+    fn blocked_words_are_flagged() {
+        assert!(flags("frobnicate", &["frobnicate"]));
+        assert!(flags("please frobnicate this", &["frobnicate"]));
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        assert!(flags("FrObNiCaTe", &["frobnicate"]));
+    }
+
+    #[test]
+    fn asterisks_are_removed() {
+        assert!(flags("**frobnicate**", &["frobnicate"]));
+        assert!(flags("frob*nicate", &["frobnicate"]));
+    }
+
+    #[test]
+    fn regional_indicators_read_as_letters() {
+        let word: String = "frobnicate"
+            .chars()
+            .map(|x| char::from_u32(x as u32 - 'a' as u32 + 0x1f1e6).unwrap())
+            .collect();
+        assert!(flags(&word, &["frobnicate"]));
+    }
+
+    #[test]
+    fn dashes_and_newlines_split_words() {
+        let (trie, replacements) = lists(&["frobnicate"], &[]);
+        assert_eq!(
+            analyze("frob-nicate", trie, replacements),
+            analyze("frob nicate", trie, replacements)
+        );
+        assert_eq!(
+            analyze("frob\nnicate", trie, replacements),
+            analyze("frob nicate", trie, replacements)
+        );
+    }
+
+    #[test]
+    fn allowed_words_pass() {
+        let (trie, replacements) = lists(&["frob"], &["frobnicate"]);
+        assert!(!is_objectionable(analyze("frobnicate", trie, replacements)));
+    }
+
+    #[test]
+    fn profane_and_sexual_are_objectionable() {
+        assert!(is_objectionable(Type::PROFANE & Type::MILD));
+        assert!(is_objectionable(Type::SEXUAL & Type::SEVERE));
+    }
+
+    #[test]
+    fn other_categories_are_not_objectionable() {
+        assert!(!is_objectionable(Type::NONE));
+        assert!(!is_objectionable(Type::SAFE));
+        assert!(!is_objectionable(Type::MEAN & Type::SEVERE));
+        assert!(!is_objectionable(Type::OFFENSIVE & Type::SEVERE));
+        assert!(!is_objectionable(Type::SPAM & Type::SEVERE));
+    }
+
+    #[test]
+    fn mild_evasion_is_not_objectionable() {
+        assert!(!is_objectionable(
+            (Type::PROFANE & Type::MILD) | (Type::EVASIVE & Type::MILD)
+        ));
+    }
+
+    #[test]
+    fn type_names_use_highest_severity() {
+        let scan_types = (Type::PROFANE & Type::SEVERE)
+            | (Type::PROFANE & Type::MILD)
+            | (Type::SEXUAL & Type::MODERATE);
+        assert_eq!(type_names(scan_types), "severe profane, moderate sexual");
+        assert_eq!(type_names(Type::NONE), "none");
+    }
+
+    #[test]
+    fn type_names_follow_category_order() {
+        let scan_types = (Type::SPAM & Type::MILD) | (Type::MEAN & Type::SEVERE);
+        assert_eq!(type_names(scan_types), "severe mean, mild spam");
+    }
+
+    #[test]
+    fn severity_is_highest_of_any_category() {
+        assert_eq!(severity(Type::SEXUAL & Type::SEVERE), "severe");
+        assert_eq!(
+            severity((Type::PROFANE & Type::MODERATE) | (Type::MEAN & Type::MILD)),
+            "moderate"
+        );
+    }
+
+    #[test]
+    fn severity_defaults_to_mild() {
+        assert_eq!(severity(Type::NONE), "mild");
+    }
+}
@@ -0,0 +1,71 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Bit-for-bit conversion between signed and unsigned integers, for storing snowflakes in
+//! SQLite, which has no unsigned 64-bit integers
+
+pub trait ContainBytes<T> {
+    fn repack(&self) -> T;
+}
+
+impl ContainBytes<i64> for u64 {
+    fn repack(&self) -> i64 {
+        i64::from_ne_bytes(self.to_ne_bytes())
+    }
+}
+
+impl ContainBytes<u64> for i64 {
+    fn repack(&self) -> u64 {
+        u64::from_ne_bytes(self.to_ne_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values_keep_their_value() {
+        for x in [0_u64, 1, 42, i64::MAX as u64] {
+            let packed: i64 = x.repack();
+            assert_eq!(u64::try_from(packed).unwrap(), x);
+        }
+    }
+
+    #[test]
+    fn large_values_wrap_to_negative() {
+        let packed: i64 = u64::MAX.repack();
+        assert_eq!(packed, -1);
+        let packed: i64 = (1_u64 << 63).repack();
+        assert_eq!(packed, i64::MIN);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        for x in [
+            0,
+            1,
+            u64::MAX,
+            u64::MAX - 7,
+            1 << 63,
+            1_117_940_624_453_140_480,
+        ] {
+            let packed: i64 = x.repack();
+            let unpacked: u64 = packed.repack();
+            assert_eq!(unpacked, x);
+        }
+    }
+}
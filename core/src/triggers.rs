@@ -0,0 +1,99 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Finding `!trigger` names in messages
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref TRIGGERS: Regex = Regex::new(r"(?:^|\s)!(\w+)").unwrap();
+}
+
+/// Lowercased names of the triggers used in `content`, in order
+pub fn trigger_names(content: &str) -> impl Iterator<Item = String> + '_ {
+    TRIGGERS
+        .captures_iter(content)
+        .filter_map(|x| x.get(1))
+        .map(|x| x.as_str().to_lowercase())
+}
+
+/// Whether `name` would be matched whole when used as `!name`
+pub fn is_valid_trigger_name(name: &str) -> bool {
+    TRIGGERS
+        .captures(&format!("!{name}"))
+        .and_then(|x| x.get(1))
+        .is_some_and(|x| x.as_str() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(content: &str) -> Vec<String> {
+        trigger_names(content).collect()
+    }
+
+    #[test]
+    fn trigger_at_start_is_found() {
+        assert_eq!(names("!rules"), ["rules"]);
+        assert_eq!(names("!rules please"), ["rules"]);
+    }
+
+    #[test]
+    fn trigger_after_whitespace_is_found() {
+        assert_eq!(names("read the !rules"), ["rules"]);
+        assert_eq!(names("read\nthe\t!rules"), ["rules"]);
+    }
+
+    #[test]
+    fn trigger_inside_word_is_ignored() {
+        assert!(names("hey!rules").is_empty());
+        assert!(names("wow! amazing").is_empty());
+        assert!(names("no triggers here").is_empty());
+    }
+
+    #[test]
+    fn names_are_lowercased() {
+        assert_eq!(names("!RuLeS"), ["rules"]);
+    }
+
+    #[test]
+    fn names_stop_at_punctuation() {
+        assert_eq!(names("see !rules, then !faq."), ["rules", "faq"]);
+    }
+
+    #[test]
+    fn several_triggers_keep_their_order() {
+        assert_eq!(names("!b !a !b"), ["b", "a", "b"]);
+    }
+
+    #[test]
+    fn word_names_are_valid() {
+        assert!(is_valid_trigger_name("rules"));
+        assert!(is_valid_trigger_name("rules_2"));
+        assert!(is_valid_trigger_name("Rules"));
+    }
+
+    #[test]
+    fn names_with_other_characters_are_invalid() {
+        assert!(!is_valid_trigger_name(""));
+        assert!(!is_valid_trigger_name("two words"));
+        assert!(!is_valid_trigger_name("rules!"));
+        assert!(!is_valid_trigger_name("some-rules"));
+        assert!(!is_valid_trigger_name("!rules"));
+    }
+}
@@ -1,6 +1,41 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20230424_115243_entry_modals;
+mod m20230510_183012_ephemeral_responses;
+mod m20230514_201547_helper_role;
+mod m20230517_142236_entry_modal_json;
+mod m20230519_203455_mod_subscriptions;
+mod m20230521_110317_audit_channel;
+mod m20230524_190842_polls;
+mod m20230526_154120_user_preferences;
+mod m20230528_120517_audited_polls;
+mod m20230601_173209_blocked_hashes;
+mod m20230603_141022_appeal_contact;
+mod m20230605_093417_screening_post;
+mod m20230607_181204_entry_modal_updated_at;
+mod m20230609_162745_filter_linked_images;
+mod m20230611_094512_message_limits;
+mod m20230613_201358_questioning_sessions;
+mod m20230615_172841_api_tokens;
+mod m20230617_104553_feature_toggles;
+mod m20230619_213310_screening_submissions;
+mod m20230621_190526_screening_text;
+mod m20230623_141208_welcome_message_template;
+mod m20230625_182734_member_history;
+mod m20230627_203915_mod_roles_and_log_channels;
+mod m20230629_174402_filter_followup_text;
+mod m20230701_152218_questioning_role_snapshots;
+mod m20230703_081547_quiet_hours;
+mod m20230705_193022_guild_webhooks;
+mod m20230707_162841_first_messages;
+mod m20230709_141503_profanity_reviews;
+mod m20230711_120934_allowed_guilds;
+mod m20230713_104215_nsfw_channel_policy;
+mod m20230715_183406_screening_message_id;
+mod m20230717_094126_whitelisted_hashes;
+mod m20230719_101532_blocked_hash_expiry;
+mod m20230721_160248_questioning_bumps;
+mod m20230723_134517_trigger_presets;
 
 pub struct Migrator;
 
@@ -9,6 +44,41 @@ impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
             Box::new(m20230424_115243_entry_modals::Migration),
+            Box::new(m20230510_183012_ephemeral_responses::Migration),
+            Box::new(m20230514_201547_helper_role::Migration),
+            Box::new(m20230517_142236_entry_modal_json::Migration),
+            Box::new(m20230519_203455_mod_subscriptions::Migration),
+            Box::new(m20230521_110317_audit_channel::Migration),
+            Box::new(m20230524_190842_polls::Migration),
+            Box::new(m20230526_154120_user_preferences::Migration),
+            Box::new(m20230528_120517_audited_polls::Migration),
+            Box::new(m20230601_173209_blocked_hashes::Migration),
+            Box::new(m20230603_141022_appeal_contact::Migration),
+            Box::new(m20230605_093417_screening_post::Migration),
+            Box::new(m20230607_181204_entry_modal_updated_at::Migration),
+            Box::new(m20230609_162745_filter_linked_images::Migration),
+            Box::new(m20230611_094512_message_limits::Migration),
+            Box::new(m20230613_201358_questioning_sessions::Migration),
+            Box::new(m20230615_172841_api_tokens::Migration),
+            Box::new(m20230617_104553_feature_toggles::Migration),
+            Box::new(m20230619_213310_screening_submissions::Migration),
+            Box::new(m20230621_190526_screening_text::Migration),
+            Box::new(m20230623_141208_welcome_message_template::Migration),
+            Box::new(m20230625_182734_member_history::Migration),
+            Box::new(m20230627_203915_mod_roles_and_log_channels::Migration),
+            Box::new(m20230629_174402_filter_followup_text::Migration),
+            Box::new(m20230701_152218_questioning_role_snapshots::Migration),
+            Box::new(m20230703_081547_quiet_hours::Migration),
+            Box::new(m20230705_193022_guild_webhooks::Migration),
+            Box::new(m20230707_162841_first_messages::Migration),
+            Box::new(m20230709_141503_profanity_reviews::Migration),
+            Box::new(m20230711_120934_allowed_guilds::Migration),
+            Box::new(m20230713_104215_nsfw_channel_policy::Migration),
+            Box::new(m20230715_183406_screening_message_id::Migration),
+            Box::new(m20230717_094126_whitelisted_hashes::Migration),
+            Box::new(m20230719_101532_blocked_hash_expiry::Migration),
+            Box::new(m20230721_160248_questioning_bumps::Migration),
+            Box::new(m20230723_134517_trigger_presets::Migration),
         ]
     }
 }
@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Polls::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Polls::MessageId)
+                            .big_unsigned()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Polls::ChannelId).big_unsigned().not_null())
+                    .col(ColumnDef::new(Polls::GuildId).big_unsigned())
+                    .col(ColumnDef::new(Polls::Question).text().not_null())
+                    .col(ColumnDef::new(Polls::OptionsJson).text().not_null())
+                    .col(ColumnDef::new(Polls::AutoCloseAt).date_time())
+                    .col(
+                        ColumnDef::new(Polls::Closed)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Polls::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Polls {
+    Table,
+    MessageId,
+    ChannelId,
+    GuildId,
+    Question,
+    OptionsJson,
+    AutoCloseAt,
+    Closed,
+}
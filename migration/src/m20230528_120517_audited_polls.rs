@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only supports one column per ALTER TABLE
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Polls::Table)
+                    .add_column(ColumnDef::new(Polls::AuthorId).big_unsigned())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Polls::Table)
+                    .add_column(
+                        ColumnDef::new(Polls::Audited)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Polls::Table)
+                    .add_column(
+                        ColumnDef::new(Polls::Anonymous)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PollVotes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PollVotes::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PollVotes::PollId).big_unsigned().not_null())
+                    .col(ColumnDef::new(PollVotes::UserId).big_unsigned().not_null())
+                    .col(ColumnDef::new(PollVotes::Option).integer().not_null())
+                    .col(ColumnDef::new(PollVotes::Added).boolean().not_null())
+                    .col(ColumnDef::new(PollVotes::Timestamp).date_time().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PollVotes::Table).to_owned())
+            .await?;
+        for i in [Polls::AuthorId, Polls::Audited, Polls::Anonymous] {
+            manager
+                .alter_table(Table::alter().table(Polls::Table).drop_column(i).to_owned())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Polls {
+    Table,
+    AuthorId,
+    Audited,
+    Anonymous,
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum PollVotes {
+    Table,
+    Id,
+    PollId,
+    UserId,
+    Option,
+    Added,
+    Timestamp,
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BlockedHashes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BlockedHashes::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BlockedHashes::Hash).text().not_null())
+                    .col(ColumnDef::new(BlockedHashes::ExemptChannelsJson).text())
+                    .primary_key(
+                        Index::create()
+                            .col(BlockedHashes::GuildId)
+                            .col(BlockedHashes::Hash),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BlockedHashes::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum BlockedHashes {
+    Table,
+    GuildId,
+    Hash,
+    ExemptChannelsJson,
+}
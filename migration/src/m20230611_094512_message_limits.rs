@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can only add one column per statement
+        for column in [
+            Servers::MaxEmojis,
+            Servers::MaxAttachments,
+            Servers::MaxStickers,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .add_column(ColumnDef::new(column).integer())
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Servers::MaxEmojis,
+            Servers::MaxAttachments,
+            Servers::MaxStickers,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Servers {
+    Table,
+    MaxEmojis,
+    MaxAttachments,
+    MaxStickers,
+}
@@ -0,0 +1,81 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(QuestioningSessions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(QuestioningSessions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::UserId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::ModId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::Reason)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::OpenedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::ChannelId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(QuestioningSessions::Status)
+                            .text()
+                            .not_null()
+                            .default("open"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(QuestioningSessions::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum QuestioningSessions {
+    Table,
+    Id,
+    GuildId,
+    UserId,
+    ModId,
+    Reason,
+    OpenedAt,
+    ChannelId,
+    Status,
+}
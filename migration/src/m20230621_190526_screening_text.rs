@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can only add one column per statement
+        for column in [Servers::ScreeningWelcomeText, Servers::ScreeningFallbackText] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .add_column(ColumnDef::new(column).text())
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Servers::ScreeningWelcomeText, Servers::ScreeningFallbackText] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Servers {
+    Table,
+    ScreeningWelcomeText,
+    ScreeningFallbackText,
+}
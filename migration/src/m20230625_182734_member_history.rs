@@ -0,0 +1,99 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can only add one column per statement
+        for column in [Servers::RestoreOnRejoin, Servers::RestoreAllRoles] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .add_column(ColumnDef::new(column).boolean().not_null().default(false))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Servers::Table)
+                    .add_column(
+                        ColumnDef::new(Servers::RejoinWindowDays)
+                            .integer()
+                            .not_null()
+                            .default(30),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(MemberHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MemberHistory::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MemberHistory::UserId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MemberHistory::RolesJson).text().not_null())
+                    .col(ColumnDef::new(MemberHistory::LeftAt).date_time().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(MemberHistory::GuildId)
+                            .col(MemberHistory::UserId),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MemberHistory::Table).to_owned())
+            .await?;
+        for column in [
+            Servers::RestoreOnRejoin,
+            Servers::RestoreAllRoles,
+            Servers::RejoinWindowDays,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Servers {
+    Table,
+    RestoreOnRejoin,
+    RestoreAllRoles,
+    RejoinWindowDays,
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum MemberHistory {
+    Table,
+    GuildId,
+    UserId,
+    RolesJson,
+    LeftAt,
+}
@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The existing mod_role and mod_channel stay on servers as the primary role and channel,
+        // so these only hold additions and start out empty
+        manager
+            .create_table(
+                Table::create()
+                    .table(GuildModRoles::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GuildModRoles::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(GuildModRoles::RoleId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(GuildModRoles::GuildId)
+                            .col(GuildModRoles::RoleId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(GuildLogChannels::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GuildLogChannels::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(GuildLogChannels::Purpose).text().not_null())
+                    .col(
+                        ColumnDef::new(GuildLogChannels::ChannelId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(GuildLogChannels::GuildId)
+                            .col(GuildLogChannels::Purpose),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GuildLogChannels::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(GuildModRoles::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum GuildModRoles {
+    Table,
+    GuildId,
+    RoleId,
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum GuildLogChannels {
+    Table,
+    GuildId,
+    Purpose,
+    ChannelId,
+}
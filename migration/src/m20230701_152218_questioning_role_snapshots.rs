@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Sessions opened before this keep their snapshot in the intro message's Roles embed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(QuestioningSessions::Table)
+                    .add_column(ColumnDef::new(QuestioningSessions::RolesJson).text())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(QuestioningSessions::Table)
+                    .drop_column(QuestioningSessions::RolesJson)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum QuestioningSessions {
+    Table,
+    RolesJson,
+}
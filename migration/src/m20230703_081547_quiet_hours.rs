@@ -0,0 +1,96 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can only add one column per statement
+        for column in [Servers::QuietHoursStart, Servers::QuietHoursEnd] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .add_column(ColumnDef::new(column).integer())
+                        .to_owned(),
+                )
+                .await?;
+        }
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Servers::Table)
+                    .add_column(ColumnDef::new(Servers::QuietHoursTimezone).text())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(HeldNotices::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(HeldNotices::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(HeldNotices::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(HeldNotices::ChannelId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(HeldNotices::Content).text().not_null())
+                    .col(ColumnDef::new(HeldNotices::HeldAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(HeldNotices::Table).to_owned())
+            .await?;
+        for column in [
+            Servers::QuietHoursStart,
+            Servers::QuietHoursEnd,
+            Servers::QuietHoursTimezone,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Servers::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Servers {
+    Table,
+    QuietHoursStart,
+    QuietHoursEnd,
+    QuietHoursTimezone,
+}
+
+#[derive(Iden)]
+enum HeldNotices {
+    Table,
+    Id,
+    GuildId,
+    ChannelId,
+    Content,
+    HeldAt,
+}
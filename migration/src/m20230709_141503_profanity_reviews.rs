@@ -0,0 +1,121 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Servers::Table)
+                    .add_column(
+                        ColumnDef::new(Servers::ProfanityAction)
+                            .text()
+                            .not_null()
+                            .default("delete"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProfanityReviews::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProfanityReviews::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ProfanityReviews::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProfanityReviews::ChannelId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProfanityReviews::MessageId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProfanityReviews::AuthorId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ProfanityReviews::ReviewChannelId).big_unsigned())
+                    .col(ColumnDef::new(ProfanityReviews::ReviewMessageId).big_unsigned())
+                    .col(ColumnDef::new(ProfanityReviews::Excerpt).text().not_null())
+                    .col(ColumnDef::new(ProfanityReviews::Reason).text().not_null())
+                    .col(ColumnDef::new(ProfanityReviews::Types).text().not_null())
+                    .col(
+                        ColumnDef::new(ProfanityReviews::Edited)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(ProfanityReviews::FlaggedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-profanity_reviews-message_id")
+                    .table(ProfanityReviews::Table)
+                    .col(ProfanityReviews::MessageId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProfanityReviews::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Servers::Table)
+                    .drop_column(Servers::ProfanityAction)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Servers {
+    Table,
+    ProfanityAction,
+}
+
+#[derive(Iden)]
+enum ProfanityReviews {
+    Table,
+    Id,
+    GuildId,
+    ChannelId,
+    MessageId,
+    AuthorId,
+    ReviewChannelId,
+    ReviewMessageId,
+    Excerpt,
+    Reason,
+    Types,
+    Edited,
+    FlaggedAt,
+}
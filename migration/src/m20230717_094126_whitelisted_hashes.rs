@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(WhitelistedHashes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WhitelistedHashes::GuildId)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WhitelistedHashes::Hash).text().not_null())
+                    .col(
+                        ColumnDef::new(WhitelistedHashes::AddedBy)
+                            .big_unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WhitelistedHashes::AddedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(WhitelistedHashes::GuildId)
+                            .col(WhitelistedHashes::Hash),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WhitelistedHashes::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum WhitelistedHashes {
+    Table,
+    GuildId,
+    Hash,
+    AddedBy,
+    AddedAt,
+}
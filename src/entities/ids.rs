@@ -0,0 +1,155 @@
+//! Typed snowflake columns, so a role column can't be read back as a channel.
+//!
+//! SQLite has no unsigned 64-bit integers, so ids are stored bit-for-bit as `i64` (see
+//! [`ContainBytes`]) and converted to and from the matching serenity id type.
+
+use crate::ext::ContainBytes;
+use poise::serenity_prelude as serenity;
+use sea_orm::sea_query::{ArrayType, ColumnType, Nullable, ValueType, ValueTypeErr};
+use sea_orm::{ColIdx, DbErr, QueryResult, TryFromU64, TryGetError, TryGetable, Value};
+use std::fmt;
+
+macro_rules! db_id {
+    ($(#[$meta:meta])* $name:ident => $id:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub struct $name(i64);
+
+        impl From<serenity::$id> for $name {
+            fn from(x: serenity::$id) -> Self {
+                Self(x.0.repack())
+            }
+        }
+
+        impl From<$name> for serenity::$id {
+            fn from(x: $name) -> Self {
+                Self(x.0.repack())
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                serenity::$id::from(*self).fmt(f)
+            }
+        }
+
+        impl From<$name> for Value {
+            fn from(x: $name) -> Self {
+                x.0.into()
+            }
+        }
+
+        impl TryGetable for $name {
+            fn try_get_by<I: ColIdx>(res: &QueryResult, index: I) -> Result<Self, TryGetError> {
+                i64::try_get_by(res, index).map(Self)
+            }
+        }
+
+        impl ValueType for $name {
+            fn try_from(v: Value) -> Result<Self, ValueTypeErr> {
+                <i64 as ValueType>::try_from(v).map(Self)
+            }
+
+            fn type_name() -> String {
+                stringify!($name).to_owned()
+            }
+
+            fn array_type() -> ArrayType {
+                ArrayType::BigInt
+            }
+
+            fn column_type() -> ColumnType {
+                ColumnType::BigInteger
+            }
+        }
+
+        impl Nullable for $name {
+            fn null() -> Value {
+                Value::BigInt(None)
+            }
+        }
+
+        impl TryFromU64 for $name {
+            fn try_from_u64(n: u64) -> Result<Self, DbErr> {
+                Ok(Self(n.repack()))
+            }
+        }
+    };
+}
+
+db_id!(
+    /// A stored [`serenity::GuildId`]
+    DbGuildId => GuildId
+);
+db_id!(
+    /// A stored [`serenity::ChannelId`], including categories and threads
+    DbChannelId => ChannelId
+);
+db_id!(
+    /// A stored [`serenity::RoleId`]
+    DbRoleId => RoleId
+);
+db_id!(
+    /// A stored [`serenity::UserId`]
+    DbUserId => UserId
+);
+db_id!(
+    /// A stored [`serenity::MessageId`]
+    DbMessageId => MessageId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_roundtrip_through_storage() {
+        let channel = serenity::ChannelId(u64::MAX - 7);
+        let stored = DbChannelId::from(channel);
+        assert_eq!(Value::from(stored), Value::BigInt(Some(-8)));
+        assert_eq!(
+            <DbChannelId as ValueType>::try_from(Value::from(stored)).unwrap(),
+            stored
+        );
+        assert_eq!(serenity::ChannelId::from(stored), channel);
+        assert_eq!(stored.to_string(), channel.to_string());
+    }
+
+    /// Whether `$from: Into<$to>`, checked at compile time but reported as a value so that
+    /// missing conversions can be asserted (inherent methods win over the trait fallback
+    /// only when their bounds hold)
+    macro_rules! converts_into {
+        ($from:ty => $to:ty) => {{
+            struct Probe<T>(std::marker::PhantomData<T>);
+            #[allow(dead_code)]
+            trait Fallback {
+                fn converts(&self) -> bool {
+                    false
+                }
+            }
+            impl<T> Fallback for Probe<T> {}
+            impl<T: Into<$to>> Probe<T> {
+                #[allow(dead_code)]
+                fn converts(&self) -> bool {
+                    true
+                }
+            }
+            Probe::<$from>(std::marker::PhantomData).converts()
+        }};
+    }
+
+    #[test]
+    fn role_columns_are_not_channels() {
+        assert!(converts_into!(DbRoleId => serenity::RoleId));
+        assert!(!converts_into!(DbRoleId => serenity::ChannelId));
+        assert!(!converts_into!(DbChannelId => serenity::RoleId));
+        assert!(!converts_into!(DbUserId => serenity::GuildId));
+    }
+
+    #[test]
+    fn raw_integers_are_not_ids() {
+        assert!(converts_into!(serenity::ChannelId => DbChannelId));
+        assert!(!converts_into!(i64 => DbChannelId));
+        assert!(!converts_into!(u64 => DbRoleId));
+    }
+}
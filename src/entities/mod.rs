@@ -2,4 +2,22 @@
 
 pub mod prelude;
 
+pub mod allowed_guilds;
+pub mod api_tokens;
+pub mod blocked_hashes;
+pub mod first_messages;
+pub mod guild_log_channels;
+pub mod guild_mod_roles;
+pub mod held_notices;
+pub mod ids;
+pub mod member_history;
+pub mod mod_subscriptions;
+pub mod poll_votes;
+pub mod polls;
+pub mod profanity_reviews;
+pub mod questioning_sessions;
+pub mod screening_submissions;
 pub mod servers;
+pub mod trigger_presets;
+pub mod user_preferences;
+pub mod whitelisted_hashes;
@@ -1,3 +1,20 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
 
+pub use super::allowed_guilds::Entity as AllowedGuilds;
+pub use super::api_tokens::Entity as ApiTokens;
+pub use super::blocked_hashes::Entity as BlockedHashes;
+pub use super::first_messages::Entity as FirstMessages;
+pub use super::guild_log_channels::Entity as GuildLogChannels;
+pub use super::guild_mod_roles::Entity as GuildModRoles;
+pub use super::held_notices::Entity as HeldNotices;
+pub use super::member_history::Entity as MemberHistory;
+pub use super::mod_subscriptions::Entity as ModSubscriptions;
+pub use super::poll_votes::Entity as PollVotes;
+pub use super::polls::Entity as Polls;
+pub use super::profanity_reviews::Entity as ProfanityReviews;
+pub use super::questioning_sessions::Entity as QuestioningSessions;
+pub use super::screening_submissions::Entity as ScreeningSubmissions;
 pub use super::servers::Entity as Servers;
+pub use super::trigger_presets::Entity as TriggerPresets;
+pub use super::user_preferences::Entity as UserPreferences;
+pub use super::whitelisted_hashes::Entity as WhitelistedHashes;
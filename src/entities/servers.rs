@@ -1,23 +1,97 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
 
+use super::ids::{DbChannelId, DbGuildId, DbMessageId, DbRoleId};
 use sea_orm::entity::prelude::*;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+pub enum ProfanityAction {
+    #[sea_orm(string_value = "delete")]
+    Delete,
+    #[sea_orm(string_value = "review")]
+    Review,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+pub enum NsfwChannelPolicy {
+    #[sea_orm(string_value = "enforce")]
+    Enforce,
+    #[sea_orm(string_value = "skip")]
+    Skip,
+    #[sea_orm(string_value = "separate")]
+    Separate,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "servers")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
-    pub id: i64,
-    pub rules_channel: i64,
-    pub screening_channel: i64,
-    pub questioning_role: i64,
-    pub questioning_category: i64,
-    pub mod_role: i64,
-    pub mod_channel: i64,
-    pub member_role: i64,
-    pub main_channel: i64,
+    pub id: DbGuildId,
+    pub rules_channel: DbChannelId,
+    pub screening_channel: DbChannelId,
+    pub questioning_role: DbRoleId,
+    pub questioning_category: DbChannelId,
+    pub mod_role: DbRoleId,
+    pub mod_channel: DbChannelId,
+    pub member_role: DbRoleId,
+    pub main_channel: DbChannelId,
     pub blocked_images: Option<Vec<u8>>,
     pub triggers: Option<Vec<u8>>,
     pub entry_modal: Option<Vec<u8>>,
+    pub entry_modal_json: Option<String>,
+    #[sea_orm(default_value = true)]
+    pub ephemeral_responses: bool,
+    pub helper_role: Option<DbRoleId>,
+    pub audit_channel: Option<DbChannelId>,
+    pub appeal_contact: Option<String>,
+    pub screening_post: Option<DbChannelId>,
+    pub entry_modal_updated_at: Option<DateTimeUtc>,
+    #[sea_orm(default_value = false)]
+    pub filter_linked_images: bool,
+    pub max_emojis: Option<i32>,
+    pub max_attachments: Option<i32>,
+    pub max_stickers: Option<i32>,
+    #[sea_orm(default_value = true)]
+    pub profanity_filter_enabled: bool,
+    #[sea_orm(default_value = true)]
+    pub image_filter_enabled: bool,
+    #[sea_orm(default_value = true)]
+    pub triggers_enabled: bool,
+    #[sea_orm(default_value = true)]
+    pub screening_enabled: bool,
+    pub screening_daily_limit: Option<i8>,
+    pub screening_welcome_text: Option<String>,
+    pub screening_fallback_text: Option<String>,
+    pub welcome_message_template: Option<String>,
+    #[sea_orm(default_value = false)]
+    pub restore_on_rejoin: bool,
+    #[sea_orm(default_value = false)]
+    pub restore_all_roles: bool,
+    #[sea_orm(default_value = 30)]
+    pub rejoin_window_days: i32,
+    pub filter_followup_text: Option<String>,
+    pub quiet_hours_start: Option<i32>,
+    pub quiet_hours_end: Option<i32>,
+    pub quiet_hours_timezone: Option<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<String>,
+    #[sea_orm(default_value = false)]
+    pub strict_first_messages: bool,
+    #[sea_orm(default_value = 3)]
+    pub first_message_threshold: i32,
+    #[sea_orm(default_value = "delete")]
+    pub profanity_action: ProfanityAction,
+    #[sea_orm(default_value = "enforce")]
+    pub nsfw_channel_policy: NsfwChannelPolicy,
+    pub nsfw_blocked_images: Option<Vec<u8>>,
+    pub screening_message_id: Option<DbMessageId>,
+    #[sea_orm(default_value = true)]
+    pub questioning_bumps_enabled: bool,
+    #[sea_orm(default_value = true)]
+    pub questioning_acks_enabled: bool,
+    #[sea_orm(default_value = 30)]
+    pub questioning_bump_minutes: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
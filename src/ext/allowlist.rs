@@ -0,0 +1,351 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Keep the bot to the federation's own servers, once any are listed
+//!
+//! Servers come from `FEDBOT_GUILD_ALLOWLIST` and the `allowed_guilds` table. While both are
+//! empty, the bot works anywhere it's added.
+
+use super::{t, Context, Error};
+use crate::entities::{prelude::*, *};
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use poise::Event;
+use sea_orm::*;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tracing::{info, instrument};
+
+const ENFORCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Default)]
+struct Lists {
+    /// From the environment, so they can't be removed at runtime
+    fixed: HashSet<serenity::GuildId>,
+    stored: RwLock<HashSet<serenity::GuildId>>,
+}
+
+#[derive(Default, Clone)]
+pub struct Allowlist(Arc<Lists>);
+
+/// Guild IDs in a comma-separated list, skipping anything that isn't one
+fn parse_env(raw: &str) -> HashSet<serenity::GuildId> {
+    raw.split(',')
+        .filter_map(|x| x.trim().parse().ok())
+        .map(serenity::GuildId)
+        .collect()
+}
+
+impl Allowlist {
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, Error> {
+        let stored = AllowedGuilds::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|x| serenity::GuildId::from(x.guild_id))
+            .collect();
+        Ok(Self(Arc::new(Lists {
+            fixed: std::env::var("FEDBOT_GUILD_ALLOWLIST")
+                .map(|x| parse_env(&x))
+                .unwrap_or_default(),
+            stored: RwLock::new(stored),
+        })))
+    }
+
+    /// Whether the bot may work in `guild`, which it can anywhere while nothing is listed
+    pub fn allows(&self, guild: serenity::GuildId) -> bool {
+        let Ok(stored) = self.0.stored.read() else {
+            return true;
+        };
+        (self.0.fixed.is_empty() && stored.is_empty())
+            || self.0.fixed.contains(&guild)
+            || stored.contains(&guild)
+    }
+
+    fn is_fixed(&self, guild: serenity::GuildId) -> bool {
+        self.0.fixed.contains(&guild)
+    }
+
+    fn set(&self, guild: serenity::GuildId, allowed: bool) {
+        if let Ok(mut x) = self.0.stored.write() {
+            if allowed {
+                x.insert(guild);
+            } else {
+                x.remove(&guild);
+            }
+        }
+    }
+
+    /// Every listed guild, and whether it comes from the environment
+    fn entries(&self) -> Vec<(serenity::GuildId, bool)> {
+        let stored = self
+            .0
+            .stored
+            .read()
+            .map(|x| x.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        self.0
+            .fixed
+            .iter()
+            .map(|x| (*x, true))
+            .chain(
+                stored
+                    .into_iter()
+                    .filter(|x| !self.is_fixed(*x))
+                    .map(|x| (x, false)),
+            )
+            .sorted()
+            .collect()
+    }
+}
+
+/// The guild an event happened in, for the events the bot handles
+pub fn event_guild(event: &Event<'_>) -> Option<serenity::GuildId> {
+    match event {
+        Event::Message { new_message } => new_message.guild_id,
+        Event::MessageUpdate { event, .. } => event.guild_id,
+        Event::MessageDelete { guild_id, .. } | Event::MessageDeleteBulk { guild_id, .. } => {
+            *guild_id
+        }
+        Event::GuildStickersUpdate { guild_id, .. }
+        | Event::GuildEmojisUpdate { guild_id, .. }
+        | Event::GuildMemberRemoval { guild_id, .. } => Some(*guild_id),
+        Event::GuildCreate { guild, .. } => Some(guild.id),
+        Event::GuildMemberAddition { new_member } => Some(new_member.guild_id),
+        Event::GuildMemberUpdate { new, .. } => Some(new.guild_id),
+        Event::GuildUpdate {
+            new_but_incomplete, ..
+        } => Some(new_but_incomplete.id),
+        Event::ChannelDelete { channel } => Some(channel.guild_id),
+        Event::ReactionAdd { add_reaction } => add_reaction.guild_id,
+        Event::ReactionRemove { removed_reaction } => removed_reaction.guild_id,
+        Event::InteractionCreate { interaction } => match interaction {
+            serenity::Interaction::ApplicationCommand(x) => x.guild_id,
+            serenity::Interaction::MessageComponent(x) => x.guild_id,
+            serenity::Interaction::Autocomplete(x) => x.guild_id,
+            serenity::Interaction::ModalSubmit(x) => x.guild_id,
+            serenity::Interaction::Ping(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Explain that the bot is private, then leave a server it was just added to
+#[instrument(skip_all, err)]
+pub async fn refuse_guild(
+    ctx: &serenity::Context,
+    guild: &serenity::Guild,
+    bot_id: serenity::UserId,
+) -> Result<(), Error> {
+    // Leaving matters more than the notice arriving
+    if let Ok(channel) = t(super::get_alert_channel(guild, bot_id).await) {
+        _ = t(channel
+            .say(
+                ctx,
+                "This bot is private to its federation of servers, so it's leaving. Contact the bot's owner if you think this is a mistake.",
+            )
+            .await);
+    }
+    guild.id.leave(ctx).await?;
+    info!(
+        "Left guild '{}' ({}), which isn't on the allowlist",
+        guild.name, guild.id
+    );
+    Ok(())
+}
+
+/// Leave servers that are no longer on the allowlist
+pub async fn enforce(ctx: serenity::Context, allowlist: Allowlist) {
+    loop {
+        tokio::time::sleep(ENFORCE_INTERVAL).await;
+        for guild in ctx.cache.guilds() {
+            if !allowlist.allows(guild) && t(guild.leave(&ctx).await).is_ok() {
+                info!(
+                    "Left guild '{}' after it was removed from the allowlist",
+                    guild
+                );
+            }
+        }
+    }
+}
+
+/// How many servers the bot is in that the allowlist would make it leave
+fn unlisted_count(ctx: Context<'_>) -> usize {
+    let allowlist = &ctx.data().allowlist;
+    ctx.serenity_context()
+        .cache
+        .guilds()
+        .into_iter()
+        .filter(|x| !allowlist.allows(*x))
+        .count()
+}
+
+fn parse_guild(raw: &str) -> Option<serenity::GuildId> {
+    raw.trim().parse().ok().map(serenity::GuildId)
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only, subcommands("add", "remove", "list"))]
+pub async fn allowguild(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Let the bot work in a server; once any are listed, it leaves every other server
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "ID of the server to allow"] guild: String,
+) -> Result<(), Error> {
+    let Some(guild) = parse_guild(&guild) else {
+        ctx.send(|f| f.content("That isn't a server ID.").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+    AllowedGuilds::insert(allowed_guilds::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        added_by: ActiveValue::Set(ctx.author().id.into()),
+        added_at: ActiveValue::Set(chrono::Utc::now()),
+    })
+    .on_conflict(
+        sea_query::OnConflict::column(allowed_guilds::Column::GuildId)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(&ctx.data().db)
+    .await?;
+    ctx.data().allowlist.set(guild, true);
+    info!(
+        "User '{}#{}' added guild '{}' to the allowlist",
+        ctx.author().name,
+        ctx.author().discriminator,
+        guild
+    );
+
+    let unlisted = unlisted_count(ctx);
+    ctx.send(|f| {
+        f.content(if unlisted == 0 {
+            format!("Added `{guild}` to the allowlist.")
+        } else {
+            format!("Added `{guild}` to the allowlist. The bot will leave the {unlisted} servers not on it within a minute.")
+        })
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Stop the bot working in a server, leaving it within a minute
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "ID of the server to remove"] guild: String,
+) -> Result<(), Error> {
+    let Some(guild) = parse_guild(&guild) else {
+        ctx.send(|f| f.content("That isn't a server ID.").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+    if ctx.data().allowlist.is_fixed(guild) {
+        ctx.send(|f| {
+            f.content(format!(
+                "`{guild}` is allowed by `FEDBOT_GUILD_ALLOWLIST`, so it can only be removed there."
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+    let removed = AllowedGuilds::delete_by_id(ids::DbGuildId::from(guild))
+        .exec(&ctx.data().db)
+        .await?
+        .rows_affected
+        > 0;
+    ctx.data().allowlist.set(guild, false);
+    if removed {
+        info!(
+            "User '{}#{}' removed guild '{}' from the allowlist",
+            ctx.author().name,
+            ctx.author().discriminator,
+            guild
+        );
+    }
+
+    let content = if !removed {
+        format!("`{guild}` isn't on the allowlist.")
+    } else if ctx.data().allowlist.entries().is_empty() {
+        format!(
+            "Removed `{guild}`. The allowlist is now empty, so the bot works in any server again."
+        )
+    } else {
+        format!("Removed `{guild}`. The bot will leave it within a minute.")
+    };
+    ctx.send(|f| f.content(content).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Show which servers the bot is allowed in
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let entries = ctx.data().allowlist.entries();
+    let content = if entries.is_empty() {
+        "The allowlist is empty, so the bot works in any server.".to_owned()
+    } else {
+        entries
+            .iter()
+            .map(|(guild, fixed)| {
+                let name = guild
+                    .name(ctx)
+                    .map_or_else(|| "*not joined*".to_owned(), |x| format!("**{x}**"));
+                if *fixed {
+                    format!("- {name} (`{guild}`, from `FEDBOT_GUILD_ALLOWLIST`)")
+                } else {
+                    format!("- {name} (`{guild}`)")
+                }
+            })
+            .join("\n")
+    };
+    ctx.send(|f| f.content(content).ephemeral(true)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_lists_skip_junk() {
+        assert_eq!(
+            parse_env(" 123, abc,,456 "),
+            HashSet::from([serenity::GuildId(123), serenity::GuildId(456)])
+        );
+        assert!(parse_env("").is_empty());
+    }
+
+    #[test]
+    fn empty_lists_allow_everything() {
+        let allowlist = Allowlist::default();
+        assert!(allowlist.allows(serenity::GuildId(1)));
+        allowlist.set(serenity::GuildId(2), true);
+        assert!(!allowlist.allows(serenity::GuildId(1)));
+        assert!(allowlist.allows(serenity::GuildId(2)));
+        allowlist.set(serenity::GuildId(2), false);
+        assert!(allowlist.allows(serenity::GuildId(1)));
+    }
+}
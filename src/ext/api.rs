@@ -0,0 +1,371 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Read-only HTTP API letting federated partners look up a server's blocklist and triggers
+
+use super::{Context, Error};
+use crate::entities::{prelude::*, *};
+use base64::Engine;
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use sea_orm::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{error, info, instrument};
+
+const RATE_LIMIT: u32 = 30;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+const TOKEN_BYTES: usize = 32;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    BlockedHashes,
+    Triggers,
+}
+
+/// Split `/v1/guilds/:id/<route>` into the guild and route
+fn parse_route(path: &str) -> Option<(serenity::GuildId, Route)> {
+    let (guild, route) = path.strip_prefix("/v1/guilds/")?.split_once('/')?;
+    let route = match route {
+        "blocked-hashes" => Route::BlockedHashes,
+        "triggers" => Route::Triggers,
+        _ => return None,
+    };
+    Some((serenity::GuildId(guild.parse().ok()?), route))
+}
+
+/// Only token hashes are stored, so a leaked database doesn't leak working tokens
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Requests per token in the current window
+#[derive(Default)]
+struct RateLimiter(HashMap<i32, (Instant, u32)>);
+
+impl RateLimiter {
+    fn allow(&mut self, token: i32, now: Instant) -> bool {
+        let (start, count) = self.0.entry(token).or_insert((now, 0));
+        if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+            (*start, *count) = (now, 0);
+        }
+        *count += 1;
+        *count <= RATE_LIMIT
+    }
+}
+
+struct ApiState {
+    db: DatabaseConnection,
+    limiter: Mutex<RateLimiter>,
+}
+
+#[derive(FromQueryResult)]
+struct PartnerData {
+    blocked_images: Option<Vec<u8>>,
+    triggers: Option<Vec<u8>>,
+}
+
+fn reply(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn reject(status: StatusCode, msg: &str) -> Response<Body> {
+    reply(status, &serde_json::json!({ "error": msg }))
+}
+
+async fn handle(state: &ApiState, req: &Request<Body>) -> Result<Response<Body>, Error> {
+    if req.method() != Method::GET {
+        return Ok(reject(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"));
+    }
+    let Some((guild, route)) = parse_route(req.uri().path()) else {
+        return Ok(reject(StatusCode::NOT_FOUND, "not found"));
+    };
+    let Some(token) = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "))
+    else {
+        return Ok(reject(StatusCode::UNAUTHORIZED, "missing bearer token"));
+    };
+
+    // Looked up on every request so revoked tokens stop working immediately
+    let Some(token) = ApiTokens::find()
+        .filter(api_tokens::Column::TokenHash.eq(hash_token(token)))
+        .one(&state.db)
+        .await?
+    else {
+        return Ok(reject(StatusCode::UNAUTHORIZED, "invalid token"));
+    };
+    if serenity::GuildId::from(token.guild_id) != guild {
+        return Ok(reject(
+            StatusCode::FORBIDDEN,
+            "token is not valid for this server",
+        ));
+    }
+    let allowed = state
+        .limiter
+        .lock()
+        .map_err(|_| super::FedBotError::new("rate limiter lock poisoned"))?
+        .allow(token.id, Instant::now());
+    if !allowed {
+        return Ok(reject(StatusCode::TOO_MANY_REQUESTS, "rate limited"));
+    }
+
+    let Some(data): Option<PartnerData> = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::BlockedImages)
+        .column(servers::Column::Triggers)
+        .into_model()
+        .one(&state.db)
+        .await?
+    else {
+        return Ok(reject(StatusCode::NOT_FOUND, "server has no profile"));
+    };
+
+    Ok(match route {
+        Route::BlockedHashes => {
+            let hashes = match data.blocked_images {
+                Some(x) => super::serialization::decode_blocked_images(&x)?
+                    .iter()
+                    .map(image_hasher::ImageHash::to_base64)
+                    .collect(),
+                None => vec![],
+            };
+            reply(StatusCode::OK, &serde_json::json!({ "hashes": hashes }))
+        }
+        Route::Triggers => {
+            let triggers = match data.triggers {
+                // Mod-only triggers are internal to the server
+                Some(x) => super::serialization::decode_triggers(&x)?
+                    .into_iter()
+                    .filter(|(_, y)| !y.is_mod_only)
+                    .map(|(name, _)| name)
+                    .sorted()
+                    .collect(),
+                None => vec![],
+            };
+            reply(StatusCode::OK, &serde_json::json!({ "triggers": triggers }))
+        }
+    })
+}
+
+async fn respond(state: Arc<ApiState>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    Ok(match handle(&state, &req).await {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Failed to serve API request for '{}': {}", req.uri(), e);
+            reject(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+        }
+    })
+}
+
+/// Serve the partner API on `FEDBOT_API_ADDR`, if it is set
+pub async fn serve(db: DatabaseConnection) {
+    let Ok(addr) = std::env::var("FEDBOT_API_ADDR") else {
+        return;
+    };
+    let addr = match addr.parse::<SocketAddr>() {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid FEDBOT_API_ADDR '{}': {}", addr, e);
+            return;
+        }
+    };
+    let server = match Server::try_bind(&addr) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Failed to bind API server to '{}': {}", addr, e);
+            return;
+        }
+    };
+
+    let state = Arc::new(ApiState {
+        db,
+        limiter: Mutex::new(RateLimiter::default()),
+    });
+    info!("Serving partner API on '{}'", addr);
+    let result = server
+        .serve(make_service_fn(move |_| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| respond(state.clone(), req))) }
+        }))
+        .await;
+    if let Err(e) = result {
+        error!("API server stopped: {}", e);
+    }
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only, subcommands("issue", "revoke"))]
+pub async fn apitoken(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Parse a server ID option, telling the user if it's invalid
+async fn parse_guild(ctx: Context<'_>, guild: &str) -> Result<Option<serenity::GuildId>, Error> {
+    if let Ok(x) = guild.trim().parse() {
+        return Ok(Some(serenity::GuildId(x)));
+    }
+    ctx.send(|f| f.content("Invalid server ID.").ephemeral(true))
+        .await?;
+    Ok(None)
+}
+
+/// Issue a token for reading a server's blocked images and triggers over the partner API
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn issue(
+    ctx: Context<'_>,
+    #[description = "ID of the server the token can read"] guild: String,
+) -> Result<(), Error> {
+    let Some(guild) = parse_guild(ctx, &guild).await? else {
+        return Ok(());
+    };
+    if Servers::find_by_id(guild)
+        .one(&ctx.data().db)
+        .await?
+        .is_none()
+    {
+        ctx.send(|f| {
+            f.content("That server doesn't have a profile.")
+                .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let token = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(rand::thread_rng().gen::<[u8; TOKEN_BYTES]>());
+    ApiTokens::insert(api_tokens::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        token_hash: ActiveValue::Set(hash_token(&token)),
+        issued_by: ActiveValue::Set(ctx.author().id.into()),
+        issued_at: ActiveValue::Set(chrono::Utc::now()),
+        ..Default::default()
+    })
+    .exec(&ctx.data().db)
+    .await?;
+    info!(
+        "User '{}#{}' issued an API token for guild '{}'",
+        ctx.author().name,
+        ctx.author().discriminator,
+        guild
+    );
+
+    // The token can't be recovered later, so it is only ever shown here
+    ctx.send(|f| {
+        f.content(format!(
+            "API token for server `{guild}` (this won't be shown again):\n`{token}`"
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Revoke every partner API token for a server
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn revoke(
+    ctx: Context<'_>,
+    #[description = "ID of the server to revoke tokens for"] guild: String,
+) -> Result<(), Error> {
+    let Some(guild) = parse_guild(ctx, &guild).await? else {
+        return Ok(());
+    };
+    let revoked = ApiTokens::delete_many()
+        .filter(api_tokens::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .exec(&ctx.data().db)
+        .await?
+        .rows_affected;
+    info!(
+        "User '{}#{}' revoked {} API token(s) for guild '{}'",
+        ctx.author().name,
+        ctx.author().discriminator,
+        revoked,
+        guild
+    );
+
+    ctx.send(|f| {
+        f.content(format!(
+            "Revoked {revoked} API token(s) for server `{guild}`."
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_are_parsed() {
+        assert_eq!(
+            parse_route("/v1/guilds/123/blocked-hashes"),
+            Some((serenity::GuildId(123), Route::BlockedHashes))
+        );
+        assert_eq!(
+            parse_route("/v1/guilds/123/triggers"),
+            Some((serenity::GuildId(123), Route::Triggers))
+        );
+        assert_eq!(parse_route("/v1/guilds/abc/triggers"), None);
+        assert_eq!(parse_route("/v1/guilds/123/members"), None);
+        assert_eq!(parse_route("/v1/guilds/123"), None);
+    }
+
+    #[test]
+    fn tokens_are_hashed() {
+        let hash = hash_token("token");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_token("token"));
+        assert_ne!(hash, hash_token("other"));
+    }
+
+    #[test]
+    fn rate_limit_is_per_token_and_window() {
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        for _ in 0..RATE_LIMIT {
+            assert!(limiter.allow(1, now));
+        }
+        assert!(!limiter.allow(1, now));
+        assert!(limiter.allow(2, now));
+        assert!(limiter.allow(1, now + RATE_LIMIT_WINDOW));
+    }
+}
@@ -14,25 +14,22 @@
    limitations under the License.
 */
 
-use super::{ApplicationContext, ContainBytes, Context, Error};
+use super::{ApplicationContext, Context, Error, PermissionTier};
 use crate::{
-    check_mod_role,
-    entities::{prelude::*, *},
+    check_tier,
+    entities::{ids::DbRoleId, prelude::*},
+    require_profile,
 };
 use base64::{engine::general_purpose, Engine as _};
-use chrono::{
-    offset::Utc, DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
-    TimeZone, Timelike,
-};
-use chrono_tz::TZ_VARIANTS;
+use chrono::{offset::Utc, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
 use itertools::Itertools;
 use poise::serenity_prelude as serenity;
 use poise::Modal;
 use rand::Rng;
-use sea_orm::*;
+use sea_orm::EntityTrait;
 use serenity::model::application::oauth::Scope;
 use serenity::Mentionable;
-use std::{cmp::Ordering, default::Default, fmt::Display};
+use std::{borrow::Cow, default::Default, fmt::Display};
 use tracing::instrument;
 
 #[derive(Debug, Clone, Copy)]
@@ -73,60 +70,45 @@ impl<const SIZE: usize> Display for MineSweeper<SIZE> {
 }
 
 impl<const SIZE: usize> MineSweeper<SIZE> {
-    fn _get_coords(selected: usize) -> (usize, usize) {
-        let col = selected % SIZE;
-        let row = ((SIZE - col + selected) / SIZE) - 1;
-        (row, col)
+    /// (row, col) of the `selected`th square in row-major order
+    const fn get_coords(selected: usize) -> (usize, usize) {
+        (selected / SIZE, selected % SIZE)
+    }
+
+    /// (row, col) of every square adjacent to (`row`, `col`) that is on the board
+    fn neighbours(row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> {
+        (row.saturating_sub(1)..=(row + 1).min(SIZE - 1))
+            .cartesian_product(col.saturating_sub(1)..=(col + 1).min(SIZE - 1))
+            .filter(move |x| *x != (row, col))
     }
 
     fn new(mines: usize) -> Option<Self> {
+        Self::generate(mines, &mut rand::thread_rng())
+    }
+
+    /// Place `mines` mines using `rng` and number the clear squares, or `None` if they don't
+    /// leave at least one clear square to start from
+    fn generate(mines: usize, rng: &mut impl Rng) -> Option<Self> {
         let squares = SIZE * SIZE;
-        if mines > squares {
+        if mines >= squares {
             return None;
         }
 
-        let mut rng = rand::thread_rng();
         let mut sweeper = Self([[SweeperSquare::default(); SIZE]; SIZE]);
         for _ in 0..mines {
             let mut selected = rng.gen_range(0..squares);
-            let (mut row, mut col) = Self::_get_coords(selected);
+            let (mut row, mut col) = Self::get_coords(selected);
 
             while matches!(sweeper.0[row][col], SweeperSquare::Mine) {
                 selected = (selected + 1) % squares;
-                (row, col) = Self::_get_coords(selected);
+                (row, col) = Self::get_coords(selected);
             }
 
             sweeper.0[row][col] = SweeperSquare::Mine;
 
-            for i in [
-                if col > 0 { Some((col - 1, row)) } else { None },
-                if col > 0 {
-                    Some((col - 1, row + 1))
-                } else {
-                    None
-                },
-                Some((col, row + 1)),
-                Some((col + 1, row + 1)),
-                Some((col + 1, row)),
-                if row > 0 {
-                    Some((col + 1, row - 1))
-                } else {
-                    None
-                },
-                if row > 0 { Some((col, row - 1)) } else { None },
-                if row > 0 && col > 0 {
-                    Some((col - 1, row - 1))
-                } else {
-                    None
-                },
-            ]
-            .into_iter()
-            .flatten()
-            {
-                if (i.0 < SIZE) && (i.1 < SIZE) {
-                    if let SweeperSquare::Clear(x) = &mut sweeper.0[i.1][i.0] {
-                        *x += 1;
-                    }
+            for (i, j) in Self::neighbours(row, col) {
+                if let SweeperSquare::Clear(x) = &mut sweeper.0[i][j] {
+                    *x += 1;
                 }
             }
         }
@@ -149,13 +131,36 @@ impl MineSweeperSize {
     const fn val(self) -> usize {
         self as usize
     }
-}
 
-#[derive(FromQueryResult)]
-struct MoveMessageServerData {
-    mod_role: i64,
+    const fn squares(self) -> usize {
+        self.val() * self.val()
+    }
+
+    /// Roughly 15% of the board, which is about beginner difficulty
+    const fn default_mines(self) -> usize {
+        (self.squares() * 15 + 50) / 100
+    }
+
+    /// Every mine but one, so there is always a safe square
+    const fn max_mines(self) -> usize {
+        self.squares() - 1
+    }
+
+    /// How many mines to place for `requested`, clamping up to twice the maximum down when
+    /// `force` is set, or `None` if it is out of range
+    fn resolve_mines(self, requested: Option<usize>, force: bool) -> Option<usize> {
+        let max = self.max_mines();
+        match requested {
+            None => Some(self.default_mines()),
+            Some(x) if (MIN_MINES..=max).contains(&x) => Some(x),
+            Some(x) if force && x > max && x <= max * 2 => Some(max),
+            Some(_) => None,
+        }
+    }
 }
 
+const MIN_MINES: usize = 1;
+
 #[derive(Modal)]
 #[name = "Move to channel"]
 struct MoveMessageModal {
@@ -164,15 +169,8 @@ struct MoveMessageModal {
     channel: String,
 }
 
-/// Play a fun minesweeper game
-#[instrument(skip_all, err)]
-#[poise::command(slash_command)]
-pub async fn minesweeper(
-    ctx: Context<'_>,
-    size: MineSweeperSize,
-    mines: usize,
-) -> Result<(), Error> {
-    if let Some(text) = match size {
+fn generate_board(size: MineSweeperSize, mines: usize) -> Option<String> {
+    match size {
         MineSweeperSize::Small => {
             MineSweeper::<{ MineSweeperSize::Small.val() }>::new(mines).map(|x| x.to_string())
         }
@@ -182,20 +180,70 @@ pub async fn minesweeper(
         MineSweeperSize::Large => {
             MineSweeper::<{ MineSweeperSize::Large.val() }>::new(mines).map(|x| x.to_string())
         }
-    } {
-        ctx.send(|f| f.content(text)).await?;
-    } else {
+    }
+}
+
+const PLAY_AGAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Play a fun minesweeper game
+#[instrument(skip_all, err)]
+#[poise::command(slash_command)]
+pub async fn minesweeper(
+    ctx: Context<'_>,
+    size: MineSweeperSize,
+    #[description = "Defaults to about 15% of the squares"]
+    #[min = 1]
+    mines: Option<usize>,
+    #[description = "Use the most mines that fit instead of refusing, up to twice that many"]
+    force: Option<bool>,
+) -> Result<(), Error> {
+    let Some(text) = size
+        .resolve_mines(mines, force.unwrap_or(false))
+        .and_then(|x| generate_board(size, x))
+    else {
         ctx.send(|f| {
-            f.ephemeral(ctx.data().is_ephemeral)
-                .content("Too many mines!")
+            f.ephemeral(true).content(format!(
+                "A {0}x{0} board takes between {1} and {2} mines (default {3}).",
+                size.val(),
+                MIN_MINES,
+                size.max_mines(),
+                size.default_mines()
+            ))
+        })
+        .await?;
+        return Ok(());
+    };
+
+    let board = ctx
+        .send(|f| {
+            f.content(text).components(|f| {
+                f.create_action_row(|f| {
+                    f.create_button(|f| f.custom_id("playAgain").label("Play Again"))
+                })
+            })
         })
         .await?;
+
+    while let Some(response) = board
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(PLAY_AGAIN_TIMEOUT)
+        .await
+    {
+        response.defer(ctx).await?;
+        if let Some(text) = size
+            .resolve_mines(mines, force.unwrap_or(false))
+            .and_then(|x| generate_board(size, x))
+        {
+            board.edit(ctx, |f| f.content(text)).await?;
+        }
     }
+    board.edit(ctx, |f| f.components(|f| f)).await?;
     Ok(())
 }
 
-const MAX_BULK_DELETE: usize = 100;
-
 /// Purge all messages up to and including this one
 #[instrument(skip_all, err)]
 #[poise::command(guild_only, context_menu_command = "Purge To")]
@@ -204,84 +252,59 @@ pub async fn purgeto(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Err
         .guild_id()
         .ok_or(super::FedBotError::new("command must be used in guild"))?;
 
-    let server_data: MoveMessageServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (mod_role,) = (serenity::RoleId(server_data.mod_role.repack()),);
-
-    check_mod_role!(ctx, guild, mod_role);
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
 
-    let mut msg_generator = msg
+    let mut targets = msg
         .channel_id
         .messages(ctx, |f| f.after(msg.id))
         .await?
         .into_iter()
-        .map(|x| x.id)
-        .array_chunks::<MAX_BULK_DELETE>();
-
-    for i in msg_generator.by_ref() {
-        msg.channel_id.delete_messages(ctx, i).await?;
-    }
-    if let Some(x) = msg_generator.into_remainder() {
-        let remainder = x.collect::<Vec<_>>();
-        match remainder.len().cmp(&1) {
-            Ordering::Equal => {
-                msg.channel_id.delete_message(ctx, &remainder[0]).await?;
-            }
-            Ordering::Greater => {
-                msg.channel_id.delete_messages(ctx, remainder).await?;
-            }
-            Ordering::Less => (),
-        }
-    }
-
-    msg.channel_id.delete_message(ctx, msg.id).await?; // Up to *and including*
+        .map(|x| (x.id, x.timestamp))
+        .collect::<Vec<_>>();
+    targets.push((msg.id, msg.timestamp)); // Up to *and including*
+    let summary = super::bulk_delete(ctx, msg.channel_id, targets).await;
 
     ctx.send(|f| {
-        f.content("Purged messages.")
-            .ephemeral(ctx.data().is_ephemeral)
+        f.content(format!("{summary}."))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
     Ok(())
 }
 
-#[allow(clippy::unused_async)]
-pub async fn tz_autocomplete<'a>(
-    _ctx: super::Context<'a>,
-    partial: &'a str,
-) -> impl Iterator<Item = poise::AutocompleteChoice<i32>> + 'a {
-    let partial_matcher = partial.to_lowercase();
-    let now = Utc::now().naive_utc();
-    let mut all_tzs = TZ_VARIANTS
+const DATETIME_FORMATS: [&str; 5] = [
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%m/%d/%Y %I:%M%p",
+    "%m/%d/%Y %I:%M %p",
+];
+const TIME_FORMATS: [&str; 4] = ["%H:%M", "%H:%M:%S", "%I:%M%p", "%I:%M %p"];
+const DATETIME_EXAMPLES: &str = "`2024-06-01 18:30`, `6/1/2024 6:30pm` or `18:30`";
+
+const TIMESTAMP_STYLES: [(&str, &str); 7] = [
+    ("t", "Short time"),
+    ("T", "Long time"),
+    ("d", "Short date"),
+    ("D", "Long date"),
+    ("f", "Short date/time"),
+    ("F", "Long date/time"),
+    ("R", "Relative"),
+];
+
+/// Parse a user-entered date and time, using `today` for the date if only a time is given
+fn parse_datetime(input: &str, today: NaiveDate) -> Option<NaiveDateTime> {
+    let input = input.trim().to_uppercase();
+    DATETIME_FORMATS
         .iter()
-        .map(|x| poise::AutocompleteChoice {
-            name: x.name().to_owned().replace('_', " "),
-            value: x.offset_from_utc_datetime(&now).fix().local_minus_utc(),
+        .find_map(|x| NaiveDateTime::parse_from_str(&input, x).ok())
+        .or_else(|| {
+            TIME_FORMATS
+                .iter()
+                .find_map(|x| NaiveTime::parse_from_str(&input, x).ok())
+                .map(|x| today.and_time(x))
         })
-        .filter_map(|x| {
-            let lower_name = x.name.to_lowercase();
-            if lower_name.contains(&partial_matcher) {
-                Some((x, lower_name))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
-    if !partial_matcher.is_empty() {
-        all_tzs.sort_by_key(|x| {
-            if x.1 == partial_matcher {
-                0
-            } else {
-                x.1.find(&partial_matcher).unwrap_or(usize::MAX)
-            }
-        });
-    }
-    all_tzs.into_iter().map(|x| x.0).take(25)
 }
 
 /// Generate a Discord timestamp object
@@ -290,45 +313,123 @@ pub async fn tz_autocomplete<'a>(
 #[allow(clippy::too_many_arguments)]
 pub async fn timestamp(
     ctx: super::Context<'_>,
-    #[autocomplete = "tz_autocomplete"] tz: i32,
-    hour: u32,
-    minute: u32,
+    #[autocomplete = "super::timezones::tz_autocomplete"]
+    #[description = "Defaults to the timezone saved with /timezone set"]
+    tz: Option<String>,
+    #[description = "Date and time, like 2024-06-01 18:30, 6/1/2024 6:30pm or 18:30"]
+    datetime: Option<String>,
+    hour: Option<u32>,
+    minute: Option<u32>,
     second: Option<u32>,
     year: Option<i32>,
     month: Option<u32>,
     day: Option<u32>,
 ) -> Result<(), super::Error> {
-    let offset = FixedOffset::east_opt(tz).ok_or(super::FedBotError::new("unknown tz offset"))?;
-    let now = Utc::now().with_timezone(&offset);
-    let instant = NaiveDateTime::new(
-        NaiveDate::from_ymd_opt(
-            year.unwrap_or(now.year()),
-            month.unwrap_or(now.month()),
-            day.unwrap_or(now.day()),
-        )
-        .ok_or(super::FedBotError::new("unknown y/m/d"))?,
-        NaiveTime::from_hms_opt(hour, minute, second.unwrap_or(now.second()))
-            .ok_or(super::FedBotError::new("unknown h/m/s"))?,
-    );
-    let timestamp = DateTime::<FixedOffset>::from_local(instant, offset).timestamp();
-
-    let mut format_code = None;
-    if year.is_none() && month.is_none() && day.is_none() {
-        if second.is_none() {
-            format_code = Some("t");
-        } else {
-            format_code = Some("T");
+    let tz = match tz {
+        Some(x) => {
+            let Some(tz) = super::timezones::parse_tz(&x) else {
+                ctx.send(|f| {
+                    f.content(format!(
+                        "Unknown timezone `{x}`. Pick one of the suggested timezones."
+                    ))
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+                })
+                .await?;
+                return Ok(());
+            };
+            tz
         }
-    }
+        None => {
+            let Some(tz) = super::timezones::user_timezone(ctx).await? else {
+                let set_command = super::mention_command(ctx, "timezone set").await?;
+                ctx.send(|f| {
+                    f.content(format!(
+                        "Specify a `tz`, or save a default timezone with {set_command}."
+                    ))
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+                })
+                .await?;
+                return Ok(());
+            };
+            tz
+        }
+    };
+    let now = Utc::now().with_timezone(&tz);
+    let has_fields = hour.is_some()
+        || minute.is_some()
+        || second.is_some()
+        || year.is_some()
+        || month.is_some()
+        || day.is_some();
+
+    let instant = match (datetime, hour, minute) {
+        (Some(_), ..) if has_fields => {
+            ctx.send(|f| {
+                f.content("Use either `datetime` or the individual date and time fields, not both.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        }
+        (Some(x), ..) => {
+            let Some(instant) = parse_datetime(&x, now.date_naive()) else {
+                ctx.send(|f| {
+                    f.content(format!(
+                        "Couldn't read `{x}` as a date and time. Use a format like {DATETIME_EXAMPLES}."
+                    ))
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+                })
+                .await?;
+                return Ok(());
+            };
+            instant
+        }
+        (None, Some(hour), Some(minute)) => NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(
+                year.unwrap_or(now.year()),
+                month.unwrap_or(now.month()),
+                day.unwrap_or(now.day()),
+            )
+            .ok_or(super::FedBotError::new("unknown y/m/d"))?,
+            NaiveTime::from_hms_opt(hour, minute, second.unwrap_or(now.second()))
+                .ok_or(super::FedBotError::new("unknown h/m/s"))?,
+        ),
+        (None, ..) => {
+            ctx.send(|f| {
+                f.content("Specify either `datetime` or both `hour` and `minute`.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        }
+    };
+    // Times repeated by a DST change resolve to their first occurrence
+    let Some(timestamp) = tz
+        .from_local_datetime(&instant)
+        .earliest()
+        .map(|x| x.timestamp())
+    else {
+        ctx.send(|f| {
+            f.content(format!(
+                "{instant} doesn't exist in {} because of a daylight saving change.",
+                tz.name()
+            ))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
 
-    let code = format!(
-        "<t:{}{}>",
-        timestamp,
-        format_code.map_or(String::new(), |x| format!(":{x}"))
-    );
     ctx.send(|f| {
-        f.content(format!("`{}` ({})", &code, &code))
-            .ephemeral(ctx.data().is_ephemeral)
+        f.content(
+            TIMESTAMP_STYLES
+                .iter()
+                .map(|(style, name)| {
+                    format!("{name}: `<t:{timestamp}:{style}>` <t:{timestamp}:{style}>")
+                })
+                .join("\n"),
+        )
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
     Ok(())
@@ -340,7 +441,7 @@ pub async fn timestamp(
 pub async fn test(ctx: Context<'_>, debug: Option<bool>) -> Result<(), Error> {
     ctx.send(|f| {
         f.content("Test received!")
-            .ephemeral(ctx.data().is_ephemeral);
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()));
         if debug.is_some_and(|val| val) {
             f.embed(|f| f.description("hi"));
         }
@@ -350,78 +451,191 @@ pub async fn test(ctx: Context<'_>, debug: Option<bool>) -> Result<(), Error> {
     Ok(())
 }
 
+type PermissionReasons = &'static [(serenity::Permissions, &'static str)];
+
+/// Minimal permissions requested on invite, grouped with the reason each is needed
+const INVITE_PERMISSIONS: [(&str, PermissionReasons); 4] = [
+    (
+        "Moderation",
+        &[
+            (
+                serenity::Permissions::MANAGE_MESSAGES,
+                "needed to delete profane messages and blocked images, and for /purgeto",
+            ),
+            (
+                serenity::Permissions::KICK_MEMBERS,
+                "needed to remove users with blocked profile pictures",
+            ),
+        ],
+    ),
+    (
+        "User screening",
+        &[
+            (
+                serenity::Permissions::MANAGE_ROLES,
+                "needed to give and take member/questioning roles and set channel permissions",
+            ),
+            (
+                serenity::Permissions::MANAGE_CHANNELS,
+                "needed to create and delete questioning channels",
+            ),
+            (
+                serenity::Permissions::CREATE_PUBLIC_THREADS,
+                "needed to archive questioning logs in the mod channel",
+            ),
+        ],
+    ),
+    (
+        "Server assets",
+        &[
+            (
+                serenity::Permissions::MANAGE_EMOJIS_AND_STICKERS,
+                "needed to remove blocked emojis/stickers and to pirate emojis",
+            ),
+            (
+                serenity::Permissions::MANAGE_GUILD,
+                "needed to remove a blocked server icon or banner",
+            ),
+            (
+                serenity::Permissions::MANAGE_WEBHOOKS,
+                "needed to move messages while keeping their author's name and avatar",
+            ),
+        ],
+    ),
+    (
+        "Messaging",
+        &[
+            (
+                serenity::Permissions::VIEW_CHANNEL,
+                "needed to see messages to filter",
+            ),
+            (
+                serenity::Permissions::READ_MESSAGE_HISTORY,
+                "needed to log questioning channels and clean up old prompts",
+            ),
+            (
+                serenity::Permissions::SEND_MESSAGES,
+                "needed to send triggers, alerts and welcome messages",
+            ),
+            (
+                serenity::Permissions::SEND_MESSAGES_IN_THREADS,
+                "needed to post questioning logs",
+            ),
+            (
+                serenity::Permissions::EMBED_LINKS,
+                "needed for entry forms, logs and poll embeds",
+            ),
+            (
+                serenity::Permissions::ATTACH_FILES,
+                "needed to keep attachments when logging or moving messages",
+            ),
+            (
+                serenity::Permissions::ADD_REACTIONS,
+                "needed to add poll options",
+            ),
+        ],
+    ),
+];
+
 /// Get invite link
 #[instrument(skip_all, err)]
 #[poise::command(slash_command)]
 pub async fn invite(ctx: Context<'_>) -> Result<(), Error> {
+    let permissions = INVITE_PERMISSIONS
+        .iter()
+        .flat_map(|x| x.1.iter())
+        .fold(serenity::Permissions::empty(), |acc, x| acc | x.0);
     let invite_url = ctx
         .serenity_context()
         .cache
         .current_user()
-        .invite_url_with_oauth2_scopes(
-            ctx,
-            serenity::Permissions::ADMINISTRATOR,
-            &[Scope::Bot, Scope::ApplicationsCommands],
-        )
-        .await?;
-    ctx.send(|f| f.content(invite_url).ephemeral(ctx.data().is_ephemeral))
+        .invite_url_with_oauth2_scopes(ctx, permissions, &[Scope::Bot, Scope::ApplicationsCommands])
         .await?;
+    ctx.send(|f| {
+        f.content(invite_url)
+            .embed(|f| {
+                f.title("Requested permissions");
+                for (group, reasons) in INVITE_PERMISSIONS {
+                    f.field(
+                        group,
+                        reasons
+                            .iter()
+                            .map(|x| format!("`{:?}`: {}", x.0, x.1))
+                            .join("\n"),
+                        false,
+                    );
+                }
+                f
+            })
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
     Ok(())
 }
 
-/// Create a poll
+const MAX_EMBED_FIELD_LENGTH: usize = 1024;
+
+/// Show your own roles and join date in this server
 #[instrument(skip_all, err)]
-#[poise::command(slash_command)]
-pub async fn poll(
-    ctx: Context<'_>,
-    question: String,
-    #[description = "Poll options, separated by semicolons"] options: String,
-) -> Result<(), Error> {
-    let options_vec = options.split(';').map(str::trim).collect::<Vec<&str>>();
-    let options_length = options_vec.len();
-    if options_length < 2 {
-        ctx.send(|f| {
-            f.content("You must specify at least two options, separated by semicolons.")
-                .ephemeral(ctx.data().is_ephemeral)
-        })
-        .await?;
-        return Ok(());
-    }
-    if options_length > 26 {
-        ctx.send(|f| {
-            f.content("Too many options!")
-                .ephemeral(ctx.data().is_ephemeral)
-        })
-        .await?;
-        return Ok(());
-    }
-    let mut formatted_options = vec![];
-    for (val, index) in options_vec.iter().zip(0..u32::MAX) {
-        formatted_options.push(format!(
-            "{}: {}",
-            char::from_u32('\u{1f1e6}' as u32 + index)
-                .ok_or(super::FedBotError::new("Unicode decode error"))?,
-            val
-        ));
-    }
-    let msg = ctx
-        .send(|f| {
-            f.embed(|f| {
-                f.title(question)
-                    .description(formatted_options.into_iter().format("\n"))
-            })
+#[poise::command(slash_command, guild_only)]
+pub async fn whoami(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let member = guild.member(ctx, ctx.author().id).await?;
+    // Not every server has a profile, so this is optional rather than `require_profile!`
+    let server_data = Servers::find_by_id(guild).one(&ctx.data().db).await?;
+    let roles = member
+        .roles
+        .iter()
+        .filter(|x| x.0 != guild.0) // @everyone has the same id as the guild
+        .collect::<Vec<_>>();
+
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title(member.display_name())
+                .thumbnail(member.face())
+                .field(
+                    "Account created",
+                    format!("<t:{}:f>", ctx.author().created_at().unix_timestamp()),
+                    true,
+                );
+            if let Some(x) = member.joined_at {
+                f.field("Joined", format!("<t:{}:f>", x.unix_timestamp()), true);
+            }
+            if let Some(x) = &server_data {
+                let has_role = |role: DbRoleId| member.roles.contains(&role.into());
+                f.field(
+                    "Member role",
+                    if has_role(x.member_role) { "Yes" } else { "No" },
+                    true,
+                )
+                .field(
+                    "Questioning role",
+                    if has_role(x.questioning_role) {
+                        "Yes"
+                    } else {
+                        "No"
+                    },
+                    true,
+                );
+            }
+            f.field(
+                format!("Roles ({})", roles.len()),
+                super::chunk_lines(
+                    &[roles.iter().map(|x| x.mention()).join(" ")],
+                    MAX_EMBED_FIELD_LENGTH,
+                )
+                .into_iter()
+                .find(|x| !x.is_empty())
+                .unwrap_or_else(|| "None".to_owned()),
+                false,
+            )
         })
-        .await?
-        .into_message()
-        .await?;
-    for i in 0..options_length.try_into()? {
-        msg.react(
-            ctx,
-            char::from_u32('\u{1f1e6}' as u32 + i)
-                .ok_or(super::FedBotError::new("Unicode decode error"))?,
-        )
-        .await?;
-    }
+        .ephemeral(true)
+    })
+    .await?;
     Ok(())
 }
 
@@ -435,6 +649,91 @@ struct PirateEmojiName {
     name: Option<String>,
 }
 
+const MAX_EMOJIS_REACHED: isize = 30008;
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// Ask the invoker to pick an emoji to delete; returns whether one was deleted
+async fn free_emoji_slot(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    animated: bool,
+) -> Result<bool, Error> {
+    // Static and animated emojis have separate quotas
+    let emojis = guild
+        .emojis(ctx)
+        .await?
+        .into_iter()
+        .filter(|x| x.animated == animated)
+        .take(MAX_SELECT_OPTIONS)
+        .collect::<Vec<_>>();
+    if emojis.is_empty() {
+        return Ok(false);
+    }
+
+    let msg = ctx
+        .send(|f| {
+            f.content("Emoji limit reached! Select an emoji to delete to make room.")
+                .components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_select_menu(|f| {
+                            f.custom_id("deleteEmoji")
+                                .placeholder("Emoji to delete")
+                                .options(|f| {
+                                    for i in &emojis {
+                                        f.create_option(|f| {
+                                            f.label(&i.name)
+                                                .value(i.id)
+                                                .emoji(serenity::ReactionType::from(i.clone()))
+                                        });
+                                    }
+                                    f
+                                })
+                        })
+                    })
+                    .create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id("cancel")
+                                .label("Cancel")
+                                .style(serenity::ButtonStyle::Secondary)
+                        })
+                    })
+                })
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+
+    let response = msg
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(120))
+        .await;
+    msg.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(false);
+    };
+    response.defer(ctx).await?;
+
+    let Some(to_delete) = response
+        .data
+        .values
+        .first()
+        .and_then(|x| x.parse::<u64>().ok())
+        .and_then(|x| emojis.iter().find(|y| y.id.0 == x))
+    else {
+        return Ok(false);
+    };
+    to_delete.delete(ctx).await?;
+    tracing::info!(
+        "User '{}' deleted emoji '{}' to make room for a pirated emoji",
+        ctx.author().tag(),
+        to_delete.name
+    );
+    Ok(true)
+}
+
 #[instrument(skip_all, err)]
 #[poise::command(context_menu_command = "Pirate Emoji", guild_only)]
 pub async fn pirate_emoji(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
@@ -449,28 +748,19 @@ pub async fn pirate_emoji(ctx: Context<'_>, msg: serenity::Message) -> Result<()
         .guild_id()
         .ok_or(super::FedBotError::new("command must be used in guild"))?;
 
-    let server_data: MoveMessageServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (mod_role,) = (serenity::RoleId(server_data.mod_role.repack()),);
-
-    check_mod_role!(ctx, guild, mod_role);
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
 
     let mut emojis = super::EMOJI.captures_iter(&msg.content);
 
     let Some(to_pirate) = emojis.next() else {
-            ctx.send(|f| {
-                f.content("No emojis in message!")
-                    .ephemeral(ctx.data().is_ephemeral)
-            })
-            .await?;
-            return Ok(());
-        };
+        ctx.send(|f| {
+            f.content("No emojis in message!")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
 
     let mut pirate_name = to_pirate
         .get(2)
@@ -485,7 +775,7 @@ pub async fn pirate_emoji(ctx: Context<'_>, msg: serenity::Message) -> Result<()
     if emojis.next().is_some() {
         ctx.send(|f| {
             f.content("More than one emoji in message!")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
@@ -518,36 +808,118 @@ pub async fn pirate_emoji(ctx: Context<'_>, msg: serenity::Message) -> Result<()
         }
     }
 
-    let new_emoji = guild
-        .create_emoji(
-            ctx,
-            pirate_name,
-            &format!(
-                "data:image/{};base64,{}",
-                emoji_encoding,
-                general_purpose::STANDARD.encode(
-                    ctx.data()
-                        .reqwest
-                        .get(format!(
-                            "https://cdn.discordapp.com/emojis/{pirate_id}.{emoji_encoding}",
-                        ))
-                        .send()
-                        .await?
-                        .bytes()
-                        .await?
-                )
-            ),
+    let image = format!(
+        "data:image/{};base64,{}",
+        emoji_encoding,
+        general_purpose::STANDARD.encode(
+            ctx.data()
+                .reqwest
+                .get(format!(
+                    "https://cdn.discordapp.com/emojis/{pirate_id}.{emoji_encoding}",
+                ))
+                .send()
+                .await?
+                .bytes()
+                .await?
         )
-        .await?;
+    );
+
+    let new_emoji = match guild.create_emoji(ctx, pirate_name, &image).await {
+        Ok(x) => x,
+        Err(e) if super::discord_error_code(&e) == Some(MAX_EMOJIS_REACHED) => {
+            if !free_emoji_slot(ctx, guild, emoji_encoding == "gif").await? {
+                ctx.send(|f| {
+                    f.content("Emoji limit reached; please free up a slot first.")
+                        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+                })
+                .await?;
+                return Ok(());
+            }
+            guild.create_emoji(ctx, pirate_name, &image).await?
+        }
+        Err(e) => return Err(e.into()),
+    };
 
     ctx.send(|f| {
         f.content(format!("\u{1f3f4}\u{200d}\u{2620}\u{fe0f} {new_emoji}"))
-            .ephemeral(ctx.data().is_ephemeral)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
     Ok(())
 }
 
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+
+/// Preview how `msg` will look in `channel` and ask the invoker to confirm the move
+async fn confirm_move(
+    ctx: Context<'_>,
+    msg: &serenity::Message,
+    channel: &serenity::GuildChannel,
+) -> Result<bool, Error> {
+    let content = if msg.content.chars().count() > MAX_EMBED_DESCRIPTION_LENGTH {
+        format!(
+            "{}...",
+            msg.content
+                .chars()
+                .take(MAX_EMBED_DESCRIPTION_LENGTH - 3)
+                .collect::<String>()
+        )
+    } else {
+        msg.content.clone()
+    };
+    let attachments = msg.attachments.len() + msg.sticker_items.len();
+
+    let prompt = ctx
+        .send(|f| {
+            f.content(format!("Move this message to {}?", channel.mention()))
+                .embed(|f| {
+                    f.author(|f| {
+                        f.name(&msg.author.name);
+                        if let Some(x) = msg.author.avatar_url() {
+                            f.icon_url(x);
+                        }
+                        f
+                    })
+                    .description(content);
+                    if attachments > 0 {
+                        f.footer(|f| f.text(format!("+ {attachments} attachment(s)")));
+                    }
+                    f
+                })
+                .components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id("confirm")
+                                .label("Confirm Move")
+                                .style(serenity::ButtonStyle::Danger)
+                        })
+                        .create_button(|f| {
+                            f.custom_id("cancel")
+                                .label("Cancel")
+                                .style(serenity::ButtonStyle::Secondary)
+                        })
+                    })
+                })
+                .ephemeral(true)
+        })
+        .await?;
+
+    let response = prompt
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(120))
+        .await;
+    prompt.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(false);
+    };
+    response.defer(ctx).await?;
+    Ok(response.data.custom_id == "confirm")
+}
+
 #[instrument(skip_all, err)]
 #[poise::command(context_menu_command = "Move", guild_only)]
 pub async fn move_(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
@@ -562,17 +934,8 @@ pub async fn move_(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error
         .guild_id()
         .ok_or(super::FedBotError::new("command must be used in guild"))?;
 
-    let server_data: MoveMessageServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (mod_role,) = (serenity::RoleId(server_data.mod_role.repack()),);
-
-    check_mod_role!(ctx, guild, mod_role);
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Helper, &server_data);
 
     crate::defer!(ctx);
 
@@ -586,8 +949,55 @@ pub async fn move_(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error
         .find(|x| x.name == data.channel)
         .ok_or(super::FedBotError::new("could not find channel"))?;
 
+    if !confirm_move(ctx, &msg, channel).await? {
+        ctx.send(|f| f.ephemeral(true).content("Move cancelled."))
+            .await?;
+        return Ok(());
+    }
+
     crate::defer!(ctx);
 
+    // Webhooks cannot send stickers, so re-upload them as images instead, fetched before the
+    // webhook exists so a failed download doesn't leave it behind
+    let mut stickers = vec![];
+    for i in &msg.sticker_items {
+        let (url, extension) = match i.format_type {
+            serenity::StickerFormatType::Lottie => continue,
+            // serenity predates GIF stickers, which come through as an unknown format
+            serenity::StickerFormatType::Unknown => (
+                format!("https://media.discordapp.net/stickers/{}.gif", i.id),
+                "gif",
+            ),
+            _ => match i.image_url() {
+                Some(x) => (x, "png"),
+                None => continue,
+            },
+        };
+        stickers.push(serenity::AttachmentType::Bytes {
+            data: Cow::Owned(
+                ctx.data()
+                    .reqwest
+                    .get(url)
+                    .send()
+                    .await?
+                    .bytes()
+                    .await?
+                    .to_vec(),
+            ),
+            filename: format!("{}.{}", i.name, extension),
+        });
+    }
+    let mut content = msg.content.clone();
+    if !stickers.is_empty() {
+        content.push_str("\n*(stickers converted to images)*");
+    }
+    let files = msg
+        .attachments
+        .iter()
+        .map(|x| serenity::AttachmentType::from(x.url.as_str()))
+        .chain(stickers)
+        .collect::<Vec<_>>();
+
     let webhook = match msg.author.avatar_url() {
         Some(avatar) => {
             channel
@@ -596,19 +1006,25 @@ pub async fn move_(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error
         }
         None => channel.create_webhook(ctx, &msg.author.name).await?,
     };
-
-    webhook
-        .execute(ctx, true, |f| {
-            f.content(&msg.content).files(
-                msg.attachments
-                    .iter()
-                    .map(|x| x.url.as_str())
-                    .collect::<Vec<&str>>(),
-            )
-        })
-        .await?;
-
+    let sent = webhook
+        .execute(ctx, true, |f| f.content(content).files(files))
+        .await;
     webhook.delete(ctx).await?;
+    sent?;
+    super::mod_log(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild,
+        None,
+        format!(
+            "Message from {} moved from {} to {} by {}",
+            msg.author.mention(),
+            msg.channel_id.mention(),
+            channel.mention(),
+            ctx.author().mention()
+        ),
+    )
+    .await?;
     msg.reply(
         ctx,
         format!(
@@ -621,10 +1037,141 @@ pub async fn move_(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error
     msg.channel_id.delete_message(ctx, msg.id).await?;
 
     ctx.send(|f| {
-        f.ephemeral(ctx.data().is_ephemeral)
+        f.ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
             .content(format!("Moved message to {}", channel.mention()))
     })
     .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const BOARDS_PER_CASE: u64 = 200;
+
+    fn mine_count<const SIZE: usize>(board: &MineSweeper<SIZE>) -> usize {
+        board
+            .0
+            .iter()
+            .flatten()
+            .filter(|x| matches!(x, SweeperSquare::Mine))
+            .count()
+    }
+
+    /// Count adjacent mines independently of `MineSweeper::neighbours`
+    fn adjacent_mines<const SIZE: usize>(board: &MineSweeper<SIZE>, row: usize, col: usize) -> u8 {
+        let mut count = 0;
+        for i in -1isize..=1 {
+            for j in -1isize..=1 {
+                let (r, c) = (row as isize + i, col as isize + j);
+                if (i, j) != (0, 0)
+                    && (0..SIZE as isize).contains(&r)
+                    && (0..SIZE as isize).contains(&c)
+                    && matches!(board.0[r as usize][c as usize], SweeperSquare::Mine)
+                {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    fn check_boards<const SIZE: usize>() {
+        for mines in 0..SIZE * SIZE {
+            for seed in 0..BOARDS_PER_CASE {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let board =
+                    MineSweeper::<SIZE>::generate(mines, &mut rng).expect("mines fit on the board");
+                assert_eq!(mine_count(&board), mines, "seed {seed}");
+                for (row, col) in (0..SIZE).cartesian_product(0..SIZE) {
+                    if let SweeperSquare::Clear(x) = board.0[row][col] {
+                        assert_eq!(
+                            x,
+                            adjacent_mines(&board, row, col),
+                            "seed {seed}, mines {mines}, square ({row}, {col})\n{board}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clear_squares_count_adjacent_mines() {
+        check_boards::<1>();
+        check_boards::<2>();
+        check_boards::<{ MineSweeperSize::Small.val() }>();
+        check_boards::<{ MineSweeperSize::Medium.val() }>();
+        check_boards::<{ MineSweeperSize::Large.val() }>();
+    }
+
+    #[test]
+    fn too_many_mines_is_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(MineSweeper::<4>::generate(15, &mut rng).is_some());
+        assert!(MineSweeper::<4>::generate(16, &mut rng).is_none());
+        assert!(MineSweeper::<9>::generate(80, &mut rng).is_some());
+        assert!(MineSweeper::<9>::generate(81, &mut rng).is_none());
+        assert!(MineSweeper::<9>::generate(usize::MAX, &mut rng).is_none());
+    }
+
+    #[test]
+    fn mine_counts_default_and_clamp() {
+        let size = MineSweeperSize::Medium;
+        assert_eq!(size.resolve_mines(None, false), Some(5));
+        assert_eq!(size.resolve_mines(Some(0), true), None);
+        assert_eq!(size.resolve_mines(Some(35), false), Some(35));
+        assert_eq!(size.resolve_mines(Some(36), false), None);
+        assert_eq!(size.resolve_mines(Some(36), true), Some(35));
+        assert_eq!(size.resolve_mines(Some(70), true), Some(35));
+        assert_eq!(size.resolve_mines(Some(71), true), None);
+        assert_eq!(MineSweeperSize::Small.default_mines(), 2);
+        assert_eq!(MineSweeperSize::Large.default_mines(), 12);
+    }
+
+    #[test]
+    fn coords_are_row_major() {
+        assert_eq!(MineSweeper::<4>::get_coords(0), (0, 0));
+        assert_eq!(MineSweeper::<4>::get_coords(3), (0, 3));
+        assert_eq!(MineSweeper::<4>::get_coords(4), (1, 0));
+        assert_eq!(MineSweeper::<4>::get_coords(15), (3, 3));
+        assert_eq!(MineSweeper::<6>::get_coords(13), (2, 1));
+    }
+
+    #[test]
+    fn datetime_formats_parse() {
+        let today = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(18, 30, 0)
+            .unwrap();
+        assert_eq!(parse_datetime("2024-06-01 18:30", today), Some(expected));
+        assert_eq!(parse_datetime("6/1/2024 6:30pm", today), Some(expected));
+        assert_eq!(parse_datetime("06/01/2024 6:30 PM", today), Some(expected));
+        assert_eq!(parse_datetime(" 6/1/2024 18:30 ", today), Some(expected));
+        assert_eq!(parse_datetime("18:30", today), today.and_hms_opt(18, 30, 0));
+        assert_eq!(parse_datetime("6:30am", today), today.and_hms_opt(6, 30, 0));
+    }
+
+    #[test]
+    fn datetime_rejects_invalid() {
+        let today = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        assert_eq!(parse_datetime("", today), None);
+        assert_eq!(parse_datetime("tomorrow", today), None);
+        assert_eq!(parse_datetime("2024-02-30 10:00", today), None);
+        assert_eq!(parse_datetime("25:00", today), None);
+    }
+
+    #[test]
+    fn neighbours_stay_on_board() {
+        let corner = MineSweeper::<4>::neighbours(0, 0).sorted().collect_vec();
+        assert_eq!(corner, vec![(0, 1), (1, 0), (1, 1)]);
+        let edge = MineSweeper::<4>::neighbours(3, 1).sorted().collect_vec();
+        assert_eq!(edge, vec![(2, 0), (2, 1), (2, 2), (3, 0), (3, 2)]);
+        assert_eq!(MineSweeper::<4>::neighbours(1, 2).count(), 8);
+        assert_eq!(MineSweeper::<1>::neighbours(0, 0).count(), 0);
+    }
+}
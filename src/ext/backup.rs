@@ -0,0 +1,152 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{Context, Error};
+use chrono::{Duration, Utc};
+use dunce::canonicalize;
+use sea_orm::*;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, instrument};
+
+const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "fedbot-";
+const DEFAULT_BACKUP_HOUR: u32 = 4;
+const DEFAULT_BACKUPS_KEPT: usize = 7;
+
+fn backup_hour() -> u32 {
+    std::env::var("FEDBOT_BACKUP_HOUR")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .filter(|x| *x < 24)
+        .unwrap_or(DEFAULT_BACKUP_HOUR)
+}
+
+fn backups_kept() -> usize {
+    std::env::var("FEDBOT_BACKUPS_KEPT")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .filter(|x| *x > 0)
+        .unwrap_or(DEFAULT_BACKUPS_KEPT)
+}
+
+fn backup_dir() -> Result<PathBuf, Error> {
+    Ok(canonicalize(Path::new(&std::env::current_exe()?))?.with_file_name(BACKUP_DIR))
+}
+
+/// Back up the database once a day at the configured hour (UTC)
+pub async fn schedule_backups(db: DatabaseConnection) {
+    if db.get_database_backend() != DbBackend::Sqlite {
+        info!("Database backend is not sqlite, skipping automatic backups");
+        return;
+    }
+
+    loop {
+        let now = Utc::now().naive_utc();
+        let mut next = now.date().and_hms_opt(backup_hour(), 0, 0).unwrap_or(now);
+        if next <= now {
+            next += Duration::days(1);
+        }
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        if let Err(e) = backup(&db).await {
+            error!("Automatic backup failed: {}", e);
+        }
+    }
+}
+
+/// Take an online backup with `VACUUM INTO`, which is safe while the database is locked
+#[instrument(skip_all, err)]
+pub async fn backup(db: &DatabaseConnection) -> Result<PathBuf, Error> {
+    if db.get_database_backend() != DbBackend::Sqlite {
+        return Err(super::FedBotError::new("backups are only supported for sqlite").into());
+    }
+
+    let dir = backup_dir()?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!(
+        "{BACKUP_PREFIX}{}.db",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    let path_str = path
+        .to_str()
+        .ok_or(super::FedBotError::new("backup path is not valid unicode"))?;
+
+    let start = std::time::Instant::now();
+    db.execute(Statement::from_string(
+        DbBackend::Sqlite,
+        format!("VACUUM INTO '{}'", path_str.replace('\'', "''")),
+    ))
+    .await?;
+    info!(
+        "Backed up database to '{}' ({} bytes in {:?})",
+        path_str,
+        tokio::fs::metadata(&path).await?.len(),
+        start.elapsed()
+    );
+
+    rotate_backups(&dir).await?;
+    Ok(path)
+}
+
+async fn rotate_backups(dir: &Path) -> Result<(), Error> {
+    let mut backups = vec![];
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry
+            .file_name()
+            .to_str()
+            .is_some_and(|x| x.starts_with(BACKUP_PREFIX) && x.ends_with(".db"))
+        {
+            backups.push(entry.path());
+        }
+    }
+
+    // Timestamped names sort chronologically
+    backups.sort();
+    let to_remove = backups.len().saturating_sub(backups_kept());
+    for i in backups.into_iter().take(to_remove) {
+        tokio::fs::remove_file(&i).await?;
+        info!("Removed old backup '{}'", i.display());
+    }
+    Ok(())
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only, subcommands("now"))]
+pub async fn botbackup(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Back up the database immediately
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn now(ctx: Context<'_>) -> Result<(), Error> {
+    crate::defer!(ctx);
+
+    let path = backup(&ctx.data().db).await?;
+    ctx.send(|f| {
+        f.content(format!(
+            "Backed up database to `{}`.",
+            path.file_name()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+        ))
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,384 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Pause Discord calls the filters keep failing, so an outage costs one warning instead of a
+//! timeout per event
+
+use super::events::{BotEvent, EventBus};
+use super::{Context, Error};
+use crate::entities::guild_log_channels;
+use poise::serenity_prelude as serenity;
+use serenity::Mentionable;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, instrument, warn};
+
+/// Failures within [`WINDOW`] that open a circuit
+const THRESHOLD: usize = 10;
+const WINDOW: Duration = Duration::from_secs(60);
+const COOL_DOWN: Duration = Duration::from_secs(5 * 60);
+/// How long a probe may go unanswered before another is let through
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Discord calls the filters make often enough to need a breaker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Downloads,
+    Deletes,
+}
+
+impl Operation {
+    pub const ALL: [Self; 2] = [Self::Downloads, Self::Deletes];
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Downloads => "Image downloads",
+            Self::Deletes => "Message deletes",
+        }
+    }
+
+    /// What the filters do instead while this operation is paused
+    pub const fn fallback(self) -> &'static str {
+        match self {
+            Self::Downloads => "images aren't being checked",
+            Self::Deletes => "filtered messages are reported here instead of deleted",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Default)]
+struct Breaker {
+    failures: VecDeque<Instant>,
+    opened_at: Option<Instant>,
+    probe_started: Option<Instant>,
+    /// Guilds told about the current outage
+    degraded: HashSet<serenity::GuildId>,
+}
+
+impl Breaker {
+    fn state(&self, now: Instant) -> State {
+        match self.opened_at {
+            None => State::Closed,
+            Some(x) if now.duration_since(x) < COOL_DOWN => State::Open,
+            Some(_) => State::HalfOpen,
+        }
+    }
+
+    /// Whether to attempt the operation, letting one probe through at a time after the cool-down
+    fn allow(&mut self, now: Instant) -> bool {
+        match self.state(now) {
+            State::Closed => true,
+            State::Open => false,
+            State::HalfOpen => {
+                if self
+                    .probe_started
+                    .is_some_and(|x| now.duration_since(x) < PROBE_TIMEOUT)
+                {
+                    return false;
+                }
+                self.probe_started = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Close the circuit, returning the guilds to tell if it had been open
+    fn succeed(&mut self) -> Option<Vec<serenity::GuildId>> {
+        self.failures.clear();
+        self.probe_started = None;
+        self.opened_at
+            .take()
+            .map(|_| self.degraded.drain().collect())
+    }
+
+    /// Returns whether this failure opened the circuit
+    fn fail(&mut self, now: Instant) -> bool {
+        if self.opened_at.is_some() {
+            // Attempts started before the circuit opened shouldn't extend the cool-down
+            if self.probe_started.take().is_some() {
+                self.opened_at = Some(now);
+            }
+            return false;
+        }
+        self.failures.push_back(now);
+        while self
+            .failures
+            .front()
+            .is_some_and(|x| now.duration_since(*x) > WINDOW)
+        {
+            self.failures.pop_front();
+        }
+        if self.failures.len() < THRESHOLD {
+            return false;
+        }
+        self.failures.clear();
+        self.opened_at = Some(now);
+        true
+    }
+
+    fn describe(&self, now: Instant) -> String {
+        match self.state(now) {
+            State::Closed => format!("Working ({} recent failures)", self.failures.len()),
+            State::Open => {
+                let remaining = self.opened_at.map_or(Duration::ZERO, |x| {
+                    COOL_DOWN.saturating_sub(now.duration_since(x))
+                });
+                let retry = chrono::Utc::now()
+                    + chrono::Duration::from_std(remaining)
+                        .unwrap_or_else(|_| chrono::Duration::zero());
+                format!(
+                    "Paused, probing <t:{}:R> ({} servers notified)",
+                    retry.timestamp(),
+                    self.degraded.len()
+                )
+            }
+            State::HalfOpen => format!("Probing ({} servers notified)", self.degraded.len()),
+        }
+    }
+}
+
+/// One breaker per [`Operation`], shared by every guild
+#[derive(Default, Clone)]
+pub struct CircuitBreakers(Arc<[Mutex<Breaker>; Operation::ALL.len()]>);
+
+impl CircuitBreakers {
+    fn with<T>(&self, operation: Operation, f: impl FnOnce(&mut Breaker) -> T) -> Option<T> {
+        self.0[operation.index()].lock().ok().map(|mut x| f(&mut x))
+    }
+
+    pub fn allow(&self, operation: Operation) -> bool {
+        self.with(operation, |x| x.allow(Instant::now()))
+            .unwrap_or(true)
+    }
+
+    pub fn succeeded(&self, operation: Operation, events: &EventBus) {
+        let Some(Some(guilds)) = self.with(operation, Breaker::succeed) else {
+            return;
+        };
+        info!("{} are working again, resuming them", operation.label());
+        for guild in guilds {
+            events.publish(BotEvent::FilteringRecovered { guild, operation });
+        }
+    }
+
+    pub fn failed(&self, operation: Operation) {
+        if self
+            .with(operation, |x| x.fail(Instant::now()))
+            .unwrap_or(false)
+        {
+            warn!(
+                "{} failed {} times within {} seconds, pausing them for {} seconds",
+                operation.label(),
+                THRESHOLD,
+                WINDOW.as_secs(),
+                COOL_DOWN.as_secs()
+            );
+        }
+    }
+
+    /// Note that `guild` went without `operation`, telling its mods the first time per outage
+    pub fn skipped(&self, operation: Operation, guild: serenity::GuildId, events: &EventBus) {
+        debug!("Skipped {} in guild '{}'", operation.label(), guild);
+        if self
+            .with(operation, |x| x.degraded.insert(guild))
+            .unwrap_or(false)
+        {
+            events.publish(BotEvent::FilteringDegraded { guild, operation });
+        }
+    }
+}
+
+/// Whether `error` means Discord couldn't be reached or broke, rather than refusing the request
+pub fn is_outage(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(x) => match x.as_ref() {
+            serenity::HttpError::Request(_) => true,
+            serenity::HttpError::UnsuccessfulRequest(y) => y.status_code.is_server_error(),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Delete a filtered message, or report it to the mods while deletes are failing; returns whether
+/// it was deleted
+pub async fn delete_filtered(
+    reference: super::EventReference<'_>,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    id: serenity::MessageId,
+    author: &serenity::User,
+    reason: &str,
+) -> Result<bool, Error> {
+    let breakers = &reference.3.circuit_breakers;
+    if breakers.allow(Operation::Deletes) {
+        return match channel.delete_message(&reference.0, id).await {
+            Ok(()) => {
+                breakers.succeeded(Operation::Deletes, &reference.3.events);
+                Ok(true)
+            }
+            Err(e) => {
+                if is_outage(&e) {
+                    breakers.failed(Operation::Deletes);
+                } else {
+                    // Discord answered, even if it refused
+                    breakers.succeeded(Operation::Deletes, &reference.3.events);
+                }
+                Err(e.into())
+            }
+        };
+    }
+    breakers.skipped(Operation::Deletes, guild, &reference.3.events);
+    super::send_mod_log(
+        reference.0,
+        &reference.3.db,
+        &reference.3.config_health,
+        guild,
+        Some(guild_log_channels::Purpose::FilterNotices),
+        format!(
+            "Couldn't delete message from {} in {} ([context](https://discord.com/channels/{}/{}/{})) while deletes are paused (reason: {})",
+            author.mention(),
+            channel.mention(),
+            guild,
+            channel,
+            id,
+            reason
+        ),
+    )
+    .await?;
+    Ok(false)
+}
+
+/// Show whether the filters have paused any Discord calls after repeated failures
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+pub async fn breakers(ctx: Context<'_>) -> Result<(), Error> {
+    let now = Instant::now();
+    let states = Operation::ALL.map(|x| {
+        (
+            x.label(),
+            ctx.data()
+                .circuit_breakers
+                .with(x, |y| y.describe(now))
+                .unwrap_or_else(|| "Unknown".to_owned()),
+        )
+    });
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title("Circuit breakers");
+            for (label, state) in states {
+                f.field(label, state, false);
+            }
+            f
+        })
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: serenity::GuildId = serenity::GuildId(1);
+
+    fn fail_times(breaker: &mut Breaker, start: Instant, count: usize, gap: Duration) -> bool {
+        let mut opened = false;
+        for i in 0..count {
+            opened |= breaker.fail(start + gap * u32::try_from(i).unwrap());
+        }
+        opened
+    }
+
+    #[test]
+    fn opens_after_enough_failures_in_the_window() {
+        let start = Instant::now();
+        let mut breaker = Breaker::default();
+        assert!(!fail_times(
+            &mut breaker,
+            start,
+            THRESHOLD - 1,
+            Duration::from_secs(1)
+        ));
+        assert!(breaker.allow(start + Duration::from_secs(10)));
+        assert!(breaker.fail(start + Duration::from_secs(10)));
+        assert!(!breaker.allow(start + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn spread_out_failures_stay_closed() {
+        let start = Instant::now();
+        let mut breaker = Breaker::default();
+        assert!(!fail_times(
+            &mut breaker,
+            start,
+            THRESHOLD * 3,
+            Duration::from_secs(10)
+        ));
+        assert_eq!(
+            breaker.state(start + Duration::from_secs(300)),
+            State::Closed
+        );
+    }
+
+    #[test]
+    fn half_open_lets_one_probe_through() {
+        let start = Instant::now();
+        let mut breaker = Breaker::default();
+        fail_times(&mut breaker, start, THRESHOLD, Duration::ZERO);
+        let later = start + COOL_DOWN;
+        assert_eq!(breaker.state(later), State::HalfOpen);
+        assert!(breaker.allow(later));
+        assert!(!breaker.allow(later));
+        assert!(breaker.allow(later + PROBE_TIMEOUT));
+
+        // A failed probe restarts the cool-down
+        assert!(!breaker.fail(later + PROBE_TIMEOUT));
+        assert_eq!(breaker.state(later + PROBE_TIMEOUT), State::Open);
+        // Stragglers from before don't
+        assert!(!breaker.fail(later + COOL_DOWN));
+        assert_eq!(
+            breaker.state(later + PROBE_TIMEOUT + COOL_DOWN),
+            State::HalfOpen
+        );
+    }
+
+    #[test]
+    fn success_closes_and_reports_notified_guilds() {
+        let start = Instant::now();
+        let mut breaker = Breaker::default();
+        assert_eq!(breaker.succeed(), None);
+        fail_times(&mut breaker, start, THRESHOLD, Duration::ZERO);
+        assert!(breaker.degraded.insert(GUILD));
+        assert!(!breaker.degraded.insert(GUILD));
+        assert_eq!(breaker.succeed(), Some(vec![GUILD]));
+        assert_eq!(breaker.state(start), State::Closed);
+        assert!(breaker.degraded.is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::Error;
+use crate::entities::prelude::*;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+use tracing::{error, info, instrument};
+
+const VALIDATION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ConfiguredEntity {
+    RulesChannel,
+    ScreeningChannel,
+    QuestioningCategory,
+    ModChannel,
+    MainChannel,
+    AuditChannel,
+    QuestioningRole,
+    ModRole,
+    MemberRole,
+    HelperRole,
+}
+
+impl ConfiguredEntity {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::RulesChannel => "rules channel",
+            Self::ScreeningChannel => "screening channel",
+            Self::QuestioningCategory => "questioning category",
+            Self::ModChannel => "mod channel",
+            Self::MainChannel => "main channel",
+            Self::AuditChannel => "audit channel",
+            Self::QuestioningRole => "questioning role",
+            Self::ModRole => "mod role",
+            Self::MemberRole => "member role",
+            Self::HelperRole => "helper role",
+        }
+    }
+
+    const fn affected_features(self) -> &'static str {
+        match self {
+            Self::RulesChannel => "rules visibility for new members",
+            Self::ScreeningChannel => "the entry form and welcome prompt",
+            Self::QuestioningCategory => "sending users to questioning",
+            Self::ModChannel => "mod logs, entry form submissions and questioning logs",
+            Self::MainChannel => "welcome messages for accepted users",
+            Self::AuditChannel => "configuration change notices (falling back to the mod channel)",
+            Self::QuestioningRole => "questioning, accepting and returning users",
+            Self::ModRole => "mod-only commands for non-admins",
+            Self::MemberRole => "accepting and returning users",
+            Self::HelperRole => "screening commands for helpers",
+        }
+    }
+
+    const fn required_permissions(self) -> serenity::Permissions {
+        match self {
+            Self::RulesChannel => serenity::Permissions::VIEW_CHANNEL,
+            Self::ScreeningChannel => serenity::Permissions::VIEW_CHANNEL
+                .union(serenity::Permissions::SEND_MESSAGES)
+                .union(serenity::Permissions::MANAGE_MESSAGES)
+                .union(serenity::Permissions::READ_MESSAGE_HISTORY),
+            Self::QuestioningCategory => {
+                serenity::Permissions::VIEW_CHANNEL.union(serenity::Permissions::MANAGE_CHANNELS)
+            }
+            Self::ModChannel => serenity::Permissions::VIEW_CHANNEL
+                .union(serenity::Permissions::SEND_MESSAGES)
+                .union(serenity::Permissions::CREATE_PUBLIC_THREADS),
+            Self::MainChannel | Self::AuditChannel => {
+                serenity::Permissions::VIEW_CHANNEL.union(serenity::Permissions::SEND_MESSAGES)
+            }
+            _ => serenity::Permissions::empty(),
+        }
+    }
+}
+
+/// Configured channels/roles known to be missing or inaccessible, per guild
+#[derive(Default, Clone)]
+pub struct ConfigHealth(Arc<RwLock<HashMap<serenity::GuildId, HashSet<ConfiguredEntity>>>>);
+
+impl ConfigHealth {
+    pub fn is_broken(&self, guild: serenity::GuildId, entity: ConfiguredEntity) -> bool {
+        self.0
+            .read()
+            .ok()
+            .and_then(|x| x.get(&guild).map(|y| y.contains(&entity)))
+            .unwrap_or(false)
+    }
+
+    /// Replace the broken set for `guild`, returning whether it changed
+    fn update(&self, guild: serenity::GuildId, broken: HashSet<ConfiguredEntity>) -> bool {
+        let Ok(mut map) = self.0.write() else {
+            return false;
+        };
+        let changed = map.get(&guild).map_or(!broken.is_empty(), |x| *x != broken);
+        if broken.is_empty() {
+            map.remove(&guild);
+        } else {
+            map.insert(guild, broken);
+        }
+        changed
+    }
+}
+
+enum Problem {
+    Missing,
+    MissingPermissions(serenity::Permissions),
+}
+
+/// Check that every configured channel and role still exists and is usable by the bot
+#[instrument(skip_all, err)]
+pub async fn validate_guild(
+    ctx: &serenity::Context,
+    db: &DatabaseConnection,
+    health: &ConfigHealth,
+    bot_id: serenity::UserId,
+    guild: serenity::GuildId,
+) -> Result<(), Error> {
+    let Some(profile) = Servers::find_by_id(guild).one(db).await? else {
+        return Ok(());
+    };
+
+    let channels = guild.channels(ctx).await?;
+    let roles = guild.roles(ctx).await?;
+    let bot_member = guild.member(ctx, bot_id).await?;
+    let cached_guild = guild.to_guild_cached(ctx);
+
+    let mut problems = vec![];
+    for (entity, id) in [
+        (ConfiguredEntity::RulesChannel, Some(profile.rules_channel)),
+        (
+            ConfiguredEntity::ScreeningChannel,
+            Some(profile.screening_channel),
+        ),
+        (
+            ConfiguredEntity::QuestioningCategory,
+            Some(profile.questioning_category),
+        ),
+        (ConfiguredEntity::ModChannel, Some(profile.mod_channel)),
+        (ConfiguredEntity::MainChannel, Some(profile.main_channel)),
+        (ConfiguredEntity::AuditChannel, profile.audit_channel),
+    ] {
+        let Some(id) = id.map(serenity::ChannelId::from) else {
+            continue;
+        };
+        let Some(channel) = channels.get(&id) else {
+            problems.push((entity, id.mention().to_string(), Problem::Missing));
+            continue;
+        };
+        if let Some(permissions) = cached_guild
+            .as_ref()
+            .and_then(|x| x.user_permissions_in(channel, &bot_member).ok())
+        {
+            let missing = entity.required_permissions() - permissions;
+            if !missing.is_empty() {
+                problems.push((
+                    entity,
+                    id.mention().to_string(),
+                    Problem::MissingPermissions(missing),
+                ));
+            }
+        }
+    }
+    for (entity, id) in [
+        (
+            ConfiguredEntity::QuestioningRole,
+            Some(profile.questioning_role),
+        ),
+        (ConfiguredEntity::ModRole, Some(profile.mod_role)),
+        (ConfiguredEntity::MemberRole, Some(profile.member_role)),
+        (ConfiguredEntity::HelperRole, profile.helper_role),
+    ] {
+        let Some(id) = id.map(serenity::RoleId::from) else {
+            continue;
+        };
+        if !roles.contains_key(&id) {
+            problems.push((entity, format!("`{id}`"), Problem::Missing));
+        }
+    }
+
+    let broken = problems.iter().map(|x| x.0).collect::<HashSet<_>>();
+    if !health.update(guild, broken) {
+        return Ok(());
+    }
+    if problems.is_empty() {
+        info!("Configuration for guild '{}' is healthy again", guild);
+        return Ok(());
+    }
+
+    let report = problems
+        .iter()
+        .sorted_by_key(|x| x.0)
+        .map(|(entity, mention, problem)| {
+            format!(
+                "- The {} ({}) {}. Disabled: {}.",
+                entity.name(),
+                mention,
+                match problem {
+                    Problem::Missing => "no longer exists".to_owned(),
+                    Problem::MissingPermissions(x) => format!("is missing permissions `{x:?}`"),
+                },
+                entity.affected_features()
+            )
+        })
+        .join("\n");
+    info!(
+        "Found configuration problems in guild '{}':\n{}",
+        guild, report
+    );
+
+    let alert_channel = if health.is_broken(guild, ConfiguredEntity::ModChannel) {
+        let Some(x) = cached_guild else {
+            return Ok(());
+        };
+        super::get_alert_channel(&x, bot_id).await?
+    } else {
+        serenity::ChannelId::from(profile.mod_channel)
+    };
+    alert_channel
+        .send_message(ctx, |f| {
+            f.content(format!(
+                "FedBot's configuration for this server needs attention. Fix these with `/profile update`:\n{report}"
+            ))
+            .allowed_mentions(|f| f.empty_parse())
+        })
+        .await?;
+    Ok(())
+}
+
+/// Re-validate every guild's configuration once a day
+pub async fn schedule_validation(
+    ctx: serenity::Context,
+    db: DatabaseConnection,
+    health: ConfigHealth,
+    bot_id: serenity::UserId,
+) {
+    loop {
+        tokio::time::sleep(VALIDATION_INTERVAL).await;
+        for i in ctx.cache.guilds() {
+            if let Err(e) = validate_guild(&ctx, &db, &health, bot_id, i).await {
+                error!("Failed to validate configuration for guild '{}': {}", i, e);
+            }
+        }
+    }
+}
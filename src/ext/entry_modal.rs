@@ -14,12 +14,16 @@
    limitations under the License.
 */
 
-use std::{cmp::Ordering, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    sync::{Arc, RwLock},
+};
 
-use super::ContainBytes;
 use crate::{
-    check_admin,
+    check_admin, check_tier,
     entities::{prelude::*, *},
+    require_profile,
 };
 use futures_lite::stream::StreamExt;
 use itertools::Itertools;
@@ -28,22 +32,49 @@ use poise::Modal;
 use sea_orm::*;
 use serde::{Deserialize, Serialize};
 use serenity::Mentionable;
+use tracing::warn;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ModalInput {
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(super) struct ModalInput {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
-    max: Option<u64>,
+    pub(super) max: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
-    min: Option<u64>,
-    label: String,
+    pub(super) min: Option<u64>,
+    pub(super) label: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
-    placeholder: Option<String>,
-    required: bool,
-    style: serenity::InputTextStyle,
+    pub(super) placeholder: Option<String>,
+    pub(super) required: bool,
+    pub(super) style: serenity::InputTextStyle,
+}
+
+impl Display for ModalInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` ({}, {}",
+            self.label,
+            match self.style {
+                serenity::InputTextStyle::Paragraph => "Paragraph",
+                _ => "Short",
+            },
+            if self.required {
+                "required"
+            } else {
+                "optional"
+            }
+        )?;
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => write!(f, ", {min}\u{2013}{max} chars")?,
+            (Some(min), None) => write!(f, ", at least {min} chars")?,
+            (None, Some(max)) => write!(f, ", up to {max} chars")?,
+            (None, None) => (),
+        }
+        f.write_str(")")
+    }
 }
 
 struct PartialModalInput {
@@ -207,8 +238,111 @@ impl PartialModalInput {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct ModalStructure(Vec<ModalInput>);
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct ModalStructure(pub(super) Vec<ModalInput>);
+
+/// The language of modals set without one, including every modal from before languages
+pub(super) const DEFAULT_LANGUAGE: &str = "default";
+/// One button per language has to fit in an action row
+const MAX_LANGUAGES: usize = 5;
+const MAX_LANGUAGE_CODE_LENGTH: usize = 16;
+const FORM_BUTTON_ID: &str = "completeForm";
+const FORM_BUTTON_LABEL: &str = "Complete Form";
+const MAX_BUTTON_LABEL_LENGTH: usize = 80;
+
+/// One language's entry modal, and the label of the button that opens it
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct LanguageModal {
+    pub(super) label: String,
+    pub(super) inputs: ModalStructure,
+}
+
+/// A server's entry modals by language code
+#[derive(Debug, PartialEq, Default)]
+pub(super) struct EntryModals(pub(super) BTreeMap<String, LanguageModal>);
+
+/// Entry modals were a single modal before they had languages
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum StoredModals {
+    Single(ModalStructure),
+    Languages(BTreeMap<String, LanguageModal>),
+}
+
+impl From<StoredModals> for EntryModals {
+    fn from(x: StoredModals) -> Self {
+        match x {
+            StoredModals::Single(x) => Self::single(x),
+            StoredModals::Languages(x) => Self(x),
+        }
+    }
+}
+
+impl EntryModals {
+    pub(super) fn single(inputs: ModalStructure) -> Self {
+        Self(BTreeMap::from([(
+            DEFAULT_LANGUAGE.to_owned(),
+            LanguageModal {
+                label: FORM_BUTTON_LABEL.to_owned(),
+                inputs,
+            },
+        )]))
+    }
+
+    /// The only modal, if it can be stored the way it was before languages
+    pub(super) fn as_single(&self) -> Option<&ModalStructure> {
+        match self.0.iter().exactly_one() {
+            Ok((code, x)) if code == DEFAULT_LANGUAGE => Some(&x.inputs),
+            _ => None,
+        }
+    }
+
+    /// A single modal keeps the button ID it had before languages
+    fn button_id(&self, code: &str) -> String {
+        if self.0.len() == 1 {
+            FORM_BUTTON_ID.to_owned()
+        } else {
+            format!("{FORM_BUTTON_ID}-{code}")
+        }
+    }
+
+    /// The language and modal a form button opens
+    fn for_button(&self, id: &str) -> Option<(&str, &LanguageModal)> {
+        if self.0.len() == 1 {
+            return self
+                .0
+                .iter()
+                .next()
+                .filter(|_| id == FORM_BUTTON_ID)
+                .map(|(code, x)| (code.as_str(), x));
+        }
+        let code = id.strip_prefix(FORM_BUTTON_ID)?.strip_prefix('-')?;
+        self.0
+            .get_key_value(code)
+            .map(|(code, x)| (code.as_str(), x))
+    }
+
+    /// How the language a form was filled in is shown to mods, when there was a choice
+    fn language_note(&self, code: &str) -> Option<String> {
+        if self.0.len() == 1 {
+            return None;
+        }
+        Some(match self.0.get(code) {
+            Some(_) if code == DEFAULT_LANGUAGE => "default form".to_owned(),
+            Some(x) => format!("{}, `{}`", x.label, code),
+            None => format!("`{code}`"),
+        })
+    }
+}
+
+/// Lowercase `raw` if it looks like a language code such as `en` or `pt-br`
+fn parse_language(raw: &str) -> Option<String> {
+    let code = raw.trim().to_lowercase();
+    (!code.is_empty()
+        && code.len() <= MAX_LANGUAGE_CODE_LENGTH
+        && code.chars().all(|x| x.is_ascii_alphanumeric() || x == '-'))
+    .then_some(code)
+}
 
 struct EntryModal<'a>(&'a ModalStructure);
 
@@ -269,7 +403,13 @@ struct ModalCreatorForm {
 
 #[tracing::instrument(skip_all, err)]
 #[poise::command(slash_command, guild_only)]
-pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error> {
+pub async fn set_entry_modal(
+    ctx: super::Context<'_>,
+    #[description = "Language code of this version of the form, like en or fr, for servers offering several"]
+    language: Option<String>,
+    #[description = "Button label for this language, like its name (defaults to the code)"]
+    label: Option<String>,
+) -> Result<(), super::Error> {
     let guild = ctx
         .guild()
         .ok_or(super::FedBotError::new("command not in guild"))?
@@ -277,44 +417,55 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
 
     check_admin!(ctx, guild);
 
-    let sentinel: Option<i64> = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .into_tuple()
-        .one(&ctx.data().db)
-        .await?;
+    let old_profile = require_profile!(ctx);
 
-    if sentinel.is_none() {
-        let maybe_command_id = serenity::Command::get_global_application_commands(ctx)
-            .await?
-            .iter()
-            .find_map(|x| {
-                if &x.name == "profile" {
-                    Some(x.id)
-                } else {
-                    None
-                }
-            });
+    let Some(language) = language.map_or(Some(DEFAULT_LANGUAGE.to_owned()), |x| parse_language(&x))
+    else {
         ctx.send(|f| {
-            f.ephemeral(ctx.data().is_ephemeral).content(format!(
-                "No server profile! Use {} to create a profile first.",
-                if let Some(x) = maybe_command_id {
-                    format!("</profile init:{x}>")
-                } else {
-                    "`/profile init`".to_string()
-                }
+            f.content(
+                "Language codes can only have letters, digits and dashes, like `en` or `pt-br`.",
+            )
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+    let mut modals = parse_entry_modal(
+        old_profile.entry_modal_json.as_deref(),
+        old_profile.entry_modal.as_deref(),
+    )?;
+    if !modals.0.contains_key(&language) && modals.0.len() >= MAX_LANGUAGES {
+        ctx.send(|f| {
+            f.content(format!(
+                "The entry form can be offered in at most {MAX_LANGUAGES} languages. Remove one with `/profile remove_entry_modal` first."
             ))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
     }
+    let label = label
+        .map(|x| {
+            x.trim()
+                .chars()
+                .take(MAX_BUTTON_LABEL_LENGTH)
+                .collect::<String>()
+        })
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| {
+            if language == DEFAULT_LANGUAGE {
+                FORM_BUTTON_LABEL.to_owned()
+            } else {
+                language.clone()
+            }
+        });
 
     let mut current_input = PartialModalInput::default();
     let mut modal_inputs = vec![];
 
     let msg = ctx
         .send(|f| {
-            f.ephemeral(ctx.data().is_ephemeral)
+            f.ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
                 .content(concat!("Use the buttons below to build new text inputs for your entry modal.\n",
                 "Once you are satisfied with the input, click \"Add Input to Modal\" to add it.\n",
                 "Inputs added will be previewed below. Once you are finished, click \"Create Modal\" to create your new entry modal.")
@@ -370,8 +521,7 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
             }
             "addToModal" => match current_input.into_complete()? {
                 Ok(complete) => {
-                    let new_content =
-                        format!("{}\n`{}`", msg.message().await?.content, complete.label);
+                    let new_content = format!("{}\n{}", msg.message().await?.content, complete);
                     modal_inputs.push(complete);
                     current_input = PartialModalInput::default();
                     msg.edit(ctx, |f| {
@@ -389,7 +539,7 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
                     x.defer(ctx).await?;
                     x.create_followup_message(ctx, |f| {
                         f.content("Minimum length must be smaller than maximum length!")
-                            .ephemeral(ctx.data().is_ephemeral)
+                            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
                     })
                     .await?;
                 }
@@ -398,7 +548,7 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
                 current_input.style = x
                     .data
                     .values
-                    .get(0)
+                    .first()
                     .map(|x| match x.as_str() {
                         "Short" => Ok(serenity::InputTextStyle::Short),
                         "Paragraph" => Ok(serenity::InputTextStyle::Paragraph),
@@ -418,7 +568,7 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
                 current_input.min = x
                     .data
                     .values
-                    .get(0)
+                    .first()
                     .map(|x| x.as_str().parse())
                     .transpose()?;
                 x.create_interaction_response(ctx, |f| {
@@ -430,7 +580,7 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
                 current_input.max = x
                     .data
                     .values
-                    .get(0)
+                    .first()
                     .map(|x| x.as_str().parse())
                     .transpose()?;
                 x.create_interaction_response(ctx, |f| {
@@ -459,15 +609,36 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
     }
 
     if let Some(to_respond) = to_respond {
+        let mut changes = vec![(
+            "Inputs".to_owned(),
+            modal_inputs
+                .iter()
+                .map(|x| format!("`{}`", x.label))
+                .join("\n"),
+        )];
+        if language != DEFAULT_LANGUAGE {
+            changes.push(("Language".to_owned(), format!("{label}, `{language}`")));
+        }
+        modals.0.insert(
+            language,
+            LanguageModal {
+                label,
+                inputs: ModalStructure(modal_inputs),
+            },
+        );
         let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
-        model.id = ActiveValue::Unchanged(guild.as_u64().repack());
-        model.entry_modal = ActiveValue::Set(Some(rmp_serde::to_vec_named(&modal_inputs)?));
+        model.id = ActiveValue::Unchanged(guild.into());
+        model.entry_modal_json =
+            ActiveValue::Set(Some(super::serialization::encode_modals(&modals)?));
+        model.entry_modal_updated_at = ActiveValue::Set(Some(chrono::Utc::now()));
         model.update(&ctx.data().db).await?;
 
+        super::config_audit(ctx, guild, "Entry modal updated", changes).await?;
+
         display_entry_modal(ctx.serenity_context(), ctx.data(), guild).await?;
         to_respond
             .create_followup_message(ctx, |f| {
-                f.ephemeral(ctx.data().is_ephemeral)
+                f.ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
                     .content("Created new entry modal.")
             })
             .await?;
@@ -478,13 +649,128 @@ pub async fn set_entry_modal(ctx: super::Context<'_>) -> Result<(), super::Error
     Ok(())
 }
 
+/// Stop offering the entry form in a language
+#[tracing::instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn remove_entry_modal(
+    ctx: super::Context<'_>,
+    #[description = "Language code of the version to remove, or \"default\" for the one set without a language"]
+    language: String,
+) -> Result<(), super::Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let mut modals = parse_entry_modal(
+        old_profile.entry_modal_json.as_deref(),
+        old_profile.entry_modal.as_deref(),
+    )?;
+    let Some((language, removed)) =
+        parse_language(&language).and_then(|x| modals.0.remove_entry(&x))
+    else {
+        ctx.send(|f| {
+            f.content("The entry form isn't offered in that language.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    if modals.0.is_empty() {
+        // Clear the MessagePack copy too, so it isn't read in place of the JSON
+        model.entry_modal = ActiveValue::Set(None);
+        model.entry_modal_json = ActiveValue::Set(None);
+    } else {
+        model.entry_modal_json =
+            ActiveValue::Set(Some(super::serialization::encode_modals(&modals)?));
+    }
+    model.entry_modal_updated_at = ActiveValue::Set(Some(chrono::Utc::now()));
+    model.update(&ctx.data().db).await?;
+
+    super::config_audit(
+        ctx,
+        guild,
+        "Entry modal removed",
+        vec![(
+            "Language".to_owned(),
+            format!("{}, `{}`", removed.label, language),
+        )],
+    )
+    .await?;
+
+    display_entry_modal(ctx.serenity_context(), ctx.data(), guild).await?;
+    ctx.send(|f| {
+        f.content(if modals.0.is_empty() {
+            "Removed the entry form, so new members will wait for a mod instead.".to_owned()
+        } else {
+            format!("Removed the `{language}` entry form.")
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
 #[derive(FromQueryResult)]
 struct DisplayEntryModalData {
-    screening_channel: i64,
+    screening_channel: ids::DbChannelId,
+    screening_post: Option<ids::DbChannelId>,
     entry_modal: Option<Vec<u8>>,
+    entry_modal_json: Option<String>,
+    screening_welcome_text: Option<String>,
+    screening_fallback_text: Option<String>,
 }
 
-const MAX_BULK_DELETE: usize = 100;
+// TODO: Drop the MessagePack fallback once all servers have been migrated
+pub(super) fn parse_entry_modal(
+    json: Option<&str>,
+    legacy: Option<&[u8]>,
+) -> Result<EntryModals, super::Error> {
+    Ok(match (json, legacy) {
+        (Some(x), _) => super::serialization::decode_modals(x)?,
+        (None, Some(x)) => EntryModals::single(super::serialization::decode_legacy_modal(x)?),
+        (None, None) => EntryModals::default(),
+    })
+}
+
+#[derive(FromQueryResult)]
+struct LegacyEntryModalData {
+    id: ids::DbGuildId,
+    entry_modal: Option<Vec<u8>>,
+}
+
+/// Copy MessagePack entry modals into the JSON column
+#[tracing::instrument(skip_all, err)]
+pub async fn migrate_entry_modals(db: &DatabaseConnection) -> Result<(), super::Error> {
+    let to_migrate: Vec<LegacyEntryModalData> = Servers::find()
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::EntryModal)
+        .filter(servers::Column::EntryModalJson.is_null())
+        .filter(servers::Column::EntryModal.is_not_null())
+        .into_model()
+        .all(db)
+        .await?;
+
+    for i in to_migrate {
+        let x = parse_entry_modal(None, i.entry_modal.as_deref())?;
+        if !x.0.is_empty() {
+            let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+            model.id = ActiveValue::Unchanged(i.id);
+            model.entry_modal_json =
+                ActiveValue::Set(Some(super::serialization::encode_modals(&x)?));
+            model.update(db).await?;
+            tracing::info!("Migrated entry modal for server {} to JSON", i.id);
+        }
+    }
+    Ok(())
+}
 
 #[tracing::instrument(skip_all, err)]
 pub async fn display_entry_modal(
@@ -492,91 +778,616 @@ pub async fn display_entry_modal(
     data: &super::Data,
     guild: serenity::GuildId,
 ) -> Result<(), super::Error> {
-    let server_data: DisplayEntryModalData = Servers::find_by_id(guild.as_u64().repack())
+    let data = FormState::from(data);
+    if let Some(form) = post_entry_form(ctx, &data, guild).await? {
+        tokio::spawn(listen_for_forms(ctx.clone(), data, form, guild));
+    }
+    Ok(())
+}
+
+/// A posted entry form and the collector for its button
+struct PostedForm {
+    button_stream: serenity::ComponentInteractionCollector,
+    modals: EntryModals,
+}
+
+/// What the entry form and its listener need from `Data`, owned so spawned tasks can re-post
+#[derive(Clone)]
+struct FormState {
+    db: DatabaseConnection,
+    bot_id: serenity::UserId,
+    config_health: super::config_health::ConfigHealth,
+    mod_notifier: super::notifications::ModNotifier,
+    entry_forms: EntryForms,
+}
+
+impl From<&super::Data> for FormState {
+    fn from(data: &super::Data) -> Self {
+        Self {
+            db: data.db.clone(),
+            bot_id: data.bot_id,
+            config_health: data.config_health.clone(),
+            mod_notifier: data.mod_notifier.clone(),
+            entry_forms: data.entry_forms.clone(),
+        }
+    }
+}
+
+/// The live entry form message per guild, so deleting it can be told apart from replacing it
+#[derive(Default, Clone)]
+pub struct EntryForms {
+    live: Arc<RwLock<HashMap<serenity::GuildId, serenity::MessageId>>>,
+    /// Guilds whose deleted form is waiting to be posted again
+    reposting: Arc<dashmap::DashSet<serenity::GuildId>>,
+}
+
+impl EntryForms {
+    fn set(&self, guild: serenity::GuildId, msg: Option<serenity::MessageId>) {
+        if let Ok(mut map) = self.live.write() {
+            match msg {
+                Some(x) => map.insert(guild, x),
+                None => map.remove(&guild),
+            };
+        }
+    }
+
+    fn is_current(&self, guild: serenity::GuildId, msg: serenity::MessageId) -> bool {
+        self.live
+            .read()
+            .ok()
+            .is_some_and(|x| x.get(&guild) == Some(&msg))
+    }
+}
+
+/// Replace the screening channel's bot messages with the welcome prompt
+async fn post_entry_form(
+    ctx: &serenity::Context,
+    data: &FormState,
+    guild: serenity::GuildId,
+) -> Result<Option<PostedForm>, super::Error> {
+    if data.config_health.is_broken(
+        guild,
+        super::config_health::ConfiguredEntity::ScreeningChannel,
+    ) {
+        return Ok(None);
+    }
+
+    let server_data: DisplayEntryModalData = Servers::find_by_id(guild)
         .select_only()
         .column(servers::Column::Id)
         .column(servers::Column::ScreeningChannel)
+        .column(servers::Column::ScreeningPost)
         .column(servers::Column::EntryModal)
+        .column(servers::Column::EntryModalJson)
+        .column(servers::Column::ScreeningWelcomeText)
+        .column(servers::Column::ScreeningFallbackText)
         .into_model()
         .one(&data.db)
         .await?
         .ok_or(super::FedBotError::new("Failed to find query"))?;
 
-    let screening_channel = serenity::ChannelId(server_data.screening_channel.repack());
-    let mut msg_generator = screening_channel
-        .messages(ctx, |f| f)
+    let screening_channel = serenity::ChannelId::from(server_data.screening_channel);
+    let modal = parse_entry_modal(
+        server_data.entry_modal_json.as_deref(),
+        server_data.entry_modal.as_deref(),
+    )?;
+    let content = if !modal.0.is_empty() {
+        server_data
+            .screening_welcome_text
+            .as_deref()
+            .unwrap_or(FORM_WELCOME)
+    } else {
+        server_data
+            .screening_fallback_text
+            .as_deref()
+            .unwrap_or(WAIT_WELCOME)
+    };
+
+    let msg = if super::is_forum(ctx, screening_channel).await? {
+        forum_entry_post(
+            ctx,
+            data,
+            guild,
+            screening_channel,
+            server_data.screening_post,
+            content,
+            &modal,
+        )
         .await?
-        .into_iter()
-        .filter_map(|x| {
-            if x.author.id == ctx.cache.current_user_field(|y| y.id) {
-                Some(x.id)
-            } else {
-                None
-            }
-        })
-        .array_chunks::<MAX_BULK_DELETE>();
-    for i in msg_generator.by_ref() {
-        screening_channel.delete_messages(ctx, i).await?;
-    }
-    if let Some(x) = msg_generator.into_remainder() {
-        let remainder = x.collect::<Vec<_>>();
-        match remainder.len().cmp(&1) {
-            Ordering::Equal => {
-                screening_channel.delete_message(ctx, &remainder[0]).await?;
-            }
-            Ordering::Greater => {
-                screening_channel.delete_messages(ctx, remainder).await?;
-            }
-            Ordering::Less => (),
+    } else {
+        let stale = screening_channel
+            .messages(ctx, |f| f)
+            .await?
+            .into_iter()
+            .filter(|x| x.author.id == data.bot_id)
+            .map(|x| (x.id, x.timestamp));
+        let summary = super::bulk_delete(ctx, screening_channel, stale).await;
+        tracing::info!("Cleared old entry form in guild '{}': {}", guild, summary);
+
+        if !modal.0.is_empty() {
+            screening_channel
+                .send_message(ctx, |f| {
+                    f.content(content).components(|f| form_buttons(f, &modal))
+                })
+                .await?
+        } else {
+            screening_channel.say(ctx, content).await?
+        }
+    };
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.screening_message_id = ActiveValue::Set(Some(msg.id.into()));
+    model.update(&data.db).await?;
+    Ok(track_form(ctx, data, guild, modal, &msg))
+}
+
+const FORM_WELCOME: &str = "Welcome! Please fill out this form so our mods can learn a little bit more about you. Thank you for your cooperation!";
+const WAIT_WELCOME: &str = "Welcome. Please wait. Mods will be here shortly.";
+const FORUM_POST_TITLE: &str = "Start here";
+const PINNED_THREAD_FLAG: u64 = 1 << 1;
+
+/// One button per language, opening its modal
+fn form_buttons<'a>(
+    f: &'a mut serenity::CreateComponents,
+    modals: &EntryModals,
+) -> &'a mut serenity::CreateComponents {
+    f.create_action_row(|f| {
+        for (code, x) in &modals.0 {
+            f.create_button(|f| f.custom_id(modals.button_id(code)).label(&x.label));
         }
+        f
+    })
+}
+
+/// Record `msg` as the live form and listen to it, if the server has an entry form
+fn track_form(
+    ctx: &serenity::Context,
+    data: &FormState,
+    guild: serenity::GuildId,
+    modals: EntryModals,
+    msg: &serenity::Message,
+) -> Option<PostedForm> {
+    // Forms without a button are tracked too, so they're reposted if deleted
+    data.entry_forms.set(guild, Some(msg.id));
+    if modals.0.is_empty() {
+        return None;
     }
+    Some(PostedForm {
+        button_stream: msg.await_component_interactions(ctx).build(),
+        modals,
+    })
+}
 
-    if let Some(x) = server_data.entry_modal {
-        let msg = screening_channel.send_message(ctx, |f|
-        f.content("Welcome! Please fill out this form so our mods can learn a little bit more about you. Thank you for your cooperation!").components(|f| f.create_action_row(|f| f.create_button(|f| f.custom_id("completeForm").label("Complete Form"))))).await?;
-        tokio::spawn(listen_for_forms(
-            msg.await_component_interactions(ctx).build(),
-            data.db.clone(),
-            x,
-            ctx.http.clone(),
-            ctx.shard.clone(),
-            guild,
-        ));
+/// Reuse the stored "Start here" post in a forum screening channel, or create and pin a new one
+async fn forum_entry_post(
+    ctx: &serenity::Context,
+    data: &FormState,
+    guild: serenity::GuildId,
+    forum: serenity::ChannelId,
+    post: Option<ids::DbChannelId>,
+    content: &str,
+    modals: &EntryModals,
+) -> Result<serenity::Message, super::Error> {
+    let mut components = serenity::CreateComponents::default();
+    if !modals.0.is_empty() {
+        form_buttons(&mut components, modals);
+    }
+
+    if let Some(post) = post.map(serenity::ChannelId::from) {
+        let in_forum = matches!(
+            post.to_channel(ctx).await,
+            Ok(serenity::Channel::Guild(x)) if x.parent_id == Some(forum)
+        );
+        // A forum post's starter message shares its id
+        if let (true, Ok(mut msg)) = (in_forum, post.message(ctx, post.0).await) {
+            msg.edit(ctx, |f| {
+                f.content(content).components(|f| {
+                    *f = components;
+                    f
+                })
+            })
+            .await?;
+            return Ok(msg);
+        }
+    }
+
+    // serenity has no forum post builder, but posts are created through the same route as
+    // private threads, with the starter message inline
+    let mut map = serde_json::Map::new();
+    map.insert("name".to_owned(), FORUM_POST_TITLE.into());
+    map.insert(
+        "message".to_owned(),
+        serde_json::json!({ "content": content, "components": components.0 }),
+    );
+    let post = ctx.http.create_private_thread(forum.0, &map).await?;
+
+    let mut flags = serde_json::Map::new();
+    flags.insert("flags".to_owned(), PINNED_THREAD_FLAG.into());
+    ctx.http.edit_thread(post.id.0, &flags).await?;
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.screening_post = ActiveValue::Set(Some(post.id.into()));
+    model.update(&data.db).await?;
+
+    Ok(post.id.message(ctx, post.id.0).await?)
+}
+
+const MAX_MESSAGE_LENGTH: usize = 2000;
+const MASS_MENTIONS: [&str; 2] = ["@everyone", "@here"];
+
+#[derive(Debug, Modal)]
+#[name = "Screening Messages"]
+struct ScreeningTextModal {
+    #[name = "Welcome (with entry form)"]
+    #[placeholder = "Leave blank to use the default"]
+    #[paragraph]
+    welcome: Option<String>,
+    #[name = "Welcome (without entry form)"]
+    #[placeholder = "Leave blank to use the default"]
+    #[paragraph]
+    fallback: Option<String>,
+}
+
+/// Strip mass mentions from custom screening text, treating blank or default text as unset
+fn clean_screening_text(text: Option<String>, default: &str) -> Option<String> {
+    let mut text = text?;
+    // Removing one mention can join the text around it into another
+    while let Some(x) = MASS_MENTIONS.iter().find(|x| text.contains(**x)) {
+        text = text.replace(x, "");
+    }
+    let text = text.trim();
+    if text.is_empty() || text == default {
+        None
     } else {
-        screening_channel
-            .say(ctx, "Welcome. Please wait. Mods will be here shortly.")
+        Some(text.to_owned())
+    }
+}
+
+/// Customize the messages posted in the screening channel
+#[tracing::instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn screening_text(ctx: super::Context<'_>) -> Result<(), super::Error> {
+    let super::Context::Application(modal_ctx) = ctx else {
+        return Err(super::FedBotError::new("command must be used in application context").into());
+    };
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let Some(input) = ScreeningTextModal::execute_with_defaults(
+        modal_ctx,
+        ScreeningTextModal {
+            welcome: Some(
+                old_profile
+                    .screening_welcome_text
+                    .clone()
+                    .unwrap_or_else(|| FORM_WELCOME.to_owned()),
+            ),
+            fallback: Some(
+                old_profile
+                    .screening_fallback_text
+                    .clone()
+                    .unwrap_or_else(|| WAIT_WELCOME.to_owned()),
+            ),
+        },
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let welcome = clean_screening_text(input.welcome, FORM_WELCOME);
+    let fallback = clean_screening_text(input.fallback, WAIT_WELCOME);
+    for (name, text) in [("welcome", &welcome), ("fallback", &fallback)] {
+        let length = text.as_deref().map_or(0, |x| x.chars().count());
+        if length > MAX_MESSAGE_LENGTH {
+            ctx.send(|f| {
+                f.content(format!(
+                    "The {name} text must fit in one message ({MAX_MESSAGE_LENGTH} characters), but it is {length} characters."
+                ))
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
             .await?;
+            return Ok(());
+        }
+    }
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.screening_welcome_text = ActiveValue::Set(welcome);
+    model.screening_fallback_text = ActiveValue::Set(fallback);
+    let changes = super::profile_setup::diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Screening text updated", changes).await?;
+        display_entry_modal(ctx.serenity_context(), ctx.data(), guild).await?;
+    }
+
+    ctx.send(|f| {
+        f.content("Updated screening text.")
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+const USER_FORM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(24 * 3600);
+
+/// Give a user their own entry form button, for when the one in the screening channel isn't working for them
+#[tracing::instrument(skip_all, err)]
+#[poise::command(context_menu_command = "Send Entry Form", guild_only)]
+pub async fn send_entry_form(
+    ctx: super::Context<'_>,
+    user: serenity::User,
+) -> Result<(), super::Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, super::PermissionTier::Mod, &server_data);
+
+    let modals = parse_entry_modal(
+        server_data.entry_modal_json.as_deref(),
+        server_data.entry_modal.as_deref(),
+    )?;
+    if modals.0.is_empty() {
+        ctx.send(|f| {
+            f.content("This server doesn't have an entry form set up.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
     }
+
+    // Forums can't hold messages directly, so use the "Start here" post
+    let screening_channel = serenity::ChannelId::from(server_data.screening_channel);
+    let Some(channel) = (if super::is_forum(ctx, screening_channel).await? {
+        server_data.screening_post.map(serenity::ChannelId::from)
+    } else {
+        Some(screening_channel)
+    }) else {
+        ctx.send(|f| {
+            f.content("The screening forum doesn't have an entry form post yet.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+
+    crate::defer!(ctx);
+
+    let msg = channel
+        .send_message(ctx, |f| {
+            f.content(format!(
+                "{}, here is a new entry form for you.",
+                user.mention()
+            ))
+            .components(|f| form_buttons(f, &modals))
+            .allowed_mentions(|f| f.users([user.id]))
+        })
+        .await?;
+    let form = PostedForm {
+        button_stream: msg
+            .await_component_interactions(ctx)
+            .author_id(user.id)
+            .timeout(USER_FORM_TIMEOUT)
+            .build(),
+        modals,
+    };
+    let (ctx_clone, data) = (ctx.serenity_context().clone(), FormState::from(ctx.data()));
+    tokio::spawn(async move {
+        _ = super::t(listen_for_forms(ctx_clone.clone(), data, form, guild).await);
+        _ = super::t(msg.delete(&ctx_clone).await);
+    });
+
+    tracing::info!(
+        "User '{}#{}' sent an entry form to '{}#{}'",
+        ctx.author().name,
+        ctx.author().discriminator,
+        user.name,
+        user.discriminator
+    );
+    ctx.send(|f| {
+        f.content(format!("Sent an entry form to {}.", user.mention()))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
     Ok(())
 }
 
 #[derive(FromQueryResult)]
 struct FormSubmitData {
-    mod_channel: i64,
-    mod_role: i64,
+    mod_channel: ids::DbChannelId,
+    mod_role: ids::DbRoleId,
 }
 
+const MAX_EMBED_TITLE_LENGTH: usize = 256;
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
 const MAX_TOTAL_EMBED_LENGTH: usize = 6000;
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+/// One embed's worth of an entry form answer
+#[derive(Debug, PartialEq, Eq)]
+struct AnswerChunk<'a> {
+    title: String,
+    text: &'a str,
+}
+
+/// Split `text` into parts of at most `max` characters, breaking at whitespace where that doesn't
+/// waste more than half a part
+fn split_text(text: &str, max: usize) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = text;
+    while rest.chars().count() > max {
+        let end = rest.char_indices().nth(max).map_or(rest.len(), |x| x.0);
+        let cut = rest[..end]
+            .char_indices()
+            .rev()
+            .find(|x| x.1.is_whitespace())
+            .map(|(i, x)| i + x.len_utf8())
+            .filter(|x| *x >= end / 2)
+            .unwrap_or(end);
+        parts.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    parts.push(rest);
+    parts
+}
+
+/// Spread an answer over as many embeds as it needs, numbering the titles when there are several
+fn chunk_answer<'a>(label: &str, value: &'a str) -> Vec<AnswerChunk<'a>> {
+    let parts = split_text(value, MAX_EMBED_DESCRIPTION_LENGTH);
+    let total = parts.len();
+    if total == 1 {
+        return vec![AnswerChunk {
+            title: label.chars().take(MAX_EMBED_TITLE_LENGTH).collect(),
+            text: value,
+        }];
+    }
+    let suffix_length = format!(" ({total}/{total})").chars().count();
+    let label = label
+        .chars()
+        .take(MAX_EMBED_TITLE_LENGTH.saturating_sub(suffix_length))
+        .collect::<String>();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| AnswerChunk {
+            title: format!("{label} ({}/{total})", i + 1),
+            text,
+        })
+        .collect()
+}
+
+/// Group embeds of the given lengths into messages within Discord's per-message limits
+fn batch_embeds(lengths: impl IntoIterator<Item = usize>) -> Vec<std::ops::Range<usize>> {
+    let mut batches = vec![];
+    let (mut start, mut end, mut total) = (0, 0, 0);
+    for length in lengths {
+        if end > start
+            && (total + length > MAX_TOTAL_EMBED_LENGTH || end - start == MAX_EMBEDS_PER_MESSAGE)
+        {
+            batches.push(start..end);
+            (start, total) = (end, 0);
+        }
+        total += length;
+        end += 1;
+    }
+    if end > start {
+        batches.push(start..end);
+    }
+    batches
+}
+
+/// Every answer in plain text, for when they can't be shown as embeds
+fn answers_text(answers: &[(&str, &str)]) -> String {
+    answers
+        .iter()
+        .map(|(label, value)| format!("{label}\n{value}"))
+        .join("\n\n")
+}
+
+const DAILY_LIMIT_REACHED: &str =
+    "You have already submitted your form today. Please wait for a moderator response.";
+
+#[derive(FromQueryResult)]
+struct DailyLimitData {
+    screening_daily_limit: Option<i8>,
+}
+
+/// Whether `count` submissions in the last day use up the guild's limit, `None` meaning unlimited
+fn at_daily_limit(limit: Option<i8>, count: u64) -> bool {
+    limit.is_some_and(|x| count >= u64::try_from(x).unwrap_or_default())
+}
+
+async fn daily_limit_reached(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    user: serenity::UserId,
+) -> Result<bool, super::Error> {
+    let limit = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::ScreeningDailyLimit)
+        .into_model::<DailyLimitData>()
+        .one(db)
+        .await?
+        .and_then(|x| x.screening_daily_limit);
+    if limit.is_none() {
+        return Ok(false);
+    }
+
+    let count = ScreeningSubmissions::find()
+        .filter(screening_submissions::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .filter(screening_submissions::Column::UserId.eq(ids::DbUserId::from(user)))
+        .filter(
+            screening_submissions::Column::SubmittedAt
+                .gt(chrono::Utc::now() - chrono::Duration::days(1)),
+        )
+        .count(db)
+        .await?;
+    Ok(at_daily_limit(limit, count))
+}
+
+async fn record_submission(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    user: serenity::UserId,
+) -> Result<(), super::Error> {
+    let now = chrono::Utc::now();
+    // Only the last day is ever counted
+    ScreeningSubmissions::delete_many()
+        .filter(screening_submissions::Column::SubmittedAt.lt(now - chrono::Duration::days(1)))
+        .exec(db)
+        .await?;
+    ScreeningSubmissions::insert(screening_submissions::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        user_id: ActiveValue::Set(user.into()),
+        submitted_at: ActiveValue::Set(now),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
 
 #[tracing::instrument(skip_all, err)]
 async fn listen_for_forms(
-    mut button_stream: serenity::ComponentInteractionCollector,
-    db: sea_orm::DatabaseConnection,
-    raw_modal: Vec<u8>,
-    http: Arc<serenity::Http>,
-    shard: serenity::ShardMessenger,
+    ctx: serenity::Context,
+    data: FormState,
+    mut form: PostedForm,
     guild: serenity::GuildId,
 ) -> Result<(), super::Error> {
-    let modal_data: ModalStructure = rmp_serde::from_slice(&raw_modal)?;
+    let (http, shard) = (ctx.http.clone(), ctx.shard.clone());
+    while let Some(evt) = form.button_stream.next().await {
+        // Buttons from an older layout are left to the listener of the form that has them
+        let Some((code, modal)) = form.modals.for_button(&evt.data.custom_id) else {
+            continue;
+        };
+        if daily_limit_reached(&data.db, guild, evt.user.id).await? {
+            evt.create_interaction_response(&http, |f| {
+                f.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|f| f.content(DAILY_LIMIT_REACHED).ephemeral(true))
+            })
+            .await?;
+            continue;
+        }
 
-    while let Some(evt) = button_stream.next().await {
         /* Tweak of poise::Modal::execute to run a modal without a Context
            https://docs.rs/poise/0.5.4/src/poise/modal.rs.html#53-91
            Licensed under the MIT license
            https://docs.rs/crate/poise/0.5.4/source/LICENSE
         */
         evt.create_interaction_response(&http, |f| {
-            *f = EntryModal::create(Some(EntryModal(&modal_data)), "entryModal".to_string());
+            *f = EntryModal::create(Some(EntryModal(&modal.inputs)), "entryModal".to_string());
             f
         })
         .await?;
@@ -588,20 +1399,142 @@ async fn listen_for_forms(
 
         tokio::spawn(wait_for_modal(
             modal_collector,
-            db.clone(),
+            data.db.clone(),
             http.clone(),
             guild,
+            data.mod_notifier.clone(),
+            form.modals.language_note(code),
         ));
     }
     Ok(())
 }
 
+const REPOST_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+/// How far back a deletion in the audit log can be and still be the entry form's
+const DELETER_LOOKBACK_SECS: i64 = 60;
+
+/// Post the entry form again if it's among `deleted`
+pub async fn messages_deleted(
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    deleted: &[serenity::MessageId],
+    reference: super::EventReference<'_>,
+) -> Result<(), super::Error> {
+    let data = reference.3;
+    let Some(msg) = deleted
+        .iter()
+        .copied()
+        .find(|x| data.entry_forms.is_current(guild, *x))
+    else {
+        return Ok(());
+    };
+    // A sweep deleting messages one by one only gets one repost
+    if !data.entry_forms.reposting.insert(guild) {
+        return Ok(());
+    }
+    let state = FormState::from(data);
+    let ctx = reference.0.clone();
+    tokio::spawn(async move {
+        let result = repost_deleted_form(&ctx, &state, guild, channel, msg).await;
+        state.entry_forms.reposting.remove(&guild);
+        result
+    });
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, err)]
+async fn repost_deleted_form(
+    ctx: &serenity::Context,
+    data: &FormState,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    msg: serenity::MessageId,
+) -> Result<(), super::Error> {
+    // Let a sweep of the channel finish, so the new form isn't caught in it
+    tokio::time::sleep(REPOST_DELAY).await;
+
+    // No profile means it was cleared on purpose, and a different message means the form was
+    // already replaced
+    let Some(stored) = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::ScreeningMessageId)
+        .into_model::<ScreeningMessageData>()
+        .one(&data.db)
+        .await?
+    else {
+        return Ok(());
+    };
+    if stored.screening_message_id.map(serenity::MessageId::from) != Some(msg) {
+        return Ok(());
+    }
+
+    let deleter = super::t(find_deleter(ctx, guild, channel, data.bot_id).await)
+        .ok()
+        .flatten();
+    warn!(
+        "Entry form in guild '{}' was deleted, posting a new one",
+        guild
+    );
+    if let Some(form) = post_entry_form(ctx, data, guild).await? {
+        tokio::spawn(listen_for_forms(ctx.clone(), data.clone(), form, guild));
+    }
+    super::send_mod_log(
+        ctx,
+        &data.db,
+        &data.config_health,
+        guild,
+        None,
+        format!(
+            "The entry form in {} was deleted{}, so it was posted again.",
+            channel.mention(),
+            deleter.map_or_else(String::new, |x| format!(" by {}", x.mention()))
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct ScreeningMessageData {
+    screening_message_id: Option<ids::DbMessageId>,
+}
+
+/// Who recently deleted the bot's messages in `channel`, if the audit log says
+async fn find_deleter(
+    ctx: &serenity::Context,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    bot_id: serenity::UserId,
+) -> Result<Option<serenity::UserId>, super::Error> {
+    let cutoff = serenity::Timestamp::now().unix_timestamp() - DELETER_LOOKBACK_SECS;
+    let logs = guild.audit_logs(ctx, None, None, None, Some(10)).await?;
+    Ok(logs
+        .entries
+        .into_iter()
+        .filter(|x| x.id.created_at().unix_timestamp() >= cutoff)
+        .find(|x| match x.action {
+            // Deleting someone else's message logs its author, while bulk deletes log the channel
+            serenity::Action::Message(serenity::MessageAction::Delete) => {
+                x.target_id == Some(bot_id.0)
+                    && x.options.as_ref().and_then(|y| y.channel_id) == Some(channel)
+            }
+            serenity::Action::Message(serenity::MessageAction::BulkDelete) => {
+                x.target_id == Some(channel.0)
+            }
+            _ => false,
+        })
+        .map(|x| x.user_id))
+}
+
 #[tracing::instrument(skip_all, err)]
 async fn wait_for_modal(
     mut modal_collector: serenity::ModalInteractionCollector,
     db: sea_orm::DatabaseConnection,
     http: Arc<serenity::Http>,
     guild: serenity::GuildId,
+    notifier: super::notifications::ModNotifier,
+    language: Option<String>,
 ) -> Result<(), super::Error> {
     if let Some(raw_response) = modal_collector.next().await {
         raw_response
@@ -609,8 +1542,9 @@ async fn wait_for_modal(
                 f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
             })
             .await?;
+        record_submission(&db, guild, raw_response.user.id).await?;
 
-        let server_data: FormSubmitData = Servers::find_by_id(guild.as_u64().repack())
+        let server_data: FormSubmitData = Servers::find_by_id(guild)
             .select_only()
             .column(servers::Column::Id)
             .column(servers::Column::ModChannel)
@@ -621,71 +1555,268 @@ async fn wait_for_modal(
             .ok_or(super::FedBotError::new("Failed to find query"))?;
 
         let (mod_channel, mod_role) = (
-            serenity::ChannelId(server_data.mod_channel.repack()),
-            serenity::RoleId(server_data.mod_role.repack()),
+            serenity::ChannelId::from(server_data.mod_channel),
+            serenity::RoleId::from(server_data.mod_role),
         );
 
-        let mut content = format!(
-            "{}, user {} has submitted an entry form:",
+        let intro = format!(
+            "{}, user {} has submitted an entry form{}:",
             mod_role.mention(),
             raw_response.user.mention(),
+            language.map_or_else(String::new, |x| format!(" ({x})")),
         );
-        let mut msg_embeds = vec![];
-        let mut embeds_length: usize = 0;
-
-        for (label, value) in raw_response
+        let answers = raw_response
             .data
             .components
             .iter()
-            .map(|x| {
-                x.components
-                    .iter()
-                    .filter_map(|x| match x {
-                        serenity::ActionRowComponent::InputText(y) => {
-                            if let Some(label) = y.custom_id.get(uuid::fmt::Simple::LENGTH..) {
-                                return Some((label, y.value.as_str()));
-                            }
-                            None
-                        }
-                        _ => None,
-                    })
-                    .collect::<Vec<(&str, &str)>>()
+            .flat_map(|x| &x.components)
+            .filter_map(|x| match x {
+                serenity::ActionRowComponent::InputText(y) => y
+                    .custom_id
+                    .get(uuid::fmt::Simple::LENGTH..)
+                    .map(|label| (label, y.value.as_str())),
+                _ => None,
             })
-            .concat()
-        {
-            let this_embed_length = raw_response.user.tag().len()
-                + raw_response.user.face().len()
-                + label.len()
-                + value.len();
+            .collect::<Vec<_>>();
 
-            if embeds_length + this_embed_length > MAX_TOTAL_EMBED_LENGTH {
+        let author = raw_response.user.tag();
+        let chunks = answers
+            .iter()
+            .flat_map(|(label, value)| chunk_answer(label, value))
+            .collect::<Vec<_>>();
+        let mut sent_intro = false;
+        for batch in batch_embeds(
+            chunks
+                .iter()
+                .map(|x| author.chars().count() + x.title.chars().count() + x.text.chars().count()),
+        ) {
+            let embeds = chunks[batch]
+                .iter()
+                .map(|x| {
+                    let mut embed = serenity::CreateEmbed::default();
+                    embed
+                        .author(|f| {
+                            f.name(&author)
+                                .icon_url(raw_response.user.face())
+                                .url(format!(
+                                    "https://discordapp.com/users/{}",
+                                    raw_response.user.id
+                                ))
+                        })
+                        .title(&x.title)
+                        .description(x.text);
+                    embed
+                })
+                .collect::<Vec<_>>();
+            let content = if sent_intro { "" } else { intro.as_str() };
+            if let Err(e) = mod_channel
+                .send_message(&http, |f| f.content(content).add_embeds(embeds))
+                .await
+            {
+                warn!(
+                    "Failed to post entry form answers from '{}' in guild '{}', attaching them instead: {}",
+                    author, guild, e
+                );
+                let content = if sent_intro {
+                    format!(
+                        "Some of {}'s entry form answers couldn't be shown, so all of them are attached.",
+                        raw_response.user.mention()
+                    )
+                } else {
+                    format!("{intro} (attached, as the answers couldn't be shown)")
+                };
                 mod_channel
-                    .send_message(&http, |f| f.content(content).add_embeds(msg_embeds))
+                    .send_message(&http, |f| {
+                        f.content(content)
+                            .add_file(serenity::AttachmentType::Bytes {
+                                data: answers_text(&answers).into_bytes().into(),
+                                filename: "entry-form.txt".to_owned(),
+                            })
+                    })
                     .await?;
-                content = String::new();
-                msg_embeds = vec![];
-                embeds_length = 0;
+                break;
             }
-
-            embeds_length += this_embed_length;
-            let mut embed = serenity::CreateEmbed::default();
-            embed.author(|f| {
-                f.name(raw_response.user.tag())
-                    .icon_url(raw_response.user.face())
-                    .url(format!(
-                        "https://discordapp.com/users/{}",
-                        raw_response.user.id
-                    ))
-            });
-            embed.title(label);
-            embed.description(value);
-            msg_embeds.push(embed);
-        }
-        if !msg_embeds.is_empty() {
-            mod_channel
-                .send_message(&http, |f| f.content(content).add_embeds(msg_embeds))
-                .await?;
+            sent_intro = true;
         }
+
+        super::notifications::notify_mods(
+            http,
+            db,
+            notifier,
+            guild,
+            mod_channel,
+            format!("User {} submitted an entry form", raw_response.user.tag()),
+        )
+        .await?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(min: Option<u64>, max: Option<u64>, required: bool) -> ModalInput {
+        ModalInput {
+            max,
+            min,
+            label: "About you".to_owned(),
+            placeholder: None,
+            required,
+            style: serenity::InputTextStyle::Short,
+        }
+    }
+
+    #[test]
+    fn input_summary_includes_lengths() {
+        assert_eq!(
+            input(Some(10), Some(500), true).to_string(),
+            "`About you` (Short, required, 10\u{2013}500 chars)"
+        );
+        assert_eq!(
+            input(Some(10), None, false).to_string(),
+            "`About you` (Short, optional, at least 10 chars)"
+        );
+        assert_eq!(
+            input(None, Some(500), true).to_string(),
+            "`About you` (Short, required, up to 500 chars)"
+        );
+        assert_eq!(
+            input(None, None, false).to_string(),
+            "`About you` (Short, optional)"
+        );
+    }
+
+    #[test]
+    fn buttons_open_their_language() {
+        let mut modals = EntryModals::single(ModalStructure(vec![]));
+        assert_eq!(modals.button_id(DEFAULT_LANGUAGE), FORM_BUTTON_ID);
+        assert_eq!(
+            modals.for_button(FORM_BUTTON_ID).map(|x| x.0),
+            Some(DEFAULT_LANGUAGE)
+        );
+        assert_eq!(modals.language_note(DEFAULT_LANGUAGE), None);
+
+        modals.0.insert(
+            "fr".to_owned(),
+            LanguageModal {
+                label: "Français".to_owned(),
+                inputs: ModalStructure(vec![]),
+            },
+        );
+        assert_eq!(modals.button_id("fr"), "completeForm-fr");
+        assert_eq!(
+            modals.for_button("completeForm-fr").map(|x| x.0),
+            Some("fr")
+        );
+        assert!(modals.for_button(FORM_BUTTON_ID).is_none());
+        assert!(modals.for_button("completeForm-de").is_none());
+        assert_eq!(
+            modals.language_note("fr").as_deref(),
+            Some("Français, `fr`")
+        );
+        assert_eq!(
+            modals.language_note(DEFAULT_LANGUAGE).as_deref(),
+            Some("default form")
+        );
+    }
+
+    #[test]
+    fn language_codes_are_normalized() {
+        assert_eq!(parse_language(" PT-br ").as_deref(), Some("pt-br"));
+        assert_eq!(parse_language("en").as_deref(), Some("en"));
+        assert_eq!(parse_language(""), None);
+        assert_eq!(parse_language("en gb"), None);
+        assert_eq!(parse_language(&"a".repeat(17)), None);
+    }
+
+    #[test]
+    fn daily_limit_counts_last_day_submissions() {
+        assert!(!at_daily_limit(None, 100));
+        assert!(!at_daily_limit(Some(2), 1));
+        assert!(at_daily_limit(Some(2), 2));
+        assert!(at_daily_limit(Some(2), 3));
+    }
+
+    #[test]
+    fn screening_text_is_cleaned() {
+        assert_eq!(
+            clean_screening_text(Some(" Hi @everyone and @here! ".to_owned()), FORM_WELCOME),
+            Some("Hi  and !".to_owned())
+        );
+        assert_eq!(
+            clean_screening_text(Some("@@everyoneeveryone".to_owned()), FORM_WELCOME),
+            None
+        );
+        assert_eq!(
+            clean_screening_text(Some("  ".to_owned()), FORM_WELCOME),
+            None
+        );
+        assert_eq!(
+            clean_screening_text(Some(FORM_WELCOME.to_owned()), FORM_WELCOME),
+            None
+        );
+        assert_eq!(clean_screening_text(None, FORM_WELCOME), None);
+    }
+
+    #[test]
+    fn full_length_answers_fit_one_embed() {
+        let value = "a".repeat(4000);
+        assert_eq!(
+            chunk_answer("About you", &value),
+            [AnswerChunk {
+                title: "About you".to_owned(),
+                text: &value
+            }]
+        );
+    }
+
+    #[test]
+    fn oversized_answers_are_continued() {
+        let value = "word ".repeat(1000);
+        let chunks = chunk_answer("About you", &value);
+        assert_eq!(
+            chunks.iter().map(|x| x.title.as_str()).collect::<Vec<_>>(),
+            ["About you (1/2)", "About you (2/2)"]
+        );
+        assert!(chunks
+            .iter()
+            .all(|x| x.text.chars().count() <= MAX_EMBED_DESCRIPTION_LENGTH));
+        assert!(chunks[0].text.ends_with(' '));
+        assert_eq!(chunks.iter().map(|x| x.text).collect::<String>(), value);
+
+        let title = chunk_answer(&"q".repeat(300), &value)[0].title.clone();
+        assert_eq!(title.chars().count(), MAX_EMBED_TITLE_LENGTH);
+        assert!(title.ends_with(" (1/2)"));
+    }
+
+    #[test]
+    fn text_without_spaces_is_split_by_characters() {
+        let value = "\u{e9}".repeat(9000);
+        assert_eq!(
+            split_text(&value, MAX_EMBED_DESCRIPTION_LENGTH)
+                .iter()
+                .map(|x| x.chars().count())
+                .collect::<Vec<_>>(),
+            [4096, 4096, 808]
+        );
+        assert_eq!(split_text("", MAX_EMBED_DESCRIPTION_LENGTH), [""]);
+    }
+
+    #[test]
+    fn embeds_are_batched_within_message_limits() {
+        // Author tag and title around each 4000 character answer
+        assert_eq!(batch_embeds([4050, 4050, 1000, 4050]), [0..1, 1..3, 3..4]);
+        assert_eq!(batch_embeds([100; 12]), [0..10, 10..12]);
+        assert_eq!(batch_embeds([6000, 1]), [0..1, 1..2]);
+        assert!(batch_embeds([]).is_empty());
+    }
+
+    #[test]
+    fn answers_text_lists_every_answer() {
+        assert_eq!(
+            answers_text(&[("Name", "Ann"), ("About you", "Hi\nthere")]),
+            "Name\nAnn\n\nAbout you\nHi\nthere"
+        );
+    }
+}
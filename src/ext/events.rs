@@ -0,0 +1,311 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! In-process bus for things the bot has done, so integrations can subscribe to them instead of
+//! being called from every feature
+
+use super::circuit_breaker::Operation;
+use super::config_health::ConfigHealth;
+use super::{Context, Error};
+use crate::entities::guild_log_channels;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::DatabaseConnection;
+use serenity::Mentionable;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{error, instrument, warn};
+
+/// Events a subscriber can fall behind by before it starts missing them
+const CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BotEvent {
+    MessageFiltered {
+        guild: serenity::GuildId,
+        user: serenity::UserId,
+        reason: String,
+    },
+    ImageBlocked {
+        guild: serenity::GuildId,
+        user: serenity::UserId,
+        hash: String,
+    },
+    MemberAccepted {
+        guild: serenity::GuildId,
+        user: serenity::UserId,
+        moderator: serenity::UserId,
+    },
+    MemberQuestioned {
+        guild: serenity::GuildId,
+        users: Vec<serenity::UserId>,
+        moderator: serenity::UserId,
+        reason: String,
+    },
+    MemberReturned {
+        guild: serenity::GuildId,
+        user: serenity::UserId,
+        moderator: serenity::UserId,
+    },
+    MemberKicked {
+        guild: serenity::GuildId,
+        user: serenity::UserId,
+        reason: String,
+    },
+    TriggerFired {
+        guild: serenity::GuildId,
+        channel: serenity::ChannelId,
+        name: String,
+    },
+    FilteringDegraded {
+        guild: serenity::GuildId,
+        operation: Operation,
+    },
+    FilteringRecovered {
+        guild: serenity::GuildId,
+        operation: Operation,
+    },
+}
+
+impl BotEvent {
+    /// The guild, log purpose and text of this event's mod log notice, if it has one
+    fn notice(
+        &self,
+    ) -> Option<(
+        serenity::GuildId,
+        Option<guild_log_channels::Purpose>,
+        String,
+    )> {
+        Some(match self {
+            Self::MemberAccepted {
+                guild,
+                user,
+                moderator,
+            } => (
+                *guild,
+                None,
+                format!(
+                    "User {} accepted by mod {}",
+                    user.mention(),
+                    moderator.mention()
+                ),
+            ),
+            Self::MemberQuestioned {
+                guild,
+                users,
+                moderator,
+                reason,
+            } => (
+                *guild,
+                None,
+                format!(
+                    "{} {} sent to questioning by mod {} (reason: {})",
+                    if users.len() == 1 { "User" } else { "Users" },
+                    users.iter().map(Mentionable::mention).format(", "),
+                    moderator.mention(),
+                    reason
+                ),
+            ),
+            Self::MemberReturned {
+                guild,
+                user,
+                moderator,
+            } => (
+                *guild,
+                None,
+                format!(
+                    "User {} returned from questioning by mod {}",
+                    user.mention(),
+                    moderator.mention()
+                ),
+            ),
+            Self::FilteringDegraded { guild, operation } => (
+                *guild,
+                Some(guild_log_channels::Purpose::FilterNotices),
+                format!(
+                    "{} keep failing on Discord's side, so {} until they recover.",
+                    operation.label(),
+                    operation.fallback()
+                ),
+            ),
+            Self::FilteringRecovered { guild, operation } => (
+                *guild,
+                Some(guild_log_channels::Purpose::FilterNotices),
+                format!(
+                    "{} are working again, so filtering is back to normal.",
+                    operation.label()
+                ),
+            ),
+            Self::MessageFiltered { .. }
+            | Self::ImageBlocked { .. }
+            | Self::MemberKicked { .. }
+            | Self::TriggerFired { .. } => return None,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<BotEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+            dropped: Arc::default(),
+        }
+    }
+}
+
+impl EventBus {
+    /// Never waits; subscribers that have fallen too far behind miss the oldest events instead
+    pub fn publish(&self, event: BotEvent) {
+        // Only fails when nobody is subscribed, in which case there is nobody to tell
+        _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Events missed by lagging subscribers since startup
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Subscription {
+    receiver: broadcast::Receiver<BotEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// The next event, counting any missed by falling behind, or `None` once the bus is gone
+    pub async fn next(&mut self) -> Option<BotEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(x) => return Some(x),
+                Err(broadcast::error::RecvError::Lagged(x)) => {
+                    self.dropped.fetch_add(x, Ordering::Relaxed);
+                    warn!("Event subscriber fell behind and missed {} events", x);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Post the mod log notice for each event that has one
+pub async fn log_notices(
+    ctx: serenity::Context,
+    db: DatabaseConnection,
+    health: ConfigHealth,
+    mut events: Subscription,
+) {
+    while let Some(event) = events.next().await {
+        let Some((guild, purpose, msg)) = event.notice() else {
+            continue;
+        };
+        if let Err(e) = super::send_mod_log(&ctx, &db, &health, guild, purpose, msg).await {
+            error!("Failed to post mod log notice in guild '{}': {}", guild, e);
+        }
+    }
+}
+
+/// Show how many events subscribers have missed by falling behind since startup
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+pub async fn events(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send(|f| {
+        f.content(format!(
+            "Subscribers have missed {} events since startup.",
+            ctx.data().events.dropped()
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: serenity::GuildId = serenity::GuildId(1);
+
+    #[tokio::test]
+    async fn screening_events_reach_subscribers_as_notices() {
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        bus.publish(BotEvent::MemberAccepted {
+            guild: GUILD,
+            user: serenity::UserId(2),
+            moderator: serenity::UserId(3),
+        });
+        bus.publish(BotEvent::MemberQuestioned {
+            guild: GUILD,
+            users: vec![serenity::UserId(2), serenity::UserId(4)],
+            moderator: serenity::UserId(3),
+            reason: "raid".to_owned(),
+        });
+        bus.publish(BotEvent::TriggerFired {
+            guild: GUILD,
+            channel: serenity::ChannelId(5),
+            name: "rules".to_owned(),
+        });
+
+        assert_eq!(
+            events.next().await.and_then(|x| x.notice()),
+            Some((GUILD, None, "User <@2> accepted by mod <@3>".to_owned()))
+        );
+        assert_eq!(
+            events.next().await.and_then(|x| x.notice()),
+            Some((
+                GUILD,
+                None,
+                "Users <@2>, <@4> sent to questioning by mod <@3> (reason: raid)".to_owned()
+            ))
+        );
+        assert_eq!(events.next().await.and_then(|x| x.notice()), None);
+    }
+
+    #[tokio::test]
+    async fn lagging_subscribers_count_missed_events() {
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        for i in 0..CAPACITY as u64 + 3 {
+            bus.publish(BotEvent::MemberReturned {
+                guild: GUILD,
+                user: serenity::UserId(i),
+                moderator: serenity::UserId(i),
+            });
+        }
+        assert!(matches!(
+            events.next().await,
+            Some(BotEvent::MemberReturned {
+                user: serenity::UserId(3),
+                ..
+            })
+        ));
+        assert_eq!(bus.dropped(), 3);
+    }
+}
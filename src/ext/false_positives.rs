@@ -0,0 +1,403 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Let mods report images the filter shouldn't have deleted, and whitelist them once reviewed
+//!
+//! Reports and reviews carry everything they need in their button IDs, so they keep working
+//! across restarts.
+
+use super::{t, Context, Error, PermissionTier};
+use crate::entities::{prelude::*, *};
+use crate::{check_tier, require_profile};
+use image_hasher::ImageHash;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::collections::HashSet;
+use tracing::{info, instrument};
+
+const REPORT_PREFIX: &str = "image-report-";
+const REVIEW_PREFIX: &str = "image-review-";
+const MAX_AUTOCOMPLETE_OPTIONS: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReviewAction {
+    Whitelist,
+    Keep,
+}
+
+impl ReviewAction {
+    const ALL: [Self; 2] = [Self::Whitelist, Self::Keep];
+
+    const fn id(self) -> &'static str {
+        match self {
+            Self::Whitelist => "whitelist",
+            Self::Keep => "keep",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Whitelist => "Whitelist",
+            Self::Keep => "Keep Blocked",
+        }
+    }
+
+    const fn style(self) -> serenity::ButtonStyle {
+        match self {
+            Self::Whitelist => serenity::ButtonStyle::Success,
+            Self::Keep => serenity::ButtonStyle::Secondary,
+        }
+    }
+
+    fn custom_id(self, hash: &str) -> String {
+        format!("{REVIEW_PREFIX}{}-{}", self.id(), hash)
+    }
+
+    fn parse(custom_id: &str) -> Option<(Self, ImageHash)> {
+        let (action, hash) = custom_id.strip_prefix(REVIEW_PREFIX)?.split_once('-')?;
+        let action = Self::ALL.into_iter().find(|x| x.id() == action)?;
+        Some((action, ImageHash::from_base64(hash).ok()?))
+    }
+}
+
+fn report_id(author: serenity::UserId, hash: &str) -> String {
+    format!("{REPORT_PREFIX}{author}-{hash}")
+}
+
+/// The author of the deleted message and the hash it matched; base64 never contains a dash
+fn parse_report(custom_id: &str) -> Option<(serenity::UserId, ImageHash)> {
+    let (author, hash) = custom_id.strip_prefix(REPORT_PREFIX)?.split_once('-')?;
+    Some((
+        serenity::UserId(author.parse().ok()?),
+        ImageHash::from_base64(hash).ok()?,
+    ))
+}
+
+/// The button on the image filter's deletion notices
+pub fn report_button<'a>(
+    f: &'a mut serenity::CreateComponents,
+    author: serenity::UserId,
+    hash: &str,
+) -> &'a mut serenity::CreateComponents {
+    f.create_action_row(|f| {
+        f.create_button(|f| {
+            f.custom_id(report_id(author, hash))
+                .label("Report false positive")
+                .style(serenity::ButtonStyle::Secondary)
+        })
+    })
+}
+
+/// Hashes the mods of `guild` have cleared, which the filter never acts on
+pub async fn load_whitelist(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+) -> Result<HashSet<ImageHash>, Error> {
+    Ok(WhitelistedHashes::find()
+        .filter(whitelisted_hashes::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|x| ImageHash::from_base64(&x.hash).ok())
+        .collect())
+}
+
+/// Take `hash` off the whitelist, returning whether it was on it
+pub async fn unwhitelist_hash(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    hash: &ImageHash,
+) -> Result<bool, Error> {
+    Ok(
+        WhitelistedHashes::delete_by_id((guild.into(), hash.to_base64()))
+            .exec(db)
+            .await?
+            .rows_affected
+            > 0,
+    )
+}
+
+async fn respond_ephemeral(
+    ctx: &serenity::Context,
+    interaction: &serenity::MessageComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|f| f.content(content).ephemeral(true))
+        })
+        .await?;
+    Ok(())
+}
+
+/// Whether the member pressing a button can act on reports, answering them if not
+async fn check_mod(
+    ctx: &serenity::Context,
+    data: &super::Data,
+    interaction: &serenity::MessageComponentInteraction,
+    guild: serenity::GuildId,
+    member: &serenity::Member,
+) -> Result<bool, Error> {
+    let Some(server_data) = Servers::find_by_id(guild).one(&data.db).await? else {
+        return Ok(false);
+    };
+    if super::has_mod_role(&data.db, guild, server_data.mod_role.into(), &member.roles).await? {
+        return Ok(true);
+    }
+    respond_ephemeral(ctx, interaction, "Only mods can review filtered images.").await?;
+    Ok(false)
+}
+
+/// Handle the report button on deletion notices and the buttons on the reviews it posts
+#[instrument(skip_all, err)]
+pub async fn handle_buttons(
+    interaction: &serenity::MessageComponentInteraction,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let (Some(guild), Some(member)) = (interaction.guild_id, interaction.member.as_ref()) else {
+        return Ok(());
+    };
+    if let Some((author, hash)) = parse_report(&interaction.data.custom_id) {
+        if check_mod(reference.0, reference.3, interaction, guild, member).await? {
+            report(interaction, reference, guild, member, author, &hash).await?;
+        }
+    } else if let Some((action, hash)) = ReviewAction::parse(&interaction.data.custom_id) {
+        if check_mod(reference.0, reference.3, interaction, guild, member).await? {
+            review(interaction, reference, guild, member, action, &hash).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Send a deletion notice's image to the mods for review
+async fn report(
+    interaction: &serenity::MessageComponentInteraction,
+    reference: super::EventReference<'_>,
+    guild: serenity::GuildId,
+    member: &serenity::Member,
+    author: serenity::UserId,
+    hash: &ImageHash,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    let Some(review_channel) = super::mod_channel(
+        data,
+        guild,
+        Some(guild_log_channels::Purpose::FilterNotices),
+    )
+    .await?
+    else {
+        respond_ephemeral(
+            ctx,
+            interaction,
+            "There's no mod channel to send the report to.",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    // Take the button off first, so the image is only reported once
+    interaction
+        .create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|f| f.components(|f| f))
+        })
+        .await?;
+
+    // The notice quotes the deleted message below its first line
+    let excerpt = interaction
+        .message
+        .content
+        .split_once('\n')
+        .map_or("*No text*", |x| x.1);
+    let hash = hash.to_base64();
+    review_channel
+        .send_message(ctx, |f| {
+            f.embed(|f| {
+                f.title("Image filter false positive report")
+                    .description(excerpt)
+                    .field("Hash", format!("`{hash}`"), true)
+                    .field("Author", author.mention(), true)
+                    .field("Channel", interaction.channel_id.mention(), true)
+                    .field("Reported by", member.mention(), true)
+                    .timestamp(interaction.message.timestamp)
+            })
+            .components(|f| {
+                f.create_action_row(|f| {
+                    for action in ReviewAction::ALL {
+                        f.create_button(|f| {
+                            f.custom_id(action.custom_id(&hash))
+                                .label(action.label())
+                                .style(action.style())
+                        });
+                    }
+                    f
+                })
+            })
+            .allowed_mentions(|f| f.empty_users())
+        })
+        .await?;
+    _ = t(interaction
+        .create_followup_message(ctx, |f| {
+            f.content("Sent the image to the mods for review.")
+                .ephemeral(true)
+        })
+        .await);
+    info!(
+        "User '{}#{}' reported blocked image '{}' as a false positive in guild '{}'",
+        member.user.name, member.user.discriminator, hash, guild
+    );
+    Ok(())
+}
+
+/// Whitelist a reported hash or keep it blocked, closing the review
+async fn review(
+    interaction: &serenity::MessageComponentInteraction,
+    reference: super::EventReference<'_>,
+    guild: serenity::GuildId,
+    member: &serenity::Member,
+    action: ReviewAction,
+    hash: &ImageHash,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    let hash = hash.to_base64();
+    let status = match action {
+        ReviewAction::Whitelist => {
+            WhitelistedHashes::insert(whitelisted_hashes::ActiveModel {
+                guild_id: ActiveValue::Set(guild.into()),
+                hash: ActiveValue::Set(hash.clone()),
+                added_by: ActiveValue::Set(member.user.id.into()),
+                added_at: ActiveValue::Set(chrono::Utc::now()),
+            })
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    whitelisted_hashes::Column::GuildId,
+                    whitelisted_hashes::Column::Hash,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(&data.db)
+            .await?;
+            format!("Whitelisted by {}", member.mention())
+        }
+        ReviewAction::Keep => format!("Kept blocked by {}", member.mention()),
+    };
+
+    let mut embed = interaction
+        .message
+        .embeds
+        .first()
+        .cloned()
+        .map(serenity::CreateEmbed::from)
+        .unwrap_or_default();
+    embed.field("Status", status, false);
+    interaction
+        .create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|f| f.set_embed(embed).components(|f| f))
+        })
+        .await?;
+    info!(
+        "User '{}#{}' reviewed reported image '{}' in guild '{}' ({})",
+        member.user.name,
+        member.user.discriminator,
+        hash,
+        guild,
+        action.id()
+    );
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+async fn whitelist_autocomplete<'a>(
+    ctx: Context<'a>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let hashes = match ctx.guild_id() {
+        Some(x) => load_whitelist(&ctx.data().db, x).await.unwrap_or_default(),
+        None => HashSet::default(),
+    };
+    hashes
+        .into_iter()
+        .map(|x| x.to_base64())
+        .filter(move |x| x.starts_with(partial))
+        .take(MAX_AUTOCOMPLETE_OPTIONS)
+}
+
+/// Let the filter act on a whitelisted image again
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn unwhitelist(
+    ctx: Context<'_>,
+    #[description = "Whitelisted image hash, as shown by /blocklist list"]
+    #[autocomplete = "whitelist_autocomplete"]
+    hash: String,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    let removed = match ImageHash::from_base64(hash.trim()) {
+        Ok(x) => unwhitelist_hash(&ctx.data().db, guild, &x).await?,
+        Err(_) => false,
+    };
+    if removed {
+        info!(
+            "User '{}' removed image '{}' from the whitelist in guild '{}'",
+            ctx.author().tag(),
+            hash.trim(),
+            guild
+        );
+    }
+    ctx.send(|f| {
+        f.content(if removed {
+            "The image is no longer whitelisted, so it's filtered again if it's on a blocklist."
+        } else {
+            "That image isn't whitelisted."
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_ids_round_trip() {
+        let hash = ImageHash::from_bytes(&[0, 1, 2, 3, 251, 252, 253, 254]).unwrap();
+        let encoded = hash.to_base64();
+        assert_eq!(
+            parse_report(&report_id(serenity::UserId(42), &encoded)),
+            Some((serenity::UserId(42), hash.clone()))
+        );
+        for action in ReviewAction::ALL {
+            assert_eq!(
+                ReviewAction::parse(&action.custom_id(&encoded)),
+                Some((action, hash.clone()))
+            );
+        }
+        assert_eq!(parse_report("image-report-x-AAAA"), None);
+        assert_eq!(ReviewAction::parse("image-review-delete-AAAA"), None);
+    }
+}
@@ -0,0 +1,241 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{t, Context, Error};
+use crate::{check_admin, entities::*, require_profile};
+use futures_lite::stream::StreamExt;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use tracing::{info, instrument};
+
+const TOGGLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+const MAX_BUTTONS_PER_ROW: usize = 5;
+
+/// Bot features a guild can turn off, all enabled by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    pub profanity_filter: bool,
+    pub image_filter: bool,
+    pub triggers: bool,
+    pub screening: bool,
+    pub questioning_bumps: bool,
+    pub questioning_acks: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            profanity_filter: true,
+            image_filter: true,
+            triggers: true,
+            screening: true,
+            questioning_bumps: true,
+            questioning_acks: true,
+        }
+    }
+}
+
+impl From<&servers::Model> for Features {
+    fn from(x: &servers::Model) -> Self {
+        Self {
+            profanity_filter: x.profanity_filter_enabled,
+            image_filter: x.image_filter_enabled,
+            triggers: x.triggers_enabled,
+            screening: x.screening_enabled,
+            questioning_bumps: x.questioning_bumps_enabled,
+            questioning_acks: x.questioning_acks_enabled,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Feature {
+    ProfanityFilter,
+    ImageFilter,
+    Triggers,
+    Screening,
+    QuestioningBumps,
+    QuestioningAcks,
+}
+
+impl Feature {
+    const ALL: [Self; 6] = [
+        Self::ProfanityFilter,
+        Self::ImageFilter,
+        Self::Triggers,
+        Self::Screening,
+        Self::QuestioningBumps,
+        Self::QuestioningAcks,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::ProfanityFilter => "Profanity filter",
+            Self::ImageFilter => "Image filter",
+            Self::Triggers => "Triggers",
+            Self::Screening => "Screening alerts",
+            Self::QuestioningBumps => "Questioning reminders",
+            Self::QuestioningAcks => "Questioning replies seen",
+        }
+    }
+
+    const fn column(self) -> servers::Column {
+        match self {
+            Self::ProfanityFilter => servers::Column::ProfanityFilterEnabled,
+            Self::ImageFilter => servers::Column::ImageFilterEnabled,
+            Self::Triggers => servers::Column::TriggersEnabled,
+            Self::Screening => servers::Column::ScreeningEnabled,
+            Self::QuestioningBumps => servers::Column::QuestioningBumpsEnabled,
+            Self::QuestioningAcks => servers::Column::QuestioningAcksEnabled,
+        }
+    }
+
+    fn flag(self, features: &mut Features) -> &mut bool {
+        match self {
+            Self::ProfanityFilter => &mut features.profanity_filter,
+            Self::ImageFilter => &mut features.image_filter,
+            Self::Triggers => &mut features.triggers,
+            Self::Screening => &mut features.screening,
+            Self::QuestioningBumps => &mut features.questioning_bumps,
+            Self::QuestioningAcks => &mut features.questioning_acks,
+        }
+    }
+}
+
+fn render<'a, 'b>(
+    f: &'b mut poise::CreateReply<'a>,
+    prefix: &str,
+    mut features: Features,
+) -> &'b mut poise::CreateReply<'a> {
+    f.content("Toggle bot features for this server. Changes are saved immediately.")
+        .components(|f| {
+            for (row, chunk) in Feature::ALL.chunks(MAX_BUTTONS_PER_ROW).enumerate() {
+                f.create_action_row(|f| {
+                    for (offset, feature) in chunk.iter().enumerate() {
+                        let index = row * MAX_BUTTONS_PER_ROW + offset;
+                        let enabled = *feature.flag(&mut features);
+                        f.create_button(|f| {
+                            f.custom_id(format!("{prefix}{index}"))
+                                .label(format!(
+                                    "{}: {}",
+                                    feature.name(),
+                                    if enabled { "on" } else { "off" }
+                                ))
+                                .style(if enabled {
+                                    serenity::ButtonStyle::Success
+                                } else {
+                                    serenity::ButtonStyle::Secondary
+                                })
+                        });
+                    }
+                    f
+                });
+            }
+            f
+        })
+}
+
+/// Turn individual bot features on or off for this server
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn features(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let mut profile = require_profile!(ctx);
+    let mut features = Features::from(&profile);
+
+    // Component IDs are keyed by the invoking interaction
+    let prefix = format!("{}-", ctx.id());
+    let msg = ctx
+        .send(|f| render(f, &prefix, features).ephemeral(true))
+        .await?;
+    let mut collector = msg
+        .message()
+        .await?
+        .await_component_interactions(ctx)
+        .author_id(ctx.author().id)
+        .timeout(TOGGLE_TIMEOUT)
+        .build();
+
+    while let Some(x) = collector.next().await {
+        x.create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+        let Some(feature) = x
+            .data
+            .custom_id
+            .strip_prefix(&prefix)
+            .and_then(|y| y.parse::<usize>().ok())
+            .and_then(|y| Feature::ALL.get(y).copied())
+        else {
+            continue;
+        };
+
+        let flag = feature.flag(&mut features);
+        *flag = !*flag;
+        let enabled = *flag;
+        let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+        model.id = ActiveValue::Unchanged(guild.into());
+        model.set(feature.column(), enabled.into());
+        let changes = super::profile_setup::diff_profile(Some(&profile), &model);
+        profile = model.update(&ctx.data().db).await?;
+        ctx.data().set_features_for(guild, features);
+        info!(
+            "User '{}#{}' turned {} {} in guild '{}'",
+            ctx.author().name,
+            ctx.author().discriminator,
+            feature.name(),
+            if enabled { "on" } else { "off" },
+            guild
+        );
+
+        if !changes.is_empty() {
+            super::config_audit(ctx, guild, "Features updated", changes).await?;
+        }
+        msg.edit(ctx, |f| render(f, &prefix, features)).await?;
+    }
+    // The interaction token may have expired by now
+    _ = t(msg.edit(ctx, |f| f.components(|f| f)).await);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_toggle_flips_one_feature() {
+        for (index, feature) in Feature::ALL.into_iter().enumerate() {
+            let mut features = Features::default();
+            *feature.flag(&mut features) = false;
+            let enabled = [
+                features.profanity_filter,
+                features.image_filter,
+                features.triggers,
+                features.screening,
+                features.questioning_bumps,
+                features.questioning_acks,
+            ];
+            assert_eq!(enabled.iter().filter(|x| !**x).count(), 1);
+            assert!(!enabled[index]);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Explain filter deletions to users who immediately ask about them
+
+use super::{Error, EventReference};
+use crate::entities::{prelude::*, *};
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::time::{Duration, Instant};
+use tracing::{info, instrument};
+
+/// How long after a deletion the user's next message counts as a follow-up
+const WINDOW: Duration = Duration::from_secs(120);
+/// Watches beyond this are dropped rather than stored, so a raid can't grow the map unbounded
+const MAX_WATCHES: usize = 1000;
+
+/// Users whose message was just deleted by a filter, keyed by where it was deleted
+#[derive(Default, Clone)]
+pub struct FilterFollowups(
+    std::sync::Arc<dashmap::DashMap<(serenity::ChannelId, serenity::UserId), Instant>>,
+);
+
+impl FilterFollowups {
+    /// Watch for `user`'s next message in `channel`
+    pub fn arm(&self, channel: serenity::ChannelId, user: serenity::UserId) {
+        if self.0.len() >= MAX_WATCHES && !self.0.contains_key(&(channel, user)) {
+            self.clean();
+            if self.0.len() >= MAX_WATCHES {
+                return;
+            }
+        }
+        self.0.insert((channel, user), Instant::now());
+    }
+
+    /// Stop watching `user` in `channel`, returning whether the watch was still live
+    fn take(&self, channel: serenity::ChannelId, user: serenity::UserId) -> bool {
+        self.0
+            .remove(&(channel, user))
+            .is_some_and(|(_, x)| x.elapsed() < WINDOW)
+    }
+
+    /// Remove expired watches, returning how many were removed
+    pub fn clean(&self) -> usize {
+        let mut removed = 0;
+        self.0.retain(|_, x| {
+            let expired = x.elapsed() >= WINDOW;
+            removed += usize::from(expired);
+            !expired
+        });
+        removed
+    }
+}
+
+#[derive(FromQueryResult)]
+struct FollowupData {
+    rules_channel: ids::DbChannelId,
+    filter_followup_text: Option<String>,
+}
+
+/// Reply once with the server's explanation if `message` follows a filter deletion
+#[instrument(skip_all, err)]
+pub async fn answer_followup(
+    message: &serenity::Message,
+    guild: serenity::GuildId,
+    reference: EventReference<'_>,
+) -> Result<bool, Error> {
+    // Taking the watch means the reply can't fire twice, and the bot's own messages never arm one
+    if !reference
+        .3
+        .filter_followups
+        .take(message.channel_id, message.author.id)
+    {
+        return Ok(false);
+    }
+
+    let Some(FollowupData {
+        rules_channel,
+        filter_followup_text: Some(text),
+    }) = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::RulesChannel)
+        .column(servers::Column::FilterFollowupText)
+        .into_model::<FollowupData>()
+        .one(&reference.3.db)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    message
+        .reply(
+            reference.0,
+            super::triggers::fit_message(format!(
+                "{}\nSee {} for the server rules.",
+                text,
+                serenity::ChannelId::from(rules_channel).mention()
+            )),
+        )
+        .await?;
+    info!(
+        "Sent filter follow-up to '{}#{}'",
+        message.author.name, message.author.discriminator
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNEL: serenity::ChannelId = serenity::ChannelId(1);
+    const USER: serenity::UserId = serenity::UserId(2);
+
+    #[test]
+    fn watches_fire_once() {
+        let followups = FilterFollowups::default();
+        followups.arm(CHANNEL, USER);
+        assert!(!followups.take(serenity::ChannelId(3), USER));
+        assert!(followups.take(CHANNEL, USER));
+        assert!(!followups.take(CHANNEL, USER));
+    }
+
+    #[test]
+    fn expired_watches_are_ignored_and_cleaned() {
+        let followups = FilterFollowups::default();
+        followups.0.insert((CHANNEL, USER), Instant::now() - WINDOW);
+        followups.arm(serenity::ChannelId(3), USER);
+        assert_eq!(followups.clean(), 1);
+        assert!(!followups.take(CHANNEL, USER));
+    }
+
+    #[test]
+    fn watches_are_bounded() {
+        let followups = FilterFollowups::default();
+        for i in 0..=MAX_WATCHES as u64 {
+            followups.arm(serenity::ChannelId(i), USER);
+        }
+        assert_eq!(followups.0.len(), MAX_WATCHES);
+    }
+}
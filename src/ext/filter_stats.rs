@@ -0,0 +1,259 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Counters showing where image filter work comes from
+
+use super::image_filtering::ResolveUrl;
+use super::{t, Context, Error};
+use futures_lite::stream::StreamExt;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{info, instrument};
+
+const LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+const RESET_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+#[derive(Default)]
+struct Counters {
+    sources: [AtomicU64; ResolveUrl::KINDS.len()],
+    downloads: AtomicU64,
+    cache_hits: AtomicU64,
+    decode_failures: AtomicU64,
+    matches: AtomicU64,
+}
+
+/// Image filter counters since startup or the last reset; these are only for display, so every
+/// access is relaxed
+#[derive(Default, Clone)]
+pub struct FilterStats(Arc<Counters>);
+
+/// Counter values taken at one moment
+#[derive(Debug, PartialEq, Eq)]
+struct Snapshot {
+    sources: [u64; ResolveUrl::KINDS.len()],
+    downloads: u64,
+    cache_hits: u64,
+    decode_failures: u64,
+    matches: u64,
+}
+
+impl FilterStats {
+    pub fn count_source(&self, source: &ResolveUrl) {
+        self.0.sources[source.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_download(&self, cache_hit: bool) {
+        self.0.downloads.fetch_add(1, Ordering::Relaxed);
+        if cache_hit {
+            self.0.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count_decode_failure(&self) {
+        self.0.decode_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_match(&self) {
+        self.0.matches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let load = |x: &AtomicU64| x.load(Ordering::Relaxed);
+        Snapshot {
+            sources: self.0.sources.each_ref().map(load),
+            downloads: load(&self.0.downloads),
+            cache_hits: load(&self.0.cache_hits),
+            decode_failures: load(&self.0.decode_failures),
+            matches: load(&self.0.matches),
+        }
+    }
+
+    fn reset(&self) {
+        for i in self.0.sources.iter().chain([
+            &self.0.downloads,
+            &self.0.cache_hits,
+            &self.0.decode_failures,
+            &self.0.matches,
+        ]) {
+            i.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Snapshot {
+    fn is_empty(&self) -> bool {
+        self == &Self {
+            sources: [0; ResolveUrl::KINDS.len()],
+            downloads: 0,
+            cache_hits: 0,
+            decode_failures: 0,
+            matches: 0,
+        }
+    }
+
+    /// Images checked per kind, busiest first, skipping kinds never seen
+    fn source_lines(&self) -> Vec<String> {
+        ResolveUrl::KINDS
+            .iter()
+            .zip(self.sources)
+            .filter(|x| x.1 > 0)
+            .sorted_by_key(|x| std::cmp::Reverse(x.1))
+            .map(|(kind, count)| format!("{kind}: {count}"))
+            .collect()
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} downloads ({} cached), {} decode failures, {} matches",
+            self.downloads, self.cache_hits, self.decode_failures, self.matches
+        )
+    }
+}
+
+/// Log the counters every [`LOG_INTERVAL`] while there is anything to report
+pub async fn schedule_stats_log(stats: FilterStats) {
+    loop {
+        tokio::time::sleep(LOG_INTERVAL).await;
+        let snapshot = stats.snapshot();
+        if !snapshot.is_empty() {
+            info!(
+                "Image filter stats: {}; sources: {}",
+                snapshot.summary(),
+                snapshot.source_lines().join(", ")
+            );
+        }
+    }
+}
+
+fn render<'a, 'b>(
+    f: &'b mut poise::CreateReply<'a>,
+    reset_id: &str,
+    snapshot: &Snapshot,
+) -> &'b mut poise::CreateReply<'a> {
+    let sources = snapshot.source_lines();
+    f.embed(|f| {
+        f.title("Image filter stats")
+            .field(
+                "Sources",
+                if sources.is_empty() {
+                    "None yet".to_owned()
+                } else {
+                    sources.join("\n")
+                },
+                true,
+            )
+            .field("Downloads", snapshot.downloads, true)
+            .field("Cache hits", snapshot.cache_hits, true)
+            .field("Decode failures", snapshot.decode_failures, true)
+            .field("Matches", snapshot.matches, true)
+    })
+    .components(|f| {
+        f.create_action_row(|f| {
+            f.create_button(|f| {
+                f.custom_id(reset_id)
+                    .label("Reset")
+                    .style(serenity::ButtonStyle::Danger)
+            })
+        })
+    })
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(
+    slash_command,
+    owners_only,
+    subcommands("filters", "super::events::events", "super::circuit_breaker::breakers")
+)]
+pub async fn botstats(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show which image sources the filters have been checking since startup
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn filters(ctx: Context<'_>) -> Result<(), Error> {
+    let stats = &ctx.data().filter_stats;
+
+    // Component IDs are keyed by the invoking interaction
+    let reset_id = format!("{}-reset", ctx.id());
+    let msg = ctx
+        .send(|f| render(f, &reset_id, &stats.snapshot()).ephemeral(true))
+        .await?;
+
+    let mut collector = msg
+        .message()
+        .await?
+        .await_component_interactions(ctx)
+        .author_id(ctx.author().id)
+        .timeout(RESET_TIMEOUT)
+        .build();
+    while let Some(x) = collector.next().await {
+        x.create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+        if x.data.custom_id != reset_id {
+            continue;
+        }
+        stats.reset();
+        info!("Image filter stats reset by {}", ctx.author().tag());
+        msg.edit(ctx, |f| render(f, &reset_id, &stats.snapshot()))
+            .await?;
+    }
+    // The interaction token may have expired by now
+    _ = t(msg.edit(ctx, |f| f.components(|f| f)).await);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Count one image the way `HashData::check` does, without downloading anything
+    fn check(stats: &FilterStats, source: &ResolveUrl, cache_hit: bool, outcome: Option<bool>) {
+        stats.count_source(source);
+        stats.count_download(cache_hit);
+        match outcome {
+            None => stats.count_decode_failure(),
+            Some(true) => stats.count_match(),
+            Some(false) => {}
+        }
+    }
+
+    #[test]
+    fn checks_are_counted_by_source() {
+        let stats = FilterStats::default();
+        assert!(stats.snapshot().is_empty());
+
+        let emoji = ResolveUrl::Emoji(serenity::EmojiId(1));
+        check(&stats, &emoji, true, Some(false));
+        check(&stats, &emoji, false, Some(true));
+        check(&stats, &ResolveUrl::Attachment("a.png"), false, None);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.source_lines(), ["emoji: 2", "attachment: 1"]);
+        assert_eq!(
+            snapshot.summary(),
+            "3 downloads (1 cached), 1 decode failures, 1 matches"
+        );
+
+        stats.reset();
+        assert!(stats.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,316 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Send newly accepted members straight to questioning when one of their first messages is
+//! filtered, as raiders tend to pass screening and post their payload right away
+//!
+//! Members are only counted from when they gain the member role, so everyone without a counter is
+//! trusted; once a member's counter reaches the threshold it is deleted and they're remembered as
+//! trusted in memory, so their later messages cost no database access at all.
+
+use super::profile_setup::diff_profile;
+use super::{t, Context, Error};
+use crate::entities::{prelude::*, *};
+use crate::{check_admin, require_profile};
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::sync::Arc;
+use tracing::{info, instrument};
+
+const DEFAULT_THRESHOLD: i32 = 3;
+const MAX_EVIDENCE_LENGTH: usize = 4000;
+
+/// Members known to have no counter, either because they passed the threshold or were never new
+#[derive(Default, Clone)]
+pub struct TrustedMembers(Arc<dashmap::DashSet<(serenity::GuildId, serenity::UserId)>>);
+
+/// What happens to a new member's counter when they post
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Counted(i32),
+    Trusted,
+    Escalated,
+}
+
+/// `count` is the number of clean messages the member has already posted
+const fn outcome(count: i32, threshold: i32, filtered: bool) -> Outcome {
+    if filtered {
+        Outcome::Escalated
+    } else if count + 1 >= threshold {
+        Outcome::Trusted
+    } else {
+        Outcome::Counted(count + 1)
+    }
+}
+
+fn counter_key(
+    guild: serenity::GuildId,
+    user: serenity::UserId,
+) -> (ids::DbGuildId, ids::DbUserId) {
+    (guild.into(), user.into())
+}
+
+/// Count `msg` towards its author's first messages, sending them to questioning if `filter`
+/// (the name of the filter that deleted it) is set
+#[instrument(skip_all, err)]
+pub async fn check_message(
+    msg: &serenity::Message,
+    guild: serenity::GuildId,
+    filter: Option<&str>,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    let Some(threshold) = data.first_message_threshold(guild) else {
+        return Ok(());
+    };
+    let key = (guild, msg.author.id);
+    if data.trusted_members.0.contains(&key) {
+        return Ok(());
+    }
+    let Some(counter) = FirstMessages::find_by_id(counter_key(guild, msg.author.id))
+        .one(&data.db)
+        .await?
+    else {
+        data.trusted_members.0.insert(key);
+        return Ok(());
+    };
+
+    let position = counter.count + 1;
+    match outcome(counter.count, threshold, filter.is_some()) {
+        Outcome::Counted(count) => {
+            let mut counter: first_messages::ActiveModel = counter.into();
+            counter.count = ActiveValue::Set(count);
+            counter.update(&data.db).await?;
+            return Ok(());
+        }
+        Outcome::Trusted => {
+            counter.delete(&data.db).await?;
+            data.trusted_members.0.insert(key);
+            return Ok(());
+        }
+        Outcome::Escalated => {
+            // Losing the member role would reset it anyway
+            counter.delete(&data.db).await?;
+        }
+    }
+
+    let server_data = Servers::find_by_id(guild)
+        .one(&data.db)
+        .await?
+        .ok_or(super::FedBotError::new("server profile missing"))?;
+    let reason = format!(
+        "Message {} of their first {} tripped the {} filter",
+        position,
+        threshold,
+        filter.unwrap_or_default()
+    );
+    if !super::user_screening::question_user(
+        ctx,
+        data,
+        guild,
+        &msg.author,
+        data.bot_id,
+        &reason,
+        &server_data,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    let channel = super::user_screening::find_questioning_channel(
+        ctx,
+        &data.db,
+        guild,
+        server_data.questioning_category.into(),
+        msg.author.id,
+    )
+    .await?;
+    if let Some(channel) = &channel {
+        let attachments = msg
+            .attachments
+            .iter()
+            .map(|x| x.filename.as_str())
+            .collect::<Vec<_>>();
+        _ = t(channel
+            .send_message(ctx, |f| {
+                f.embed(|f| {
+                    f.title("Filtered message")
+                        .description(super::quote_content(&msg.content, MAX_EVIDENCE_LENGTH))
+                        .field("Filter", filter.unwrap_or_default(), true)
+                        .field("Channel", msg.channel_id.mention(), true)
+                        .timestamp(msg.timestamp);
+                    if !attachments.is_empty() {
+                        f.field("Attachments", attachments.join("\n"), false);
+                    }
+                    f
+                })
+                .allowed_mentions(|f| f.empty_users())
+            })
+            .await);
+    }
+
+    super::mod_log(
+        ctx,
+        data,
+        guild,
+        None,
+        format!(
+            "Automatically sent {} to questioning, as message {} of their first {} tripped the {} filter.{}",
+            msg.author.mention(),
+            position,
+            threshold,
+            filter.unwrap_or_default(),
+            channel.map_or_else(String::new, |x| format!(" Evidence is in {}.", x.mention()))
+        ),
+    )
+    .await?;
+    info!(
+        "Sent '{}#{}' to questioning for a filtered first message in guild '{}'",
+        msg.author.name, msg.author.discriminator, guild
+    );
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct MemberRoleData {
+    member_role: ids::DbRoleId,
+}
+
+/// Start counting when a member gains the member role, and forget them when they lose it
+///
+/// Without the cached member there is no telling whether the role was just gained, so only a
+/// missing role is acted on then.
+#[instrument(skip_all, err)]
+pub async fn member_updated(
+    old: Option<&serenity::Member>,
+    new: &serenity::Member,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let data = reference.3;
+    if data.first_message_threshold(new.guild_id).is_none()
+        || old.is_some_and(|x| x.roles == new.roles)
+    {
+        return Ok(());
+    }
+    let Some(server_data) = Servers::find_by_id(new.guild_id)
+        .select_only()
+        .column(servers::Column::MemberRole)
+        .into_model::<MemberRoleData>()
+        .one(&data.db)
+        .await?
+    else {
+        return Ok(());
+    };
+    let member_role = serenity::RoleId::from(server_data.member_role);
+    let key = (new.guild_id, new.user.id);
+
+    match (
+        old.map(|x| x.roles.contains(&member_role)),
+        new.roles.contains(&member_role),
+    ) {
+        (Some(false), true) => {
+            data.trusted_members.0.remove(&key);
+            FirstMessages::insert(first_messages::ActiveModel {
+                guild_id: ActiveValue::Set(new.guild_id.into()),
+                user_id: ActiveValue::Set(new.user.id.into()),
+                count: ActiveValue::Set(0),
+            })
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    first_messages::Column::GuildId,
+                    first_messages::Column::UserId,
+                ])
+                .update_column(first_messages::Column::Count)
+                .to_owned(),
+            )
+            .exec(&data.db)
+            .await?;
+        }
+        (None | Some(true), false) => {
+            data.trusted_members.0.remove(&key);
+            FirstMessages::delete_by_id(counter_key(new.guild_id, new.user.id))
+                .exec(&data.db)
+                .await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Send members to questioning when any of their first messages after being accepted is filtered
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn first_messages(
+    ctx: Context<'_>,
+    #[description = "Whether to watch newly accepted members' first messages"] enabled: bool,
+    #[description = "How many messages to watch (defaults to 3)"]
+    #[min = 1]
+    #[max = 50]
+    threshold: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let threshold = threshold.unwrap_or(if old_profile.strict_first_messages {
+        old_profile.first_message_threshold
+    } else {
+        DEFAULT_THRESHOLD
+    });
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.strict_first_messages = ActiveValue::Set(enabled);
+    model.first_message_threshold = ActiveValue::Set(threshold);
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+    ctx.data()
+        .set_first_message_threshold(guild, enabled.then_some(threshold));
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "First message checks updated", changes).await?;
+    }
+
+    ctx.send(|f| {
+        f.content(if enabled {
+            format!("Members who trip a filter in their first {threshold} messages after being accepted will be sent to questioning.")
+        } else {
+            "Turned off first message checks.".to_owned()
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_end_in_trust_or_questioning() {
+        assert_eq!(outcome(0, 3, false), Outcome::Counted(1));
+        assert_eq!(outcome(1, 3, false), Outcome::Counted(2));
+        assert_eq!(outcome(2, 3, false), Outcome::Trusted);
+        assert_eq!(outcome(0, 1, false), Outcome::Trusted);
+        assert_eq!(outcome(2, 3, true), Outcome::Escalated);
+    }
+}
@@ -0,0 +1,324 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Temporary image blocks, which lapse at their expiry and are swept off the blocklists daily
+
+use super::{Context, Error};
+use crate::entities::{prelude::*, *};
+use chrono::{DateTime, Utc};
+use image_hasher::ImageHash;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use std::collections::{HashMap, HashSet};
+use tracing::{error, info};
+
+const CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 3600);
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// How long newly blocked images stay blocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockExpiry {
+    Permanent,
+    Day,
+    Week,
+    Month,
+}
+
+impl BlockExpiry {
+    const ALL: [Self; 4] = [Self::Permanent, Self::Day, Self::Week, Self::Month];
+
+    const fn id(self) -> &'static str {
+        match self {
+            Self::Permanent => "permanent",
+            Self::Day => "24h",
+            Self::Week => "7d",
+            Self::Month => "30d",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Permanent => "Permanent",
+            Self::Day => "24 hours",
+            Self::Week => "7 days",
+            Self::Month => "30 days",
+        }
+    }
+
+    fn parse(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.id() == id)
+    }
+
+    fn duration(self) -> Option<chrono::Duration> {
+        match self {
+            Self::Permanent => None,
+            Self::Day => Some(chrono::Duration::days(1)),
+            Self::Week => Some(chrono::Duration::days(7)),
+            Self::Month => Some(chrono::Duration::days(30)),
+        }
+    }
+
+    /// When images blocked at `now` stop being blocked, or `None` if they never do
+    pub fn expires_at(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.duration().map(|x| now + x)
+    }
+}
+
+pub fn is_expired(expires_at: Option<&DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    expires_at.is_some_and(|x| *x <= now)
+}
+
+/// Ask how long to block images for, or `None` if the blocker didn't answer
+pub async fn ask_expiry(ctx: Context<'_>) -> Result<Option<BlockExpiry>, Error> {
+    let prompt = ctx
+        .send(|f| {
+            f.content("How long should the image(s) stay blocked?")
+                .components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_select_menu(|f| {
+                            f.custom_id("expiry")
+                                .placeholder("Block duration")
+                                .options(|f| {
+                                    for i in BlockExpiry::ALL {
+                                        f.create_option(|f| f.label(i.label()).value(i.id()));
+                                    }
+                                    f
+                                })
+                        })
+                    })
+                })
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    let response = prompt
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(60))
+        .await;
+    prompt.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(None);
+    };
+    response.defer(ctx).await?;
+    Ok(response
+        .data
+        .values
+        .first()
+        .and_then(|x| BlockExpiry::parse(x)))
+}
+
+/// Remove every hash in `expired` from `list`, returning the ones it held
+fn drop_expired(list: &mut Vec<ImageHash>, expired: &HashSet<ImageHash>) -> Vec<ImageHash> {
+    let (removed, kept) = list.drain(..).partition(|x| expired.contains(x));
+    *list = kept;
+    removed
+}
+
+#[derive(FromQueryResult)]
+struct ExpiryServerData {
+    blocked_images: Option<Vec<u8>>,
+    nsfw_blocked_images: Option<Vec<u8>>,
+}
+
+/// Take `expired` off both of `guild`'s blocklists, returning the removed hashes and whether
+/// each was on the NSFW blocklist
+async fn unblock_expired(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    expired: &HashSet<ImageHash>,
+) -> Result<Vec<(ImageHash, bool)>, Error> {
+    let Some(server_data) = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::BlockedImages)
+        .column(servers::Column::NsfwBlockedImages)
+        .into_model::<ExpiryServerData>()
+        .one(db)
+        .await?
+    else {
+        return Ok(vec![]);
+    };
+    let decode = |x: Option<Vec<u8>>| {
+        x.map_or(Ok(vec![]), |x| {
+            super::serialization::decode_blocked_images(&x)
+        })
+    };
+    let (mut hashes, mut nsfw_hashes) = (
+        decode(server_data.blocked_images)?,
+        decode(server_data.nsfw_blocked_images)?,
+    );
+    let removed = drop_expired(&mut hashes, expired);
+    let nsfw_removed = drop_expired(&mut nsfw_hashes, expired);
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    if !removed.is_empty() {
+        model.blocked_images =
+            ActiveValue::Set(Some(super::serialization::encode_blocked_images(&hashes)));
+    }
+    if !nsfw_removed.is_empty() {
+        model.nsfw_blocked_images = ActiveValue::Set(Some(
+            super::serialization::encode_blocked_images(&nsfw_hashes),
+        ));
+    }
+    if model.is_changed() {
+        model.update(db).await?;
+    }
+    Ok(removed
+        .into_iter()
+        .map(|x| (x, false))
+        .chain(nsfw_removed.into_iter().map(|x| (x, true)))
+        .collect())
+}
+
+/// Unblock every image whose block has run out, telling each server's mods what was removed
+async fn remove_expired(
+    ctx: &serenity::Context,
+    db: &DatabaseConnection,
+    health: &super::config_health::ConfigHealth,
+) -> Result<(), Error> {
+    let mut expired: HashMap<serenity::GuildId, HashSet<ImageHash>> = HashMap::new();
+    let rows = BlockedHashes::find()
+        .filter(blocked_hashes::Column::ExpiresAt.lte(Utc::now()))
+        .all(db)
+        .await?;
+    for i in &rows {
+        if let Ok(hash) = ImageHash::from_base64(&i.hash) {
+            expired.entry(i.guild_id.into()).or_default().insert(hash);
+        }
+    }
+
+    for (guild, hashes) in expired {
+        let removed = match unblock_expired(db, guild, &hashes).await {
+            Ok(x) => x,
+            Err(e) => {
+                error!(
+                    "Failed to unblock expired images in guild '{}': {}",
+                    guild, e
+                );
+                continue;
+            }
+        };
+        // Settings of hashes that are no longer blocked would only apply to a later re-block
+        BlockedHashes::delete_many()
+            .filter(blocked_hashes::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+            .filter(blocked_hashes::Column::Hash.is_in(hashes.iter().map(ImageHash::to_base64)))
+            .exec(db)
+            .await?;
+        if removed.is_empty() {
+            continue;
+        }
+
+        info!(
+            "Unblocked {} expired image(s) in guild '{}' (hashes: {})",
+            removed.len(),
+            guild,
+            removed.iter().map(|x| x.0.to_base64()).join(", ")
+        );
+        let mut lines = vec![format!(
+            "Unblocked {} image(s) whose block expired:",
+            removed.len()
+        )];
+        lines.extend(removed.iter().map(|(hash, nsfw)| {
+            if *nsfw {
+                format!("- `{}` (NSFW blocklist)", hash.to_base64())
+            } else {
+                format!("- `{}`", hash.to_base64())
+            }
+        }));
+        for i in super::chunk_lines(&lines, MAX_MESSAGE_LENGTH) {
+            super::send_mod_log(
+                ctx,
+                db,
+                health,
+                guild,
+                Some(guild_log_channels::Purpose::FilterNotices),
+                i,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sweep expired blocks off the blocklists once a day
+pub async fn schedule_cleanup(
+    ctx: serenity::Context,
+    db: DatabaseConnection,
+    health: super::config_health::ConfigHealth,
+) {
+    loop {
+        tokio::time::sleep(CLEANUP_INTERVAL).await;
+        if let Err(e) = remove_expired(&ctx, &db, &health).await {
+            error!("Failed to remove expired image blocks: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> ImageHash {
+        ImageHash::from_bytes(&[byte; 8]).unwrap()
+    }
+
+    #[test]
+    fn expiry_ids_round_trip() {
+        for i in BlockExpiry::ALL {
+            assert_eq!(BlockExpiry::parse(i.id()), Some(i));
+        }
+        assert_eq!(BlockExpiry::parse("1y"), None);
+    }
+
+    #[test]
+    fn expiry_is_counted_from_block_time() {
+        let now = Utc::now();
+        assert_eq!(BlockExpiry::Permanent.expires_at(now), None);
+        assert_eq!(
+            BlockExpiry::Day.expires_at(now),
+            Some(now + chrono::Duration::hours(24))
+        );
+        assert_eq!(
+            BlockExpiry::Month.expires_at(now),
+            Some(now + chrono::Duration::days(30))
+        );
+    }
+
+    #[test]
+    fn blocks_lapse_at_their_expiry() {
+        let now = Utc::now();
+        assert!(!is_expired(None, now));
+        assert!(!is_expired(
+            Some(&(now + chrono::Duration::seconds(1))),
+            now
+        ));
+        assert!(is_expired(Some(&now), now));
+        assert!(is_expired(Some(&(now - chrono::Duration::days(1))), now));
+    }
+
+    #[test]
+    fn only_expired_hashes_are_dropped() {
+        let mut list = vec![hash(1), hash(2), hash(3)];
+        let expired = HashSet::from([hash(2), hash(4)]);
+        assert_eq!(drop_expired(&mut list, &expired), [hash(2)]);
+        assert_eq!(list, [hash(1), hash(3)]);
+    }
+}
@@ -14,38 +14,409 @@
    limitations under the License.
 */
 
-use super::{Context, Error};
+use super::{Context, Error, PermissionTier};
 use crate::{
-    check_mod_role,
+    check_admin, check_tier,
     entities::{prelude::*, *},
+    require_profile,
 };
+use chrono::{DateTime, Utc};
+use fedbot_core::images::ImageSources;
 use image::io::Reader as ImageReader;
 use image_hasher::ImageHash;
+use itertools::Itertools;
 use poise::serenity_prelude as serenity;
 use sea_orm::*;
 use serenity::model::channel::ReactionType;
 use serenity::Mentionable;
-use std::{borrow::Cow, boxed::Box, io::Cursor};
-use tracing::{info, instrument};
+use std::{
+    boxed::Box,
+    collections::{HashMap, HashSet},
+    io::Cursor,
+};
+use tracing::{debug, info, instrument, warn};
 
-use super::{t, ContainBytes, EMOJI};
+use super::circuit_breaker::Operation;
+use super::hash_expiry::BlockExpiry;
+use super::profanity_checks::Censorable;
+use super::{t, EMOJI, URL};
 
 const UNKNOWN_EMOJI: isize = 10014;
+const MAX_EXCERPT_LENGTH: usize = 200;
+
+/// Images that must never be blocked, hashed on startup
+const SAFE_IMAGES: [(&str, &str); 6] = [
+    (
+        "Discord's default avatar (blurple)",
+        "https://cdn.discordapp.com/embed/avatars/0.png",
+    ),
+    (
+        "Discord's default avatar (grey)",
+        "https://cdn.discordapp.com/embed/avatars/1.png",
+    ),
+    (
+        "Discord's default avatar (green)",
+        "https://cdn.discordapp.com/embed/avatars/2.png",
+    ),
+    (
+        "Discord's default avatar (orange)",
+        "https://cdn.discordapp.com/embed/avatars/3.png",
+    ),
+    (
+        "Discord's default avatar (red)",
+        "https://cdn.discordapp.com/embed/avatars/4.png",
+    ),
+    (
+        "Discord's default avatar (pink)",
+        "https://cdn.discordapp.com/embed/avatars/5.png",
+    ),
+];
+
+const EXPECTED_CDN_HOSTS: [&str; 2] = ["cdn.discordapp.com", "media.discordapp.net"];
+
+/// A blocked hash matching more distinct URLs than this within the window is suspiciously generic
+const GENERIC_MATCH_THRESHOLD: usize = 10;
+const GENERIC_MATCH_WINDOW: std::time::Duration = std::time::Duration::from_secs(3600);
+
+#[derive(Default)]
+pub struct HashMatchTracker(
+    tokio::sync::Mutex<HashMap<ImageHash, Vec<(String, std::time::Instant)>>>,
+);
+
+impl HashMatchTracker {
+    /// Record a match and return whether the hash is now considered suspiciously generic
+    async fn record(&self, hash: &ImageHash, url: &str) -> bool {
+        let mut matches = self.0.lock().await;
+        let urls = matches.entry(hash.clone()).or_default();
+        urls.retain(|(x, time)| time.elapsed() < GENERIC_MATCH_WINDOW && x != url);
+        urls.push((url.to_owned(), std::time::Instant::now()));
+        urls.len() > GENERIC_MATCH_THRESHOLD
+    }
+}
 
-#[derive(FromQueryResult)]
-struct BlockImageServerData {
-    mod_role: i64,
+fn is_expected_cdn(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|x| x.host_str().map(|y| EXPECTED_CDN_HOSTS.contains(&y)))
+        .unwrap_or(false)
+}
+
+const LOTTIE_STICKER_PREFIX: &str = "https://cdn.discordapp.com/stickers/";
+const LOTTIE_PLACEHOLDER: &str = "Lottie sticker (no preview available)";
+
+/// Lottie stickers are JSON animations rather than images, so their bytes are fingerprinted instead
+fn lottie_hash(bytes: &[u8]) -> Result<ImageHash, Error> {
+    // FNV-1a, since the result is stored and must stay the same between builds
+    let digest = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |acc, x| {
+        (acc ^ u64::from(*x)).wrapping_mul(0x0100_0000_01b3)
+    });
+    ImageHash::from_bytes(&digest.to_be_bytes())
+        .map_err(|e| super::FedBotError::new(format!("{e:?}")).into())
+}
+
+fn is_lottie_url(url: &str) -> bool {
+    url.starts_with(LOTTIE_STICKER_PREFIX) && url.ends_with(".json")
+}
+
+const DEFAULT_MIN_IMAGE_DIMENSION: u32 = 32;
+/// Responses declaring a smaller body than this aren't downloaded at all
+const MIN_IMAGE_BYTES: u64 = 1024;
+
+/// Images narrower and shorter than this are too low-information to hash reliably
+fn min_image_dimension() -> u32 {
+    std::env::var("FEDBOT_MIN_IMAGE_DIMENSION")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_MIN_IMAGE_DIMENSION)
+}
+
+const fn is_tiny(width: u32, height: u32, floor: u32) -> bool {
+    width < floor && height < floor
+}
+
+/// Hash downloaded content from `url`, which is usually an image, or `None` if it is too small
+fn hash_content(data: &super::Data, url: &str, bytes: &[u8]) -> Result<Option<ImageHash>, Error> {
+    if is_lottie_url(url) {
+        return lottie_hash(bytes).map(Some);
+    }
+    let img = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .decode()?;
+    if is_tiny(img.width(), img.height(), min_image_dimension()) {
+        debug!(
+            "Skipped hashing {}x{} image at '{}'",
+            img.width(),
+            img.height(),
+            url
+        );
+        return Ok(None);
+    }
+    Ok(Some(data.hasher.hash_image(&img)))
+}
+
+async fn safe_image_name(data: &super::Data, hash: &ImageHash) -> Option<&'static str> {
+    data.safe_images
+        .read()
+        .await
+        .iter()
+        .find_map(|(name, x)| if x == hash { Some(*name) } else { None })
+}
+
+/// Hash the built-in safe images, leaving out any that can't be fetched so one CDN hiccup
+/// doesn't stop the bot starting
+pub async fn load_safe_images(
+    reqwest: &reqwest_middleware::ClientWithMiddleware,
+    hasher: &image_hasher::Hasher,
+) -> Vec<(&'static str, ImageHash)> {
+    let mut safe_images = vec![];
+    for (name, url) in SAFE_IMAGES {
+        match hash_safe_image(reqwest, hasher, url).await {
+            Ok(x) => safe_images.push((name, x)),
+            Err(e) => warn!("Couldn't hash safe image '{}': {}", name, e),
+        }
+    }
+    safe_images
+}
+
+async fn hash_safe_image(
+    reqwest: &reqwest_middleware::ClientWithMiddleware,
+    hasher: &image_hasher::Hasher,
+    url: &str,
+) -> Result<ImageHash, Error> {
+    let img = ImageReader::new(Cursor::new(reqwest.get(url).send().await?.bytes().await?))
+        .with_guessed_format()?
+        .decode()?;
+    Ok(hasher.hash_image(&img))
+}
+
+/// Channels each partially blocked hash in `guild` is allowed in
+async fn load_exemptions(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+) -> Result<HashMap<ImageHash, Vec<serenity::ChannelId>>, Error> {
+    let mut exemptions = HashMap::new();
+    for i in BlockedHashes::find()
+        .filter(blocked_hashes::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .all(db)
+        .await?
+    {
+        let (Ok(hash), Some(channels)) = (
+            ImageHash::from_base64(&i.hash),
+            i.exempt_channels_json.as_deref(),
+        ) else {
+            continue;
+        };
+        let channels: Vec<u64> = serde_json::from_str(channels)?;
+        exemptions.insert(
+            hash,
+            channels.into_iter().map(serenity::ChannelId).collect(),
+        );
+    }
+    Ok(exemptions)
+}
+
+/// Store the channels `hash` is allowed in, or remove its exemptions if `channels` is empty
+async fn save_exemptions(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    hash: &ImageHash,
+    channels: &[serenity::ChannelId],
+) -> Result<(), Error> {
+    if channels.is_empty() {
+        return clear_metadata(db, guild, hash, blocked_hashes::Column::ExemptChannelsJson).await;
+    }
+    BlockedHashes::insert(blocked_hashes::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        hash: ActiveValue::Set(hash.to_base64()),
+        exempt_channels_json: ActiveValue::Set(Some(serde_json::to_string(
+            &channels.iter().map(|x| x.0).collect::<Vec<_>>(),
+        )?)),
+        ..Default::default()
+    })
+    .on_conflict(
+        sea_query::OnConflict::columns([
+            blocked_hashes::Column::GuildId,
+            blocked_hashes::Column::Hash,
+        ])
+        .update_column(blocked_hashes::Column::ExemptChannelsJson)
+        .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// When each temporarily blocked hash in `guild` stops being blocked
+async fn load_expiries(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+) -> Result<HashMap<ImageHash, DateTime<Utc>>, Error> {
+    Ok(BlockedHashes::find()
+        .filter(blocked_hashes::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .filter(blocked_hashes::Column::ExpiresAt.is_not_null())
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|x| Some((ImageHash::from_base64(&x.hash).ok()?, x.expires_at?)))
+        .collect())
+}
+
+/// Store when `hash` stops being blocked, or make its block permanent if `expires_at` is `None`
+async fn save_expiry(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    hash: &ImageHash,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(), Error> {
+    let Some(expires_at) = expires_at else {
+        return clear_metadata(db, guild, hash, blocked_hashes::Column::ExpiresAt).await;
+    };
+    BlockedHashes::insert(blocked_hashes::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        hash: ActiveValue::Set(hash.to_base64()),
+        expires_at: ActiveValue::Set(Some(expires_at)),
+        ..Default::default()
+    })
+    .on_conflict(
+        sea_query::OnConflict::columns([
+            blocked_hashes::Column::GuildId,
+            blocked_hashes::Column::Hash,
+        ])
+        .update_column(blocked_hashes::Column::ExpiresAt)
+        .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Clear one of `hash`'s settings, dropping its row once none are left
+async fn clear_metadata(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    hash: &ImageHash,
+    column: blocked_hashes::Column,
+) -> Result<(), Error> {
+    let key = Condition::all()
+        .add(blocked_hashes::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .add(blocked_hashes::Column::Hash.eq(hash.to_base64()));
+    BlockedHashes::update_many()
+        .col_expr(column, sea_query::Expr::value(Value::String(None)))
+        .filter(key.clone())
+        .exec(db)
+        .await?;
+    BlockedHashes::delete_many()
+        .filter(key)
+        .filter(blocked_hashes::Column::ExemptChannelsJson.is_null())
+        .filter(blocked_hashes::Column::ExpiresAt.is_null())
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+const NSFW_FLAG_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Channels' NSFW flags, remembered for a few minutes so uncached channels cost one request
+#[derive(Default, Clone)]
+pub struct NsfwChannels(
+    std::sync::Arc<dashmap::DashMap<serenity::ChannelId, (bool, std::time::Instant)>>,
+);
+
+impl NsfwChannels {
+    async fn is_nsfw(&self, ctx: &serenity::Context, channel: serenity::ChannelId) -> bool {
+        if let Some(nsfw) = self
+            .0
+            .get(&channel)
+            .map(|x| *x)
+            .filter(|x| x.1.elapsed() < NSFW_FLAG_TTL)
+            .map(|x| x.0)
+        {
+            return nsfw;
+        }
+        let nsfw = channel_nsfw(ctx, channel).await;
+        self.0.insert(channel, (nsfw, std::time::Instant::now()));
+        nsfw
+    }
+}
+
+async fn guild_channel(
+    ctx: &serenity::Context,
+    channel: serenity::ChannelId,
+) -> Option<serenity::GuildChannel> {
+    match ctx.cache.guild_channel(channel) {
+        Some(x) => Some(x),
+        None => t(channel.to_channel(ctx).await).ok()?.guild(),
+    }
+}
+
+/// Whether `channel` is age-restricted, with threads following their parent channel
+async fn channel_nsfw(ctx: &serenity::Context, channel: serenity::ChannelId) -> bool {
+    let Some(channel) = guild_channel(ctx, channel).await else {
+        return false;
+    };
+    match channel.kind {
+        serenity::ChannelType::PublicThread
+        | serenity::ChannelType::PrivateThread
+        | serenity::ChannelType::NewsThread => match channel.parent_id {
+            Some(parent) => guild_channel(ctx, parent).await.is_some_and(|x| x.nsfw),
+            None => false,
+        },
+        _ => channel.nsfw,
+    }
+}
+
+/// Which blocklists apply to images in a channel
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ListScope {
+    Both,
+    NsfwOnly,
+    Neither,
+}
+
+/// The NSFW blocklist applies everywhere, and the main one everywhere the policy doesn't exempt
+const fn list_scope(policy: servers::NsfwChannelPolicy, nsfw: bool) -> ListScope {
+    match (policy, nsfw) {
+        (_, false) | (servers::NsfwChannelPolicy::Enforce, true) => ListScope::Both,
+        (servers::NsfwChannelPolicy::Skip, true) => ListScope::Neither,
+        (servers::NsfwChannelPolicy::Separate, true) => ListScope::NsfwOnly,
+    }
+}
+
+/// Which blocklists apply in `channel`, only looking the channel up if the policy cares
+async fn channel_scope(
+    ctx: &serenity::Context,
+    data: &super::Data,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+) -> ListScope {
+    match data.nsfw_policy_for(guild) {
+        servers::NsfwChannelPolicy::Enforce => ListScope::Both,
+        policy => list_scope(policy, data.nsfw_channels.is_nsfw(ctx, channel).await),
+    }
 }
 
 #[derive(FromQueryResult)]
 struct ScanImageServerData {
     blocked_images: Option<Vec<u8>>,
+    nsfw_blocked_images: Option<Vec<u8>>,
+}
+
+/// A blocked hash found by [`HashData::check`], and where the image came from
+struct BlockedImage<'a> {
+    hash: ImageHash,
+    source: ResolveUrl<'a>,
 }
 
 struct HashData<'a> {
     hashes: Option<Vec<ImageHash>>,
+    nsfw_hashes: Vec<ImageHash>,
+    exemptions: HashMap<ImageHash, Vec<serenity::ChannelId>>,
+    expiries: HashMap<ImageHash, DateTime<Utc>>,
+    whitelist: HashSet<ImageHash>,
     loaded: bool,
     guild: serenity::GuildId,
+    channel: Option<serenity::ChannelId>,
+    scope: ListScope,
     data: &'a super::Data,
 }
 
@@ -53,27 +424,119 @@ impl<'a> HashData<'a> {
     fn new(guild: serenity::GuildId, data: &'a super::Data) -> Self {
         Self {
             hashes: None,
+            nsfw_hashes: vec![],
+            exemptions: HashMap::new(),
+            expiries: HashMap::new(),
+            whitelist: HashSet::new(),
             loaded: false,
             guild,
+            channel: None,
+            scope: ListScope::Both,
             data,
         }
     }
 
-    async fn check(&mut self, text: Option<&str>) -> Option<ImageHash> {
-        if let Some(text) = text {
-            if let Ok(response) = t(self.data.reqwest.get(text).send().await) {
-                // Add unwrap_tracing macro
-                let img = t(t(
-                    ImageReader::new(Cursor::new(t(response.bytes().await).ok()?))
-                        .with_guessed_format(),
-                )
-                .ok()?
-                .decode())
-                .ok()?;
+    /// Skip partially blocked hashes that are allowed in `channel`
+    const fn in_channel(mut self, channel: serenity::ChannelId) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Only check the blocklists in `scope`
+    const fn in_scope(mut self, scope: ListScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Which blocklist `hash` is on, if any, ignoring the scope
+    async fn list_of(&mut self, hash: &ImageHash) -> Option<Blocklist> {
+        self.get().await;
+        if self.nsfw_hashes.contains(hash) {
+            Some(Blocklist::Nsfw)
+        } else if self.hashes.as_ref().is_some_and(|x| x.contains(hash)) {
+            Some(Blocklist::Main)
+        } else {
+            None
+        }
+    }
 
-                let hash = self.data.hasher.hash_image(&img);
-                if self.get().await.is_some_and(|x| x.contains(&hash)) {
-                    return Some(hash);
+    /// Whether `hash` is on a blocklist in scope
+    async fn blocks(&mut self, hash: &ImageHash) -> bool {
+        match self.list_of(hash).await {
+            Some(Blocklist::Nsfw) => self.scope != ListScope::Neither,
+            Some(Blocklist::Main) => self.scope == ListScope::Both,
+            None => false,
+        }
+    }
+
+    /// Download and hash the image at `url`, skipping images too small to hash reliably
+    async fn fetch_hash(&self, url: &str) -> Option<ImageHash> {
+        let breakers = &self.data.circuit_breakers;
+        if !breakers.allow(Operation::Downloads) {
+            breakers.skipped(Operation::Downloads, self.guild, &self.data.events);
+            return None;
+        }
+        let response = t(self.data.reqwest.get(url).send().await)
+            .ok()
+            .filter(|x| !x.status().is_server_error());
+        let Some(response) = response else {
+            breakers.failed(Operation::Downloads);
+            return None;
+        };
+        breakers.succeeded(Operation::Downloads, &self.data.events);
+        if let Some(x) = response
+            .content_length()
+            .filter(|x| *x < MIN_IMAGE_BYTES && !is_lottie_url(url))
+        {
+            debug!("Skipped downloading {} byte image at '{}'", x, url);
+            return None;
+        }
+        self.data.filter_stats.count_download(
+            response
+                .headers()
+                .get("x-cache")
+                .is_some_and(|x| x == "HIT"),
+        );
+        let Ok(bytes) = t(response.bytes().await) else {
+            breakers.failed(Operation::Downloads);
+            return None;
+        };
+        let hash = t(hash_content(self.data, url, &bytes));
+        if hash.is_err() {
+            self.data.filter_stats.count_decode_failure();
+        }
+        hash.ok().flatten()
+    }
+
+    async fn check<'b>(&mut self, source: ResolveUrl<'b>) -> Option<BlockedImage<'b>> {
+        self.data.filter_stats.count_source(&source);
+        if let Some(url) = source.resolve() {
+            let text = url.as_ref();
+            if let Some(hash) = self.fetch_hash(text).await {
+                if self.blocks(&hash).await {
+                    // Expired blocks may not have been swept off the blocklist yet
+                    if safe_image_name(self.data, &hash).await.is_some()
+                        || self.whitelist.contains(&hash)
+                        || super::hash_expiry::is_expired(self.expiries.get(&hash), Utc::now())
+                    {
+                        return None;
+                    }
+                    if self
+                        .channel
+                        .is_some_and(|x| self.exemptions.get(&hash).is_some_and(|y| y.contains(&x)))
+                    {
+                        return None;
+                    }
+                    if self.data.hash_matches.record(&hash, text).await && !is_expected_cdn(text) {
+                        info!(
+                            "Skipped suspiciously generic blocked image at '{}' (hash: '{}')",
+                            text,
+                            hash.to_base64()
+                        );
+                        return None;
+                    }
+                    self.data.filter_stats.count_match();
+                    return Some(BlockedImage { hash, source });
                 }
             }
         }
@@ -84,34 +547,55 @@ impl<'a> HashData<'a> {
         if !self.loaded {
             self.loaded = true;
 
-            let mut real_hashes: Vec<ImageHash> = vec![];
-            if let Some(raw_hashes) = t(Servers::find_by_id(self.guild.as_u64().repack())
+            if let Some(server_data) = t(Servers::find_by_id(self.guild)
                 .select_only()
                 .column(servers::Column::Id)
                 .column(servers::Column::BlockedImages)
+                .column(servers::Column::NsfwBlockedImages)
                 .into_model::<ScanImageServerData>()
                 .one(&self.data.db)
                 .await)
             .ok()?
-            .and_then(|m| m.blocked_images)
             {
-                let raw_hash_slices: &[u8] = &raw_hashes;
-                for i in raw_hash_slices.chunks_exact(super::HASH_BYTES.into()) {
-                    real_hashes
-                        .push(t(ImageHash::from_bytes(i).map_err(|x| format!("{x:?}"))).ok()?);
+                if let Some(raw_hashes) = server_data.blocked_images {
+                    self.hashes =
+                        Some(t(super::serialization::decode_blocked_images(&raw_hashes)).ok()?);
+                }
+                if let Some(raw_hashes) = server_data.nsfw_blocked_images {
+                    self.nsfw_hashes =
+                        t(super::serialization::decode_blocked_images(&raw_hashes)).ok()?;
                 }
-                self.hashes = Some(real_hashes);
+            }
+            if let Ok(x) = t(load_exemptions(&self.data.db, self.guild).await) {
+                self.exemptions = x;
+            }
+            if let Ok(x) = t(load_expiries(&self.data.db, self.guild).await) {
+                self.expiries = x;
+            }
+            if let Ok(x) =
+                t(super::false_positives::load_whitelist(&self.data.db, self.guild).await)
+            {
+                self.whitelist = x;
             }
         }
         self.hashes.as_ref()
     }
 
-    async fn retrieve(mut self) -> Option<Vec<ImageHash>> {
+    /// The main and NSFW blocklists
+    async fn retrieve(mut self) -> (Vec<ImageHash>, Vec<ImageHash>) {
         self.get().await;
-        self.hashes
+        (self.hashes.unwrap_or_default(), self.nsfw_hashes)
     }
 }
 
+/// One of a guild's two blocklists
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Blocklist {
+    Main,
+    /// Applies in NSFW channels too, for servers that keep them separate
+    Nsfw,
+}
+
 macro_rules! impl_ref {
     (impl $trait:ident for $type:ty {
         $(fn $name:ident $params:tt -> $ret:ty $body:block)*
@@ -130,47 +614,62 @@ macro_rules! impl_ref {
     }
 }
 
+/// Where the filter finds images on Discord
 #[derive(Clone, Copy)]
-pub enum ResolveUrl<'a> {
-    Direct(&'a str),
-    Emoji(serenity::EmojiId),
-    Sticker(&'a serenity::StickerItem),
-    Reaction(&'a serenity::MessageReaction),
-    Icon(&'a str),
-    Banner(&'a str),
-}
-
-impl<'a> ResolveUrl<'a> {
-    fn resolve(&self) -> Option<Cow<'a, str>> {
-        match self {
-            Self::Emoji(id) => Some(Cow::Owned(format!(
-                "https://cdn.discordapp.com/emojis/{}",
-                id.as_u64()
-            ))),
-            Self::Sticker(sticker) => sticker.image_url().map(Cow::Owned),
-            Self::Reaction(reaction) => match reaction.reaction_type {
-                ReactionType::Custom { id, .. } => Some(Self::Emoji(id).resolve()),
-                _ => None,
-            }
-            .flatten(),
-            Self::Direct(text) | Self::Icon(text) | Self::Banner(text) => Some(Cow::Borrowed(text)),
+pub enum Serenity {}
+
+impl ImageSources for Serenity {
+    type EmojiId = serenity::EmojiId;
+    type Sticker = serenity::StickerItem;
+    type Reaction = serenity::MessageReaction;
+
+    fn sticker_url(sticker: &serenity::StickerItem) -> Option<String> {
+        sticker.image_url()
+    }
+
+    fn is_lottie(sticker: &serenity::StickerItem) -> bool {
+        sticker.format_type == serenity::StickerFormatType::Lottie
+    }
+
+    fn custom_emoji(reaction: &serenity::MessageReaction) -> Option<serenity::EmojiId> {
+        match reaction.reaction_type {
+            ReactionType::Custom { id, .. } => Some(id),
+            _ => None,
         }
     }
 }
 
+pub type ResolveUrl<'a> = fedbot_core::images::ResolveUrl<'a, Serenity>;
+
+/// Raw links in message content, e.g. `https://example.com/image.png`
+fn linked_urls(content: &str) -> Vec<ResolveUrl<'_>> {
+    URL.find_iter(content)
+        .map(|x| ResolveUrl::Direct(x.as_str()))
+        .collect()
+}
+
 pub trait Filterable {
-    fn get_urls(&self) -> Vec<ResolveUrl>;
+    /// Images in the message, including raw links in its content if `include_links` is set
+    fn get_urls(&self, include_links: bool) -> Vec<ResolveUrl<'_>>;
+
+    /// Text content of the message, if known
+    fn text(&self) -> Option<&str>;
 }
 
 impl_ref! {
 impl Filterable for serenity::Message {
-    fn get_urls(&self) -> Vec<ResolveUrl> {
-        vec![
+    fn get_urls(&self, include_links: bool) -> Vec<ResolveUrl<'_>> {
+        [
             EMOJI.captures_iter(&self.content).map(|x| x.get(3).and_then(|y| t(y.as_str().parse()).ok().map(serenity::EmojiId))
             ).filter_map(|x| x.map(ResolveUrl::Emoji)).collect::<Vec<ResolveUrl>>(),
+            if include_links {
+                linked_urls(&self.content)
+            } else {
+                vec![]
+            },
             self.attachments
                 .iter()
-                .map(|x| ResolveUrl::Direct(x.url.as_str()))
+                .map(|x| ResolveUrl::Attachment(x.url.as_str()))
                 .collect::<Vec<ResolveUrl>>(),
             self.embeds
                 .iter()
@@ -186,25 +685,33 @@ impl Filterable for serenity::Message {
                         x.thumbnail.as_ref().map(|y| y.url.as_str()),
                     ]
                 })
-                .filter_map(|x| x.map(ResolveUrl::Direct))
+                .filter_map(|x| x.map(ResolveUrl::Embed))
                 .collect::<Vec<ResolveUrl>>(),
         ]
         .concat()
     }
+
+    fn text(&self) -> Option<&str> {
+        Some(&self.content)
+    }
 }
 }
 
 impl_ref! {
 impl Filterable for &serenity::MessageUpdateEvent {
-    fn get_urls(&self) -> Vec<ResolveUrl> {
+    fn get_urls(&self, include_links: bool) -> Vec<ResolveUrl<'_>> {
         vec![
             self.content.as_ref().map(|i|
             EMOJI.captures_iter(i).map(|x| x.get(3).and_then(|y| t(y.as_str().parse()).ok().map(serenity::EmojiId))
             ).filter_map(|x| x.map(ResolveUrl::Emoji)).collect::<Vec<ResolveUrl>>()),
+            self.content
+                .as_deref()
+                .filter(|_| include_links)
+                .map(linked_urls),
 
             self.attachments
                 .as_ref()
-                .map(|i| i.iter().map(|x| ResolveUrl::Direct(x.url.as_str())).collect::<Vec<ResolveUrl>>()),
+                .map(|i| i.iter().map(|x| ResolveUrl::Attachment(x.url.as_str())).collect::<Vec<ResolveUrl>>()),
             self.embeds.as_ref().map(|i| {
                 i.iter()
                     .flat_map(|x| {
@@ -219,7 +726,7 @@ impl Filterable for &serenity::MessageUpdateEvent {
                             x.thumbnail.as_ref().map(|y| y.url.as_str()),
                         ]
                     })
-                    .filter_map(|x| x.map(ResolveUrl::Direct))
+                    .filter_map(|x| x.map(ResolveUrl::Embed))
                     .collect::<Vec<ResolveUrl>>()
             }),
         ]
@@ -228,6 +735,10 @@ impl Filterable for &serenity::MessageUpdateEvent {
         .flatten()
         .collect()
     }
+
+    fn text(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
 }
 }
 
@@ -238,30 +749,66 @@ pub async fn filter_message<T: Filterable>(
     channel: serenity::ChannelId,
     id: serenity::MessageId,
     author: &serenity::User,
+    origin: super::MessageOrigin<'_>,
     reference: super::EventReference<'_>,
 ) -> Result<bool, super::Error> {
-    let mut hash_struct = HashData::new(guild, reference.3);
-
-    for i in filter.get_urls() {
-        if let Some(x) = hash_struct
-            .check(i.resolve().as_ref().map(AsRef::as_ref))
-            .await
-        {
-            channel.delete_message(&reference.0, id).await?;
+    let scope = channel_scope(reference.0, reference.3, guild, channel).await;
+    if scope == ListScope::Neither {
+        return Ok(false);
+    }
+    let mut hash_struct = HashData::new(guild, reference.3)
+        .in_channel(channel)
+        .in_scope(scope);
+
+    for i in filter.get_urls(reference.3.filters_linked_images(guild)) {
+        if let Some(x) = hash_struct.check(i).await {
+            let hash = x.hash.to_base64();
+            let excerpt = filter
+                .text()
+                .filter(|y| !y.trim().is_empty())
+                .map_or_else(String::new, |y| {
+                    format!("\n{}", super::quote_content(y, MAX_EXCERPT_LENGTH))
+                });
+            let reason = format!("blocked image, hash `{}` in {}", hash, x.source.kind());
+            if !super::circuit_breaker::delete_filtered(
+                reference, guild, channel, id, author, &reason,
+            )
+            .await?
+            {
+                return Ok(false);
+            }
             channel
                 .send_message(&reference.0, |f| {
                     f.content(format!(
-                        "Deleted message from {} (reason: blocked image)",
-                        author.mention()
+                        "Deleted message from {} (reason: blocked image, hash `{}` in {}){}",
+                        author.mention(),
+                        hash,
+                        x.source.kind(),
+                        excerpt
                     ))
+                    // Links in the excerpt may point at the blocked image itself
+                    .flags(serenity::MessageFlags::SUPPRESS_EMBEDS)
+                    .components(|f| super::false_positives::report_button(f, author.id, &hash))
                 })
                 .await?;
+            reference.3.filter_followups.arm(channel, author.id);
+            reference
+                .3
+                .events
+                .publish(super::events::BotEvent::ImageBlocked {
+                    guild,
+                    user: author.id,
+                    hash: hash.clone(),
+                });
             info!(
-                "Deleted blocked image from '{}#{}' (hash: '{}')",
+                "Deleted blocked image from '{}#{}' (hash: '{}', kind: {})",
                 author.name,
                 author.discriminator,
-                x.to_base64()
+                hash,
+                x.source.kind()
             );
+            super::log_filtered_edit(reference, guild, channel, id, author, origin, &reason)
+                .await?;
             return Ok(true);
         }
     }
@@ -275,13 +822,14 @@ pub async fn filter_stickers(
     guild: serenity::GuildId,
     reference: super::EventReference<'_>,
 ) -> Result<(), super::Error> {
+    // Server stickers can be sent in any channel, so every list applies
     let mut hash_struct = HashData::new(guild, reference.3);
 
     for i in stickers {
         if let Some(url) = i.image_url() {
-            if let Some(hash) = hash_struct.check(Some(&url)).await {
+            if let Some(x) = hash_struct.check(ResolveUrl::Direct(&url)).await {
                 i.delete(reference.0).await?;
-                info!("Deleted sticker! (hash: '{}')", hash.to_base64());
+                info!("Deleted sticker! (hash: '{}')", x.hash.to_base64());
             }
         }
     }
@@ -296,13 +844,25 @@ pub async fn filter_member(
 ) -> Result<(), super::Error> {
     let mut hash_struct = HashData::new(guild, reference.3);
 
-    if let Some(hash) = hash_struct.check(Some(&member.face())).await {
-        kick_blocked_user(reference.0, guild, member.user.id).await?;
-        info!("Kicked user for image (hash: '{}')", hash.to_base64());
+    if let Some(x) = hash_struct.check(ResolveUrl::Direct(&member.face())).await {
+        kick_blocked_user(reference.0, reference.3, guild, member.user.id).await?;
+        info!("Kicked user for image (hash: '{}')", x.hash.to_base64());
     }
     Ok(())
 }
 
+/// Blocked hash matching `face` in `guild`, if any
+pub async fn blocked_match(
+    data: &super::Data,
+    guild: serenity::GuildId,
+    face: &str,
+) -> Option<ImageHash> {
+    HashData::new(guild, data)
+        .check(ResolveUrl::Direct(face))
+        .await
+        .map(|x| x.hash)
+}
+
 #[instrument(skip_all, err)]
 pub async fn filter_server(
     server: &serenity::PartialGuild,
@@ -311,20 +871,24 @@ pub async fn filter_server(
 ) -> Result<(), super::Error> {
     let mut hash_struct = HashData::new(guild, reference.3);
 
-    if let Some(hash) = hash_struct.check(server.icon_url().as_deref()).await {
-        guild.edit(reference.0, |f| f.icon(None)).await?;
-        info!(
-            "Removed blocked image from server icon (hash: '{}')",
-            hash.to_base64()
-        );
+    if let Some(icon) = server.icon_url() {
+        if let Some(x) = hash_struct.check(ResolveUrl::Icon(&icon)).await {
+            guild.edit(reference.0, |f| f.icon(None)).await?;
+            info!(
+                "Removed blocked image from server icon (hash: '{}')",
+                x.hash.to_base64()
+            );
+        }
     }
 
-    if let Some(hash) = hash_struct.check(server.banner_url().as_deref()).await {
-        guild.edit(reference.0, |f| f.banner(None)).await?;
-        info!(
-            "Removed blocked image from server banner (hash: '{}')",
-            hash.to_base64()
-        );
+    if let Some(banner) = server.banner_url() {
+        if let Some(x) = hash_struct.check(ResolveUrl::Banner(&banner)).await {
+            guild.edit(reference.0, |f| f.banner(None)).await?;
+            info!(
+                "Removed blocked image from server banner (hash: '{}')",
+                x.hash.to_base64()
+            );
+        }
     }
     Ok(())
 }
@@ -338,9 +902,12 @@ pub async fn filter_emojis(
     let mut hash_struct = HashData::new(guild, reference.3);
 
     for i in stickers {
-        if let Some(hash) = hash_struct.check(Some(&i.url())).await {
+        if let Some(name) = i.name.check_profanity() {
+            info!("Deleted emoji with profane name '{}'", name);
+            i.delete(reference.0).await?;
+        } else if let Some(x) = hash_struct.check(ResolveUrl::Direct(&i.url())).await {
             i.delete(reference.0).await?;
-            info!("Deleted emoji! (hash: '{}')", hash.to_base64());
+            info!("Deleted emoji! (hash: '{}')", x.hash.to_base64());
         }
     }
     Ok(())
@@ -352,20 +919,42 @@ pub async fn filter_reaction(
     guild: serenity::GuildId,
     reference: super::EventReference<'_>,
 ) -> Result<(), super::Error> {
-    let mut hash_struct = HashData::new(guild, reference.3);
+    let scope = channel_scope(reference.0, reference.3, guild, reaction.channel_id).await;
+    if scope == ListScope::Neither {
+        return Ok(());
+    }
+    let mut hash_struct = HashData::new(guild, reference.3)
+        .in_channel(reaction.channel_id)
+        .in_scope(scope);
 
     if let ReactionType::Custom { id, .. } = reaction.emoji {
-        if let Some(hash) = hash_struct
-            .check(ResolveUrl::Emoji(id).resolve().as_ref().map(AsRef::as_ref))
-            .await
-        {
+        if let Some(x) = hash_struct.check(ResolveUrl::Emoji(id)).await {
             reaction.delete(reference.0).await?;
-            info!("Deleted reaction! (hash: '{}')", hash.to_base64());
+            info!("Deleted reaction! (hash: '{}')", x.hash.to_base64());
         }
     }
     Ok(())
 }
 
+/// Every image in `msg` that can be blocked, including stickers and custom reactions
+fn message_images<'a>(
+    data: &super::Data,
+    guild: serenity::GuildId,
+    msg: &'a serenity::Message,
+) -> Vec<ResolveUrl<'a>> {
+    let mut urls = msg.get_urls(data.filters_linked_images(guild));
+    for i in &msg.sticker_items {
+        urls.push(ResolveUrl::Sticker(i));
+    }
+
+    for i in &msg.reactions {
+        if let ReactionType::Custom { .. } = &i.reaction_type {
+            urls.push(ResolveUrl::Reaction(i));
+        }
+    }
+    urls
+}
+
 /// Block an image
 #[instrument(skip_all, err)]
 #[poise::command(context_menu_command = "Block Image(s) or Reaction(s)", guild_only)]
@@ -375,35 +964,16 @@ pub async fn block_msg(ctx: Context<'_>, msg: serenity::Message) -> Result<(), E
         .ok_or(super::FedBotError::new("message not in guild"))?
         .id;
 
-    let server_data: BlockImageServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (mod_role,) = (serenity::RoleId(server_data.mod_role.repack()),);
-
-    check_mod_role!(ctx, guild, mod_role);
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
 
     crate::defer!(ctx);
 
-    let mut urls = msg.get_urls();
-    for i in &msg.sticker_items {
-        urls.push(ResolveUrl::Sticker(i));
-    }
-
-    for i in &msg.reactions {
-        if let ReactionType::Custom { .. } = &i.reaction_type {
-            urls.push(ResolveUrl::Reaction(i));
-        }
-    }
-
+    let urls = message_images(ctx.data(), guild, &msg);
     if urls.is_empty() {
         ctx.send(|f| {
             f.content("No image(s) found!")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
@@ -413,6 +983,90 @@ pub async fn block_msg(ctx: Context<'_>, msg: serenity::Message) -> Result<(), E
     Ok(())
 }
 
+/// Check whether a message's images are blocked, without blocking or deleting anything
+#[instrument(skip_all, err)]
+#[poise::command(context_menu_command = "Check Image(s)", guild_only)]
+pub async fn check_msg(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("message not in guild"))?;
+
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    crate::defer!(ctx);
+
+    let urls = message_images(ctx.data(), guild, &msg);
+    if urls.is_empty() {
+        ctx.send(|f| f.content("No image(s) found!").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    // Loaded once up front so every image is checked against the same blocklist
+    let mut hash_struct = HashData::new(guild, ctx.data());
+    hash_struct.get().await;
+    let mut lines = vec![];
+    for (index, i) in urls.iter().enumerate() {
+        let hash = match i.resolve() {
+            Some(url) => hash_struct.fetch_hash(&url).await,
+            None => None,
+        };
+        let verdict = match hash {
+            None => "couldn't be downloaded or read".to_owned(),
+            Some(x) => match hash_struct.list_of(&x).await {
+                None => format!("not blocked (hash `{}`)", x.to_base64()),
+                Some(list) => {
+                    let list = if list == Blocklist::Nsfw {
+                        " on the NSFW blocklist"
+                    } else {
+                        ""
+                    };
+                    let expiry = hash_struct.expiries.get(&x);
+                    let list = match expiry {
+                        Some(y) => format!("{list} until <t:{}:f>", y.timestamp()),
+                        None => list.to_owned(),
+                    };
+                    match hash_struct.exemptions.get(&x) {
+                        _ if hash_struct.whitelist.contains(&x) => format!(
+                            "**whitelisted**, blocked{list} but never filtered (hash `{}`)",
+                            x.to_base64()
+                        ),
+                        _ if super::hash_expiry::is_expired(expiry, Utc::now()) => format!(
+                            "**expired**, blocked{list} and no longer filtered (hash `{}`)",
+                            x.to_base64()
+                        ),
+                        Some(channels) => format!(
+                            "**partially blocked**{list}, allowed in {} (hash `{}`)",
+                            channels.iter().map(Mentionable::mention).join(", "),
+                            x.to_base64()
+                        ),
+                        None => format!("**blocked**{list} (hash `{}`)", x.to_base64()),
+                    }
+                }
+            },
+        };
+        lines.push(format!("{}. {}: {}", index + 1, i.kind(), verdict));
+    }
+
+    for (index, i) in super::chunk_lines(&lines, MAX_EMBED_DESCRIPTION_LENGTH)
+        .into_iter()
+        .enumerate()
+    {
+        ctx.send(|f| {
+            f.embed(|f| {
+                if index == 0 {
+                    f.title("Image check");
+                }
+                f.description(i)
+            })
+            .ephemeral(true)
+        })
+        .await?;
+    }
+    Ok(())
+}
+
 /// Block the server icon or banner
 #[instrument(skip_all, err)]
 #[poise::command(slash_command, rename = "block_icon", guild_only)]
@@ -422,17 +1076,8 @@ pub async fn block_server(ctx: Context<'_>) -> Result<(), Error> {
         .ok_or(super::FedBotError::new("message not in guild"))?
         .id;
 
-    let server_data: BlockImageServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (mod_role,) = (serenity::RoleId(server_data.mod_role.repack()),);
-
-    check_mod_role!(ctx, guild, mod_role);
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
 
     crate::defer!(ctx);
 
@@ -450,7 +1095,7 @@ pub async fn block_server(ctx: Context<'_>) -> Result<(), Error> {
     if urls.is_empty() {
         ctx.send(|f| {
             f.content("No image(s) found!")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
@@ -469,17 +1114,8 @@ pub async fn block_pfp(ctx: Context<'_>, user: serenity::User) -> Result<(), Err
         .ok_or(super::FedBotError::new("message not in guild"))?
         .id;
 
-    let server_data: BlockImageServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (mod_role,) = (serenity::RoleId(server_data.mod_role.repack()),);
-
-    check_mod_role!(ctx, guild, mod_role);
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
 
     crate::defer!(ctx);
 
@@ -516,11 +1152,26 @@ async fn confirm_blocks(
                                 f.custom_id(format!("{index}-keep"))
                                     .style(serenity::ButtonStyle::Success)
                                     .label("Keep")
-                            })
+                            });
+                            // Only message images have a poster to ban
+                            if msg.is_some() {
+                                f.create_button(|f| {
+                                    f.custom_id(format!("{index}-ban"))
+                                        .style(serenity::ButtonStyle::Danger)
+                                        .label("Block + Ban Poster")
+                                });
+                            }
+                            f
                         })
                     })
-                    .embed(|f| f.image(url))
-                    .ephemeral(ctx.data().is_ephemeral)
+                    .embed(|f| {
+                        if i.is_lottie() {
+                            f.description(LOTTIE_PLACEHOLDER)
+                        } else {
+                            f.image(url)
+                        }
+                    })
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
                 })
                 .await?,
             );
@@ -556,41 +1207,124 @@ async fn confirm_blocks(
         ));
     }
 
-    let mut new_hashes: Vec<u8> = vec![];
-    let old_hashes = HashData::new(guild, ctx.data()).retrieve().await;
+    let mut new_hashes: Vec<ImageHash> = vec![];
     let mut hashes_changed = false;
     let mut msg_deleted = false;
     let mut indexes_to_delete = vec![];
+    let mut refusals = vec![];
     while let Some(i) = interactions.join_next().await {
-        if let Some((index, to_delete)) = i? {
+        if let Some((index, decision)) = i? {
             if let Some(msg) = responses.get(index) {
                 msg.delete(ctx).await?;
             }
-            if to_delete {
-                indexes_to_delete.push(index);
+            if decision != BlockDecision::Keep {
+                indexes_to_delete.push((index, decision == BlockDecision::BlockAndBan));
             }
         }
     }
 
-    for index in indexes_to_delete {
+    let choices = if indexes_to_delete.is_empty() {
+        Some((Blocklist::Main, BlockExpiry::Permanent))
+    } else if let Some(x) = ask_blocklist(ctx, guild).await? {
+        super::hash_expiry::ask_expiry(ctx).await?.map(|y| (x, y))
+    } else {
+        None
+    };
+    let Some((list, expiry)) = choices else {
+        ctx.send(|f| {
+            f.content("No images blocked.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+    let expires_at = expiry.expires_at(Utc::now());
+    let old_hashes = match (HashData::new(guild, ctx.data()).retrieve().await, list) {
+        ((x, _), Blocklist::Main) | ((_, x), Blocklist::Nsfw) => x,
+    };
+    let old_expiries = load_expiries(&ctx.data().db, guild).await?;
+
+    let mut ban_hashes = vec![];
+    for (index, ban) in indexes_to_delete {
         if let Some(resolve) = urls.get(index) {
             if let Some(url) = &resolve.resolve() {
-                let hash =
-                    hash_and_delete(ctx, msg, user, &mut msg_deleted, guild, url, resolve).await?;
-                if !old_hashes.as_ref().is_some_and(|x| x.contains(&hash)) {
+                // Only images from messages can be scoped to channels
+                let exemptions = if msg.is_some() {
+                    ask_exemptions(ctx, guild, Some(url), &[])
+                        .await?
+                        .unwrap_or_default()
+                } else {
+                    vec![]
+                };
+                // Leave the source message and its author alone if the image stays allowed here
+                let (source, author) = if exemptions.contains(&ctx.channel_id()) {
+                    (None, None)
+                } else {
+                    (msg, user)
+                };
+                let hash = match hash_and_delete(
+                    ctx,
+                    source,
+                    author,
+                    &mut msg_deleted,
+                    guild,
+                    url,
+                    resolve,
+                )
+                .await?
+                {
+                    Ok(x) => x,
+                    Err(reason) => {
+                        refusals.push(reason);
+                        continue;
+                    }
+                };
+                if msg.is_some() {
+                    save_exemptions(&ctx.data().db, guild, &hash, &exemptions).await?;
+                }
+                // Blocking an image again also replaces how long it stays blocked
+                save_expiry(&ctx.data().db, guild, &hash, expires_at).await?;
+                if old_hashes.contains(&hash) && old_expiries.get(&hash) != expires_at.as_ref() {
+                    hashes_changed = true;
+                    info!(
+                        "Changed expiry of blocked image to {:?} (blocker: '{}') (hash: '{}')",
+                        expiry,
+                        ctx.author().tag(),
+                        hash.to_base64()
+                    );
+                }
+                // Blocking an image again overrides an earlier review
+                if super::false_positives::unwhitelist_hash(&ctx.data().db, guild, &hash).await? {
+                    hashes_changed = true;
+                    info!(
+                        "Removed re-blocked image from the whitelist (blocker: '{}') (hash: '{}')",
+                        ctx.author().tag(),
+                        hash.to_base64()
+                    );
+                }
+                if ban && source.is_some() {
+                    ban_hashes.push(hash.clone());
+                }
+                if !old_hashes.contains(&hash) {
                     hashes_changed = true;
                     info!(
                         "Added new blocked image (blocker: '{}') (hash: '{}')",
                         ctx.author().tag(),
                         hash.to_base64()
                     );
-                    new_hashes.extend_from_slice(hash.as_bytes());
+                    new_hashes.push(hash);
                 }
             }
         }
     }
 
-    if let Some(msg) = msg {
+    // Fetched now, as the message may be gone by the time the poster is banned
+    let poster = match (msg, ban_hashes.is_empty()) {
+        (Some(msg), false) => Some(ctx.channel_id().message(ctx, msg).await?.author),
+        _ => None,
+    };
+
+    if let Some(msg) = msg {
         if msg_deleted {
             let author = ctx.channel_id().message(ctx, msg).await?.author.mention();
             ctx.channel_id()
@@ -604,31 +1338,653 @@ async fn confirm_blocks(
         }
     }
 
+    if let (Some(poster), Some(hash)) = (poster, ban_hashes.first()) {
+        if let Some(deleted_messages) = ban_poster(ctx, guild, &poster, hash).await? {
+            // The original message went with the rest of the poster's last day
+            msg_deleted |= deleted_messages;
+            // Block the account art too, so it can't come back on an alt
+            if let Some(avatar) = poster.avatar_url() {
+                match hash_and_delete(
+                    ctx,
+                    None,
+                    None,
+                    &mut msg_deleted,
+                    guild,
+                    &avatar,
+                    &ResolveUrl::Direct(&avatar),
+                )
+                .await?
+                {
+                    Ok(hash) => {
+                        if !old_hashes.contains(&hash) && !new_hashes.contains(&hash) {
+                            save_expiry(&ctx.data().db, guild, &hash, expires_at).await?;
+                            hashes_changed = true;
+                            info!(
+                                "Added banned poster's avatar to blocked images (blocker: '{}') (hash: '{}')",
+                                ctx.author().tag(),
+                                hash.to_base64()
+                            );
+                            new_hashes.push(hash);
+                        }
+                    }
+                    Err(reason) => refusals.push(format!("{}'s avatar: {reason}", poster.tag())),
+                }
+            }
+        }
+    }
+
+    if !refusals.is_empty() {
+        ctx.send(|f| {
+            f.content(format!(
+                "Refused to block image(s):\n{}",
+                refusals.iter().map(|x| format!("- {x}")).format("\n")
+            ))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    }
+
     if !hashes_changed {
         ctx.send(|f| {
             f.content("No images blocked.")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
     }
 
-    if let Some(hashes) = old_hashes {
-        for i in hashes {
-            new_hashes.extend_from_slice(i.as_bytes());
+    new_hashes.extend(old_hashes);
+    let encoded = ActiveValue::Set(Some(super::serialization::encode_blocked_images(
+        &new_hashes,
+    )));
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    match list {
+        Blocklist::Main => model.blocked_images = encoded,
+        Blocklist::Nsfw => model.nsfw_blocked_images = encoded,
+    }
+    model.update(&ctx.data().db).await?;
+
+    let mut confirmation = match list {
+        Blocklist::Main => "Added image(s) to blocklist!",
+        Blocklist::Nsfw => "Added image(s) to the NSFW blocklist!",
+    }
+    .to_owned();
+    if let Some(x) = expires_at {
+        confirmation.push_str(&format!(" They will be unblocked <t:{}:R>.", x.timestamp()));
+    }
+    ctx.send(|f| {
+        f.content(confirmation)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+
+    if let Some(msg) = msg {
+        if !msg_deleted {
+            offer_original_deletion(ctx, msg).await?;
         }
     }
+
+    Ok(())
+}
+
+/// Ask which list to block images on when blocking from an NSFW channel of a server that keeps a
+/// separate NSFW blocklist, or `None` if the blocker didn't answer
+async fn ask_blocklist(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+) -> Result<Option<Blocklist>, Error> {
+    if ctx.data().nsfw_policy_for(guild) != servers::NsfwChannelPolicy::Separate
+        || !ctx
+            .data()
+            .nsfw_channels
+            .is_nsfw(ctx.serenity_context(), ctx.channel_id())
+            .await
+    {
+        return Ok(Some(Blocklist::Main));
+    }
+
+    let prompt = ctx
+        .send(|f| {
+            f.content("This is an NSFW channel. Which blocklist should the image(s) go on?")
+                .components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id("nsfw")
+                                .label("NSFW blocklist (every channel)")
+                                .style(serenity::ButtonStyle::Danger)
+                        })
+                        .create_button(|f| {
+                            f.custom_id("main")
+                                .label("Main blocklist (outside NSFW channels)")
+                                .style(serenity::ButtonStyle::Primary)
+                        })
+                    })
+                })
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    let response = prompt
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(60))
+        .await;
+    prompt.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(None);
+    };
+    response.defer(ctx).await?;
+    Ok(Some(if response.data.custom_id == "nsfw" {
+        Blocklist::Nsfw
+    } else {
+        Blocklist::Main
+    }))
+}
+
+/// Let the blocker also delete the message the newly blocked images came from
+async fn offer_original_deletion(ctx: Context<'_>, msg: serenity::MessageId) -> Result<(), Error> {
+    let prompt = ctx
+        .send(|f| {
+            f.content("Also delete the original message?")
+                .components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id("delete")
+                                .label("Delete message")
+                                .style(serenity::ButtonStyle::Danger)
+                        })
+                    })
+                })
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+
+    let response = prompt
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(15))
+        .await;
+    prompt.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(());
+    };
+    response.defer(ctx).await?;
+
+    let author = ctx.channel_id().message(ctx, msg).await?.author;
+    ctx.channel_id().delete_message(ctx, msg).await?;
+    ctx.channel_id()
+        .send_message(ctx, |f| {
+            f.content(format!(
+                "Deleted message from {} (reason: blocked image)",
+                author.mention()
+            ))
+        })
+        .await?;
+    info!(
+        "Deleted original message of newly blocked image from '{}' (blocker: '{}')",
+        author.tag(),
+        ctx.author().tag()
+    );
+    Ok(())
+}
+
+/// Why the bot can't ban `target` from `guild`, if it can't
+async fn ban_refusal(
+    ctx: Context<'_>,
+    guild: &serenity::Guild,
+    target: serenity::UserId,
+) -> Result<Option<&'static str>, Error> {
+    let bot_id = ctx.data().bot_id;
+    if target == guild.owner_id {
+        return Ok(Some("they own the server"));
+    }
+    if !guild.member_permissions(ctx, bot_id).await?.ban_members() {
+        return Ok(Some("the bot is missing the Ban Members permission"));
+    }
+    // Roles only matter while the poster is still in the server
+    if guild.members.contains_key(&target)
+        && guild.greater_member_hierarchy(&ctx.serenity_context().cache, bot_id, target)
+            != Some(bot_id)
+    {
+        return Ok(Some("their highest role isn't below the bot's"));
+    }
+    Ok(None)
+}
+
+/// Ban the poster of a newly blocked image, returning whether their last day of messages went
+/// too, or `None` if they couldn't be banned
+async fn ban_poster(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    poster: &serenity::User,
+    hash: &ImageHash,
+) -> Result<Option<bool>, Error> {
+    let server = ctx
+        .guild()
+        .ok_or(super::FedBotError::new("server not in cache"))?;
+    if let Some(reason) = ban_refusal(ctx, &server, poster.id).await? {
+        ctx.send(|f| {
+            f.content(format!(
+                "Blocked the image, but couldn't ban {}: {reason}.",
+                poster.mention()
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        super::mod_log(
+            ctx.serenity_context(),
+            ctx.data(),
+            guild,
+            None,
+            format!(
+                "{} blocked an image from {}, but they couldn't be banned: {reason}.",
+                ctx.author().mention(),
+                poster.mention()
+            ),
+        )
+        .await?;
+        return Ok(None);
+    }
+
+    let prompt = ctx
+        .send(|f| {
+            f.content(format!(
+                "Also delete {}'s messages from the last day?",
+                poster.mention()
+            ))
+            .components(|f| {
+                f.create_action_row(|f| {
+                    f.create_button(|f| {
+                        f.custom_id("purge")
+                            .label("Delete messages")
+                            .style(serenity::ButtonStyle::Danger)
+                    })
+                    .create_button(|f| {
+                        f.custom_id("keep")
+                            .label("Keep messages")
+                            .style(serenity::ButtonStyle::Secondary)
+                    })
+                })
+            })
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    let response = prompt
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(15))
+        .await;
+    prompt.delete(ctx).await?;
+    let purge = if let Some(response) = response {
+        response.defer(ctx).await?;
+        response.data.custom_id == "purge"
+    } else {
+        false
+    };
+
+    guild
+        .ban_with_reason(
+            ctx,
+            poster.id,
+            u8::from(purge),
+            format!("Posted a blocked image (hash: {})", hash.to_base64()),
+        )
+        .await?;
+    info!(
+        "Banned '{}' for posting a blocked image (banner: '{}') (hash: '{}')",
+        poster.tag(),
+        ctx.author().tag(),
+        hash.to_base64()
+    );
+    super::mod_log(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild,
+        None,
+        format!(
+            "{} blocked an image and banned its poster {} ({}).",
+            ctx.author().mention(),
+            poster.mention(),
+            if purge {
+                "deleted their last day of messages"
+            } else {
+                "kept their messages"
+            }
+        ),
+    )
+    .await?;
+    Ok(Some(purge))
+}
+
+const MAX_SELECT_OPTIONS: usize = 25;
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+
+/// Ask which channels `url` should stay allowed in, or `None` if nothing was chosen in time
+async fn ask_exemptions(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    url: Option<&str>,
+    current: &[serenity::ChannelId],
+) -> Result<Option<Vec<serenity::ChannelId>>, Error> {
+    // Keep the current channel and existing exemptions selectable even in large servers
+    let channels = guild
+        .channels(ctx)
+        .await?
+        .into_values()
+        .filter(|x| x.kind == serenity::ChannelType::Text)
+        .sorted_by_key(|x| {
+            (
+                x.id != ctx.channel_id() && !current.contains(&x.id),
+                x.position,
+            )
+        })
+        .take(MAX_SELECT_OPTIONS)
+        .collect::<Vec<_>>();
+
+    let prompt = ctx
+        .send(|f| {
+            if let Some(url) = url {
+                f.embed(|f| f.thumbnail(url));
+            }
+            f.content("Block this image everywhere, or select channels where it stays allowed.")
+                .components(|f| {
+                    if !channels.is_empty() {
+                        f.create_action_row(|f| {
+                            f.create_select_menu(|f| {
+                                f.custom_id("exempt")
+                                    .placeholder("Only block outside these channels")
+                                    .min_values(1)
+                                    .max_values(channels.len() as u64)
+                                    .options(|f| {
+                                        for i in &channels {
+                                            f.create_option(|f| {
+                                                f.label(format!("#{}", i.name))
+                                                    .value(i.id)
+                                                    .default_selection(current.contains(&i.id))
+                                            });
+                                        }
+                                        f
+                                    })
+                            })
+                        });
+                    }
+                    f.create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id("everywhere")
+                                .label("Block everywhere")
+                                .style(serenity::ButtonStyle::Danger)
+                        })
+                    })
+                })
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+
+    let response = prompt
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(60))
+        .await;
+    prompt.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(None);
+    };
+    response.defer(ctx).await?;
+    Ok(Some(
+        response
+            .data
+            .values
+            .iter()
+            .filter_map(|x| x.parse().ok().map(serenity::ChannelId))
+            .collect(),
+    ))
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands(
+        "list_blocklist",
+        "edit_exemptions",
+        "super::false_positives::unwhitelist"
+    )
+)]
+pub async fn blocklist(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// List this server's blocked images
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn list_blocklist(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    crate::defer!(ctx);
+
+    let (hashes, nsfw_hashes) = HashData::new(guild, ctx.data()).retrieve().await;
+    let whitelist = super::false_positives::load_whitelist(&ctx.data().db, guild).await?;
+    if hashes.is_empty() && nsfw_hashes.is_empty() && whitelist.is_empty() {
+        ctx.send(|f| {
+            f.content("No images are blocked.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let exemptions = load_exemptions(&ctx.data().db, guild).await?;
+    let expiries = load_expiries(&ctx.data().db, guild).await?;
+    // Whitelisted images are labelled on the blocklists and listed on their own
+    for (title, list, labelled) in [
+        ("Blocked images", hashes, true),
+        (
+            "NSFW blocklist, applying in every channel",
+            nsfw_hashes,
+            true,
+        ),
+        (
+            "Whitelisted images, never filtered (remove with /blocklist unwhitelist)",
+            whitelist
+                .iter()
+                .cloned()
+                .sorted_by_key(ImageHash::to_base64)
+                .collect(),
+            false,
+        ),
+    ] {
+        if list.is_empty() {
+            continue;
+        }
+        let lines = list
+            .iter()
+            .map(|x| {
+                let line = match exemptions.get(x) {
+                    _ if !labelled => return format!("`{}`", x.to_base64()),
+                    _ if whitelist.contains(x) => {
+                        format!("`{}` (whitelisted)", x.to_base64())
+                    }
+                    Some(channels) => format!(
+                        "`{}` (partial block, allowed in {})",
+                        x.to_base64(),
+                        channels.iter().map(Mentionable::mention).join(", ")
+                    ),
+                    None => format!("`{}`", x.to_base64()),
+                };
+                match expiries.get(x) {
+                    Some(y) => format!("{line} (expires <t:{}:R>)", y.timestamp()),
+                    None => line,
+                }
+            })
+            .collect::<Vec<_>>();
+        for (index, i) in super::chunk_lines(&lines, MAX_EMBED_DESCRIPTION_LENGTH)
+            .into_iter()
+            .enumerate()
+        {
+            ctx.send(|f| {
+                f.embed(|f| {
+                    if index == 0 {
+                        f.title(format!("{title} ({})", list.len()));
+                    }
+                    f.description(i)
+                })
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+async fn hash_autocomplete<'a>(
+    ctx: Context<'a>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let (hashes, nsfw_hashes) = match ctx.guild_id() {
+        Some(x) => HashData::new(x, ctx.data()).retrieve().await,
+        None => Default::default(),
+    };
+    hashes
+        .into_iter()
+        .chain(nsfw_hashes)
+        .map(|x| x.to_base64())
+        .filter(move |x| x.starts_with(partial))
+        .take(MAX_SELECT_OPTIONS)
+}
+
+/// Change which channels a blocked image is allowed in
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "exemptions")]
+async fn edit_exemptions(
+    ctx: Context<'_>,
+    #[description = "Blocked image hash, as shown by /blocklist list"]
+    #[autocomplete = "hash_autocomplete"]
+    hash: String,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    let (hashes, nsfw_hashes) = HashData::new(guild, ctx.data()).retrieve().await;
+    let Some(hash) = ImageHash::from_base64(hash.trim())
+        .ok()
+        .filter(|x| hashes.contains(x) || nsfw_hashes.contains(x))
+    else {
+        ctx.send(|f| {
+            f.content("That image isn't on the blocklist.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+
+    let current = load_exemptions(&ctx.data().db, guild)
+        .await?
+        .remove(&hash)
+        .unwrap_or_default();
+    let Some(channels) = ask_exemptions(ctx, guild, None, &current).await? else {
+        return Ok(());
+    };
+    save_exemptions(&ctx.data().db, guild, &hash, &channels).await?;
+
+    info!(
+        "User '{}' set exemptions for blocked image '{}' in guild '{}' to {:?}",
+        ctx.author().tag(),
+        hash.to_base64(),
+        guild,
+        channels
+    );
+    ctx.send(|f| {
+        f.content(if channels.is_empty() {
+            "This image is now blocked everywhere.".to_owned()
+        } else {
+            format!(
+                "This image is now allowed in {}.",
+                channels.iter().map(Mentionable::mention).join(", ")
+            )
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, poise::ChoiceParameter)]
+pub enum NsfwPolicy {
+    #[name = "Enforce everywhere"]
+    Enforce,
+    #[name = "Skip NSFW channels"]
+    Skip,
+    #[name = "Separate NSFW blocklist"]
+    Separate,
+}
+
+impl From<NsfwPolicy> for servers::NsfwChannelPolicy {
+    fn from(x: NsfwPolicy) -> Self {
+        match x {
+            NsfwPolicy::Enforce => Self::Enforce,
+            NsfwPolicy::Skip => Self::Skip,
+            NsfwPolicy::Separate => Self::Separate,
+        }
+    }
+}
+
+/// Choose how the image filter treats age-restricted channels
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn nsfw_policy(
+    ctx: Context<'_>,
+    #[description = "How blocked images are handled in NSFW channels"] policy: NsfwPolicy,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
     let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
-    model.id = ActiveValue::Unchanged(guild.as_u64().repack());
-    model.blocked_images = ActiveValue::Set(Some(new_hashes));
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.nsfw_channel_policy = ActiveValue::Set(policy.into());
+    let changes = super::profile_setup::diff_profile(Some(&old_profile), &model);
     model.update(&ctx.data().db).await?;
+    ctx.data().set_nsfw_policy_for(guild, policy.into());
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "NSFW channel policy updated", changes).await?;
+    }
 
     ctx.send(|f| {
-        f.content("Added image(s) to blocklist!")
-            .ephemeral(ctx.data().is_ephemeral)
+        f.content(match policy {
+            NsfwPolicy::Enforce => "Blocked images will be removed from every channel.",
+            NsfwPolicy::Skip => "Images in NSFW channels won't be filtered.",
+            NsfwPolicy::Separate => "NSFW channels will only be filtered against the NSFW blocklist, which also applies everywhere else. Images blocked from an NSFW channel can go on either list.",
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
-
     Ok(())
 }
 
@@ -640,14 +1996,30 @@ async fn hash_and_delete(
     mut guild: serenity::GuildId,
     url: &str,
     resolve: &ResolveUrl<'_>,
-) -> Result<ImageHash, Error> {
-    let img = ImageReader::new(Cursor::new(
-        ctx.data().reqwest.get(url).send().await?.bytes().await?,
-    ))
-    .with_guessed_format()?
-    .decode()?;
+) -> Result<Result<ImageHash, String>, Error> {
+    let Some(hash) = hash_content(
+        ctx.data(),
+        url,
+        &ctx.data().reqwest.get(url).send().await?.bytes().await?,
+    )?
+    else {
+        let floor = min_image_dimension();
+        return Ok(Err(format!(
+            "this image is smaller than {floor}x{floor}, so its hash is too generic to block safely"
+        )));
+    };
 
-    let hash = ctx.data().hasher.hash_image(&img);
+    if let Some(name) = safe_image_name(ctx.data(), &hash).await {
+        info!(
+            "Refused to block safe image '{}' (blocker: '{}') (hash: '{}')",
+            name,
+            ctx.author().tag(),
+            hash.to_base64()
+        );
+        return Ok(Err(format!(
+            "this image matches {name}, and blocking it would delete legitimate content"
+        )));
+    }
 
     match resolve {
         ResolveUrl::Emoji(id) => match guild.emoji(ctx, *id).await {
@@ -682,12 +2054,12 @@ async fn hash_and_delete(
                 }
             }
         },
-        ResolveUrl::Direct(_) => {
+        ResolveUrl::Direct(_) | ResolveUrl::Attachment(_) | ResolveUrl::Embed(_) => {
             if msg.is_some() {
                 *msg_to_be_deleted = true;
             }
             if let Some(user) = user {
-                kick_blocked_user(ctx, guild, user).await?;
+                kick_blocked_user(ctx.serenity_context(), ctx.data(), guild, user).await?;
                 info!("Kicked user for image (hash: '{}')", hash.to_base64());
             }
         }
@@ -720,40 +2092,293 @@ async fn hash_and_delete(
             );
         }
     };
-    Ok(hash)
+    Ok(Ok(hash))
+}
+
+#[derive(FromQueryResult)]
+struct AppealData {
+    appeal_contact: Option<String>,
 }
 
-async fn kick_blocked_user<
-    T: serenity::CacheHttp + AsRef<serenity::Http> + AsRef<serenity::Cache> + Copy,
->(
-    ctx: T,
+/// DM `user` why they're being kicked, then kick them, and tell the mods whether the DM arrived
+async fn kick_blocked_user(
+    ctx: &serenity::Context,
+    data: &super::Data,
     guild: serenity::GuildId,
     user: serenity::UserId,
 ) -> Result<(), Error> {
-    let dm = user.create_dm_channel(ctx).await?;
-    // TODO: Get invite
-    dm.say(ctx, format!("{}, you have been kicked from {} for having a blocked image in your profile picture. Please change your profile and reapply.", user.mention(), guild.name(ctx).unwrap_or(String::from("the server")))).await?;
+    let appeal_contact = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::AppealContact)
+        .into_model::<AppealData>()
+        .one(&data.db)
+        .await?
+        .and_then(|x| x.appeal_contact);
+    let mut content = format!(
+        "{}, you have been kicked from {} for having a blocked image in your profile picture. Please change your profile and reapply.",
+        user.mention(),
+        guild.name(ctx).unwrap_or(String::from("the server"))
+    );
+    if let Some(x) = appeal_contact {
+        content.push_str(&format!("\nTo appeal, contact {x}."));
+    }
 
-    guild
-        .kick_with_reason(ctx, user, "Blocked image in profile picture")
-        .await?;
+    let notified = notify_then_kick(
+        || async {
+            user.create_dm_channel(ctx).await?.say(ctx, content).await?;
+            Ok(())
+        },
+        || async {
+            guild
+                .kick_with_reason(ctx, user, "Blocked image in profile picture")
+                .await?;
+            Ok(())
+        },
+    )
+    .await?;
+    data.events.publish(super::events::BotEvent::MemberKicked {
+        guild,
+        user,
+        reason: "Blocked image in profile picture".to_owned(),
+    });
+
+    super::mod_log(
+        ctx,
+        data,
+        guild,
+        Some(guild_log_channels::Purpose::FilterNotices),
+        format!(
+            "Kicked {} for a blocked image in their profile picture ({}).",
+            user.mention(),
+            if notified {
+                "user notified via DM"
+            } else {
+                "could not DM user"
+            }
+        ),
+    )
+    .await?;
     Ok(())
 }
 
+/// Run the best-effort `dm` before `kick`, returning whether the DM was delivered
+///
+/// The DM has to come first, as the user may share no other server with the bot afterwards.
+async fn notify_then_kick<D, DFut, K, KFut>(dm: D, kick: K) -> Result<bool, Error>
+where
+    D: FnOnce() -> DFut,
+    DFut: std::future::Future<Output = Result<(), Error>>,
+    K: FnOnce() -> KFut,
+    KFut: std::future::Future<Output = Result<(), Error>>,
+{
+    let notified = match dm().await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to DM user before kicking them: {}", e);
+            false
+        }
+    };
+    kick().await?;
+    Ok(notified)
+}
+
+/// What a mod chose for one image awaiting confirmation
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum BlockDecision {
+    Keep,
+    Block,
+    BlockAndBan,
+}
+
+/// Read the image index and decision from a confirmation button's ID
+fn parse_decision(custom_id: &str) -> Option<(usize, BlockDecision)> {
+    let mut split_string = custom_id.split('-');
+    let index = split_string.next().and_then(|x| x.parse::<usize>().ok());
+    let result = split_string.next().and_then(|x| match x {
+        "keep" => Some(BlockDecision::Keep),
+        "block" => Some(BlockDecision::Block),
+        "ban" => Some(BlockDecision::BlockAndBan),
+        _ => None,
+    });
+    index.and_then(|a| result.map(|b| (a, b)))
+}
+
 async fn get_response(
     http: std::sync::Arc<serenity::Http>,
     interaction: serenity::CollectComponentInteraction,
-) -> Option<(usize, bool)> {
+) -> Option<(usize, BlockDecision)> {
     if let Some(response) = interaction.await {
-        let mut split_string = response.data.custom_id.split('-');
-        let index = split_string.next().and_then(|x| x.parse::<usize>().ok());
-        let result = split_string.next().and_then(|x| match x {
-            "keep" => Some(false),
-            "block" => Some(true),
-            _ => None,
-        });
         response.defer(http).await.ok();
-        return index.and_then(|a| result.map(|b| (a, b)));
+        return parse_decision(&response.data.custom_id);
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future::block_on;
+    use std::cell::RefCell;
+
+    fn run(dm_ok: bool, kick_ok: bool) -> (Result<bool, Error>, Vec<&'static str>) {
+        let calls = RefCell::new(vec![]);
+        let result = block_on(notify_then_kick(
+            || async {
+                calls.borrow_mut().push("dm");
+                if dm_ok {
+                    Ok(())
+                } else {
+                    Err(crate::ext::FedBotError::new("dms closed").into())
+                }
+            },
+            || async {
+                calls.borrow_mut().push("kick");
+                if kick_ok {
+                    Ok(())
+                } else {
+                    Err(crate::ext::FedBotError::new("missing permissions").into())
+                }
+            },
+        ));
+        (result, calls.into_inner())
+    }
+
+    #[test]
+    fn dm_is_sent_before_kick() {
+        let (result, calls) = run(true, true);
+        assert!(result.unwrap());
+        assert_eq!(calls, ["dm", "kick"]);
+    }
+
+    #[test]
+    fn failed_dm_still_kicks() {
+        let (result, calls) = run(false, true);
+        assert!(!result.unwrap());
+        assert_eq!(calls, ["dm", "kick"]);
+    }
+
+    #[test]
+    fn failed_kick_is_an_error() {
+        let (result, _) = run(true, false);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn lottie_hash_is_stable() {
+        let hash = lottie_hash(br#"{"v":"5.5.2","fr":60}"#).unwrap();
+        assert_eq!(hash.as_bytes().len(), usize::from(crate::ext::HASH_BYTES));
+        assert_eq!(hash, lottie_hash(br#"{"v":"5.5.2","fr":60}"#).unwrap());
+        assert_ne!(hash, lottie_hash(br#"{"v":"5.5.2","fr":30}"#).unwrap());
+        assert_eq!(
+            lottie_hash(b"").unwrap().as_bytes(),
+            0xcbf2_9ce4_8422_2325_u64.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn only_images_small_in_both_dimensions_are_tiny() {
+        assert!(is_tiny(16, 16, 32));
+        assert!(is_tiny(31, 31, 32));
+        assert!(!is_tiny(32, 16, 32));
+        assert!(!is_tiny(16, 400, 32));
+        assert!(!is_tiny(16, 16, 0));
+    }
+
+    #[test]
+    fn links_are_extracted_from_content() {
+        let urls = linked_urls("look https://example.com/banned.png and <http://a.b/c.gif>!");
+        assert_eq!(
+            urls.iter()
+                .filter_map(|x| match x {
+                    ResolveUrl::Direct(y) => Some(*y),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            ["https://example.com/banned.png", "http://a.b/c.gif"]
+        );
+        assert!(linked_urls("no links here, just http:/ text").is_empty());
+    }
+
+    fn sticker(format_type: u8) -> serenity::StickerItem {
+        serde_json::from_value(serde_json::json!({
+            "id": "42",
+            "name": "sticker",
+            "format_type": format_type,
+        }))
+        .unwrap()
+    }
+
+    fn reaction(emoji: serde_json::Value) -> serenity::MessageReaction {
+        serde_json::from_value(serde_json::json!({ "count": 1, "me": false, "emoji": emoji }))
+            .unwrap()
+    }
+
+    #[test]
+    fn emojis_resolve_to_cdn() {
+        assert_eq!(
+            ResolveUrl::Emoji(serenity::EmojiId(123))
+                .resolve()
+                .as_deref(),
+            Some("https://cdn.discordapp.com/emojis/123")
+        );
+        let custom = reaction(serde_json::json!({ "id": "123", "name": "x" }));
+        assert_eq!(
+            ResolveUrl::Reaction(&custom).resolve().as_deref(),
+            Some("https://cdn.discordapp.com/emojis/123")
+        );
+        let unicode = reaction(serde_json::json!({ "id": null, "name": "\u{1f44d}" }));
+        assert_eq!(ResolveUrl::Reaction(&unicode).resolve(), None);
+    }
+
+    #[test]
+    fn urls_resolve_unchanged() {
+        let url = "https://example.com/a.png";
+        for i in [
+            ResolveUrl::Direct(url),
+            ResolveUrl::Attachment(url),
+            ResolveUrl::Embed(url),
+            ResolveUrl::Icon(url),
+            ResolveUrl::Banner(url),
+        ] {
+            assert_eq!(i.resolve().as_deref(), Some(url));
+        }
+    }
+
+    #[test]
+    fn stickers_resolve_by_format() {
+        let png = sticker(1);
+        assert_eq!(
+            ResolveUrl::Sticker(&png).resolve().as_deref(),
+            Some("https://cdn.discordapp.com/stickers/42.png")
+        );
+        let lottie = sticker(3);
+        let lottie_url = ResolveUrl::Sticker(&lottie).resolve();
+        assert!(lottie_url.is_some_and(|x| x.starts_with(LOTTIE_STICKER_PREFIX)));
+        assert!(ResolveUrl::Sticker(&lottie).is_lottie());
+        assert_eq!(ResolveUrl::Sticker(&sticker(99)).resolve(), None);
+    }
+
+    #[test]
+    fn confirmation_buttons_parse() {
+        assert_eq!(parse_decision("0-keep"), Some((0, BlockDecision::Keep)));
+        assert_eq!(parse_decision("3-block"), Some((3, BlockDecision::Block)));
+        assert_eq!(
+            parse_decision("12-ban"),
+            Some((12, BlockDecision::BlockAndBan))
+        );
+        assert_eq!(parse_decision("1-unban"), None);
+        assert_eq!(parse_decision("ban"), None);
+    }
+
+    #[test]
+    fn nsfw_channels_follow_the_policy() {
+        use servers::NsfwChannelPolicy::*;
+        for policy in [Enforce, Skip, Separate] {
+            assert_eq!(list_scope(policy, false), ListScope::Both);
+        }
+        assert_eq!(list_scope(Enforce, true), ListScope::Both);
+        assert_eq!(list_scope(Skip, true), ListScope::Neither);
+        assert_eq!(list_scope(Separate, true), ListScope::NsfwOnly);
+    }
+}
@@ -0,0 +1,259 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Remembering members' roles when they leave, so accepted members can skip screening on rejoin
+
+use super::{config_health::ConfiguredEntity, t, Error};
+use crate::entities::{prelude::*, *};
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use tracing::{info, instrument};
+
+#[derive(FromQueryResult)]
+struct RejoinData {
+    restore_on_rejoin: bool,
+    restore_all_roles: bool,
+    rejoin_window_days: i32,
+    member_role: ids::DbRoleId,
+    questioning_role: ids::DbRoleId,
+    mod_role: ids::DbRoleId,
+    helper_role: Option<ids::DbRoleId>,
+}
+
+impl RejoinData {
+    /// Roles to give back to a returning member, or `None` if they weren't accepted when they left
+    fn roles_to_restore(
+        &self,
+        saved: &[serenity::RoleId],
+        staff: &[serenity::RoleId],
+    ) -> Option<Vec<serenity::RoleId>> {
+        let member_role = serenity::RoleId::from(self.member_role);
+        if !saved.contains(&member_role)
+            || saved.contains(&serenity::RoleId::from(self.questioning_role))
+        {
+            return None;
+        }
+        if !self.restore_all_roles {
+            return Some(vec![member_role]);
+        }
+        // Staff roles are never handed back automatically
+        Some(
+            saved
+                .iter()
+                .filter(|x| !staff.contains(x))
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+#[derive(FromQueryResult)]
+struct RestoreEnabled {
+    restore_on_rejoin: bool,
+}
+
+/// Save a departing member's roles, only in guilds that restore them
+#[instrument(skip_all, err)]
+pub async fn record_departure(
+    guild: serenity::GuildId,
+    member: &serenity::Member,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    if member.roles.is_empty() {
+        return Ok(());
+    }
+    let enabled = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::RestoreOnRejoin)
+        .into_model::<RestoreEnabled>()
+        .one(&reference.3.db)
+        .await?
+        .is_some_and(|x| x.restore_on_rejoin);
+    if !enabled {
+        return Ok(());
+    }
+    MemberHistory::insert(member_history::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        user_id: ActiveValue::Set(member.user.id.into()),
+        roles_json: ActiveValue::Set(serde_json::to_string(
+            &member.roles.iter().map(|x| x.0).collect::<Vec<_>>(),
+        )?),
+        left_at: ActiveValue::Set(chrono::Utc::now()),
+    })
+    .on_conflict(
+        sea_query::OnConflict::columns([
+            member_history::Column::GuildId,
+            member_history::Column::UserId,
+        ])
+        .update_columns([
+            member_history::Column::RolesJson,
+            member_history::Column::LeftAt,
+        ])
+        .to_owned(),
+    )
+    .exec(&reference.3.db)
+    .await?;
+    Ok(())
+}
+
+/// Give a recently departed, previously accepted member their roles back, returning whether they were restored
+#[instrument(skip_all, err)]
+pub async fn restore_returning_member(
+    member: &serenity::Member,
+    guild: serenity::GuildId,
+    reference: super::EventReference<'_>,
+) -> Result<bool, Error> {
+    let data = reference.3;
+    let Some(server_data) = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::RestoreOnRejoin)
+        .column(servers::Column::RestoreAllRoles)
+        .column(servers::Column::RejoinWindowDays)
+        .column(servers::Column::MemberRole)
+        .column(servers::Column::QuestioningRole)
+        .column(servers::Column::ModRole)
+        .column(servers::Column::HelperRole)
+        .into_model::<RejoinData>()
+        .one(&data.db)
+        .await?
+    else {
+        return Ok(false);
+    };
+    if !server_data.restore_on_rejoin
+        || data
+            .config_health
+            .is_broken(guild, ConfiguredEntity::MemberRole)
+    {
+        return Ok(false);
+    }
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(server_data.rejoin_window_days.into());
+    let Some(history) = MemberHistory::find_by_id((guild.into(), member.user.id.into()))
+        .filter(member_history::Column::LeftAt.gt(cutoff))
+        .one(&data.db)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    // Anyone who left mid-questioning goes through screening again
+    let open_sessions = QuestioningSessions::find()
+        .filter(questioning_sessions::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .filter(questioning_sessions::Column::UserId.eq(ids::DbUserId::from(member.user.id)))
+        .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+        .count(&data.db)
+        .await?;
+    if open_sessions > 0 {
+        return Ok(false);
+    }
+
+    let saved = serde_json::from_str::<Vec<u64>>(&history.roles_json)?
+        .into_iter()
+        .map(serenity::RoleId)
+        .collect::<Vec<_>>();
+    let mut staff = super::mod_roles(&data.db, guild, server_data.mod_role.into()).await?;
+    staff.extend(server_data.helper_role.map(serenity::RoleId::from));
+    let Some(mut roles) = server_data.roles_to_restore(&saved, &staff) else {
+        return Ok(false);
+    };
+    // Deleted and integration-managed roles can't be granted
+    if let Some(x) = guild.to_guild_cached(reference.0) {
+        roles.retain(|y| x.roles.get(y).is_some_and(|z| !z.managed));
+    }
+    if t(member.clone().add_roles(reference.0, &roles).await).is_err() {
+        return Ok(false);
+    }
+    info!(
+        "Restored {} role(s) for returning member '{}#{}' in guild '{}'",
+        roles.len(),
+        member.user.name,
+        member.user.discriminator,
+        guild
+    );
+
+    super::mod_log(
+        reference.0,
+        data,
+        guild,
+        Some(guild_log_channels::Purpose::JoinAlerts),
+        format!(
+            "Returning member {} auto-accepted (left <t:{}:R>), restored roles: {}",
+            member.mention(),
+            history.left_at.timestamp(),
+            roles.iter().map(Mentionable::mention).format(" ")
+        ),
+    )
+    .await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMBER: u64 = 1;
+    const QUESTIONING: u64 = 2;
+    const MOD: u64 = 3;
+    const EXTRA_MOD: u64 = 4;
+    const HELPER: u64 = 5;
+    const OTHER: u64 = 6;
+    const STAFF: [serenity::RoleId; 3] = [
+        serenity::RoleId(MOD),
+        serenity::RoleId(EXTRA_MOD),
+        serenity::RoleId(HELPER),
+    ];
+
+    fn rejoin_data(restore_all_roles: bool) -> RejoinData {
+        RejoinData {
+            restore_on_rejoin: true,
+            restore_all_roles,
+            rejoin_window_days: 30,
+            member_role: serenity::RoleId(MEMBER).into(),
+            questioning_role: serenity::RoleId(QUESTIONING).into(),
+            mod_role: serenity::RoleId(MOD).into(),
+            helper_role: Some(serenity::RoleId(HELPER).into()),
+        }
+    }
+
+    fn roles(ids: &[u64]) -> Vec<serenity::RoleId> {
+        ids.iter().copied().map(serenity::RoleId).collect()
+    }
+
+    #[test]
+    fn only_accepted_members_are_restored() {
+        let data = rejoin_data(false);
+        assert_eq!(data.roles_to_restore(&roles(&[OTHER]), &STAFF), None);
+        assert_eq!(
+            data.roles_to_restore(&roles(&[MEMBER, QUESTIONING]), &STAFF),
+            None
+        );
+        assert_eq!(
+            data.roles_to_restore(&roles(&[MEMBER, OTHER]), &STAFF),
+            Some(roles(&[MEMBER]))
+        );
+    }
+
+    #[test]
+    fn staff_roles_are_never_restored() {
+        assert_eq!(
+            rejoin_data(true)
+                .roles_to_restore(&roles(&[MEMBER, MOD, EXTRA_MOD, HELPER, OTHER]), &STAFF),
+            Some(roles(&[MEMBER, OTHER]))
+        );
+    }
+}
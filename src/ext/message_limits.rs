@@ -0,0 +1,244 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{Context, Error, EMOJI};
+use crate::{
+    check_admin,
+    entities::{prelude::*, *},
+    require_profile,
+};
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use tracing::{info, instrument};
+
+/// Per-message caps for a guild, `None` meaning unlimited
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLimits {
+    pub emojis: Option<i32>,
+    pub attachments: Option<i32>,
+    pub stickers: Option<i32>,
+}
+
+impl MessageLimits {
+    /// Reason for the first cap `counts` goes over, if any
+    fn exceeded(&self, counts: MessageCounts) -> Option<&'static str> {
+        let over = |limit: Option<i32>, count: usize| {
+            limit.is_some_and(|x| count > usize::try_from(x).unwrap_or_default())
+        };
+        if over(self.emojis, counts.emojis) {
+            Some("too many emojis")
+        } else if over(self.attachments, counts.attachments) {
+            Some("too many attachments")
+        } else if over(self.stickers, counts.stickers) {
+            Some("too many stickers")
+        } else {
+            None
+        }
+    }
+}
+
+/// What a message contains, as counted against [`MessageLimits`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MessageCounts {
+    emojis: usize,
+    attachments: usize,
+    stickers: usize,
+}
+
+impl MessageCounts {
+    fn new(content: &str, attachments: usize, stickers: usize) -> Self {
+        Self {
+            emojis: EMOJI.find_iter(content).count(),
+            attachments,
+            stickers,
+        }
+    }
+}
+
+impl From<&serenity::Message> for MessageCounts {
+    fn from(x: &serenity::Message) -> Self {
+        Self::new(&x.content, x.attachments.len(), x.sticker_items.len())
+    }
+}
+
+impl From<&serenity::MessageUpdateEvent> for MessageCounts {
+    fn from(x: &serenity::MessageUpdateEvent) -> Self {
+        // Stickers can't be added by editing
+        Self::new(
+            x.content.as_deref().unwrap_or_default(),
+            x.attachments.as_ref().map_or(0, Vec::len),
+            0,
+        )
+    }
+}
+
+/// Delete a message that goes over the guild's caps, returning whether it was deleted
+#[instrument(skip_all, err)]
+pub async fn enforce_limits(
+    counts: MessageCounts,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    id: serenity::MessageId,
+    author: &serenity::User,
+    reference: super::EventReference<'_>,
+) -> Result<bool, Error> {
+    let Some(reason) = reference.3.limits_for(guild).exceeded(counts) else {
+        return Ok(false);
+    };
+
+    // Only look up the author's roles once a cap has actually been hit
+    let Some(profile) = Servers::find_by_id(guild).one(&reference.3.db).await? else {
+        return Ok(false);
+    };
+    let member = guild.member(reference.0, author.id).await?;
+    if super::has_mod_role(
+        &reference.3.db,
+        guild,
+        profile.mod_role.into(),
+        &member.roles,
+    )
+    .await?
+        || member.permissions(reference.0)?.administrator()
+    {
+        return Ok(false);
+    }
+
+    if !super::circuit_breaker::delete_filtered(reference, guild, channel, id, author, reason)
+        .await?
+    {
+        return Ok(false);
+    }
+    channel
+        .send_message(&reference.0, |f| {
+            f.content(format!(
+                "Deleted message from {} (reason: {})",
+                author.mention(),
+                reason
+            ))
+        })
+        .await?;
+    reference.3.filter_followups.arm(channel, author.id);
+    info!(
+        "Deleted message from '{}#{}' ({})",
+        author.name, author.discriminator, reason
+    );
+    Ok(true)
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, subcommands("set_limits"), guild_only)]
+pub async fn limits(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Treat 0 as unlimited and clamp anything past what the database column holds
+fn to_limit(x: Option<u32>) -> Option<i32> {
+    x.filter(|y| *y != 0)
+        .map(|y| i32::try_from(y).unwrap_or(i32::MAX))
+}
+
+/// Set how much a message can contain before it is deleted as spam (0 or unset for unlimited)
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "set")]
+pub async fn set_limits(
+    ctx: Context<'_>,
+    #[description = "Maximum custom emojis per message"] emojis: Option<u32>,
+    #[description = "Maximum attachments per message"] attachments: Option<u32>,
+    #[description = "Maximum stickers per message"] stickers: Option<u32>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let limits = MessageLimits {
+        emojis: to_limit(emojis),
+        attachments: to_limit(attachments),
+        stickers: to_limit(stickers),
+    };
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.max_emojis = ActiveValue::Set(limits.emojis);
+    model.max_attachments = ActiveValue::Set(limits.attachments);
+    model.max_stickers = ActiveValue::Set(limits.stickers);
+    let changes = super::profile_setup::diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+    ctx.data().set_limits_for(guild, limits);
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Message limits updated", changes).await?;
+    }
+
+    ctx.send(|f| {
+        f.content("Updated message limits.")
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMOJI_WALL: &str = "<:a:1> <a:b:2> <:c:3> :not_custom:";
+
+    #[test]
+    fn counts_custom_emojis_only() {
+        assert_eq!(MessageCounts::new(EMOJI_WALL, 0, 0).emojis, 3);
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let counts = MessageCounts::new(&EMOJI_WALL.repeat(30), 10, 3);
+        assert_eq!(MessageLimits::default().exceeded(counts), None);
+    }
+
+    #[test]
+    fn caps_are_inclusive() {
+        let limits = MessageLimits {
+            emojis: Some(3),
+            attachments: Some(2),
+            stickers: Some(1),
+        };
+        assert_eq!(limits.exceeded(MessageCounts::new(EMOJI_WALL, 2, 1)), None);
+        assert_eq!(
+            limits.exceeded(MessageCounts::new(&EMOJI_WALL.repeat(2), 0, 0)),
+            Some("too many emojis")
+        );
+        assert_eq!(
+            limits.exceeded(MessageCounts::new("", 3, 0)),
+            Some("too many attachments")
+        );
+        assert_eq!(
+            limits.exceeded(MessageCounts::new("", 0, 2)),
+            Some("too many stickers")
+        );
+    }
+
+    #[test]
+    fn zero_means_unlimited() {
+        assert_eq!(to_limit(Some(0)), None);
+        assert_eq!(to_limit(None), None);
+        assert_eq!(to_limit(Some(5)), Some(5));
+        assert_eq!(to_limit(Some(u32::MAX)), Some(i32::MAX));
+    }
+}
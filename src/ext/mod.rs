@@ -14,15 +14,42 @@
    limitations under the License.
 */
 
+pub mod allowlist;
+pub mod api;
 pub mod assorted;
+pub mod backup;
+pub mod circuit_breaker;
+pub mod config_health;
 pub mod entry_modal;
+pub mod events;
+pub mod false_positives;
+pub mod features;
+pub mod filter_followups;
+pub mod filter_stats;
+pub mod first_messages;
+pub mod hash_expiry;
 pub mod image_filtering;
+pub mod member_history;
+pub mod message_limits;
+pub mod notifications;
+pub mod owner;
+pub mod polls;
 pub mod profanity_checks;
+pub mod profanity_reviews;
 pub mod profile_setup;
+pub mod profile_wizard;
+pub mod questioning_bumps;
+pub mod quiet_hours;
+pub mod serialization;
+pub mod timezones;
+pub mod trigger_presets;
 pub mod triggers;
 pub mod user_screening;
+pub mod userinfo;
+pub mod webhooks;
 
 use crate::entities::{prelude::*, *};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use poise::serenity_prelude as serenity;
 use poise::Event;
@@ -30,12 +57,13 @@ use regex::Regex;
 use reqwest_middleware::ClientWithMiddleware;
 use sea_orm::DatabaseConnection;
 use sea_orm::*;
+use serenity::Mentionable;
 use tokio::sync::RwLock;
 use tracing::instrument;
 
 use std::{collections::HashMap, error, fmt};
 
-pub const HASH_BYTES: u8 = 8;
+pub use fedbot_core::{blocklist::HASH_BYTES, ContainBytes};
 
 #[inline]
 pub fn t<S, E: ToString + std::fmt::Display>(x: Result<S, E>) -> Result<S, E> {
@@ -46,21 +74,43 @@ pub fn t<S, E: ToString + std::fmt::Display>(x: Result<S, E>) -> Result<S, E> {
 }
 
 #[macro_export]
-macro_rules! check_mod_role {
-    ($ctx:expr, $guild:expr, $mod_role:expr) => {
-        if !$ctx.author().has_role($ctx, $guild, $mod_role).await? {
+macro_rules! check_tier {
+    ($ctx:expr, $guild:expr, $required:expr, $profile:expr) => {
+        $crate::check_tier!(
+            $ctx,
+            $guild,
+            $required,
+            &$crate::ext::mod_roles(
+                &$ctx.data().db,
+                $guild,
+                serenity::RoleId::from($profile.mod_role)
+            )
+            .await?,
+            $profile.helper_role.map(serenity::RoleId::from)
+        )
+    };
+    ($ctx:expr, $guild:expr, $required:expr, $mod_roles:expr, $helper_role:expr) => {
+        let required: $crate::ext::PermissionTier = $required;
+        if !$crate::ext::resolve_tier($ctx, $guild, $mod_roles, $helper_role)
+            .await?
+            .is_some_and(|x| x >= required)
+        {
             tracing::info!(
-                "User '{}#{}' attempted to access privileged command '{}' in guild '{}'",
+                "User '{}#{}' attempted to access {} command '{}' in guild '{}'",
                 $ctx.author().name,
                 $ctx.author().discriminator,
+                required,
                 $ctx.invoked_command_name(),
                 $guild
                     .name($ctx)
                     .ok_or($crate::ext::FedBotError::new("cannot get server name"))?
             );
             $ctx.send(|f| {
-                f.ephemeral($ctx.data().is_ephemeral)
-                    .content("You do not have authorization to access this command.")
+                f.ephemeral($ctx.data().is_ephemeral_for($ctx.guild_id()))
+                    .content(format!(
+                        "You do not have authorization to access this command (requires {} tier or higher).",
+                        required
+                    ))
             })
             .await?;
             return Ok(());
@@ -71,6 +121,8 @@ macro_rules! check_mod_role {
 #[macro_export]
 macro_rules! check_admin {
     ($ctx:expr, $guild:expr) => {
+        // Permissions are computed from all of the member's roles, so holders of a mod role
+        // that has ADMINISTRATOR already pass
         if !$guild
             .member($ctx, $ctx.author().id)
             .await?
@@ -87,7 +139,8 @@ macro_rules! check_admin {
                     .ok_or($crate::ext::FedBotError::new("cannot get server name"))?
             );
             $ctx.send(|f| {
-                f.ephemeral($ctx.data().is_ephemeral).content(
+                f.ephemeral($ctx.data().is_ephemeral_for($ctx.guild_id()))
+                    .content(
                     "You do not have `ADMINISTRATOR` permissions and cannot access this command.",
                 )
             })
@@ -97,10 +150,20 @@ macro_rules! check_admin {
     };
 }
 
+#[macro_export]
+macro_rules! require_profile {
+    ($ctx:expr) => {
+        match $crate::ext::require_profile($ctx).await? {
+            Ok(x) => x,
+            Err($crate::ext::ProfileMissing) => return Ok(()),
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! defer {
     ($ctx:ident) => {
-        if $ctx.data().is_ephemeral {
+        if $ctx.data().is_ephemeral_for($ctx.guild_id()) {
             $ctx.defer_ephemeral().await?;
         } else {
             $ctx.defer().await?;
@@ -111,22 +174,140 @@ macro_rules! defer {
 lazy_static! {
     static ref EMOJI: Regex = Regex::new(r"<(a?):([\w_]+):(\d+)>").unwrap();
     static ref USER: Regex = Regex::new(r"<@(\d+)>").unwrap();
+    static ref URL: Regex = Regex::new(r"https?://[^\s<>]+").unwrap();
 }
 
 #[derive(Default, Clone)]
-pub struct TriggerCooldown(
-    std::sync::Arc<tokio::sync::RwLock<HashMap<serenity::UserId, std::time::Instant>>>,
-);
+pub struct TriggerCooldown(std::sync::Arc<dashmap::DashMap<serenity::UserId, std::time::Instant>>);
 
 pub struct Data {
-    pub login_time: Option<serenity::Timestamp>,
+    pub bot_id: serenity::UserId,
     pub is_ephemeral: bool,
     // pub users: HashMap<serenity::UserId, AppUser, RandomState>,
     pub db: DatabaseConnection,
     pub hasher: image_hasher::Hasher,
     pub reqwest: ClientWithMiddleware,
-    pub triggers: RwLock<HashMap<serenity::GuildId, HashMap<String, String>>>,
+    pub webhook_client: reqwest::Client,
+    pub triggers: RwLock<HashMap<serenity::GuildId, HashMap<String, triggers::Trigger>>>,
     pub trigger_cooldown: TriggerCooldown,
+    pub ephemeral_overrides: std::sync::RwLock<HashMap<serenity::GuildId, bool>>,
+    pub linked_image_filters: std::sync::RwLock<HashMap<serenity::GuildId, bool>>,
+    pub message_limits:
+        std::sync::RwLock<HashMap<serenity::GuildId, message_limits::MessageLimits>>,
+    pub feature_toggles: std::sync::RwLock<HashMap<serenity::GuildId, features::Features>>,
+    pub first_message_thresholds: std::sync::RwLock<HashMap<serenity::GuildId, i32>>,
+    pub nsfw_policies: std::sync::RwLock<HashMap<serenity::GuildId, servers::NsfwChannelPolicy>>,
+    pub nsfw_channels: image_filtering::NsfwChannels,
+    pub trusted_members: first_messages::TrustedMembers,
+    pub user_timezones: std::sync::RwLock<HashMap<serenity::UserId, Option<chrono_tz::Tz>>>,
+    pub safe_images: RwLock<Vec<(&'static str, image_hasher::ImageHash)>>,
+    pub hash_matches: image_filtering::HashMatchTracker,
+    pub mod_notifier: notifications::ModNotifier,
+    pub config_health: config_health::ConfigHealth,
+    pub entry_forms: entry_modal::EntryForms,
+    pub filter_followups: filter_followups::FilterFollowups,
+    pub questioning_bumps: questioning_bumps::QuestioningBumps,
+    pub filter_stats: filter_stats::FilterStats,
+    pub circuit_breakers: circuit_breaker::CircuitBreakers,
+    pub events: events::EventBus,
+    pub allowlist: allowlist::Allowlist,
+}
+
+impl Data {
+    /// Whether responses in `guild` should be ephemeral, falling back to the global default
+    pub fn is_ephemeral_for(&self, guild: Option<serenity::GuildId>) -> bool {
+        guild
+            .and_then(|x| {
+                self.ephemeral_overrides
+                    .read()
+                    .ok()
+                    .and_then(|y| y.get(&x).copied())
+            })
+            .unwrap_or(self.is_ephemeral)
+    }
+
+    pub fn set_ephemeral_for(&self, guild: serenity::GuildId, value: bool) {
+        if let Ok(mut x) = self.ephemeral_overrides.write() {
+            x.insert(guild, value);
+        }
+    }
+
+    /// Whether image links in message content should be checked in `guild`
+    pub fn filters_linked_images(&self, guild: serenity::GuildId) -> bool {
+        self.linked_image_filters
+            .read()
+            .ok()
+            .and_then(|x| x.get(&guild).copied())
+            .unwrap_or(false)
+    }
+
+    pub fn set_filters_linked_images(&self, guild: serenity::GuildId, value: bool) {
+        if let Ok(mut x) = self.linked_image_filters.write() {
+            x.insert(guild, value);
+        }
+    }
+
+    /// Per-message caps in `guild`, unlimited if none are set
+    pub fn limits_for(&self, guild: serenity::GuildId) -> message_limits::MessageLimits {
+        self.message_limits
+            .read()
+            .ok()
+            .and_then(|x| x.get(&guild).copied())
+            .unwrap_or_default()
+    }
+
+    pub fn set_limits_for(&self, guild: serenity::GuildId, value: message_limits::MessageLimits) {
+        if let Ok(mut x) = self.message_limits.write() {
+            x.insert(guild, value);
+        }
+    }
+
+    /// Features enabled in `guild`, all of them if it hasn't turned any off
+    pub fn features_for(&self, guild: serenity::GuildId) -> features::Features {
+        self.feature_toggles
+            .read()
+            .ok()
+            .and_then(|x| x.get(&guild).copied())
+            .unwrap_or_default()
+    }
+
+    pub fn set_features_for(&self, guild: serenity::GuildId, value: features::Features) {
+        if let Ok(mut x) = self.feature_toggles.write() {
+            x.insert(guild, value);
+        }
+    }
+
+    /// How many of a new member's messages are watched in `guild`, if any
+    pub fn first_message_threshold(&self, guild: serenity::GuildId) -> Option<i32> {
+        self.first_message_thresholds
+            .read()
+            .ok()
+            .and_then(|x| x.get(&guild).copied())
+    }
+
+    pub fn set_first_message_threshold(&self, guild: serenity::GuildId, value: Option<i32>) {
+        if let Ok(mut x) = self.first_message_thresholds.write() {
+            match value {
+                Some(y) => x.insert(guild, y),
+                None => x.remove(&guild),
+            };
+        }
+    }
+
+    /// How image filters treat NSFW channels in `guild`, enforcing everywhere if unset
+    pub fn nsfw_policy_for(&self, guild: serenity::GuildId) -> servers::NsfwChannelPolicy {
+        self.nsfw_policies
+            .read()
+            .ok()
+            .and_then(|x| x.get(&guild).copied())
+            .unwrap_or(servers::NsfwChannelPolicy::Enforce)
+    }
+
+    pub fn set_nsfw_policy_for(&self, guild: serenity::GuildId, value: servers::NsfwChannelPolicy) {
+        if let Ok(mut x) = self.nsfw_policies.write() {
+            x.insert(guild, value);
+        }
+    }
 }
 
 // User data, which is stored and accessible in all command invocations
@@ -147,34 +328,36 @@ pub type EventReference<'a> = (
 impl TriggerCooldown {
     const DURATION: std::time::Duration = std::time::Duration::from_secs(5);
 
-    pub async fn on_cooldown(&self, user: serenity::UserId) -> bool {
+    pub fn on_cooldown(&self, user: serenity::UserId) -> bool {
         self.0
-            .read()
-            .await
             .get(&user)
             .is_some_and(|x| x.elapsed() < Self::DURATION)
     }
 
-    pub async fn activate(&self, user: serenity::UserId) {
-        self.0.write().await.insert(user, std::time::Instant::now());
+    pub fn activate(&self, user: serenity::UserId) {
+        self.0.insert(user, std::time::Instant::now());
     }
 
-    pub async fn clean(&self) {
-        self.0
-            .write()
-            .await
-            .drain_filter(|_, x| x.elapsed() > Self::DURATION); // .for_each(|_| ());
+    /// Remove expired cooldowns, returning how many were removed
+    pub fn clean(&self) -> usize {
+        let mut removed = 0;
+        self.0.retain(|_, x| {
+            let expired = x.elapsed() > Self::DURATION;
+            removed += usize::from(expired);
+            !expired
+        });
+        removed
     }
 }
 
 pub async fn get_alert_channel(
     guild: &serenity::Guild,
-    reference: EventReference<'_>,
+    bot_id: serenity::UserId,
 ) -> Result<serenity::ChannelId, Error> {
     let prompt_channel: serenity::ChannelId;
     if let Some(channel) = guild.public_updates_channel_id.or(guild.system_channel_id) {
         prompt_channel = channel;
-    } else if let Some(channel) = guild.default_channel(reference.2.bot_id).await {
+    } else if let Some(channel) = guild.default_channel(bot_id).await {
         prompt_channel = channel.into();
     } else {
         return Err(FedBotError::new(format!(
@@ -188,7 +371,7 @@ pub async fn get_alert_channel(
 
 #[derive(FromQueryResult)]
 struct ModLogData {
-    mod_channel: i64,
+    mod_channel: ids::DbChannelId,
 }
 #[instrument(skip_all, err)]
 
@@ -196,27 +379,515 @@ pub async fn mod_log(
     ctx: &serenity::Context,
     data: &Data,
     guild: serenity::GuildId,
-    channel: Option<serenity::ChannelId>,
+    purpose: Option<guild_log_channels::Purpose>,
+    msg: impl std::fmt::Display,
+) -> Result<(), Error> {
+    send_mod_log(ctx, &data.db, &data.config_health, guild, purpose, msg).await
+}
+
+/// [`mod_log`] for tasks that outlive the event handler and so can't borrow [`Data`]
+pub async fn send_mod_log(
+    ctx: &serenity::Context,
+    db: &DatabaseConnection,
+    health: &config_health::ConfigHealth,
+    guild: serenity::GuildId,
+    purpose: Option<guild_log_channels::Purpose>,
     msg: impl std::fmt::Display,
 ) -> Result<(), Error> {
-    if let Some(x) = channel {
-        x
+    let Some(channel) = route_mod_channel(db, health, guild, purpose).await? else {
+        return Ok(());
+    };
+    let msg = msg.to_string();
+    if quiet_hours::hold(db, guild, channel, purpose, &msg).await? {
+        return Ok(());
+    }
+    channel
+        .send_message(ctx, |f| {
+            f.content(msg).allowed_mentions(|f| f.empty_users())
+        })
+        .await?;
+    Ok(())
+}
+
+/// The channel notices for `purpose` are routed to, falling back to the guild's primary mod
+/// channel unless that is known to be missing or inaccessible
+pub async fn mod_channel(
+    data: &Data,
+    guild: serenity::GuildId,
+    purpose: Option<guild_log_channels::Purpose>,
+) -> Result<Option<serenity::ChannelId>, Error> {
+    route_mod_channel(&data.db, &data.config_health, guild, purpose).await
+}
+
+async fn route_mod_channel(
+    db: &DatabaseConnection,
+    health: &config_health::ConfigHealth,
+    guild: serenity::GuildId,
+    purpose: Option<guild_log_channels::Purpose>,
+) -> Result<Option<serenity::ChannelId>, Error> {
+    if let Some(x) = purpose {
+        if let Some(routed) = GuildLogChannels::find_by_id((guild.into(), x))
+            .one(db)
+            .await?
+        {
+            return Ok(Some(routed.channel_id.into()));
+        }
+    }
+    if health.is_broken(guild, config_health::ConfiguredEntity::ModChannel) {
+        return Ok(None);
+    }
+    let server_data: ModLogData = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::ModChannel)
+        .into_model()
+        .one(db)
+        .await?
+        .ok_or(FedBotError::new("Failed to find query"))?;
+    Ok(Some(server_data.mod_channel.into()))
+}
+
+/// How a filtered message reached the bot
+#[derive(Clone, Copy)]
+pub enum MessageOrigin<'a> {
+    Sent,
+    /// `before` is only known if serenity had the original message cached, and `after` is
+    /// missing when the edit didn't touch the content
+    Edited {
+        before: Option<&'a str>,
+        after: Option<&'a str>,
+    },
+}
+
+const MAX_QUOTE_LENGTH: usize = 800;
+
+/// Truncate message content to `max_length` characters and break up any mentions in it
+pub fn quote_content(content: &str, max_length: usize) -> String {
+    let quoted = if content.chars().count() > max_length {
+        format!(
+            "{}...",
+            content
+                .chars()
+                .take(max_length.saturating_sub(3))
+                .collect::<String>()
+        )
     } else {
-        let server_data: ModLogData = Servers::find_by_id(guild.as_u64().repack())
-            .select_only()
-            .column(servers::Column::Id)
-            .column(servers::Column::ModChannel)
-            .into_model()
-            .one(&data.db)
+        content.to_owned()
+    };
+    quoted
+        .replace('@', "@\u{200b}")
+        .lines()
+        .map(|x| format!("> {x}"))
+        .join("\n")
+}
+
+/// Show mods what a filtered edit changed, since editing into a violation says something about intent
+#[instrument(skip_all, err)]
+pub async fn log_filtered_edit(
+    reference: EventReference<'_>,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    id: serenity::MessageId,
+    author: &serenity::User,
+    origin: MessageOrigin<'_>,
+    reason: &str,
+) -> Result<(), Error> {
+    let MessageOrigin::Edited { before, after } = origin else {
+        return Ok(());
+    };
+    let before = before.map_or_else(
+        || "*Original message wasn't cached*".to_owned(),
+        |x| quote_content(x, MAX_QUOTE_LENGTH),
+    );
+    let after = after.map_or_else(
+        || "*Content unchanged*".to_owned(),
+        |x| quote_content(x, MAX_QUOTE_LENGTH),
+    );
+    mod_log(
+        reference.0,
+        reference.3,
+        guild,
+        Some(guild_log_channels::Purpose::FilterNotices),
+        format!(
+            "Deleted an edited message from {} in {} ([context](https://discord.com/channels/{}/{}/{})) (reason: {})\n**Before:**\n{}\n**After:**\n{}",
+            author.mention(),
+            channel.mention(),
+            guild,
+            channel,
+            id,
+            reason,
+            before,
+            after
+        ),
+    )
+    .await
+}
+
+#[derive(FromQueryResult)]
+struct AuditData {
+    mod_channel: ids::DbChannelId,
+    audit_channel: Option<ids::DbChannelId>,
+}
+
+const MAX_EMBED_FIELD_LENGTH: usize = 1024;
+
+/// Post a configuration change notice to the audit channel, or the mod channel if there isn't one
+#[instrument(skip_all, err)]
+pub async fn config_audit(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    title: &str,
+    fields: Vec<(String, String)>,
+) -> Result<(), Error> {
+    let server_data: AuditData = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::ModChannel)
+        .column(servers::Column::AuditChannel)
+        .into_model()
+        .one(&ctx.data().db)
+        .await?
+        .ok_or(FedBotError::new("Failed to find query"))?;
+
+    let health = &ctx.data().config_health;
+    let channel = match server_data.audit_channel {
+        Some(x) if !health.is_broken(guild, config_health::ConfiguredEntity::AuditChannel) => x,
+        _ if !health.is_broken(guild, config_health::ConfiguredEntity::ModChannel) => {
+            server_data.mod_channel
+        }
+        _ => return Ok(()),
+    };
+
+    serenity::ChannelId::from(channel)
+        .send_message(ctx, |f| {
+            f.embed(|f| {
+                f.author(|f| f.name(ctx.author().tag()).icon_url(ctx.author().face()))
+                    .title(title)
+                    .timestamp(serenity::Timestamp::now());
+                for (name, value) in fields {
+                    let value = if value.chars().count() > MAX_EMBED_FIELD_LENGTH {
+                        format!(
+                            "{}...",
+                            value
+                                .chars()
+                                .take(MAX_EMBED_FIELD_LENGTH - 3)
+                                .collect::<String>()
+                        )
+                    } else {
+                        value
+                    };
+                    f.field(name, value, false);
+                }
+                f
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Group `lines` into chunks no longer than `max_len`, truncating any single line that is too long
+pub fn chunk_lines(lines: &[String], max_len: usize) -> Vec<String> {
+    let mut chunks: Vec<String> = vec![];
+    for i in lines {
+        let line = if i.chars().count() > max_len {
+            format!("{}...", i.chars().take(max_len - 3).collect::<String>())
+        } else {
+            i.clone()
+        };
+        match chunks.last_mut() {
+            Some(x) if x.chars().count() + 1 + line.chars().count() <= max_len => {
+                x.push('\n');
+                x.push_str(&line);
+            }
+            _ => chunks.push(line),
+        }
+    }
+    chunks
+}
+
+/// Whether `channel` is a forum channel
+pub async fn is_forum(
+    ctx: impl serenity::CacheHttp,
+    channel: serenity::ChannelId,
+) -> Result<bool, Error> {
+    Ok(matches!(
+        channel.to_channel(ctx).await?,
+        serenity::Channel::Guild(x) if x.kind == serenity::ChannelType::Forum
+    ))
+}
+
+/// Extract the JSON error code from a failed Discord API request
+pub fn discord_error_code(err: &serenity::SerenityError) -> Option<isize> {
+    if let serenity::SerenityError::Http(container) = err {
+        if let serenity::HttpError::UnsuccessfulRequest(x) = &**container {
+            return Some(x.error.code);
+        }
+    }
+    None
+}
+
+const MAX_BULK_DELETE: usize = 100;
+const BULK_DELETE_MAX_AGE: i64 = 14 * 24 * 3600; // Discord rejects bulk deletes of older messages
+const RATE_LIMIT_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+const MAX_RATE_LIMIT_RETRIES: u32 = 4;
+
+/// Outcome of [`bulk_delete`]
+#[derive(Debug, Default)]
+pub struct DeleteSummary {
+    pub bulk_deleted: usize,
+    pub individually_deleted: usize,
+    pub failed: Vec<(serenity::MessageId, String)>,
+}
+
+impl DeleteSummary {
+    pub fn deleted(&self) -> usize {
+        self.bulk_deleted + self.individually_deleted
+    }
+}
+
+impl fmt::Display for DeleteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Deleted {} messages ({} in bulk, {} individually)",
+            self.deleted(),
+            self.bulk_deleted,
+            self.individually_deleted
+        )?;
+        if let Some((_, reason)) = self.failed.first() {
+            write!(f, "; {} failed: {}", self.failed.len(), reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split messages into bulk-deletable chunks and those that must be deleted one at a time
+fn partition_for_deletion(
+    messages: impl IntoIterator<Item = (serenity::MessageId, serenity::Timestamp)>,
+    now: i64,
+) -> (Vec<Vec<serenity::MessageId>>, Vec<serenity::MessageId>) {
+    let (recent, old): (Vec<_>, Vec<_>) = messages
+        .into_iter()
+        .partition(|x| now - x.1.unix_timestamp() < BULK_DELETE_MAX_AGE);
+    let mut individual = old.into_iter().map(|x| x.0).collect::<Vec<_>>();
+    let mut chunks = recent
+        .chunks(MAX_BULK_DELETE)
+        .map(|x| x.iter().map(|y| y.0).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // Bulk deletion needs at least two messages
+    if chunks.last().is_some_and(|x| x.len() == 1) {
+        individual.extend(chunks.pop().into_iter().flatten());
+    }
+    (chunks, individual)
+}
+
+fn is_rate_limited(err: &serenity::SerenityError) -> bool {
+    matches!(
+        err,
+        serenity::SerenityError::Http(x) if matches!(
+            &**x,
+            serenity::HttpError::UnsuccessfulRequest(y) if y.status_code.as_u16() == 429
+        )
+    )
+}
+
+/// Run a request, retrying with exponential backoff while Discord rate-limits it
+async fn with_backoff<T, F: std::future::Future<Output = serenity::Result<T>>>(
+    mut request: impl FnMut() -> F,
+) -> serenity::Result<T> {
+    let mut delay = RATE_LIMIT_BACKOFF;
+    for _ in 0..MAX_RATE_LIMIT_RETRIES {
+        match request().await {
+            Err(e) if is_rate_limited(&e) => {
+                tracing::warn!(
+                    "Rate limited while deleting messages, retrying in {:?}",
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            x => return x,
+        }
+    }
+    request().await
+}
+
+/// Delete `messages` from `channel`, bulk deleting where Discord allows it
+#[instrument(skip_all)]
+pub async fn bulk_delete(
+    http: impl AsRef<serenity::Http>,
+    channel: serenity::ChannelId,
+    messages: impl IntoIterator<Item = (serenity::MessageId, serenity::Timestamp)>,
+) -> DeleteSummary {
+    let http = http.as_ref();
+    let (chunks, individual) =
+        partition_for_deletion(messages, serenity::Timestamp::now().unix_timestamp());
+    tracing::info!(
+        "Deleting {} recent message(s) in bulk and {} old message(s) individually in channel '{}'",
+        chunks.iter().map(Vec::len).sum::<usize>(),
+        individual.len(),
+        channel
+    );
+
+    let mut summary = DeleteSummary::default();
+    for i in chunks {
+        match with_backoff(|| channel.delete_messages(http, &i)).await {
+            Ok(()) => summary.bulk_deleted += i.len(),
+            Err(e) => summary
+                .failed
+                .extend(i.into_iter().map(|x| (x, e.to_string()))),
+        }
+    }
+    for i in individual {
+        match with_backoff(|| channel.delete_message(http, i)).await {
+            Ok(()) => summary.individually_deleted += 1,
+            Err(e) => summary.failed.push((i, e.to_string())),
+        }
+    }
+    summary
+}
+
+/// Parse durations like `90m`, `1h30m` or `2d 12h` (units: w, d, h, m, s)
+pub fn parse_duration(input: &str) -> Option<std::time::Duration> {
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for i in input.chars().filter(|x| !x.is_whitespace()) {
+        if i.is_ascii_digit() {
+            number.push(i);
+            continue;
+        }
+        let unit = match i.to_ascii_lowercase() {
+            'w' => 7 * 24 * 3600,
+            'd' => 24 * 3600,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(number.parse::<u64>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        return None;
+    }
+    Some(std::time::Duration::from_secs(total))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PermissionTier {
+    Helper,
+    Mod,
+    Admin,
+}
+
+impl fmt::Display for PermissionTier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Helper => "Helper",
+            Self::Mod => "Mod",
+            Self::Admin => "Admin",
+        })
+    }
+}
+
+/// Every role whose holders count as mods in `guild`, starting with the primary mod role
+pub async fn mod_roles(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    primary: serenity::RoleId,
+) -> Result<Vec<serenity::RoleId>, Error> {
+    let mut roles = vec![primary];
+    roles.extend(
+        GuildModRoles::find()
+            .filter(guild_mod_roles::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+            .all(db)
             .await?
-            .ok_or(FedBotError::new("Failed to find query"))?;
-        serenity::ChannelId(server_data.mod_channel.repack())
+            .into_iter()
+            .map(|x| serenity::RoleId::from(x.role_id)),
+    );
+    Ok(roles)
+}
+
+/// Whether a member with `roles` holds any of the guild's mod roles
+pub async fn has_mod_role(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    primary: serenity::RoleId,
+    roles: &[serenity::RoleId],
+) -> Result<bool, Error> {
+    Ok(mod_roles(db, guild, primary)
+        .await?
+        .iter()
+        .any(|x| roles.contains(x)))
+}
+
+/// Resolve the invoker's highest permission tier in `guild`, if any
+pub async fn resolve_tier(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    mod_roles: &[serenity::RoleId],
+    helper_role: Option<serenity::RoleId>,
+) -> Result<Option<PermissionTier>, Error> {
+    let member = guild.member(ctx, ctx.author().id).await?;
+    Ok(if member.permissions(ctx)?.administrator() {
+        Some(PermissionTier::Admin)
+    } else if mod_roles.iter().any(|x| member.roles.contains(x)) {
+        Some(PermissionTier::Mod)
+    } else if helper_role.is_some_and(|x| member.roles.contains(&x)) {
+        Some(PermissionTier::Helper)
+    } else {
+        None
+    })
+}
+
+/// Format a clickable mention for a slash command, or plain text if it is not registered
+pub fn format_command_mention(name: &str, command_id: Option<serenity::CommandId>) -> String {
+    if let Some(x) = command_id {
+        format!("</{name}:{x}>")
+    } else {
+        format!("`/{name}`")
+    }
+}
+
+pub async fn mention_command(ctx: Context<'_>, name: &str) -> Result<String, Error> {
+    let root_name = name.split(' ').next().unwrap_or(name);
+    let command_id = serenity::Command::get_global_application_commands(ctx)
+        .await?
+        .iter()
+        .find_map(|x| {
+            if x.name == root_name {
+                Some(x.id)
+            } else {
+                None
+            }
+        });
+    Ok(format_command_mention(name, command_id))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileMissing;
+
+/// Fetch the server profile, telling the user how to create one if it is missing
+pub async fn require_profile(
+    ctx: Context<'_>,
+) -> Result<Result<servers::Model, ProfileMissing>, Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(FedBotError::new("command called outside server"))?;
+
+    if let Some(x) = Servers::find_by_id(guild).one(&ctx.data().db).await? {
+        return Ok(Ok(x));
     }
-    .send_message(ctx, |f| {
-        f.content(msg).allowed_mentions(|f| f.empty_users())
+
+    let init_command = mention_command(ctx, "profile init").await?;
+    ctx.send(|f| {
+        f.ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            .content(format!(
+                "No server profile! Use {init_command} to create a profile first."
+            ))
     })
     .await?;
-    Ok(())
+    Ok(Err(ProfileMissing))
 }
 
 #[derive(Debug, Clone)]
@@ -240,18 +911,173 @@ impl FedBotError {
     }
 }
 
-pub trait ContainBytes<T> {
-    fn repack(&self) -> T;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
 
-impl ContainBytes<i64> for u64 {
-    fn repack(&self) -> i64 {
-        i64::from_ne_bytes(self.to_ne_bytes())
+    #[test]
+    fn command_mention_uses_registered_id() {
+        assert_eq!(
+            format_command_mention("profile init", Some(serenity::CommandId(1234))),
+            "</profile init:1234>"
+        );
+    }
+
+    #[test]
+    fn command_mention_falls_back_to_text() {
+        assert_eq!(
+            format_command_mention("profile init", None),
+            "`/profile init`"
+        );
+    }
+
+    fn roundtrip(x: u64) -> u64 {
+        let packed: i64 = x.repack();
+        packed.repack()
+    }
+
+    #[test]
+    fn repack_roundtrip_edge_cases() {
+        assert_eq!(roundtrip(0), 0);
+        assert_eq!(roundtrip(u64::MAX), u64::MAX);
+        assert_eq!(roundtrip(1 << 63), 1 << 63);
+        assert_eq!(roundtrip(i64::MAX as u64), i64::MAX as u64);
+    }
+
+    #[test]
+    fn repack_roundtrip_snowflake() {
+        let discord_id: u64 = 80_351_110_224_678_912;
+        let packed: i64 = discord_id.repack();
+        assert_eq!(packed, 80_351_110_224_678_912);
+        assert_eq!(roundtrip(discord_id), discord_id);
+    }
+
+    #[test]
+    fn repack_roundtrip_every_bit() {
+        for i in 0..u64::BITS {
+            let x = 1u64 << i;
+            assert_eq!(roundtrip(x), x);
+            assert_eq!(roundtrip(!x), !x);
+        }
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(
+            parse_duration("2d 12H"),
+            Some(Duration::from_secs(60 * 3600))
+        );
+        assert_eq!(
+            parse_duration("1w"),
+            Some(Duration::from_secs(7 * 24 * 3600))
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("5y"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+    }
+
+    #[test]
+    fn chunks_respect_limit() {
+        let lines = (0..100).map(|x| format!("line {x}")).collect::<Vec<_>>();
+        let chunks = chunk_lines(&lines, 50);
+        assert!(chunks.iter().all(|x| x.chars().count() <= 50));
+        assert_eq!(chunks.join("\n"), lines.join("\n"));
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let chunks = chunk_lines(&["a".repeat(20), "b".to_owned()], 10);
+        assert_eq!(
+            chunks,
+            vec![format!("{}...", "a".repeat(7)), "b".to_owned()]
+        );
+    }
+
+    fn synthetic_messages(
+        count: u64,
+        age: i64,
+        now: i64,
+    ) -> Vec<(serenity::MessageId, serenity::Timestamp)> {
+        (0..count)
+            .map(|x| {
+                (
+                    serenity::MessageId(x + 1),
+                    serenity::Timestamp::from_unix_timestamp(now - age).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bulk_delete_chunks_recent_messages() {
+        let now = 1_700_000_000;
+        let (chunks, individual) = partition_for_deletion(synthetic_messages(250, 60, now), now);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![100, 100, 50]
+        );
+        assert!(individual.is_empty());
+    }
+
+    #[test]
+    fn bulk_delete_single_leftover_is_individual() {
+        let now = 1_700_000_000;
+        let (chunks, individual) = partition_for_deletion(synthetic_messages(101, 60, now), now);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(individual, vec![serenity::MessageId(101)]);
+
+        let (chunks, individual) = partition_for_deletion(synthetic_messages(1, 60, now), now);
+        assert!(chunks.is_empty());
+        assert_eq!(individual, vec![serenity::MessageId(1)]);
+    }
+
+    #[test]
+    fn bulk_delete_skips_old_messages() {
+        let now = 1_700_000_000;
+        let mut messages = synthetic_messages(3, BULK_DELETE_MAX_AGE + 1, now);
+        messages.extend(
+            synthetic_messages(5, 60, now)
+                .into_iter()
+                .map(|(id, ts)| (serenity::MessageId(id.0 + 10), ts)),
+        );
+        let (chunks, individual) = partition_for_deletion(messages, now);
+        assert_eq!(
+            chunks,
+            vec![(11..=15).map(serenity::MessageId).collect::<Vec<_>>()]
+        );
+        assert_eq!(
+            individual,
+            (1..=3).map(serenity::MessageId).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn repack_preserves_bits_above_i64_max() {
+        let packed: i64 = u64::MAX.repack();
+        assert_eq!(packed, -1);
+        let unpacked: u64 = i64::MIN.repack();
+        assert_eq!(unpacked, 1 << 63);
     }
-}
 
-impl ContainBytes<u64> for i64 {
-    fn repack(&self) -> u64 {
-        u64::from_ne_bytes(self.to_ne_bytes())
+    #[test]
+    fn quoted_content_is_truncated_and_defused() {
+        assert_eq!(
+            quote_content("hi @everyone\n<@&123>", MAX_QUOTE_LENGTH),
+            "> hi @\u{200b}everyone\n> <@\u{200b}&123>"
+        );
+        let quoted = quote_content(&"a".repeat(MAX_QUOTE_LENGTH + 1), MAX_QUOTE_LENGTH);
+        assert_eq!(quoted.chars().count(), MAX_QUOTE_LENGTH + 2);
+        assert!(quoted.ends_with("..."));
     }
 }
@@ -0,0 +1,243 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{Context, Error, PermissionTier};
+use crate::{
+    check_tier,
+    entities::{prelude::*, *},
+    require_profile,
+};
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{info, instrument};
+
+const QUIET_PERIOD: i64 = 30 * 60; // Seconds without human messages before the mod channel is quiet
+const ACTIVITY_CHECK_LIMIT: u64 = 25;
+const DM_COOLDOWN: Duration = Duration::from_secs(600);
+const MAX_DM_FAILURES: i32 = 3;
+
+#[derive(Default)]
+struct Digest {
+    last_sent: Option<Instant>,
+    pending: Vec<String>,
+}
+
+enum Delivery {
+    Now,
+    After(Duration),
+    Queued,
+}
+
+/// Per-mod DM digests, batched so each mod gets at most one DM per cooldown
+#[derive(Default, Clone)]
+pub struct ModNotifier(Arc<Mutex<HashMap<(serenity::GuildId, serenity::UserId), Digest>>>);
+
+impl ModNotifier {
+    async fn push(&self, key: (serenity::GuildId, serenity::UserId), msg: String) -> Delivery {
+        let mut map = self.0.lock().await;
+        let digest = map.entry(key).or_default();
+        digest.pending.push(msg);
+        match digest.last_sent.map(|x| x.elapsed()) {
+            Some(x) if x < DM_COOLDOWN => {
+                // Only the first queued event needs to schedule a flush
+                if digest.pending.len() == 1 {
+                    Delivery::After(DM_COOLDOWN - x)
+                } else {
+                    Delivery::Queued
+                }
+            }
+            _ => Delivery::Now,
+        }
+    }
+
+    async fn take(&self, key: (serenity::GuildId, serenity::UserId)) -> Vec<String> {
+        let mut map = self.0.lock().await;
+        let digest = map.entry(key).or_default();
+        digest.last_sent = Some(Instant::now());
+        std::mem::take(&mut digest.pending)
+    }
+}
+
+#[derive(Copy, Clone, Debug, poise::ChoiceParameter)]
+pub enum NotifyToggle {
+    #[name = "on"]
+    On,
+    #[name = "off"]
+    Off,
+}
+
+/// Get DMs about high-priority events while the mod channel is quiet
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn notifyme(ctx: Context<'_>, state: NotifyToggle) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    let key = (guild.into(), ctx.author().id.into());
+    let content = match state {
+        NotifyToggle::On => {
+            ModSubscriptions::insert(mod_subscriptions::ActiveModel {
+                guild_id: ActiveValue::Set(key.0),
+                user_id: ActiveValue::Set(key.1),
+                failures: ActiveValue::Set(0),
+            })
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    mod_subscriptions::Column::GuildId,
+                    mod_subscriptions::Column::UserId,
+                ])
+                .update_column(mod_subscriptions::Column::Failures)
+                .to_owned(),
+            )
+            .exec(&ctx.data().db)
+            .await?;
+            "You will be DMed about high-priority events while the mod channel is quiet."
+        }
+        NotifyToggle::Off => {
+            ModSubscriptions::delete_by_id(key)
+                .exec(&ctx.data().db)
+                .await?;
+            "You will no longer be DMed about high-priority events."
+        }
+    };
+
+    info!(
+        "User '{}' turned mod notifications {:?}",
+        ctx.author().tag(),
+        state
+    );
+    ctx.send(|f| {
+        f.content(content)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Whether any human has posted in `channel` recently
+async fn channel_is_quiet(
+    http: &Arc<serenity::Http>,
+    channel: serenity::ChannelId,
+) -> Result<bool, Error> {
+    let cutoff = serenity::Timestamp::now().unix_timestamp() - QUIET_PERIOD;
+    Ok(!channel
+        .messages(http, |f| f.limit(ACTIVITY_CHECK_LIMIT))
+        .await?
+        .iter()
+        .any(|x| !x.author.bot && x.timestamp.unix_timestamp() > cutoff))
+}
+
+/// DM subscribed mods about `msg` if nobody is watching the mod channel
+#[instrument(skip_all, err)]
+pub async fn notify_mods(
+    http: Arc<serenity::Http>,
+    db: DatabaseConnection,
+    notifier: ModNotifier,
+    guild: serenity::GuildId,
+    mod_channel: serenity::ChannelId,
+    msg: String,
+) -> Result<(), Error> {
+    let subscribers = ModSubscriptions::find()
+        .filter(mod_subscriptions::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .all(&db)
+        .await?;
+    if subscribers.is_empty() || !channel_is_quiet(&http, mod_channel).await? {
+        return Ok(());
+    }
+
+    for i in subscribers {
+        let user = serenity::UserId::from(i.user_id);
+        match notifier.push((guild, user), msg.clone()).await {
+            Delivery::Now => {
+                send_digest(&http, &db, &notifier, guild, mod_channel, user).await?;
+            }
+            Delivery::After(delay) => {
+                let (http, db, notifier) = (http.clone(), db.clone(), notifier.clone());
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    send_digest(&http, &db, &notifier, guild, mod_channel, user).await
+                });
+            }
+            Delivery::Queued => (),
+        }
+    }
+    Ok(())
+}
+
+#[instrument(skip_all, err)]
+async fn send_digest(
+    http: &Arc<serenity::Http>,
+    db: &DatabaseConnection,
+    notifier: &ModNotifier,
+    guild: serenity::GuildId,
+    mod_channel: serenity::ChannelId,
+    user: serenity::UserId,
+) -> Result<(), Error> {
+    let digest = notifier.take((guild, user)).await;
+    if digest.is_empty() {
+        return Ok(());
+    }
+
+    let content = format!(
+        "While https://discord.com/channels/{}/{} was quiet:\n{}",
+        guild,
+        mod_channel,
+        digest.iter().map(|x| format!("- {x}")).format("\n")
+    );
+    let sent = match user.create_dm_channel(http).await {
+        Ok(x) => x.say(http, content).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+
+    let key = (guild.into(), user.into());
+    let Some(subscription) = ModSubscriptions::find_by_id(key).one(db).await? else {
+        return Ok(());
+    };
+    if sent.is_ok() {
+        if subscription.failures != 0 {
+            let mut model: mod_subscriptions::ActiveModel = subscription.into();
+            model.failures = ActiveValue::Set(0);
+            model.update(db).await?;
+        }
+    } else if subscription.failures + 1 >= MAX_DM_FAILURES {
+        ModSubscriptions::delete_by_id(key).exec(db).await?;
+        mod_channel
+            .send_message(http, |f| {
+                f.content(format!(
+                    "Stopped DM notifications for {} after {} failed deliveries.",
+                    user.mention(),
+                    MAX_DM_FAILURES
+                ))
+                .allowed_mentions(|f| f.empty_users())
+            })
+            .await?;
+    } else {
+        let failures = subscription.failures + 1;
+        let mut model: mod_subscriptions::ActiveModel = subscription.into();
+        model.failures = ActiveValue::Set(failures);
+        model.update(db).await?;
+    }
+    Ok(())
+}
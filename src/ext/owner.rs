@@ -0,0 +1,188 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{t, Context, Error};
+use crate::entities::{prelude::*, *};
+use futures_lite::stream::StreamExt;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use std::collections::HashSet;
+use tracing::instrument;
+
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+const PAGE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+#[derive(FromQueryResult)]
+struct ProfileId {
+    id: ids::DbGuildId,
+}
+
+/// What the owner sees about one server the bot is in
+struct GuildSummary {
+    name: String,
+    id: serenity::GuildId,
+    members: u64,
+    has_profile: bool,
+    joined: serenity::Timestamp,
+}
+
+impl GuildSummary {
+    fn line(&self) -> String {
+        format!(
+            "**{}** (`{}`): {} members, {}, joined <t:{}:D>",
+            self.name,
+            self.id,
+            self.members,
+            if self.has_profile {
+                "has profile"
+            } else {
+                "**no profile**"
+            },
+            self.joined.unix_timestamp()
+        )
+    }
+}
+
+fn render<'a, 'b>(
+    f: &'b mut poise::CreateReply<'a>,
+    prefix: &str,
+    pages: &[String],
+    total: usize,
+    page: usize,
+) -> &'b mut poise::CreateReply<'a> {
+    f.embed(|f| {
+        f.title(format!("Servers ({total})"))
+            .description(pages.get(page).map_or("", String::as_str))
+            .footer(|f| f.text(format!("Page {} of {}", page + 1, pages.len())))
+    })
+    .components(|f| {
+        if pages.len() > 1 {
+            f.create_action_row(|f| {
+                f.create_button(|f| {
+                    f.custom_id(format!("{prefix}prev"))
+                        .label("Previous")
+                        .disabled(page == 0)
+                })
+                .create_button(|f| {
+                    f.custom_id(format!("{prefix}next"))
+                        .label("Next")
+                        .disabled(page + 1 >= pages.len())
+                })
+            });
+        }
+        f
+    })
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only, subcommands("guilds"))]
+pub async fn owner(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// List every server the bot is in, and whether each one has a profile
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn guilds(ctx: Context<'_>) -> Result<(), Error> {
+    let profiles = Servers::find()
+        .select_only()
+        .column(servers::Column::Id)
+        .into_model::<ProfileId>()
+        .all(&ctx.data().db)
+        .await?
+        .into_iter()
+        .map(|x| serenity::GuildId::from(x.id))
+        .collect::<HashSet<_>>();
+
+    let mut summaries = ctx
+        .serenity_context()
+        .cache
+        .guilds()
+        .into_iter()
+        .filter_map(|x| x.to_guild_cached(ctx))
+        .map(|x| GuildSummary {
+            has_profile: profiles.contains(&x.id),
+            name: x.name,
+            id: x.id,
+            members: x.member_count,
+            joined: x.joined_at,
+        })
+        .collect::<Vec<_>>();
+    summaries.sort_by_key(|x| x.joined.unix_timestamp());
+
+    let lines = summaries.iter().map(GuildSummary::line).collect::<Vec<_>>();
+    let pages = super::chunk_lines(&lines, MAX_EMBED_DESCRIPTION_LENGTH);
+    let mut page = 0;
+
+    // Component IDs are keyed by the invoking interaction
+    let prefix = format!("{}-", ctx.id());
+    let msg = ctx
+        .send(|f| render(f, &prefix, &pages, summaries.len(), page).ephemeral(true))
+        .await?;
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut collector = msg
+        .message()
+        .await?
+        .await_component_interactions(ctx)
+        .author_id(ctx.author().id)
+        .timeout(PAGE_TIMEOUT)
+        .build();
+    while let Some(x) = collector.next().await {
+        x.create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+        match x.data.custom_id.strip_prefix(&prefix) {
+            Some("prev") => page = page.saturating_sub(1),
+            Some("next") => page = (page + 1).min(pages.len() - 1),
+            _ => continue,
+        }
+        msg.edit(ctx, |f| render(f, &prefix, &pages, summaries.len(), page))
+            .await?;
+    }
+    // The interaction token may have expired by now
+    _ = t(msg.edit(ctx, |f| f.components(|f| f)).await);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_flags_missing_profiles() {
+        let mut summary = GuildSummary {
+            name: "Test".to_owned(),
+            id: serenity::GuildId(123),
+            members: 42,
+            has_profile: true,
+            joined: serenity::Timestamp::from_unix_timestamp(1_600_000_000).unwrap(),
+        };
+        assert_eq!(
+            summary.line(),
+            "**Test** (`123`): 42 members, has profile, joined <t:1600000000:D>"
+        );
+        summary.has_profile = false;
+        assert_eq!(
+            summary.line(),
+            "**Test** (`123`): 42 members, **no profile**, joined <t:1600000000:D>"
+        );
+    }
+}
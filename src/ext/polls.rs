@@ -0,0 +1,585 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{Context, Error, PermissionTier};
+use crate::{
+    check_tier,
+    entities::{prelude::*, *},
+    require_profile,
+};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::{collections::HashSet, sync::Arc};
+use tracing::{info, instrument};
+
+const MAX_POLL_OPTIONS: usize = 26;
+const MAX_POLL_DURATION: std::time::Duration = std::time::Duration::from_secs(28 * 24 * 3600);
+const UNKNOWN_MESSAGE: isize = 10008;
+const VOTE_LOG_PREFIX: &str = "poll-vote-log-";
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Regional indicator emoji used for the option at `index`
+fn option_emoji(index: usize) -> Result<char, Error> {
+    Ok(u32::try_from(index)
+        .ok()
+        .and_then(|x| char::from_u32('\u{1f1e6}' as u32 + x))
+        .ok_or(super::FedBotError::new("Unicode decode error"))?)
+}
+
+/// Option index for a regional indicator reaction
+fn option_index(emoji: &serenity::ReactionType) -> Option<usize> {
+    let serenity::ReactionType::Unicode(x) = emoji else {
+        return None;
+    };
+    let mut chars = x.chars();
+    let (Some(x), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let index = usize::try_from((x as u32).checked_sub('\u{1f1e6}' as u32)?).ok()?;
+    (index < MAX_POLL_OPTIONS).then_some(index)
+}
+
+/// Votes per option as cast, and how many of them had their reactions removed, replayed from an
+/// audited poll's history
+fn tally_votes(history: &[poll_votes::Model], options: usize) -> (Vec<u64>, u64) {
+    let mut cast = HashSet::new();
+    let mut current = HashSet::new();
+    for i in history {
+        if i.added {
+            cast.insert((i.user_id, i.option));
+            current.insert((i.user_id, i.option));
+        } else {
+            current.remove(&(i.user_id, i.option));
+        }
+    }
+    let removed = cast.difference(&current).count() as u64;
+    let mut counts = vec![0; options];
+    for (_, option) in cast {
+        if let Some(x) = usize::try_from(option).ok().and_then(|x| counts.get_mut(x)) {
+            *x += 1;
+        }
+    }
+    (counts, removed)
+}
+
+/// Create a poll
+#[instrument(skip_all, err)]
+#[poise::command(slash_command)]
+pub async fn poll(
+    ctx: Context<'_>,
+    question: String,
+    #[description = "Poll options, separated by semicolons"] options: String,
+    #[description = "Close the poll and post results after this long, e.g. 1h30m or 2d"]
+    duration: Option<String>,
+    #[description = "Record every vote so removed reactions can't change the results"]
+    audited: Option<bool>,
+    #[description = "Hide voters from the vote log (audited polls only)"] anonymous: Option<bool>,
+    #[description = "Allow voting for more than one option (default: true)"] multi_vote: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let options_vec = options.split(';').map(str::trim).collect::<Vec<&str>>();
+    let options_length = options_vec.len();
+    if options_length < 2 {
+        ctx.send(|f| {
+            f.content("You must specify at least two options, separated by semicolons.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+    if options_length > MAX_POLL_OPTIONS {
+        ctx.send(|f| {
+            f.content("Too many options!")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let close_at = match duration.as_deref().map(super::parse_duration) {
+        None => None,
+        Some(Some(x)) if x <= MAX_POLL_DURATION => {
+            Some(Utc::now() + chrono::Duration::from_std(x)?)
+        }
+        Some(Some(_)) => {
+            ctx.send(|f| {
+                f.content("Polls can run for at most 28 days.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        }
+        Some(None) => {
+            ctx.send(|f| {
+                f.content("Invalid duration. Combine weeks, days, hours, minutes and seconds, like `1h30m` or `2d`.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        }
+    };
+    let audited = audited.unwrap_or(false);
+
+    let mut formatted_options = vec![];
+    for (index, val) in options_vec.iter().enumerate() {
+        formatted_options.push(format!("{}: {}", option_emoji(index)?, val));
+    }
+    let mut description = formatted_options.into_iter().join("\n");
+    // Reactions can't be limited to one per user, so this is only a request to voters
+    if !multi_vote.unwrap_or(true) {
+        description += "\n\nVote for one option only.";
+    }
+    if audited {
+        description += "\n\nVotes are recorded, so removing reactions won't hide them.";
+    }
+    if let Some(x) = close_at {
+        description += &format!("\n\nCloses <t:{}:R>", x.timestamp());
+    }
+    let msg = ctx
+        .send(|f| f.embed(|f| f.title(&question).description(description)))
+        .await?
+        .into_message()
+        .await?;
+
+    // Track the poll before reacting so no early votes are missed
+    Polls::insert(polls::ActiveModel {
+        message_id: ActiveValue::Set(msg.id.into()),
+        channel_id: ActiveValue::Set(msg.channel_id.into()),
+        guild_id: ActiveValue::Set(ctx.guild_id().map(Into::into)),
+        question: ActiveValue::Set(question),
+        options_json: ActiveValue::Set(serde_json::to_string(&options_vec)?),
+        auto_close_at: ActiveValue::Set(close_at),
+        closed: ActiveValue::Set(false),
+        author_id: ActiveValue::Set(Some(ctx.author().id.into())),
+        audited: ActiveValue::Set(audited),
+        anonymous: ActiveValue::Set(anonymous.unwrap_or(false)),
+    })
+    .exec(&ctx.data().db)
+    .await?;
+
+    for i in 0..options_length {
+        msg.react(ctx, option_emoji(i)?).await?;
+    }
+
+    if let Some(x) = close_at {
+        tokio::spawn(schedule_close(
+            ctx.serenity_context().http.clone(),
+            ctx.data().db.clone(),
+            msg.id,
+            x,
+        ));
+    }
+    Ok(())
+}
+
+/// Close a poll and post its results
+#[instrument(skip_all, err)]
+#[poise::command(context_menu_command = "Close Poll")]
+pub async fn close_poll_menu(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
+    let Some(poll) = Polls::find_by_id(msg.id).one(&ctx.data().db).await? else {
+        ctx.send(|f| {
+            f.content("That message isn't a poll.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+    if poll.closed {
+        ctx.send(|f| {
+            f.content("That poll is already closed.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    // Anyone other than the poll's creator needs to be a mod
+    if poll.author_id != Some(ctx.author().id.into()) {
+        let Some(guild) = ctx.guild_id() else {
+            ctx.send(|f| {
+                f.content("Only the poll's creator can close it.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        };
+        let server_data = require_profile!(ctx);
+        check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+    }
+
+    crate::defer!(ctx);
+
+    close_poll(&ctx.serenity_context().http, &ctx.data().db, msg.id).await?;
+    ctx.send(|f| {
+        f.content("Closed poll.")
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Wait until `close_at`, then close the poll
+async fn schedule_close(
+    http: Arc<serenity::Http>,
+    db: DatabaseConnection,
+    message_id: serenity::MessageId,
+    close_at: DateTime<Utc>,
+) {
+    tokio::time::sleep((close_at - Utc::now()).to_std().unwrap_or_default()).await;
+    _ = super::t(close_poll(&http, &db, message_id).await);
+}
+
+/// Tally the poll's votes and post the results, unless it has already been closed
+async fn close_poll(
+    http: &Arc<serenity::Http>,
+    db: &DatabaseConnection,
+    message_id: serenity::MessageId,
+) -> Result<(), Error> {
+    let id = ids::DbMessageId::from(message_id);
+    // Claim the poll first so duplicate close tasks (e.g. after a reconnect) don't post twice
+    let claimed = Polls::update_many()
+        .col_expr(polls::Column::Closed, sea_query::Expr::value(true))
+        .filter(polls::Column::MessageId.eq(id))
+        .filter(polls::Column::Closed.eq(false))
+        .exec(db)
+        .await?;
+    if claimed.rows_affected == 0 {
+        return Ok(());
+    }
+    let result = post_results(http, db, message_id).await;
+    if result.is_err() {
+        // Release the claim so a later close (or the next startup) can try again
+        Polls::update_many()
+            .col_expr(polls::Column::Closed, sea_query::Expr::value(false))
+            .filter(polls::Column::MessageId.eq(id))
+            .exec(db)
+            .await?;
+    }
+    result
+}
+
+/// Tally a claimed poll's votes and post the results
+async fn post_results(
+    http: &Arc<serenity::Http>,
+    db: &DatabaseConnection,
+    message_id: serenity::MessageId,
+) -> Result<(), Error> {
+    let id = ids::DbMessageId::from(message_id);
+    let Some(poll) = Polls::find_by_id(id).one(db).await? else {
+        return Ok(());
+    };
+
+    let channel = serenity::ChannelId::from(poll.channel_id);
+    let msg = match channel.message(http, message_id).await {
+        Ok(x) => x,
+        Err(e) if super::discord_error_code(&e) == Some(UNKNOWN_MESSAGE) => {
+            info!("Poll '{}' was deleted before closing", message_id);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let options: Vec<String> = serde_json::from_str(&poll.options_json)?;
+    // Discount the bot's own reactions
+    let mut live_counts = vec![0; options.len()];
+    for i in &msg.reactions {
+        if let Some(x) = option_index(&i.reaction_type).and_then(|x| live_counts.get_mut(x)) {
+            *x = i.count - u64::from(i.me);
+        }
+    }
+
+    let mut notes = vec![];
+    let counts = if poll.audited {
+        let history = PollVotes::find()
+            .filter(poll_votes::Column::PollId.eq(id))
+            .order_by_asc(poll_votes::Column::Id)
+            .all(db)
+            .await?;
+        let (counts, removed) = tally_votes(&history, options.len());
+        if removed > 0 {
+            notes.push(format!(
+                "{removed} vote{} removed before closing, but still counted.",
+                if removed == 1 { " was" } else { "s were" }
+            ));
+        }
+        let (recorded, live) = (counts.iter().sum::<u64>(), live_counts.iter().sum::<u64>());
+        if recorded != live {
+            notes.push(format!(
+                "The live reactions show {live} votes, but {recorded} were recorded. Results use the recorded votes."
+            ));
+        }
+        counts
+    } else {
+        live_counts
+    };
+    let most_votes = counts.iter().copied().max().unwrap_or_default();
+
+    let mut description = options
+        .iter()
+        .zip(&counts)
+        .enumerate()
+        .map(|(index, (option, votes))| {
+            let line = format!(
+                "{}: {option} ({votes} vote{})",
+                option_emoji(index).unwrap_or_default(),
+                if *votes == 1 { "" } else { "s" }
+            );
+            if *votes == most_votes && most_votes > 0 {
+                format!("**{line}**")
+            } else {
+                line
+            }
+        })
+        .join("\n");
+    if !notes.is_empty() {
+        description += &format!("\n\n{}", notes.join("\n"));
+    }
+
+    let show_log = poll.audited && !poll.anonymous && poll.guild_id.is_some();
+    channel
+        .send_message(http, |f| {
+            f.reference_message(&msg)
+                .allowed_mentions(|f| f.empty_parse())
+                .embed(|f| {
+                    f.title(format!("Results: {}", poll.question))
+                        .description(description)
+                });
+            if show_log {
+                f.components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id(format!("{VOTE_LOG_PREFIX}{message_id}"))
+                                .label("Vote log")
+                                .style(serenity::ButtonStyle::Secondary)
+                        })
+                    })
+                });
+            }
+            f
+        })
+        .await?;
+    info!("Closed poll '{}'", message_id);
+    Ok(())
+}
+
+/// Reschedule close tasks for timed polls that were still open when the bot stopped
+#[instrument(skip_all, err)]
+pub async fn reschedule_polls(
+    http: Arc<serenity::Http>,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    let pending = Polls::find()
+        .filter(polls::Column::Closed.eq(false))
+        .filter(polls::Column::AutoCloseAt.is_not_null())
+        .all(db)
+        .await?;
+    for i in pending {
+        if let Some(close_at) = i.auto_close_at {
+            tokio::spawn(schedule_close(
+                http.clone(),
+                db.clone(),
+                serenity::MessageId::from(i.message_id),
+                close_at,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Record a vote being added or removed on an open audited poll
+#[instrument(skip_all, err)]
+pub async fn record_vote(
+    reaction: &serenity::Reaction,
+    added: bool,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let Some(user) = reaction.user_id else {
+        return Ok(());
+    };
+    let Some(option) = option_index(&reaction.emoji) else {
+        return Ok(());
+    };
+    if user == reference.3.bot_id {
+        return Ok(());
+    }
+
+    let Some(poll) = Polls::find_by_id(reaction.message_id)
+        .filter(polls::Column::Audited.eq(true))
+        .filter(polls::Column::Closed.eq(false))
+        .one(&reference.3.db)
+        .await?
+    else {
+        return Ok(());
+    };
+    if option >= serde_json::from_str::<Vec<String>>(&poll.options_json)?.len() {
+        return Ok(());
+    }
+
+    PollVotes::insert(poll_votes::ActiveModel {
+        poll_id: ActiveValue::Set(poll.message_id),
+        user_id: ActiveValue::Set(user.into()),
+        option: ActiveValue::Set(option.try_into()?),
+        added: ActiveValue::Set(added),
+        timestamp: ActiveValue::Set(Utc::now()),
+        ..Default::default()
+    })
+    .exec(&reference.3.db)
+    .await?;
+    Ok(())
+}
+
+/// Show a poll's recorded votes to mods who click its results' "Vote log" button
+#[instrument(skip_all, err)]
+pub async fn send_vote_log(
+    interaction: &serenity::MessageComponentInteraction,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let Some(poll_id) = interaction
+        .data
+        .custom_id
+        .strip_prefix(VOTE_LOG_PREFIX)
+        .and_then(|x| x.parse::<u64>().ok())
+    else {
+        return Ok(());
+    };
+    let (Some(guild), Some(member)) = (interaction.guild_id, interaction.member.as_ref()) else {
+        return Ok(());
+    };
+    let db = &reference.3.db;
+
+    let is_mod = member.permissions.is_some_and(|x| x.administrator())
+        || match Servers::find_by_id(guild).one(db).await? {
+            Some(x) => super::has_mod_role(db, guild, x.mod_role.into(), &member.roles).await?,
+            None => false,
+        };
+    let poll = Polls::find_by_id(serenity::MessageId(poll_id))
+        .one(db)
+        .await?;
+
+    let content = match poll {
+        _ if !is_mod => "Only mods can view the vote log.".to_owned(),
+        Some(x) if x.audited && !x.anonymous => {
+            let options: Vec<String> = serde_json::from_str(&x.options_json)?;
+            let history = PollVotes::find()
+                .filter(poll_votes::Column::PollId.eq(x.message_id))
+                .order_by_asc(poll_votes::Column::Id)
+                .all(db)
+                .await?;
+            if history.is_empty() {
+                "No votes were recorded.".to_owned()
+            } else {
+                history
+                    .iter()
+                    .map(|i| {
+                        let option = usize::try_from(i.option).unwrap_or(usize::MAX);
+                        format!(
+                            "<t:{}:f> {} {} {}: {}",
+                            i.timestamp.timestamp(),
+                            serenity::UserId::from(i.user_id).mention(),
+                            if i.added { "voted for" } else { "removed" },
+                            option_emoji(option).unwrap_or_default(),
+                            options.get(option).map_or("", String::as_str)
+                        )
+                    })
+                    .join("\n")
+            }
+        }
+        _ => "No vote log is available for this poll.".to_owned(),
+    };
+
+    interaction
+        .create_interaction_response(reference.0, |f| {
+            f.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|f| {
+                    f.ephemeral(true).allowed_mentions(|f| f.empty_parse());
+                    if content.chars().count() > MAX_MESSAGE_LENGTH {
+                        f.content("The vote log is attached.").add_file(
+                            serenity::AttachmentType::Bytes {
+                                data: content.as_bytes().to_vec().into(),
+                                filename: "vote-log.txt".to_owned(),
+                            },
+                        )
+                    } else {
+                        f.content(&content)
+                    }
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(user_id: u64, option: i32, added: bool) -> poll_votes::Model {
+        poll_votes::Model {
+            id: 0,
+            poll_id: serenity::MessageId(0).into(),
+            user_id: serenity::UserId(user_id).into(),
+            option,
+            added,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn option_emoji_roundtrip() {
+        for i in 0..MAX_POLL_OPTIONS {
+            let emoji = serenity::ReactionType::Unicode(option_emoji(i).unwrap().to_string());
+            assert_eq!(option_index(&emoji), Some(i));
+        }
+        let other = serenity::ReactionType::Unicode("\u{1f44d}".to_owned());
+        assert_eq!(option_index(&other), None);
+        let flag = serenity::ReactionType::Unicode("\u{1f1fa}\u{1f1f8}".to_owned());
+        assert_eq!(option_index(&flag), None);
+    }
+
+    #[test]
+    fn tally_counts_votes_as_cast() {
+        let history = [
+            vote(1, 0, true),
+            vote(2, 0, true),
+            vote(3, 1, true),
+            vote(2, 0, false),
+            vote(2, 1, true),
+            // Removing a vote that was never recorded changes nothing
+            vote(4, 0, false),
+        ];
+        assert_eq!(tally_votes(&history, 2), (vec![2, 2], 1));
+    }
+
+    #[test]
+    fn tally_counts_revotes_once() {
+        let history = [
+            vote(1, 0, true),
+            vote(1, 0, false),
+            vote(1, 0, true),
+            vote(1, 0, false),
+        ];
+        assert_eq!(tally_votes(&history, 2), (vec![1, 0], 1));
+    }
+
+    #[test]
+    fn tally_ignores_unknown_options() {
+        let history = [vote(1, 5, true), vote(1, 0, true), vote(1, -1, true)];
+        assert_eq!(tally_votes(&history, 2), (vec![1, 0], 0));
+    }
+}
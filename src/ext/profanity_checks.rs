@@ -14,100 +14,113 @@
    limitations under the License.
 */
 
+use super::{Context, Error};
 use dunce::canonicalize;
-use lazy_static::lazy_static;
+use fedbot_core::profanity::{is_objectionable, severity, type_names};
 use poise::serenity_prelude as serenity;
-use rustrict::{Censor, Type};
+use rustrict::Type;
 use serenity::Mentionable;
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::{OnceLock, RwLock},
+};
 use tracing::{info, instrument};
 
-lazy_static! {
-    static ref CENSOR_BANNED: rustrict::Banned = {
-        let path = canonicalize(Path::new(&std::env::current_exe().unwrap()))
-            .unwrap()
-            .with_file_name("banned_chars.txt");
-        let mut banned = rustrict::Banned::new();
-        if let Some(x) = match std::fs::read_to_string(path) {
-            Ok(x) => Ok(Some(x)),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::NotFound => Ok(None),
-                other => Err(other),
-            },
-        }
-        .unwrap()
-        {
-            for i in x.lines().filter_map(|x| x.chars().next()) {
-                banned.insert(i);
-            }
-        }
-        banned
-    };
-    static ref CENSOR_REPLACEMENTS: rustrict::Replacements = {
-        let path = canonicalize(Path::new(&std::env::current_exe().unwrap()))
-            .unwrap()
-            .with_file_name("replace_chars.txt");
-        let mut replacements = rustrict::Replacements::new();
-        if let Some(x) = match std::fs::read_to_string(path) {
-            Ok(x) => Ok(Some(x)),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::NotFound => Ok(None),
-                other => Err(other),
-            },
-        }
-        .unwrap()
-        {
-            for (src, dest) in x.lines().filter_map(|x| {
-                let mut line = x.chars();
-                line.next().and_then(|y| line.next().map(|z| (y, z)))
-            }) {
-                replacements.insert(src, dest);
-            }
-        }
-        replacements
-    };
-    static ref CENSOR_TRIE: rustrict::Trie = {
-        let allow_path = canonicalize(Path::new(&std::env::current_exe().unwrap()))
-            .unwrap()
-            .with_file_name("allowlist.txt");
-        let block_path = canonicalize(Path::new(&std::env::current_exe().unwrap()))
-            .unwrap()
-            .with_file_name("blocklist.txt");
-        let mut trie = rustrict::Trie::new();
-        if let Some(x) = match std::fs::read_to_string(allow_path) {
-            Ok(x) => Ok(Some(x)),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::NotFound => Ok(None),
-                other => Err(other),
-            },
+// rustrict only accepts `'static` lists, so each load is leaked. Reloads are rare enough for that
+// to not matter.
+static CENSOR_BANNED: OnceLock<RwLock<&'static rustrict::Banned>> = OnceLock::new();
+static CENSOR_REPLACEMENTS: OnceLock<RwLock<&'static rustrict::Replacements>> = OnceLock::new();
+static CENSOR_TRIE: OnceLock<RwLock<&'static rustrict::Trie>> = OnceLock::new();
+
+/// Contents of a config file next to the executable, or `None` if it doesn't exist
+fn read_config(name: &str) -> Result<Option<String>, Error> {
+    let path = canonicalize(Path::new(&std::env::current_exe()?))?.with_file_name(name);
+    match std::fs::read_to_string(path) {
+        Ok(x) => Ok(Some(x)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn load_banned() -> Result<rustrict::Banned, Error> {
+    let mut banned = rustrict::Banned::new();
+    if let Some(x) = read_config("banned_chars.txt")? {
+        for i in x.lines().filter_map(|x| x.chars().next()) {
+            banned.insert(i);
         }
-        .unwrap()
-        {
-            for i in x.lines() {
-                trie.set(i.to_lowercase().as_str(), Type::SAFE);
-            }
+    }
+    Ok(banned)
+}
+
+fn load_replacements() -> Result<rustrict::Replacements, Error> {
+    let mut replacements = rustrict::Replacements::new();
+    if let Some(x) = read_config("replace_chars.txt")? {
+        for (src, dest) in x.lines().filter_map(|x| {
+            let mut line = x.chars();
+            line.next().and_then(|y| line.next().map(|z| (y, z)))
+        }) {
+            replacements.insert(src, dest);
         }
-        if let Some(x) = match std::fs::read_to_string(block_path) {
-            Ok(x) => Ok(Some(x)),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::NotFound => Ok(None),
-                other => Err(other),
-            },
+    }
+    Ok(replacements)
+}
+
+fn load_trie() -> Result<rustrict::Trie, Error> {
+    let mut trie = rustrict::Trie::new();
+    if let Some(x) = read_config("allowlist.txt")? {
+        for i in x.lines() {
+            trie.set(i.to_lowercase().as_str(), Type::SAFE);
         }
-        .unwrap()
-        {
-            for i in x.lines() {
-                trie.set(i.to_lowercase().as_str(), Type::PROFANE & Type::SEVERE);
-            }
+    }
+    if let Some(x) = read_config("blocklist.txt")? {
+        for i in x.lines() {
+            trie.set(i.to_lowercase().as_str(), Type::PROFANE & Type::SEVERE);
         }
-        trie
-    };
+    }
+    Ok(trie)
+}
+
+/// Current version of a censor list, loading it on first use
+fn read_static<T>(
+    cell: &'static OnceLock<RwLock<&'static T>>,
+    load: fn() -> Result<T, Error>,
+) -> &'static T {
+    *cell
+        .get_or_init(|| RwLock::new(Box::leak(Box::new(load().unwrap()))))
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
 }
 
 pub fn init_statics() {
-    lazy_static::initialize(&CENSOR_BANNED);
-    lazy_static::initialize(&CENSOR_REPLACEMENTS);
-    lazy_static::initialize(&CENSOR_TRIE);
+    read_static(&CENSOR_BANNED, load_banned);
+    read_static(&CENSOR_REPLACEMENTS, load_replacements);
+    read_static(&CENSOR_TRIE, load_trie);
+}
+
+/// Re-read every censor list, keeping the old lists if any file can't be read
+fn reload_statics() -> Result<(), Error> {
+    let (banned, replacements, trie) = (load_banned()?, load_replacements()?, load_trie()?);
+    replace_static(&CENSOR_BANNED, banned);
+    replace_static(&CENSOR_REPLACEMENTS, replacements);
+    replace_static(&CENSOR_TRIE, trie);
+    Ok(())
+}
+
+fn replace_static<T>(cell: &'static OnceLock<RwLock<&'static T>>, value: T) {
+    let value: &'static T = Box::leak(Box::new(value));
+    if cell.set(RwLock::new(value)).is_err() {
+        if let Some(x) = cell.get() {
+            *x.write().unwrap_or_else(std::sync::PoisonError::into_inner) = value;
+        }
+    }
+}
+
+fn analyze(text: &str) -> Type {
+    fedbot_core::profanity::analyze(
+        text,
+        read_static(&CENSOR_TRIE, load_trie),
+        read_static(&CENSOR_REPLACEMENTS, load_replacements),
+    )
 }
 
 pub trait Censorable {
@@ -146,24 +159,7 @@ macro_rules! censor_impl {
     ($x:ty) => {
         impl Censorable for $x {
             fn check_profanity(&self) -> Option<&str> {
-                let scan_types = Censor::new(self.to_lowercase().chars().filter_map(|x|
-                    // Convert dashes and newlines to spaces to trigger false positive detection
-                    if x == '\n' || x == '-' {Some(' ')}
-                    // Remove asterisks to stop self-censor detection for markdown bolding
-                    else if x == '*' {None}
-                    // Replace regional_indicator characters with their ASCII equivalents
-                    else if ('\u{1f1e6}'..='\u{1f1ff}').contains(&x) {Some((x as u8 - ('\u{1f1e6}' as u8 - 'a' as u8)) as char)}
-                    // Keep other characters unchanged
-                    else {Some(x)})
-                )
-                .with_trie(&CENSOR_TRIE)
-                .with_replacements(&CENSOR_REPLACEMENTS)
-                .with_ignore_false_positives(false)
-                .analyze();
-                if (scan_types.is(Type::PROFANE) & !scan_types.is(Type::EVASIVE))
-                | (scan_types.is(Type::SEXUAL) & !scan_types.is(Type::EVASIVE))
-                | scan_types.is(Type::PROFANE & Type::MODERATE_OR_HIGHER & Type::EVASIVE)
-                | scan_types.is(Type::PROFANE & Type::MODERATE_OR_HIGHER & Type::EVASIVE) {
+                if is_objectionable(analyze(self)) {
                     Some(self)
                 } else {
                     None
@@ -209,26 +205,159 @@ censor_impl! {serenity::EmbedField, name, value}
 #[instrument(skip_all, err)]
 pub async fn filter_message<T: Censorable>(
     filter: T,
+    guild: serenity::GuildId,
     channel: serenity::ChannelId,
     id: serenity::MessageId,
     author: &serenity::User,
+    origin: super::MessageOrigin<'_>,
     reference: super::EventReference<'_>,
 ) -> Result<bool, super::Error> {
     if let Some(objectionable) = filter.check_profanity() {
-        channel.delete_message(&reference.0, id).await?;
-        channel
-            .send_message(&reference.0, |f| {
-                f.content(format!(
-                    "Deleted message from {} (reason: profanity)",
-                    author.mention()
-                ))
-            })
+        let scan_types = analyze(objectionable);
+        let reason = format!("{} profanity", severity(scan_types));
+        if super::profanity_reviews::reviews_enabled(&reference.3.db, guild).await? {
+            super::profanity_reviews::queue_review(
+                reference,
+                guild,
+                channel,
+                id,
+                author,
+                objectionable,
+                &reason,
+                &type_names(scan_types),
+            )
             .await?;
+            return Ok(false);
+        }
+        if !super::circuit_breaker::delete_filtered(reference, guild, channel, id, author, &reason)
+            .await?
+        {
+            return Ok(false);
+        }
+        notify_deleted(reference, guild, channel, author, &reason).await?;
+        super::log_filtered_edit(reference, guild, channel, id, author, origin, &reason).await?;
         info!(
-            "Deleted profane message from '{}#{}' (content: '{}')",
-            author.name, author.discriminator, objectionable
+            "Deleted profane message from '{}#{}' (types: {}, content: '{}')",
+            author.name,
+            author.discriminator,
+            type_names(scan_types),
+            objectionable
         );
         return Ok(true);
     }
     Ok(false)
 }
+
+/// Tell the channel why `author`'s message was deleted
+pub async fn notify_deleted(
+    reference: super::EventReference<'_>,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    author: &serenity::User,
+    reason: &str,
+) -> Result<(), super::Error> {
+    channel
+        .send_message(&reference.0, |f| {
+            f.content(format!(
+                "Deleted message from {} (reason: {})",
+                author.mention(),
+                reason
+            ))
+        })
+        .await?;
+    reference.3.filter_followups.arm(channel, author.id);
+    reference
+        .3
+        .events
+        .publish(super::events::BotEvent::MessageFiltered {
+            guild,
+            user: author.id,
+            reason: reason.to_owned(),
+        });
+    Ok(())
+}
+
+/// Log server renames, and revert the name if the new one is profane
+#[instrument(skip_all, err)]
+pub async fn filter_server_name(
+    old_name: Option<&str>,
+    server: &serenity::PartialGuild,
+    reference: super::EventReference<'_>,
+) -> Result<(), super::Error> {
+    let Some(old_name) = old_name.filter(|x| *x != server.name) else {
+        return Ok(());
+    };
+    super::mod_log(
+        reference.0,
+        reference.3,
+        server.id,
+        None,
+        format!("Server renamed from '{}' to '{}'", old_name, server.name),
+    )
+    .await?;
+
+    if !reference.3.features_for(server.id).profanity_filter {
+        return Ok(());
+    }
+    if let Some(objectionable) = server.name.as_str().check_profanity() {
+        let mut guild = server.id;
+        guild.edit(reference.0, |f| f.name(old_name)).await?;
+        super::mod_log(
+            reference.0,
+            reference.3,
+            server.id,
+            Some(crate::entities::guild_log_channels::Purpose::FilterNotices),
+            format!(
+                "Reverted server name to '{}' (reason: {} profanity)",
+                old_name,
+                severity(analyze(objectionable))
+            ),
+        )
+        .await?;
+        info!(
+            "Reverted profane server name '{}' in guild '{}'",
+            objectionable, server.id
+        );
+    }
+    Ok(())
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only, subcommands("reload"))]
+pub async fn filter(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Re-read the profanity filter's word and character lists without restarting
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, owners_only)]
+async fn reload(ctx: Context<'_>) -> Result<(), Error> {
+    let content = match reload_statics() {
+        Ok(()) => {
+            info!(
+                "User '{}#{}' reloaded the profanity filter lists",
+                ctx.author().name,
+                ctx.author().discriminator
+            );
+            "Reloaded profanity filter lists.".to_owned()
+        }
+        Err(e) => format!("Failed to reload profanity filter lists, keeping the old ones: {e}"),
+    };
+    ctx.send(|f| f.content(content).ephemeral(true)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replacing_a_static_initializes_or_overwrites() {
+        static CELL: OnceLock<RwLock<&'static u8>> = OnceLock::new();
+        replace_static(&CELL, 1);
+        assert_eq!(*read_static(&CELL, || Ok(0)), 1);
+        replace_static(&CELL, 2);
+        assert_eq!(*read_static(&CELL, || Ok(0)), 2);
+    }
+}
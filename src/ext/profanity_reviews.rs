@@ -0,0 +1,462 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Let mods decide on flagged messages instead of deleting them straight away
+//!
+//! Pending reviews are stored so their buttons keep working across restarts.
+
+use super::profile_setup::diff_profile;
+use super::{t, Context, Error};
+use crate::entities::{prelude::*, *};
+use crate::{check_admin, require_profile};
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use tracing::{info, instrument};
+
+const REVIEW_PREFIX: &str = "profanity-review-";
+const MAX_EXCERPT_LENGTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReviewAction {
+    Delete,
+    Dismiss,
+}
+
+impl ReviewAction {
+    const ALL: [Self; 2] = [Self::Delete, Self::Dismiss];
+
+    const fn id(self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Dismiss => "dismiss",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Delete => "Delete",
+            Self::Dismiss => "Dismiss",
+        }
+    }
+
+    const fn style(self) -> serenity::ButtonStyle {
+        match self {
+            Self::Delete => serenity::ButtonStyle::Danger,
+            Self::Dismiss => serenity::ButtonStyle::Secondary,
+        }
+    }
+
+    fn custom_id(self, review: i32) -> String {
+        format!("{REVIEW_PREFIX}{}-{}", self.id(), review)
+    }
+
+    fn parse(custom_id: &str) -> Option<(Self, i32)> {
+        let (action, review) = custom_id.strip_prefix(REVIEW_PREFIX)?.split_once('-')?;
+        let action = Self::ALL.into_iter().find(|x| x.id() == action)?;
+        Some((action, review.parse().ok()?))
+    }
+}
+
+#[derive(Copy, Clone, Debug, poise::ChoiceParameter)]
+pub enum ProfanityMode {
+    #[name = "Delete straight away"]
+    Delete,
+    #[name = "Ask mods to review"]
+    Review,
+}
+
+impl From<ProfanityMode> for servers::ProfanityAction {
+    fn from(x: ProfanityMode) -> Self {
+        match x {
+            ProfanityMode::Delete => Self::Delete,
+            ProfanityMode::Review => Self::Review,
+        }
+    }
+}
+
+fn review_embed<'a>(
+    f: &'a mut serenity::CreateEmbed,
+    review: &profanity_reviews::Model,
+    status: &str,
+) -> &'a mut serenity::CreateEmbed {
+    f.title("Profanity review")
+        .description(super::quote_content(&review.excerpt, MAX_EXCERPT_LENGTH))
+        .field(
+            "Author",
+            serenity::UserId::from(review.author_id).mention(),
+            true,
+        )
+        .field(
+            "Channel",
+            serenity::ChannelId::from(review.channel_id).mention(),
+            true,
+        )
+        .field(
+            "Message",
+            format!(
+                "[Jump](https://discord.com/channels/{}/{}/{})",
+                serenity::GuildId::from(review.guild_id),
+                serenity::ChannelId::from(review.channel_id),
+                serenity::MessageId::from(review.message_id)
+            ),
+            true,
+        )
+        .field("Reason", &review.reason, true)
+        .field("Types", &review.types, true)
+        .field("Status", status, false)
+        .timestamp(
+            serenity::Timestamp::from_unix_timestamp(review.flagged_at.timestamp())
+                .unwrap_or_else(|_| serenity::Timestamp::now()),
+        )
+}
+
+fn review_buttons(
+    f: &mut serenity::CreateComponents,
+    review: i32,
+) -> &mut serenity::CreateComponents {
+    f.create_action_row(|f| {
+        for action in ReviewAction::ALL {
+            f.create_button(|f| {
+                f.custom_id(action.custom_id(review))
+                    .label(action.label())
+                    .style(action.style())
+            });
+        }
+        f
+    })
+}
+
+const fn pending_status(edited: bool) -> &'static str {
+    if edited {
+        "Awaiting review. The message has been edited since it was flagged, so check it before deciding."
+    } else {
+        "Awaiting review"
+    }
+}
+
+#[derive(FromQueryResult)]
+struct ProfanityActionData {
+    profanity_action: servers::ProfanityAction,
+}
+
+/// Whether flagged messages in `guild` go to mods rather than being deleted
+pub async fn reviews_enabled(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+) -> Result<bool, Error> {
+    Ok(Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::ProfanityAction)
+        .into_model::<ProfanityActionData>()
+        .one(db)
+        .await?
+        .is_some_and(|x| x.profanity_action == servers::ProfanityAction::Review))
+}
+
+/// Ask the mods whether to delete a flagged message, unless it's already waiting for them
+#[allow(clippy::too_many_arguments)]
+pub async fn queue_review(
+    reference: super::EventReference<'_>,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    message: serenity::MessageId,
+    author: &serenity::User,
+    excerpt: &str,
+    reason: &str,
+    types: &str,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    let pending = ProfanityReviews::find()
+        .filter(profanity_reviews::Column::MessageId.eq(ids::DbMessageId::from(message)))
+        .count(&data.db)
+        .await?;
+    if pending > 0 {
+        return Ok(());
+    }
+    let Some(review_channel) = super::mod_channel(
+        data,
+        guild,
+        Some(guild_log_channels::Purpose::FilterNotices),
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let review = profanity_reviews::ActiveModel {
+        id: ActiveValue::NotSet,
+        guild_id: ActiveValue::Set(guild.into()),
+        channel_id: ActiveValue::Set(channel.into()),
+        message_id: ActiveValue::Set(message.into()),
+        author_id: ActiveValue::Set(author.id.into()),
+        review_channel_id: ActiveValue::Set(None),
+        review_message_id: ActiveValue::Set(None),
+        excerpt: ActiveValue::Set(excerpt.to_owned()),
+        reason: ActiveValue::Set(reason.to_owned()),
+        types: ActiveValue::Set(types.to_owned()),
+        edited: ActiveValue::Set(false),
+        flagged_at: ActiveValue::Set(chrono::Utc::now()),
+    }
+    .insert(&data.db)
+    .await?;
+
+    let posted = review_channel
+        .send_message(ctx, |f| {
+            f.embed(|f| review_embed(f, &review, pending_status(false)))
+                .components(|f| review_buttons(f, review.id))
+                .allowed_mentions(|f| f.empty_users())
+        })
+        .await?;
+    let mut review: profanity_reviews::ActiveModel = review.into();
+    review.review_channel_id = ActiveValue::Set(Some(review_channel.into()));
+    review.review_message_id = ActiveValue::Set(Some(posted.id.into()));
+    review.update(&data.db).await?;
+    info!(
+        "Queued profane message from '{}#{}' for review in guild '{}'",
+        author.name, author.discriminator, guild
+    );
+    Ok(())
+}
+
+/// Replace a review's embed and buttons to show where it stands
+async fn update_review(
+    ctx: &serenity::Context,
+    review: &profanity_reviews::Model,
+    status: &str,
+    open: bool,
+) -> Result<(), Error> {
+    let (Some(channel), Some(message)) = (review.review_channel_id, review.review_message_id)
+    else {
+        return Ok(());
+    };
+    serenity::ChannelId::from(channel)
+        .edit_message(ctx, serenity::MessageId::from(message), |f| {
+            f.embed(|f| review_embed(f, review, status))
+                .components(|f| {
+                    if open {
+                        review_buttons(f, review.id)
+                    } else {
+                        f
+                    }
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Warn reviewers that a flagged message has changed since they were asked about it
+#[instrument(skip_all, err)]
+pub async fn message_edited(
+    message: serenity::MessageId,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    for review in ProfanityReviews::find()
+        .filter(profanity_reviews::Column::MessageId.eq(ids::DbMessageId::from(message)))
+        .filter(profanity_reviews::Column::Edited.eq(false))
+        .all(&data.db)
+        .await?
+    {
+        let mut model: profanity_reviews::ActiveModel = review.clone().into();
+        model.edited = ActiveValue::Set(true);
+        let review = model.update(&data.db).await?;
+        // The review message may have been deleted by a mod
+        _ = t(update_review(ctx, &review, pending_status(true), true).await);
+    }
+    Ok(())
+}
+
+/// Close the reviews of flagged messages that were deleted before anyone acted on them
+#[instrument(skip_all, err)]
+pub async fn messages_deleted(
+    messages: &[serenity::MessageId],
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    let reviews = ProfanityReviews::find()
+        .filter(
+            profanity_reviews::Column::MessageId
+                .is_in(messages.iter().map(|x| ids::DbMessageId::from(*x))),
+        )
+        .all(&data.db)
+        .await?;
+    if reviews.is_empty() {
+        return Ok(());
+    }
+    ProfanityReviews::delete_many()
+        .filter(profanity_reviews::Column::Id.is_in(reviews.iter().map(|x| x.id)))
+        .exec(&data.db)
+        .await?;
+    for review in reviews {
+        _ = t(update_review(ctx, &review, "Deleted before anyone reviewed it", false).await);
+    }
+    Ok(())
+}
+
+/// Delete or keep a flagged message from the buttons on its review
+#[instrument(skip_all, err)]
+pub async fn handle_review(
+    interaction: &serenity::MessageComponentInteraction,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let Some((action, review_id)) = ReviewAction::parse(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+    let (Some(guild), Some(member)) = (interaction.guild_id, interaction.member.as_ref()) else {
+        return Ok(());
+    };
+    let (ctx, data) = (reference.0, reference.3);
+    let Some(server_data) = Servers::find_by_id(guild).one(&data.db).await? else {
+        return Ok(());
+    };
+
+    let review = ProfanityReviews::find_by_id(review_id)
+        .one(&data.db)
+        .await?;
+    let refusal =
+        if !super::has_mod_role(&data.db, guild, server_data.mod_role.into(), &member.roles).await?
+        {
+            Some("Only mods can review flagged messages.")
+        } else if review.is_none() {
+            Some("This message has already been reviewed.")
+        } else {
+            None
+        };
+    let (None, Some(review)) = (refusal, review) else {
+        interaction
+            .create_interaction_response(ctx, |f| {
+                f.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|f| {
+                        f.content(refusal.unwrap_or_default()).ephemeral(true)
+                    })
+            })
+            .await?;
+        return Ok(());
+    };
+    interaction
+        .create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+
+    // Claim the review first, so a second press or the deletion it causes finds nothing to do
+    if ProfanityReviews::delete_by_id(review.id)
+        .exec(&data.db)
+        .await?
+        .rows_affected
+        == 0
+    {
+        return Ok(());
+    }
+
+    let status = match action {
+        ReviewAction::Delete => {
+            let (channel, author) = (
+                serenity::ChannelId::from(review.channel_id),
+                serenity::UserId::from(review.author_id)
+                    .to_user(ctx)
+                    .await?,
+            );
+            if channel
+                .delete_message(ctx, serenity::MessageId::from(review.message_id))
+                .await
+                .is_ok()
+            {
+                super::profanity_checks::notify_deleted(
+                    reference,
+                    guild,
+                    channel,
+                    &author,
+                    &review.reason,
+                )
+                .await?;
+                format!("Deleted by {}", member.mention())
+            } else {
+                format!(
+                    "{} chose to delete it, but it was already gone",
+                    member.mention()
+                )
+            }
+        }
+        ReviewAction::Dismiss => format!("Dismissed by {}", member.mention()),
+    };
+    update_review(ctx, &review, &status, false).await?;
+    info!(
+        "User '{}#{}' reviewed flagged message {} in guild '{}' ({})",
+        member.user.name,
+        member.user.discriminator,
+        serenity::MessageId::from(review.message_id),
+        guild,
+        action.id()
+    );
+    Ok(())
+}
+
+/// Choose whether flagged messages are deleted straight away or sent to mods for review first
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn profanity_action(
+    ctx: Context<'_>,
+    #[description = "What to do with messages the profanity filter flags"] mode: ProfanityMode,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.profanity_action = ActiveValue::Set(mode.into());
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Profanity action updated", changes).await?;
+    }
+
+    ctx.send(|f| {
+        f.content(match mode {
+            ProfanityMode::Delete => "Flagged messages will be deleted straight away.",
+            ProfanityMode::Review => {
+                "Flagged messages will be posted for mods to delete or dismiss."
+            }
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn review_ids_round_trip() {
+        for action in ReviewAction::ALL {
+            assert_eq!(
+                ReviewAction::parse(&action.custom_id(42)),
+                Some((action, 42))
+            );
+        }
+        assert_eq!(ReviewAction::parse("profanity-review-ban-42"), None);
+        assert_eq!(ReviewAction::parse("joinAlert-accept-42"), None);
+    }
+}
@@ -15,11 +15,13 @@
 */
 
 use super::ContainBytes;
-use super::{entry_modal, Context, Error};
+use super::{entry_modal, features, profile_wizard, Context, Error, PermissionTier};
 use crate::{
-    check_admin,
+    check_admin, check_tier,
     entities::{prelude::*, *},
+    require_profile,
 };
+use itertools::Itertools;
 use poise::serenity_prelude as serenity;
 use sea_orm::*;
 use tracing::instrument;
@@ -27,31 +29,125 @@ use tracing::instrument;
 mod channel_overrides {
     use super::*;
 
+    /// The overwrites each managed channel should have, shared by setup and `/profile audit`
+    pub mod expected {
+        use super::serenity;
+
+        fn role(
+            role: serenity::RoleId,
+            allow: serenity::Permissions,
+            deny: serenity::Permissions,
+        ) -> serenity::PermissionOverwrite {
+            serenity::PermissionOverwrite {
+                allow,
+                deny,
+                kind: serenity::PermissionOverwriteType::Role(role),
+            }
+        }
+
+        pub fn mod_channel(
+            default_role: serenity::RoleId,
+            mod_role: serenity::RoleId,
+        ) -> Vec<serenity::PermissionOverwrite> {
+            vec![
+                role(
+                    mod_role,
+                    serenity::Permissions::VIEW_CHANNEL,
+                    serenity::Permissions::empty(),
+                ),
+                role(
+                    default_role,
+                    serenity::Permissions::empty(),
+                    serenity::Permissions::VIEW_CHANNEL,
+                ),
+            ]
+        }
+
+        pub fn rules_channel(default_role: serenity::RoleId) -> Vec<serenity::PermissionOverwrite> {
+            vec![role(
+                default_role,
+                serenity::Permissions::VIEW_CHANNEL,
+                serenity::Permissions::SEND_MESSAGES,
+            )]
+        }
+
+        pub fn screening_channel(
+            is_forum: bool,
+            default_role: serenity::RoleId,
+            mod_role: serenity::RoleId,
+            member_role: serenity::RoleId,
+            questioning_role: serenity::RoleId,
+        ) -> Vec<serenity::PermissionOverwrite> {
+            // In a forum, SEND_MESSAGES only covers creating posts, so replies need denying too
+            let deny = if is_forum {
+                serenity::Permissions::SEND_MESSAGES
+                    | serenity::Permissions::SEND_MESSAGES_IN_THREADS
+            } else {
+                serenity::Permissions::SEND_MESSAGES
+            };
+            vec![
+                role(default_role, serenity::Permissions::VIEW_CHANNEL, deny),
+                role(
+                    mod_role,
+                    serenity::Permissions::VIEW_CHANNEL,
+                    serenity::Permissions::SEND_MESSAGES,
+                ),
+                role(
+                    member_role,
+                    serenity::Permissions::empty(),
+                    serenity::Permissions::VIEW_CHANNEL,
+                ),
+                role(
+                    questioning_role,
+                    serenity::Permissions::empty(),
+                    serenity::Permissions::VIEW_CHANNEL,
+                ),
+            ]
+        }
+
+        pub fn questioning_category(
+            default_role: serenity::RoleId,
+            questioning_role: serenity::RoleId,
+            mod_role: serenity::RoleId,
+        ) -> Vec<serenity::PermissionOverwrite> {
+            vec![
+                role(
+                    default_role,
+                    serenity::Permissions::empty(),
+                    serenity::Permissions::VIEW_CHANNEL,
+                ),
+                role(
+                    questioning_role,
+                    serenity::Permissions::SEND_MESSAGES,
+                    serenity::Permissions::VIEW_CHANNEL,
+                ),
+                role(
+                    mod_role,
+                    serenity::Permissions::SEND_MESSAGES | serenity::Permissions::VIEW_CHANNEL,
+                    serenity::Permissions::empty(),
+                ),
+            ]
+        }
+    }
+
+    pub async fn apply(
+        ctx: Context<'_>,
+        x: serenity::ChannelId,
+        overwrites: &[serenity::PermissionOverwrite],
+    ) -> Result<(), Error> {
+        for i in overwrites {
+            x.create_permission(ctx, i).await?;
+        }
+        Ok(())
+    }
+
     pub async fn mod_channel(
         ctx: Context<'_>,
         x: serenity::ChannelId,
         default_role: serenity::RoleId,
         mod_role: serenity::RoleId,
     ) -> Result<(), Error> {
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::empty(),
-                kind: serenity::PermissionOverwriteType::Role(mod_role),
-            },
-        )
-        .await?;
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: serenity::Permissions::VIEW_CHANNEL,
-                kind: serenity::PermissionOverwriteType::Role(default_role),
-            },
-        )
-        .await?;
-        Ok(())
+        apply(ctx, x, &expected::mod_channel(default_role, mod_role)).await
     }
 
     pub async fn rules_channel(
@@ -59,16 +155,7 @@ mod channel_overrides {
         x: serenity::ChannelId,
         default_role: serenity::RoleId,
     ) -> Result<(), Error> {
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::SEND_MESSAGES,
-                kind: serenity::PermissionOverwriteType::Role(default_role),
-            },
-        )
-        .await?;
-        Ok(())
+        apply(ctx, x, &expected::rules_channel(default_role)).await
     }
 
     pub async fn screening_channel(
@@ -79,43 +166,19 @@ mod channel_overrides {
         member_role: serenity::RoleId,
         questioning_role: serenity::RoleId,
     ) -> Result<(), Error> {
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::SEND_MESSAGES,
-                kind: serenity::PermissionOverwriteType::Role(default_role),
-            },
-        )
-        .await?;
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::SEND_MESSAGES,
-                kind: serenity::PermissionOverwriteType::Role(mod_role),
-            },
-        )
-        .await?;
-        x.create_permission(
+        let is_forum = super::super::is_forum(ctx, x).await?;
+        apply(
             ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: serenity::Permissions::VIEW_CHANNEL,
-                kind: serenity::PermissionOverwriteType::Role(member_role),
-            },
-        )
-        .await?;
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: serenity::Permissions::VIEW_CHANNEL,
-                kind: serenity::PermissionOverwriteType::Role(questioning_role),
-            },
+            x,
+            &expected::screening_channel(
+                is_forum,
+                default_role,
+                mod_role,
+                member_role,
+                questioning_role,
+            ),
         )
-        .await?;
-        Ok(())
+        .await
     }
 
     pub async fn questioning_category(
@@ -125,152 +188,500 @@ mod channel_overrides {
         questioning_role: serenity::RoleId,
         mod_role: serenity::RoleId,
     ) -> Result<(), Error> {
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: serenity::Permissions::VIEW_CHANNEL,
-                kind: serenity::PermissionOverwriteType::Role(default_role),
-            },
-        )
-        .await?;
-        x.create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::SEND_MESSAGES,
-                deny: serenity::Permissions::VIEW_CHANNEL,
-                kind: serenity::PermissionOverwriteType::Role(questioning_role),
-            },
-        )
-        .await?;
-        x.create_permission(
+        apply(
             ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::SEND_MESSAGES | serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::empty(),
-                kind: serenity::PermissionOverwriteType::Role(mod_role),
-            },
+            x,
+            &expected::questioning_category(default_role, questioning_role, mod_role),
         )
-        .await?;
-        Ok(())
+        .await
     }
 }
 
+/// Render a profile setting the way it should appear in an audit notice
+fn format_setting(column: servers::Column, value: &Value) -> Option<String> {
+    use serenity::Mentionable;
+    use servers::Column;
+
+    Some(match (column, value) {
+        // Internal state rather than settings
+        (
+            Column::EntryModal
+            | Column::EntryModalJson
+            | Column::EntryModalUpdatedAt
+            | Column::ScreeningPost
+            | Column::ScreeningMessageId,
+            _,
+        ) => return None,
+        (Column::MaxEmojis | Column::MaxAttachments | Column::MaxStickers, Value::Int(x)) => {
+            x.map_or_else(|| "unlimited".to_owned(), |y| y.to_string())
+        }
+        (Column::RejoinWindowDays, Value::Int(Some(x))) => format!("{x} days"),
+        (Column::QuestioningBumpMinutes, Value::Int(Some(x))) => format!("{x} minutes"),
+        (Column::FirstMessageThreshold, Value::Int(Some(x))) => format!("{x} messages"),
+        (Column::QuietHoursStart | Column::QuietHoursEnd, Value::Int(x)) => {
+            x.map_or_else(|| "*off*".to_owned(), super::quiet_hours::format_minutes)
+        }
+        (Column::QuietHoursTimezone, Value::String(x)) => x
+            .as_ref()
+            .map_or_else(|| "UTC".to_owned(), |y| y.to_string()),
+        (Column::ScreeningDailyLimit, Value::TinyInt(x)) => {
+            x.map_or_else(|| "unlimited".to_owned(), |y| y.to_string())
+        }
+        (
+            Column::ScreeningWelcomeText
+            | Column::ScreeningFallbackText
+            | Column::WelcomeMessageTemplate
+            | Column::FilterFollowupText,
+            Value::String(x),
+        ) => x
+            .as_deref()
+            .map_or_else(|| "*default*".to_owned(), |y| super::quote_content(y, 100)),
+        (_, Value::BigInt(None) | Value::String(None)) => "*none*".to_owned(),
+        (
+            Column::RulesChannel
+            | Column::ScreeningChannel
+            | Column::QuestioningCategory
+            | Column::ModChannel
+            | Column::MainChannel
+            | Column::AuditChannel,
+            Value::BigInt(Some(x)),
+        ) => serenity::ChannelId(x.repack()).mention().to_string(),
+        (
+            Column::QuestioningRole | Column::ModRole | Column::MemberRole | Column::HelperRole,
+            Value::BigInt(Some(x)),
+        ) => serenity::RoleId(x.repack()).mention().to_string(),
+        (
+            Column::EphemeralResponses
+            | Column::FilterLinkedImages
+            | Column::ProfanityFilterEnabled
+            | Column::ImageFilterEnabled
+            | Column::TriggersEnabled
+            | Column::ScreeningEnabled
+            | Column::QuestioningBumpsEnabled
+            | Column::QuestioningAcksEnabled
+            | Column::RestoreOnRejoin
+            | Column::RestoreAllRoles
+            | Column::StrictFirstMessages,
+            Value::Bool(Some(x)),
+        ) => x.to_string(),
+        (
+            Column::AppealContact | Column::ProfanityAction | Column::NsfwChannelPolicy,
+            Value::String(Some(x)),
+        ) => x.to_string(),
+        // The path often carries a token of its own
+        (Column::WebhookUrl, Value::String(Some(x))) => reqwest::Url::parse(x)
+            .ok()
+            .and_then(|x| x.host_str().map(str::to_owned))
+            .unwrap_or_default(),
+        (Column::WebhookSecret, Value::String(Some(_))) => "*hidden*".to_owned(),
+        _ => return None,
+    })
+}
+
+/// Field-by-field description of the settings changed by `new`
+pub(super) fn diff_profile(
+    old: Option<&servers::Model>,
+    new: &servers::ActiveModel,
+) -> Vec<(String, String)> {
+    servers::Column::iter()
+        .filter_map(|column| {
+            let ActiveValue::Set(new_value) = new.get(column) else {
+                return None;
+            };
+            let new_text = format_setting(column, &new_value)?;
+            Some((
+                column.to_string(),
+                match old.map(|x| x.get(column)) {
+                    Some(old_value) if old_value == new_value => return None,
+                    Some(old_value) => format!(
+                        "{} → {}",
+                        format_setting(column, &old_value).unwrap_or_default(),
+                        new_text
+                    ),
+                    None => new_text,
+                },
+            ))
+        })
+        .collect()
+}
+
 /// Blank supercommand
 #[instrument(skip_all, err)]
 #[poise::command(
     slash_command,
-    subcommands("init", "update", "entry_modal::set_entry_modal"),
+    subcommands(
+        "init",
+        "update",
+        "show",
+        "entry_modal::set_entry_modal",
+        "entry_modal::remove_entry_modal",
+        "profile_wizard::wizard",
+        "features::features",
+        "entry_modal::screening_text",
+        "set_welcome",
+        "filter_followup",
+        "add_mod_role",
+        "remove_mod_role",
+        "log_channel",
+        "audit",
+        "super::first_messages::first_messages",
+        "super::profanity_reviews::profanity_action",
+        "super::image_filtering::nsfw_policy"
+    ),
     guild_only
 )]
 pub async fn profile(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-/// Create a new server profile
+/// Blank supercommand
 #[instrument(skip_all, err)]
-#[poise::command(slash_command, guild_only)]
-#[allow(clippy::too_many_arguments)]
-async fn init(
-    ctx: Context<'_>,
-    #[channel_types("Text")] rules_channel: serenity::GuildChannel,
-    #[channel_types("Text")] screening_channel: serenity::GuildChannel,
-    questioning_role: serenity::Role,
-    #[channel_types("Category")] questioning_category: serenity::Channel,
-    mod_role: serenity::Role,
-    #[channel_types("Text")] mod_channel: serenity::GuildChannel,
-    member_role: serenity::Role,
-    #[channel_types("Text")] main_channel: serenity::GuildChannel,
-) -> Result<(), Error> {
-    let guild = ctx
-        .guild_id()
-        .ok_or(super::FedBotError::new("command called outside server"))?;
-
-    check_admin!(ctx, guild);
-
-    let maybe_category = questioning_category;
-    let questioning_category: serenity::ChannelCategory;
-    if let serenity::Channel::Category(x) = maybe_category {
-        questioning_category = x;
-    } else {
-        return Err(super::FedBotError::new("questioning_category is not a category").into());
-    }
-
-    crate::defer!(ctx);
-
-    let new_server = servers::ActiveModel {
-        id: ActiveValue::Set(guild.as_u64().repack()),
-        rules_channel: ActiveValue::Set(rules_channel.id.as_u64().repack()),
-        screening_channel: ActiveValue::Set(screening_channel.id.as_u64().repack()),
-        questioning_role: ActiveValue::Set(questioning_role.id.as_u64().repack()),
-        questioning_category: ActiveValue::Set(questioning_category.id.as_u64().repack()),
-        mod_role: ActiveValue::Set(mod_role.id.as_u64().repack()),
-        mod_channel: ActiveValue::Set(mod_channel.id.as_u64().repack()),
-        member_role: ActiveValue::Set(member_role.id.as_u64().repack()),
-        main_channel: ActiveValue::Set(main_channel.id.as_u64().repack()),
-        ..Default::default()
-    };
-    Servers::insert(new_server).exec(&ctx.data().db).await?;
+#[poise::command(
+    slash_command,
+    subcommands("quiet_hours", "super::webhooks::webhook"),
+    guild_only
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
 
-    let default_role = serenity::RoleId(guild.0); // @everyone has the same id as the guild
-    let default_perms = if let Some(x) = default_role.to_role_cached(ctx) {
+/// Fetch a role's current permissions, preferring the cache
+async fn role_permissions(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    role: serenity::RoleId,
+) -> Result<serenity::Permissions, Error> {
+    Ok(if let Some(x) = role.to_role_cached(ctx) {
         x
     } else {
         guild
             .roles(ctx)
             .await?
-            .remove(&default_role)
+            .remove(&role)
             .ok_or(super::FedBotError::new("role missing from guild"))?
     }
-    .permissions;
+    .permissions)
+}
+
+/// Save a new server profile and apply its channel and role permissions
+pub(super) async fn create_profile(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    new_server: servers::ActiveModel,
+) -> Result<(), Error> {
+    let changes = diff_profile(None, &new_server);
+    let profile = new_server.insert(&ctx.data().db).await?;
+    ctx.data().set_ephemeral_for(guild, true);
+
+    let (
+        rules_channel,
+        screening_channel,
+        questioning_role,
+        questioning_category,
+        mod_role,
+        mod_channel,
+        member_role,
+    ) = (
+        serenity::ChannelId::from(profile.rules_channel),
+        serenity::ChannelId::from(profile.screening_channel),
+        serenity::RoleId::from(profile.questioning_role),
+        serenity::ChannelId::from(profile.questioning_category),
+        serenity::RoleId::from(profile.mod_role),
+        serenity::ChannelId::from(profile.mod_channel),
+        serenity::RoleId::from(profile.member_role),
+    );
+
+    let default_role = serenity::RoleId(guild.0); // @everyone has the same id as the guild
+    let default_perms = role_permissions(ctx, guild, default_role).await?;
     guild
         .edit_role(ctx, default_role, |f| {
             f.permissions(default_perms & !serenity::Permissions::VIEW_CHANNEL)
         })
         .await?;
 
+    let member_perms = role_permissions(ctx, guild, member_role).await?;
     guild
-        .edit_role(ctx, member_role.id, |f| {
-            f.permissions(member_role.permissions | serenity::Permissions::VIEW_CHANNEL)
+        .edit_role(ctx, member_role, |f| {
+            f.permissions(member_perms | serenity::Permissions::VIEW_CHANNEL)
         })
         .await?;
 
-    channel_overrides::mod_channel(ctx, mod_channel.id, default_role, mod_role.id).await?;
-    channel_overrides::rules_channel(ctx, rules_channel.id, default_role).await?;
+    channel_overrides::mod_channel(ctx, mod_channel, default_role, mod_role).await?;
+    channel_overrides::rules_channel(ctx, rules_channel, default_role).await?;
     channel_overrides::screening_channel(
         ctx,
-        screening_channel.id,
+        screening_channel,
         default_role,
-        mod_role.id,
-        member_role.id,
-        questioning_role.id,
+        mod_role,
+        member_role,
+        questioning_role,
     )
     .await?;
     channel_overrides::questioning_category(
         ctx,
-        questioning_category.id,
+        questioning_category,
         default_role,
-        questioning_role.id,
-        mod_role.id,
+        questioning_role,
+        mod_role,
     )
     .await?;
 
     super::entry_modal::display_entry_modal(ctx.serenity_context(), ctx.data(), guild).await?;
 
+    super::config_audit(ctx, guild, "Server profile created", changes).await?;
+    Ok(())
+}
+
+/// A channel ID or mention typed in by hand
+fn parse_channel_id(text: &str) -> Option<serenity::ChannelId> {
+    let text = text.trim();
+    text.parse()
+        .ok()
+        .or_else(|| serenity::utils::parse_channel(text))
+        .map(serenity::ChannelId)
+}
+
+/// Use the picked channel, or look up the one given by ID when it couldn't be picked
+async fn resolve_channel(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    label: &str,
+    picked: Option<serenity::ChannelId>,
+    id: Option<String>,
+    kinds: &[serenity::ChannelType],
+) -> Result<Option<serenity::ChannelId>, Error> {
+    if picked.is_some() {
+        return Ok(picked);
+    }
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let id = parse_channel_id(&id)
+        .ok_or_else(|| super::FedBotError::new(format!("{label} is not a channel ID")))?;
+    let kind = match id.to_channel(ctx).await? {
+        serenity::Channel::Guild(x) if x.guild_id == guild => Some(x.kind),
+        serenity::Channel::Category(x) if x.guild_id == guild => {
+            Some(serenity::ChannelType::Category)
+        }
+        _ => None,
+    };
+    match kind {
+        Some(x) if kinds.contains(&x) => Ok(Some(id)),
+        _ => Err(super::FedBotError::new(format!(
+            "{label} is not a {} channel in this server",
+            kinds.iter().map(|x| x.name()).join(" or ")
+        ))
+        .into()),
+    }
+}
+
+/// [`resolve_channel`] for settings that can't be left out
+async fn require_channel(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    label: &str,
+    picked: Option<serenity::ChannelId>,
+    id: Option<String>,
+    kinds: &[serenity::ChannelType],
+) -> Result<serenity::ChannelId, Error> {
+    resolve_channel(ctx, guild, label, picked, id, kinds)
+        .await?
+        .ok_or_else(|| {
+            super::FedBotError::new(format!("{label} is required, either picked or by ID")).into()
+        })
+}
+
+/// Create a new server profile
+///
+/// Channels can be given by ID instead, for when they can't be found by name
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+#[allow(clippy::too_many_arguments)]
+async fn init(
+    ctx: Context<'_>,
+    questioning_role: serenity::Role,
+    mod_role: serenity::Role,
+    member_role: serenity::Role,
+    #[channel_types("Text")] rules_channel: Option<serenity::GuildChannel>,
+    #[channel_types("Text", "Forum")] screening_channel: Option<serenity::GuildChannel>,
+    #[channel_types("Category")] questioning_category: Option<serenity::Channel>,
+    #[channel_types("Text")] mod_channel: Option<serenity::GuildChannel>,
+    #[channel_types("Text")] main_channel: Option<serenity::GuildChannel>,
+    #[description = "Role allowed to use screening commands"] helper_role: Option<serenity::Role>,
+    #[description = "Channel for configuration change notices (defaults to the mod channel)"]
+    #[channel_types("Text")]
+    audit_channel: Option<serenity::GuildChannel>,
+    #[description = "Who kicked users can contact to appeal, e.g. an invite link or a mod's tag"]
+    appeal_contact: Option<String>,
+    #[description = "Rules channel ID, if it can't be picked"] rules_channel_id: Option<String>,
+    #[description = "Screening channel ID, if it can't be picked"] screening_channel_id: Option<
+        String,
+    >,
+    #[description = "Questioning category ID, if it can't be picked"]
+    questioning_category_id: Option<String>,
+    #[description = "Mod channel ID, if it can't be picked"] mod_channel_id: Option<String>,
+    #[description = "Main channel ID, if it can't be picked"] main_channel_id: Option<String>,
+    #[description = "Audit channel ID, if it can't be picked"] audit_channel_id: Option<String>,
+) -> Result<(), Error> {
+    use serenity::ChannelType;
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    if questioning_category
+        .as_ref()
+        .is_some_and(|x| !matches!(x, serenity::Channel::Category(_)))
+    {
+        return Err(super::FedBotError::new("questioning_category is not a category").into());
+    }
+
+    const TEXT: &[ChannelType] = &[ChannelType::Text];
+    let rules_channel = require_channel(
+        ctx,
+        guild,
+        "rules_channel",
+        rules_channel.map(|x| x.id),
+        rules_channel_id,
+        TEXT,
+    )
+    .await?;
+    let screening_channel = require_channel(
+        ctx,
+        guild,
+        "screening_channel",
+        screening_channel.map(|x| x.id),
+        screening_channel_id,
+        &[ChannelType::Text, ChannelType::Forum],
+    )
+    .await?;
+    let questioning_category = require_channel(
+        ctx,
+        guild,
+        "questioning_category",
+        questioning_category.map(|x| x.id()),
+        questioning_category_id,
+        &[ChannelType::Category],
+    )
+    .await?;
+    let mod_channel = require_channel(
+        ctx,
+        guild,
+        "mod_channel",
+        mod_channel.map(|x| x.id),
+        mod_channel_id,
+        TEXT,
+    )
+    .await?;
+    let main_channel = require_channel(
+        ctx,
+        guild,
+        "main_channel",
+        main_channel.map(|x| x.id),
+        main_channel_id,
+        TEXT,
+    )
+    .await?;
+    let audit_channel = resolve_channel(
+        ctx,
+        guild,
+        "audit_channel",
+        audit_channel.map(|x| x.id),
+        audit_channel_id,
+        TEXT,
+    )
+    .await?;
+
+    crate::defer!(ctx);
+
+    let new_server = servers::ActiveModel {
+        id: ActiveValue::Set(guild.into()),
+        rules_channel: ActiveValue::Set(rules_channel.into()),
+        screening_channel: ActiveValue::Set(screening_channel.into()),
+        questioning_role: ActiveValue::Set(questioning_role.id.into()),
+        questioning_category: ActiveValue::Set(questioning_category.into()),
+        mod_role: ActiveValue::Set(mod_role.id.into()),
+        mod_channel: ActiveValue::Set(mod_channel.into()),
+        member_role: ActiveValue::Set(member_role.id.into()),
+        main_channel: ActiveValue::Set(main_channel.into()),
+        helper_role: ActiveValue::Set(helper_role.map(|x| x.id.into())),
+        audit_channel: ActiveValue::Set(audit_channel.map(Into::into)),
+        appeal_contact: ActiveValue::Set(appeal_contact),
+        ..Default::default()
+    };
+    create_profile(ctx, guild, new_server).await?;
+
     ctx.send(|f| {
         f.content("Created server profile!")
-            .ephemeral(ctx.data().is_ephemeral)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await
     .map(|_| ())
     .map_err(Into::into)
 }
 
-#[derive(FromQueryResult)]
-struct UpdateServerData {
-    questioning_role: i64,
-    member_role: i64,
-    mod_role: i64,
+const SCREENING_CHANNEL_CHECK_LIMIT: u64 = 10;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Ask for confirmation if a prospective screening channel looks like it's in active use
+async fn confirm_screening_channel(
+    ctx: Context<'_>,
+    channel: serenity::ChannelId,
+) -> Result<bool, Error> {
+    // Forums hold posts rather than messages, and the entry form is added as its own post
+    if super::is_forum(ctx, channel).await? {
+        return Ok(true);
+    }
+
+    let user_messages = channel
+        .messages(ctx, |f| f.limit(SCREENING_CHANNEL_CHECK_LIMIT))
+        .await?
+        .into_iter()
+        .filter(|x| !x.author.bot)
+        .count();
+    if user_messages == 0 {
+        return Ok(true);
+    }
+
+    let msg = ctx
+        .send(|f| {
+            f.content(format!(
+                "Warning: the selected screening channel has {user_messages} user messages. Are you sure?"
+            ))
+            .components(|f| {
+                f.create_action_row(|f| {
+                    f.create_button(|f| {
+                        f.custom_id("confirm")
+                            .label("Confirm")
+                            .style(serenity::ButtonStyle::Danger)
+                    })
+                    .create_button(|f| {
+                        f.custom_id("cancel")
+                            .label("Cancel")
+                            .style(serenity::ButtonStyle::Secondary)
+                    })
+                })
+            })
+            .ephemeral(true)
+        })
+        .await?;
+
+    let response = msg
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(120))
+        .await;
+    msg.delete(ctx).await?;
+
+    let Some(response) = response else {
+        return Ok(false);
+    };
+    response.defer(ctx).await?;
+    Ok(response.data.custom_id == "confirm")
 }
 
 /// Update an existing server profile
@@ -280,13 +691,38 @@ struct UpdateServerData {
 async fn update(
     ctx: Context<'_>,
     #[channel_types("Text")] rules_channel: Option<serenity::GuildChannel>,
-    #[channel_types("Text")] screening_channel: Option<serenity::GuildChannel>,
+    #[channel_types("Text", "Forum")] screening_channel: Option<serenity::GuildChannel>,
     questioning_role: Option<serenity::Role>,
     #[channel_types("Category")] questioning_category: Option<serenity::Channel>,
     mod_role: Option<serenity::Role>,
     #[channel_types("Text")] mod_channel: Option<serenity::GuildChannel>,
     member_role: Option<serenity::Role>,
     #[channel_types("Text")] main_channel: Option<serenity::GuildChannel>,
+    #[description = "Whether bot responses are only visible to the command user"]
+    ephemeral_responses: Option<bool>,
+    #[description = "Role allowed to use screening commands"] helper_role: Option<serenity::Role>,
+    #[description = "Channel for configuration change notices"]
+    #[channel_types("Text")]
+    audit_channel: Option<serenity::GuildChannel>,
+    #[description = "Who kicked users can contact to appeal, e.g. an invite link or a mod's tag"]
+    appeal_contact: Option<String>,
+    #[description = "Whether to check image links in messages against the blocklist"]
+    filter_linked_images: Option<bool>,
+    #[description = "Entry form submissions allowed per user per day (0 for unlimited)"]
+    #[max = 100]
+    screening_daily_limit: Option<u8>,
+    #[description = "Whether previously accepted members get their member role back when they rejoin"]
+    restore_on_rejoin: Option<bool>,
+    #[description = "Whether rejoining members also get their other non-staff roles back"]
+    restore_all_roles: Option<bool>,
+    #[description = "How many days after leaving a member can still rejoin without screening"]
+    #[min = 1]
+    #[max = 365]
+    rejoin_window_days: Option<u16>,
+    #[description = "Minutes a questioned user waits for a reply before the mods are reminded"]
+    #[min = 1]
+    #[max = 1440]
+    questioning_bump_minutes: Option<u16>,
 ) -> Result<(), Error> {
     let guild = ctx
         .guild_id()
@@ -294,51 +730,122 @@ async fn update(
 
     check_admin!(ctx, guild);
 
+    let old_profile = require_profile!(ctx);
+
+    if let Some(x) = &screening_channel {
+        if !confirm_screening_channel(ctx, x.id).await? {
+            ctx.send(|f| {
+                f.content("Cancelled profile update.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        }
+    }
+
     let new_server = servers::ActiveModel {
-        id: ActiveValue::Unchanged(guild.as_u64().repack()),
+        id: ActiveValue::Unchanged(guild.into()),
         rules_channel: if let Some(x) = &rules_channel {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
         } else {
             ActiveValue::NotSet
         },
         screening_channel: if let Some(x) = &screening_channel {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
         } else {
             ActiveValue::NotSet
         },
         questioning_role: if let Some(x) = &questioning_role {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
         } else {
             ActiveValue::NotSet
         },
         questioning_category: if let Some(x) = &questioning_category {
-            ActiveValue::Set(x.id().as_u64().repack())
+            ActiveValue::Set(x.id().into())
         } else {
             ActiveValue::NotSet
         },
         mod_role: if let Some(x) = &mod_role {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
         } else {
             ActiveValue::NotSet
         },
         mod_channel: if let Some(x) = &mod_channel {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
         } else {
             ActiveValue::NotSet
         },
         member_role: if let Some(x) = &member_role {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
         } else {
             ActiveValue::NotSet
         },
         main_channel: if let Some(x) = &main_channel {
-            ActiveValue::Set(x.id.as_u64().repack())
+            ActiveValue::Set(x.id.into())
+        } else {
+            ActiveValue::NotSet
+        },
+        ephemeral_responses: if let Some(x) = ephemeral_responses {
+            ActiveValue::Set(x)
+        } else {
+            ActiveValue::NotSet
+        },
+        helper_role: if let Some(x) = &helper_role {
+            ActiveValue::Set(Some(x.id.into()))
+        } else {
+            ActiveValue::NotSet
+        },
+        audit_channel: if let Some(x) = &audit_channel {
+            ActiveValue::Set(Some(x.id.into()))
+        } else {
+            ActiveValue::NotSet
+        },
+        appeal_contact: if let Some(x) = appeal_contact {
+            ActiveValue::Set(Some(x))
+        } else {
+            ActiveValue::NotSet
+        },
+        filter_linked_images: if let Some(x) = filter_linked_images {
+            ActiveValue::Set(x)
+        } else {
+            ActiveValue::NotSet
+        },
+        screening_daily_limit: if let Some(x) = screening_daily_limit {
+            ActiveValue::Set(i8::try_from(x).ok().filter(|y| *y != 0))
+        } else {
+            ActiveValue::NotSet
+        },
+        restore_on_rejoin: if let Some(x) = restore_on_rejoin {
+            ActiveValue::Set(x)
+        } else {
+            ActiveValue::NotSet
+        },
+        restore_all_roles: if let Some(x) = restore_all_roles {
+            ActiveValue::Set(x)
+        } else {
+            ActiveValue::NotSet
+        },
+        rejoin_window_days: if let Some(x) = rejoin_window_days {
+            ActiveValue::Set(x.into())
+        } else {
+            ActiveValue::NotSet
+        },
+        questioning_bump_minutes: if let Some(x) = questioning_bump_minutes {
+            ActiveValue::Set(x.into())
         } else {
             ActiveValue::NotSet
         },
         ..Default::default()
     };
-    Servers::update(new_server).exec(&ctx.data().db).await?;
+    let changes = diff_profile(Some(&old_profile), &new_server);
+    let server_data = Servers::update(new_server).exec(&ctx.data().db).await?;
+
+    if let Some(x) = ephemeral_responses {
+        ctx.data().set_ephemeral_for(guild, x);
+    }
+    if let Some(x) = filter_linked_images {
+        ctx.data().set_filters_linked_images(guild, x);
+    }
 
     if let Some(x) = member_role {
         guild
@@ -348,33 +855,14 @@ async fn update(
             .await?;
     }
 
-    let server_data: UpdateServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::QuestioningRole)
-        .column(servers::Column::MemberRole)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
     let (questioning_role, member_role, mod_role) = (
-        serenity::RoleId(server_data.questioning_role.repack()),
-        serenity::RoleId(server_data.member_role.repack()),
-        serenity::RoleId(server_data.mod_role.repack()),
+        serenity::RoleId::from(server_data.questioning_role),
+        serenity::RoleId::from(server_data.member_role),
+        serenity::RoleId::from(server_data.mod_role),
     );
 
     let default_role = serenity::RoleId(guild.0); // @everyone has the same id as the guild
-    let default_perms = if let Some(x) = default_role.to_role_cached(ctx) {
-        x
-    } else {
-        guild
-            .roles(ctx)
-            .await?
-            .remove(&default_role)
-            .ok_or(super::FedBotError::new("role missing from guild"))?
-    }
-    .permissions;
+    let default_perms = role_permissions(ctx, guild, default_role).await?;
     guild
         .edit_role(ctx, default_role, |f| {
             f.permissions(default_perms & !serenity::Permissions::VIEW_CHANNEL)
@@ -418,11 +906,994 @@ async fn update(
         .await?;
     }
 
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Server profile updated", changes).await?;
+    }
+
+    // Clear any stale configuration warnings now that settings have changed
+    super::config_health::validate_guild(
+        ctx.serenity_context(),
+        &ctx.data().db,
+        &ctx.data().config_health,
+        ctx.data().bot_id,
+        guild,
+    )
+    .await?;
+
     ctx.send(|f| {
         f.content("Updated server profile!")
-            .ephemeral(ctx.data().is_ephemeral)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await
     .map(|_| ())
     .map_err(Into::into)
 }
+
+/// Set the message posted in the main channel when a user is accepted
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn set_welcome(
+    ctx: Context<'_>,
+    #[description = "Welcome message; {user} and {server} are replaced with the user and server"]
+    template: String,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let length = template.chars().count();
+    if length > MAX_MESSAGE_LENGTH {
+        ctx.send(|f| {
+            f.content(format!(
+                "Welcome messages must fit in one message ({MAX_MESSAGE_LENGTH} characters), but this one is {length} characters."
+            ))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.welcome_message_template = ActiveValue::Set(Some(template.clone()));
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Welcome message updated", changes).await?;
+    }
+
+    let preview = super::triggers::render_template(
+        &template,
+        ctx.author(),
+        &guild.name(ctx).unwrap_or_default(),
+    );
+    ctx.send(|f| {
+        f.content(format!(
+            "Updated welcome message. Preview:\n{}",
+            super::quote_content(&preview, MAX_MESSAGE_LENGTH)
+        ))
+        .allowed_mentions(|f| f.empty_parse())
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Set the explanation sent to users who post again right after a filter deletes their message
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn filter_followup(
+    ctx: Context<'_>,
+    #[description = "Explanation text, followed by a link to the rules channel (leave empty to turn off)"]
+    text: Option<String>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let text = text.filter(|x| !x.trim().is_empty());
+    let length = text.as_deref().map_or(0, |x| x.chars().count());
+    if length > MAX_MESSAGE_LENGTH {
+        ctx.send(|f| {
+            f.content(format!(
+                "Follow-up text must fit in one message ({MAX_MESSAGE_LENGTH} characters), but this one is {length} characters."
+            ))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.filter_followup_text = ActiveValue::Set(text.clone());
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Filter follow-up updated", changes).await?;
+    }
+
+    ctx.send(|f| {
+        f.content(if text.is_some() {
+            "Updated filter follow-up text."
+        } else {
+            "Turned off filter follow-ups."
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Hold join alerts and filter notices overnight, posting them as one digest when the window ends
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "quiethours")]
+async fn quiet_hours(
+    ctx: Context<'_>,
+    #[description = "Start of quiet hours as HH:MM (leave both times empty to turn off)"]
+    start: Option<String>,
+    #[description = "End of quiet hours as HH:MM, which may be after midnight"] end: Option<String>,
+    #[description = "Timezone the times are in (defaults to UTC)"]
+    #[autocomplete = "super::timezones::tz_autocomplete"]
+    timezone: Option<String>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let window = match (start, end) {
+        (None, None) => None,
+        (Some(start), Some(end)) => {
+            match (
+                super::quiet_hours::parse_minutes(&start),
+                super::quiet_hours::parse_minutes(&end),
+            ) {
+                (Some(start), Some(end)) if start != end => Some((start, end)),
+                _ => {
+                    ctx.send(|f| {
+                        f.content("Quiet hours need two different times written as HH:MM, e.g. `23:00` and `07:00`.")
+                            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+                    })
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+        _ => {
+            ctx.send(|f| {
+                f.content("Give both a start and an end time, or neither to turn quiet hours off.")
+                    .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+            })
+            .await?;
+            return Ok(());
+        }
+    };
+    let timezone = match timezone {
+        Some(x) => {
+            let Some(tz) = super::timezones::parse_tz(&x) else {
+                ctx.send(|f| {
+                    f.content("Unknown timezone.")
+                        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+                })
+                .await?;
+                return Ok(());
+            };
+            Some(tz.name().to_owned())
+        }
+        None => None,
+    };
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.quiet_hours_start = ActiveValue::Set(window.map(|x| x.0));
+    model.quiet_hours_end = ActiveValue::Set(window.map(|x| x.1));
+    model.quiet_hours_timezone = ActiveValue::Set(timezone.clone());
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Quiet hours updated", changes).await?;
+    }
+
+    ctx.send(|f| {
+        f.content(window.map_or_else(
+            || "Turned off quiet hours.".to_owned(),
+            |(start, end)| {
+                format!(
+                    "Join alerts and filter notices will be held from {} to {} ({}).",
+                    super::quiet_hours::format_minutes(start),
+                    super::quiet_hours::format_minutes(end),
+                    timezone.as_deref().unwrap_or("UTC")
+                )
+            },
+        ))
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Give an additional role mod permissions alongside the primary mod role
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn add_mod_role(ctx: Context<'_>, role: serenity::Role) -> Result<(), Error> {
+    use serenity::Mentionable;
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let profile = require_profile!(ctx);
+
+    if role.id == serenity::RoleId::from(profile.mod_role) {
+        ctx.send(|f| {
+            f.content("That role is already the primary mod role.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    GuildModRoles::insert(guild_mod_roles::ActiveModel {
+        guild_id: ActiveValue::Set(guild.into()),
+        role_id: ActiveValue::Set(role.id.into()),
+    })
+    .on_conflict(
+        sea_query::OnConflict::columns([
+            guild_mod_roles::Column::GuildId,
+            guild_mod_roles::Column::RoleId,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec_without_returning(&ctx.data().db)
+    .await?;
+
+    let default_role = serenity::RoleId(guild.0); // @everyone has the same id as the guild
+    channel_overrides::mod_channel(ctx, profile.mod_channel.into(), default_role, role.id).await?;
+    channel_overrides::questioning_category(
+        ctx,
+        profile.questioning_category.into(),
+        default_role,
+        profile.questioning_role.into(),
+        role.id,
+    )
+    .await?;
+
+    super::config_audit(
+        ctx,
+        guild,
+        "Mod role added",
+        vec![("Role".to_owned(), role.mention().to_string())],
+    )
+    .await?;
+
+    ctx.send(|f| {
+        f.content(format!("{} now counts as a mod role.", role.mention()))
+            .allowed_mentions(|f| f.empty_parse())
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Stop an additional role from counting as a mod role
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn remove_mod_role(ctx: Context<'_>, role: serenity::Role) -> Result<(), Error> {
+    use serenity::Mentionable;
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let profile = require_profile!(ctx);
+
+    let content = if role.id == serenity::RoleId::from(profile.mod_role) {
+        "The primary mod role can only be replaced with `/profile update`.".to_owned()
+    } else if GuildModRoles::delete_by_id((guild.into(), role.id.into()))
+        .exec(&ctx.data().db)
+        .await?
+        .rows_affected
+        == 0
+    {
+        format!("{} is not an additional mod role.", role.mention())
+    } else {
+        super::config_audit(
+            ctx,
+            guild,
+            "Mod role removed",
+            vec![("Role".to_owned(), role.mention().to_string())],
+        )
+        .await?;
+        format!("{} no longer counts as a mod role.", role.mention())
+    };
+
+    ctx.send(|f| {
+        f.content(content)
+            .allowed_mentions(|f| f.empty_parse())
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, poise::ChoiceParameter)]
+pub enum LogPurpose {
+    #[name = "Join alerts"]
+    JoinAlerts,
+    #[name = "Filter notices"]
+    FilterNotices,
+}
+
+impl From<LogPurpose> for guild_log_channels::Purpose {
+    fn from(x: LogPurpose) -> Self {
+        match x {
+            LogPurpose::JoinAlerts => Self::JoinAlerts,
+            LogPurpose::FilterNotices => Self::FilterNotices,
+        }
+    }
+}
+
+/// Send one kind of mod notice to its own channel instead of the mod channel
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn log_channel(
+    ctx: Context<'_>,
+    #[description = "Which notices to route"] purpose: LogPurpose,
+    #[description = "Channel for these notices (leave empty to use the mod channel again)"]
+    #[channel_types("Text")]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    use serenity::Mentionable;
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    require_profile!(ctx);
+
+    let key = (guild.into(), guild_log_channels::Purpose::from(purpose));
+    let destination = if let Some(x) = &channel {
+        GuildLogChannels::insert(guild_log_channels::ActiveModel {
+            guild_id: ActiveValue::Set(key.0),
+            purpose: ActiveValue::Set(key.1),
+            channel_id: ActiveValue::Set(x.id.into()),
+        })
+        .on_conflict(
+            sea_query::OnConflict::columns([
+                guild_log_channels::Column::GuildId,
+                guild_log_channels::Column::Purpose,
+            ])
+            .update_column(guild_log_channels::Column::ChannelId)
+            .to_owned(),
+        )
+        .exec_without_returning(&ctx.data().db)
+        .await?;
+        x.mention().to_string()
+    } else {
+        GuildLogChannels::delete_by_id(key)
+            .exec(&ctx.data().db)
+            .await?;
+        "the mod channel".to_owned()
+    };
+
+    super::config_audit(
+        ctx,
+        guild,
+        "Log channel updated",
+        vec![(purpose.name().to_owned(), destination.clone())],
+    )
+    .await?;
+
+    ctx.send(|f| {
+        f.content(format!(
+            "{} will now be sent to {}.",
+            purpose.name(),
+            destination
+        ))
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Show this server's profile settings
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn serverinfo(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let profile = require_profile!(ctx);
+
+    check_tier!(ctx, guild, PermissionTier::Mod, &profile);
+
+    let settings = servers::Column::iter()
+        .filter_map(|column| {
+            format_setting(column, &profile.get(column))
+                .map(|x| format!("**{}**: {}", column.to_string(), x))
+        })
+        .join("\n");
+    let entry_modal = match profile.entry_modal_updated_at {
+        Some(x) => format!("Entry modal last updated: <t:{}:R>", x.timestamp()),
+        None if profile.entry_modal.is_some() || profile.entry_modal_json.is_some() => {
+            "Entry modal configured (last update unknown)".to_owned()
+        }
+        None => "No entry modal configured".to_owned(),
+    };
+
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title("Server profile")
+                .description(settings)
+                .field("Entry modal", entry_modal, false)
+        })
+        .allowed_mentions(|f| f.empty_parse())
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct GuildSettings {
+    ephemeral_responses: bool,
+    filter_linked_images: bool,
+    max_emojis: Option<i32>,
+    max_attachments: Option<i32>,
+    max_stickers: Option<i32>,
+    profanity_filter_enabled: bool,
+    image_filter_enabled: bool,
+    triggers_enabled: bool,
+    screening_enabled: bool,
+    questioning_bumps_enabled: bool,
+    questioning_acks_enabled: bool,
+    strict_first_messages: bool,
+    first_message_threshold: i32,
+    nsfw_channel_policy: servers::NsfwChannelPolicy,
+}
+
+#[instrument(skip_all, err)]
+pub async fn add_guild_settings(
+    guild: &serenity::Guild,
+    is_new: bool,
+    reference: super::EventReference<'_>,
+) -> Result<(), super::Error> {
+    if is_new {
+        return Ok(());
+    }
+
+    if let Some(settings) = Servers::find_by_id(guild.id)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::EphemeralResponses)
+        .column(servers::Column::FilterLinkedImages)
+        .column(servers::Column::MaxEmojis)
+        .column(servers::Column::MaxAttachments)
+        .column(servers::Column::MaxStickers)
+        .column(servers::Column::ProfanityFilterEnabled)
+        .column(servers::Column::ImageFilterEnabled)
+        .column(servers::Column::TriggersEnabled)
+        .column(servers::Column::ScreeningEnabled)
+        .column(servers::Column::QuestioningBumpsEnabled)
+        .column(servers::Column::QuestioningAcksEnabled)
+        .column(servers::Column::StrictFirstMessages)
+        .column(servers::Column::FirstMessageThreshold)
+        .column(servers::Column::NsfwChannelPolicy)
+        .into_model::<GuildSettings>()
+        .one(&reference.3.db)
+        .await?
+    {
+        reference
+            .3
+            .set_ephemeral_for(guild.id, settings.ephemeral_responses);
+        reference
+            .3
+            .set_filters_linked_images(guild.id, settings.filter_linked_images);
+        reference.3.set_limits_for(
+            guild.id,
+            super::message_limits::MessageLimits {
+                emojis: settings.max_emojis,
+                attachments: settings.max_attachments,
+                stickers: settings.max_stickers,
+            },
+        );
+        reference.3.set_features_for(
+            guild.id,
+            super::features::Features {
+                profanity_filter: settings.profanity_filter_enabled,
+                image_filter: settings.image_filter_enabled,
+                triggers: settings.triggers_enabled,
+                screening: settings.screening_enabled,
+                questioning_bumps: settings.questioning_bumps_enabled,
+                questioning_acks: settings.questioning_acks_enabled,
+            },
+        );
+        reference.3.set_first_message_threshold(
+            guild.id,
+            settings
+                .strict_first_messages
+                .then_some(settings.first_message_threshold),
+        );
+        reference
+            .3
+            .set_nsfw_policy_for(guild.id, settings.nsfw_channel_policy);
+    }
+
+    Ok(())
+}
+
+const AUDIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// How one managed channel's live overwrites differ from what the profile sets up
+#[derive(Default)]
+struct OverwriteDrift {
+    /// Live overwrites with the missing bits merged in, so unrelated bits survive a repair
+    repairs: Vec<serenity::PermissionOverwrite>,
+    lines: Vec<String>,
+}
+
+fn overwrite_target(
+    kind: serenity::PermissionOverwriteType,
+    default_role: serenity::RoleId,
+) -> String {
+    use serenity::Mentionable;
+
+    match kind {
+        serenity::PermissionOverwriteType::Role(x) if x == default_role => "@everyone".to_owned(),
+        serenity::PermissionOverwriteType::Role(x) => x.mention().to_string(),
+        serenity::PermissionOverwriteType::Member(x) => x.mention().to_string(),
+        _ => "unknown".to_owned(),
+    }
+}
+
+fn permission_names(x: serenity::Permissions) -> String {
+    if x.is_empty() {
+        "nothing".to_owned()
+    } else {
+        x.get_permission_names().join(", ")
+    }
+}
+
+/// Compare `live` overwrites against `expected`, flagging overwrites for `managed` roles that
+/// setup never creates on this channel
+fn overwrite_drift(
+    expected: &[serenity::PermissionOverwrite],
+    live: &[serenity::PermissionOverwrite],
+    managed: &[serenity::RoleId],
+    default_role: serenity::RoleId,
+) -> OverwriteDrift {
+    let mut drift = OverwriteDrift::default();
+    for i in expected {
+        let current = live.iter().find(|x| x.kind == i.kind);
+        let (allow, deny) = current.map_or_else(
+            || {
+                (
+                    serenity::Permissions::empty(),
+                    serenity::Permissions::empty(),
+                )
+            },
+            |x| (x.allow, x.deny),
+        );
+        let (missing_allow, missing_deny) = (i.allow - allow, i.deny - deny);
+        if missing_allow.is_empty() && missing_deny.is_empty() {
+            continue;
+        }
+        let mut problems = vec![];
+        if current.is_none() {
+            problems.push("overwrite missing".to_owned());
+        }
+        if !missing_allow.is_empty() {
+            problems.push(format!("should allow {}", permission_names(missing_allow)));
+        }
+        if !missing_deny.is_empty() {
+            problems.push(format!("should deny {}", permission_names(missing_deny)));
+        }
+        drift.lines.push(format!(
+            "{}: {}",
+            overwrite_target(i.kind, default_role),
+            problems.join("; ")
+        ));
+        drift.repairs.push(serenity::PermissionOverwrite {
+            allow: (allow | i.allow) - i.deny,
+            deny: (deny | i.deny) - i.allow,
+            kind: i.kind,
+        });
+    }
+    for i in live {
+        let serenity::PermissionOverwriteType::Role(role) = i.kind else {
+            continue;
+        };
+        if managed.contains(&role) && !expected.iter().any(|x| x.kind == i.kind) {
+            drift.lines.push(format!(
+                "{}: unexpected overwrite (allows {}, denies {})",
+                overwrite_target(i.kind, default_role),
+                permission_names(i.allow),
+                permission_names(i.deny)
+            ));
+        }
+    }
+    drift
+}
+
+/// Expected overwrites for each of `mod_roles`, keeping the ones they share only once
+fn for_mod_roles(
+    mod_roles: &[serenity::RoleId],
+    expected: impl Fn(serenity::RoleId) -> Vec<serenity::PermissionOverwrite>,
+) -> Vec<serenity::PermissionOverwrite> {
+    let mut overwrites: Vec<serenity::PermissionOverwrite> = vec![];
+    for i in mod_roles.iter().flat_map(|x| expected(*x)) {
+        if !overwrites.iter().any(|x| x.kind == i.kind) {
+            overwrites.push(i);
+        }
+    }
+    overwrites
+}
+
+/// Live overwrites on `channel`, and whether it is a forum
+async fn live_overwrites(
+    ctx: Context<'_>,
+    channel: serenity::ChannelId,
+) -> Result<(Vec<serenity::PermissionOverwrite>, bool), Error> {
+    Ok(match channel.to_channel(ctx).await? {
+        serenity::Channel::Guild(x) => (
+            x.permission_overwrites,
+            x.kind == serenity::ChannelType::Forum,
+        ),
+        serenity::Channel::Category(x) => (x.permission_overwrites, false),
+        _ => return Err(super::FedBotError::new("managed channel is not in a server").into()),
+    })
+}
+
+/// Mention of a configured channel or role, flagged if it no longer exists
+fn configured_entity(mention: impl std::fmt::Display, exists: bool) -> String {
+    if exists {
+        mention.to_string()
+    } else {
+        format!("⚠️ {mention} (missing)")
+    }
+}
+
+/// Count of items in an optional blob, or a warning if it can't be read
+fn blob_count<T>(raw: Option<&[u8]>, decode: impl Fn(&[u8]) -> Result<Vec<T>, Error>) -> String {
+    raw.map_or(Ok(0), |x| decode(x).map(|y| y.len()))
+        .map_or_else(|_| "⚠️ Unreadable".to_owned(), |x| x.to_string())
+}
+
+/// Show the channels, roles and screening setup the bot is currently configured with
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    use serenity::Mentionable;
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let profile = require_profile!(ctx);
+
+    crate::defer!(ctx);
+
+    let channels = guild.channels(ctx).await?;
+    let roles = guild.roles(ctx).await?;
+    let channel = |x: Option<ids::DbChannelId>| {
+        x.map(serenity::ChannelId::from).map_or_else(
+            || "*none*".to_owned(),
+            |y| configured_entity(y.mention(), channels.contains_key(&y)),
+        )
+    };
+    let role = |x: Option<ids::DbRoleId>| {
+        x.map(serenity::RoleId::from).map_or_else(
+            || "*none*".to_owned(),
+            |y| configured_entity(y.mention(), roles.contains_key(&y)),
+        )
+    };
+
+    let entry_modal = match entry_modal::parse_entry_modal(
+        profile.entry_modal_json.as_deref(),
+        profile.entry_modal.as_deref(),
+    ) {
+        Ok(x) => match x.0.values().exactly_one() {
+            Ok(y) => format!("Set ({} inputs)", y.inputs.0.len()),
+            Err(_) if x.0.is_empty() => "Not set".to_owned(),
+            Err(_) => format!("Set in {} languages ({})", x.0.len(), x.0.keys().join(", ")),
+        },
+        Err(_) => "⚠️ Unreadable".to_owned(),
+    };
+    let blocked_images = blob_count(
+        profile.blocked_images.as_deref(),
+        super::serialization::decode_blocked_images,
+    );
+    let nsfw_channels = match profile.nsfw_channel_policy {
+        servers::NsfwChannelPolicy::Enforce => "Filtered".to_owned(),
+        servers::NsfwChannelPolicy::Skip => "Not filtered".to_owned(),
+        servers::NsfwChannelPolicy::Separate => format!(
+            "Separate blocklist ({} images)",
+            blob_count(
+                profile.nsfw_blocked_images.as_deref(),
+                super::serialization::decode_blocked_images,
+            )
+        ),
+    };
+    let triggers = blob_count(profile.triggers.as_deref(), |x| {
+        super::serialization::decode_triggers(x).map(|y| y.into_keys().collect())
+    });
+
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title("Server profile")
+                .field("Rules channel", channel(Some(profile.rules_channel)), true)
+                .field(
+                    "Screening channel",
+                    channel(Some(profile.screening_channel)),
+                    true,
+                )
+                .field("Main channel", channel(Some(profile.main_channel)), true)
+                .field("Mod channel", channel(Some(profile.mod_channel)), true)
+                .field("Audit channel", channel(profile.audit_channel), true)
+                .field(
+                    "Questioning category",
+                    channel(Some(profile.questioning_category)),
+                    true,
+                )
+                .field("Mod role", role(Some(profile.mod_role)), true)
+                .field("Helper role", role(profile.helper_role), true)
+                .field("Member role", role(Some(profile.member_role)), true)
+                .field(
+                    "Questioning role",
+                    role(Some(profile.questioning_role)),
+                    true,
+                )
+                .field("Entry modal", entry_modal, true)
+                .field("Blocked images", blocked_images, true)
+                .field("NSFW channels", nsfw_channels, true)
+                .field("Triggers", triggers, true)
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Check that the channel permissions set up by the profile are still in place
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn audit(ctx: Context<'_>) -> Result<(), Error> {
+    use serenity::Mentionable;
+
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let profile = require_profile!(ctx);
+
+    crate::defer!(ctx);
+
+    let default_role = serenity::RoleId(guild.0); // @everyone has the same id as the guild
+    let (mod_role, member_role, questioning_role) = (
+        serenity::RoleId::from(profile.mod_role),
+        serenity::RoleId::from(profile.member_role),
+        serenity::RoleId::from(profile.questioning_role),
+    );
+    let mod_roles = super::mod_roles(&ctx.data().db, guild, mod_role).await?;
+    let managed = [default_role, member_role, questioning_role]
+        .into_iter()
+        .chain(mod_roles.iter().copied())
+        .collect::<Vec<_>>();
+
+    let mut drifts = vec![];
+    for (label, channel) in [
+        ("Rules channel", profile.rules_channel),
+        ("Screening channel", profile.screening_channel),
+        ("Mod channel", profile.mod_channel),
+        ("Questioning category", profile.questioning_category),
+    ] {
+        let channel = serenity::ChannelId::from(channel);
+        let (live, is_forum) = live_overwrites(ctx, channel).await?;
+        // Extra mod roles get the same mod channel and questioning access as the primary one
+        let expected = match label {
+            "Rules channel" => channel_overrides::expected::rules_channel(default_role),
+            "Screening channel" => channel_overrides::expected::screening_channel(
+                is_forum,
+                default_role,
+                mod_role,
+                member_role,
+                questioning_role,
+            ),
+            "Mod channel" => for_mod_roles(&mod_roles, |x| {
+                channel_overrides::expected::mod_channel(default_role, x)
+            }),
+            _ => for_mod_roles(&mod_roles, |x| {
+                channel_overrides::expected::questioning_category(default_role, questioning_role, x)
+            }),
+        };
+        let drift = overwrite_drift(&expected, &live, &managed, default_role);
+        if !drift.lines.is_empty() {
+            drifts.push((label, channel, drift));
+        }
+    }
+
+    let repair_count = drifts.iter().map(|x| x.2.repairs.len()).sum::<usize>();
+    // Component IDs are keyed by the invoking interaction
+    let repair_id = format!("{}-repair", ctx.id());
+    let msg = ctx
+        .send(|f| {
+            f.embed(|f| {
+                f.title("Permission audit");
+                if drifts.is_empty() {
+                    f.description("All managed channel permissions match the profile.");
+                }
+                for (label, channel, drift) in &drifts {
+                    let value = super::chunk_lines(
+                        &[format!("{}\n{}", channel.mention(), drift.lines.join("\n"))],
+                        super::MAX_EMBED_FIELD_LENGTH,
+                    );
+                    f.field(label, value.concat(), false);
+                }
+                f
+            })
+            .components(|f| {
+                if repair_count > 0 {
+                    f.create_action_row(|f| {
+                        f.create_button(|f| {
+                            f.custom_id(&repair_id)
+                                .label(format!("Repair ({repair_count})"))
+                                .style(serenity::ButtonStyle::Danger)
+                        })
+                    });
+                }
+                f
+            })
+            .allowed_mentions(|f| f.empty_parse())
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    if repair_count == 0 {
+        return Ok(());
+    }
+
+    let Some(x) = msg
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .filter(move |x| x.data.custom_id == repair_id)
+        .timeout(AUDIT_TIMEOUT)
+        .await
+    else {
+        // The interaction token may have expired by now
+        _ = super::t(msg.edit(ctx, |f| f.components(|f| f)).await);
+        return Ok(());
+    };
+    x.create_interaction_response(ctx, |f| {
+        f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+    })
+    .await?;
+
+    for (_, channel, drift) in &drifts {
+        channel_overrides::apply(ctx, *channel, &drift.repairs).await?;
+    }
+    super::mod_log(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild,
+        None,
+        format!(
+            "Repaired {} permission overwrites in {} by {}",
+            repair_count,
+            drifts.iter().map(|x| x.1.mention()).format(", "),
+            ctx.author().mention()
+        ),
+    )
+    .await?;
+    msg.edit(ctx, |f| {
+        f.content(format!("Repaired {repair_count} permission overwrites."))
+            .components(|f| f)
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_entities_and_unreadable_blobs_are_flagged() {
+        assert_eq!(configured_entity("<#1>", true), "<#1>");
+        assert_eq!(configured_entity("<#1>", false), "⚠️ <#1> (missing)");
+        let decode = |x: &[u8]| -> Result<Vec<u8>, Error> {
+            if x.is_empty() {
+                Err(crate::ext::FedBotError::new("empty").into())
+            } else {
+                Ok(x.to_vec())
+            }
+        };
+        assert_eq!(blob_count(None, decode), "0");
+        assert_eq!(blob_count(Some(&[1, 2]), decode), "2");
+        assert_eq!(blob_count(Some(&[]), decode), "⚠️ Unreadable");
+    }
+
+    #[test]
+    fn channel_ids_and_mentions_parse() {
+        assert_eq!(parse_channel_id(" 1234 "), Some(serenity::ChannelId(1234)));
+        assert_eq!(parse_channel_id("<#1234>"), Some(serenity::ChannelId(1234)));
+        assert_eq!(parse_channel_id("general"), None);
+        assert_eq!(parse_channel_id("<@1234>"), None);
+    }
+
+    #[test]
+    fn drift_repairs_keep_unrelated_bits() {
+        let (everyone, mods, members) = (
+            serenity::RoleId(1),
+            serenity::RoleId(2),
+            serenity::RoleId(3),
+        );
+        let expected = channel_overrides::expected::mod_channel(everyone, mods);
+        let live = vec![
+            serenity::PermissionOverwrite {
+                allow: serenity::Permissions::SEND_MESSAGES,
+                deny: serenity::Permissions::VIEW_CHANNEL,
+                kind: serenity::PermissionOverwriteType::Role(mods),
+            },
+            serenity::PermissionOverwrite {
+                allow: serenity::Permissions::empty(),
+                deny: serenity::Permissions::VIEW_CHANNEL,
+                kind: serenity::PermissionOverwriteType::Role(everyone),
+            },
+            serenity::PermissionOverwrite {
+                allow: serenity::Permissions::VIEW_CHANNEL,
+                deny: serenity::Permissions::empty(),
+                kind: serenity::PermissionOverwriteType::Role(members),
+            },
+        ];
+        let drift = overwrite_drift(&expected, &live, &[everyone, mods, members], everyone);
+        assert_eq!(
+            drift.lines,
+            [
+                "<@&2>: should allow View Channel",
+                "<@&3>: unexpected overwrite (allows View Channel, denies nothing)"
+            ]
+        );
+        let [repair] = drift.repairs.as_slice() else {
+            panic!("expected one repair");
+        };
+        assert_eq!(
+            repair.allow,
+            serenity::Permissions::SEND_MESSAGES | serenity::Permissions::VIEW_CHANNEL
+        );
+        assert!(repair.deny.is_empty());
+
+        assert!(
+            overwrite_drift(&expected, &expected, &[everyone, mods], everyone)
+                .lines
+                .is_empty()
+        );
+    }
+}
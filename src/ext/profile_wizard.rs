@@ -0,0 +1,436 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{t, ContainBytes, Context, Error};
+use crate::{
+    check_admin,
+    entities::{prelude::*, *},
+};
+use futures_lite::stream::StreamExt;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+
+const WIZARD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+const MAX_SELECT_OPTIONS: usize = 25;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingKind {
+    TextChannel,
+    Category,
+    Role,
+}
+
+struct WizardStep {
+    column: servers::Column,
+    label: &'static str,
+    kind: SettingKind,
+    default_name: &'static str,
+    optional: bool,
+}
+
+const WIZARD_STEPS: [WizardStep; 10] = [
+    WizardStep {
+        column: servers::Column::RulesChannel,
+        label: "rules channel",
+        kind: SettingKind::TextChannel,
+        default_name: "rules",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::ScreeningChannel,
+        label: "screening channel",
+        kind: SettingKind::TextChannel,
+        default_name: "screening",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::QuestioningRole,
+        label: "questioning role",
+        kind: SettingKind::Role,
+        default_name: "Questioning",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::QuestioningCategory,
+        label: "questioning category",
+        kind: SettingKind::Category,
+        default_name: "Questioning",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::ModRole,
+        label: "mod role",
+        kind: SettingKind::Role,
+        default_name: "Mod",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::ModChannel,
+        label: "mod channel",
+        kind: SettingKind::TextChannel,
+        default_name: "mod-channel",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::MemberRole,
+        label: "member role",
+        kind: SettingKind::Role,
+        default_name: "Member",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::MainChannel,
+        label: "main channel",
+        kind: SettingKind::TextChannel,
+        default_name: "general",
+        optional: false,
+    },
+    WizardStep {
+        column: servers::Column::HelperRole,
+        label: "helper role",
+        kind: SettingKind::Role,
+        default_name: "Helper",
+        optional: true,
+    },
+    WizardStep {
+        column: servers::Column::AuditChannel,
+        label: "audit channel",
+        kind: SettingKind::TextChannel,
+        default_name: "audit-log",
+        optional: true,
+    },
+];
+
+fn mention_setting(kind: SettingKind, id: Option<u64>) -> String {
+    match (kind, id) {
+        (_, None) => "*none*".to_owned(),
+        (SettingKind::Role, Some(x)) => serenity::RoleId(x).mention().to_string(),
+        (_, Some(x)) => serenity::ChannelId(x).mention().to_string(),
+    }
+}
+
+struct Wizard {
+    // Component IDs are keyed by the invoking interaction
+    prefix: String,
+    step: usize,
+    values: [Option<u64>; WIZARD_STEPS.len()],
+    options: Vec<(u64, String)>,
+}
+
+impl Wizard {
+    fn id(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    /// Refresh the select menu choices for the current step, keeping the current value visible
+    async fn load_options(
+        &mut self,
+        ctx: Context<'_>,
+        guild: serenity::GuildId,
+    ) -> Result<(), Error> {
+        let Some(step) = WIZARD_STEPS.get(self.step) else {
+            self.options = vec![];
+            return Ok(());
+        };
+        let mut options = match step.kind {
+            SettingKind::TextChannel | SettingKind::Category => {
+                let wanted = if step.kind == SettingKind::Category {
+                    serenity::ChannelType::Category
+                } else {
+                    serenity::ChannelType::Text
+                };
+                guild
+                    .channels(ctx)
+                    .await?
+                    .into_values()
+                    .filter(|x| x.kind == wanted)
+                    .sorted_by_key(|x| x.position)
+                    .map(|x| (x.id.0, x.name))
+                    .collect::<Vec<_>>()
+            }
+            SettingKind::Role => guild
+                .roles(ctx)
+                .await?
+                .into_values()
+                // @everyone has the same id as the guild
+                .filter(|x| x.id.0 != guild.0 && !x.managed)
+                .sorted_by_key(|x| std::cmp::Reverse(x.position))
+                .map(|x| (x.id.0, x.name))
+                .collect::<Vec<_>>(),
+        };
+        if let Some(pos) = self.values[self.step]
+            .and_then(|x| options.iter().position(|y| y.0 == x))
+            .filter(|x| *x >= MAX_SELECT_OPTIONS)
+        {
+            let selected = options.remove(pos);
+            options.insert(0, selected);
+        }
+        options.truncate(MAX_SELECT_OPTIONS);
+        self.options = options;
+        Ok(())
+    }
+
+    fn to_model(&self, guild: serenity::GuildId) -> Result<servers::ActiveModel, Error> {
+        let mut model = servers::ActiveModel {
+            id: ActiveValue::Set(guild.into()),
+            ..Default::default()
+        };
+        for (step, value) in WIZARD_STEPS.iter().zip(self.values) {
+            match value {
+                Some(x) => model.set(step.column, x.repack().into()),
+                None if step.optional => model.set(step.column, Value::BigInt(None)),
+                None => return Err(super::FedBotError::new("missing required setting").into()),
+            }
+        }
+        Ok(model)
+    }
+
+    fn render<'a, 'b>(&self, f: &'b mut poise::CreateReply<'a>) -> &'b mut poise::CreateReply<'a> {
+        let Some(step) = WIZARD_STEPS.get(self.step) else {
+            return f
+                .content("Review your settings, then confirm to create the server profile.")
+                .embed(|f| {
+                    f.title("Server profile");
+                    for (step, value) in WIZARD_STEPS.iter().zip(self.values) {
+                        f.field(step.label, mention_setting(step.kind, value), true);
+                    }
+                    f
+                })
+                .components(|f| {
+                    f.create_action_row(|f| {
+                        f.create_button(|f| f.custom_id(self.id("back")).label("Back"))
+                            .create_button(|f| {
+                                f.custom_id(self.id("confirm"))
+                                    .label("Confirm")
+                                    .style(serenity::ButtonStyle::Success)
+                            })
+                            .create_button(|f| {
+                                f.custom_id(self.id("cancel"))
+                                    .label("Cancel")
+                                    .style(serenity::ButtonStyle::Danger)
+                            })
+                    })
+                });
+        };
+
+        let value = self.values[self.step];
+        f.content(format!(
+            "**Step {}/{}:** {}\nCurrent: {}",
+            self.step + 1,
+            WIZARD_STEPS.len(),
+            if self.options.is_empty() {
+                format!(
+                    "No {} yet — create one named '{}'?",
+                    step.label, step.default_name
+                )
+            } else {
+                format!(
+                    "Select the {}{}.",
+                    step.label,
+                    if step.optional { " (optional)" } else { "" }
+                )
+            },
+            mention_setting(step.kind, value)
+        ))
+        .components(|f| {
+            if !self.options.is_empty() {
+                f.create_action_row(|f| {
+                    f.create_select_menu(|f| {
+                        f.custom_id(self.id("select"))
+                            .placeholder(step.label)
+                            .options(|f| {
+                                for (id, name) in &self.options {
+                                    f.create_option(|f| {
+                                        f.label(name)
+                                            .value(id)
+                                            .default_selection(value == Some(*id))
+                                    });
+                                }
+                                f
+                            })
+                    })
+                });
+            }
+            f.create_action_row(|f| {
+                f.create_button(|f| {
+                    f.custom_id(self.id("back"))
+                        .label("Back")
+                        .disabled(self.step == 0)
+                })
+                .create_button(|f| {
+                    f.custom_id(self.id("next"))
+                        .label("Next")
+                        .style(serenity::ButtonStyle::Primary)
+                        .disabled(!step.optional && value.is_none())
+                })
+                .create_button(|f| {
+                    f.custom_id(self.id("skip"))
+                        .label("Skip")
+                        .disabled(!step.optional)
+                })
+                .create_button(|f| {
+                    f.custom_id(self.id("create"))
+                        .label(format!("Create '{}'", step.default_name))
+                        .style(serenity::ButtonStyle::Success)
+                })
+                .create_button(|f| {
+                    f.custom_id(self.id("cancel"))
+                        .label("Cancel")
+                        .style(serenity::ButtonStyle::Danger)
+                })
+            })
+        })
+    }
+}
+
+async fn create_setting(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    step: &WizardStep,
+) -> Result<u64, Error> {
+    Ok(match step.kind {
+        SettingKind::TextChannel => {
+            guild
+                .create_channel(ctx, |f| {
+                    f.name(step.default_name).kind(serenity::ChannelType::Text)
+                })
+                .await?
+                .id
+                .0
+        }
+        SettingKind::Category => {
+            guild
+                .create_channel(ctx, |f| {
+                    f.name(step.default_name)
+                        .kind(serenity::ChannelType::Category)
+                })
+                .await?
+                .id
+                .0
+        }
+        SettingKind::Role => {
+            guild
+                .create_role(ctx, |f| f.name(step.default_name))
+                .await?
+                .id
+                .0
+        }
+    })
+}
+
+/// Create a server profile step by step
+#[tracing::instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn wizard(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    if Servers::find_by_id(guild)
+        .one(&ctx.data().db)
+        .await?
+        .is_some()
+    {
+        let update_command = super::mention_command(ctx, "profile update").await?;
+        ctx.send(|f| {
+            f.content(format!(
+                "This server already has a profile. Use {update_command} to change it."
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let mut wizard = Wizard {
+        prefix: format!("{}-", ctx.id()),
+        step: 0,
+        values: [None; WIZARD_STEPS.len()],
+        options: vec![],
+    };
+    wizard.load_options(ctx, guild).await?;
+
+    let msg = ctx.send(|f| wizard.render(f).ephemeral(true)).await?;
+    let mut collector = msg
+        .message()
+        .await?
+        .await_component_interactions(ctx)
+        .author_id(ctx.author().id)
+        .timeout(WIZARD_TIMEOUT)
+        .build();
+
+    let mut new_server = None;
+    while let Some(x) = collector.next().await {
+        x.create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+        let Some(action) = x.data.custom_id.strip_prefix(&wizard.prefix) else {
+            continue;
+        };
+        match action {
+            "select" => {
+                wizard.values[wizard.step] = x.data.values.first().and_then(|y| y.parse().ok());
+            }
+            "back" => {
+                wizard.step = wizard.step.saturating_sub(1);
+                wizard.load_options(ctx, guild).await?;
+            }
+            "next" => {
+                wizard.step += 1;
+                wizard.load_options(ctx, guild).await?;
+            }
+            "skip" => {
+                wizard.values[wizard.step] = None;
+                wizard.step += 1;
+                wizard.load_options(ctx, guild).await?;
+            }
+            "create" => {
+                if let Some(step) = WIZARD_STEPS.get(wizard.step) {
+                    wizard.values[wizard.step] = Some(create_setting(ctx, guild, step).await?);
+                    wizard.load_options(ctx, guild).await?;
+                }
+            }
+            "confirm" => {
+                new_server = Some(wizard.to_model(guild)?);
+                break;
+            }
+            "cancel" => break,
+            _ => (),
+        }
+        msg.edit(ctx, |f| wizard.render(f)).await?;
+    }
+    // The interaction token may have expired if the wizard timed out
+    _ = t(msg.delete(ctx).await);
+
+    if let Some(new_server) = new_server {
+        super::profile_setup::create_profile(ctx, guild, new_server).await?;
+        ctx.send(|f| {
+            f.content("Created server profile!")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    } else {
+        _ = t(ctx
+            .send(|f| f.content("Profile setup cancelled.").ephemeral(true))
+            .await);
+    }
+    Ok(())
+}
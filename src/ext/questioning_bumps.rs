@@ -0,0 +1,347 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Let questioned users know they haven't been forgotten: remind the mods when a user has been
+//! waiting for a reply, and mark the user's message once staff answer
+
+use super::{Error, EventReference};
+use crate::entities::{prelude::*, *};
+use dashmap::{DashMap, DashSet};
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use serenity::Mentionable;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, instrument};
+
+/// Unanswered stretches older than this are forgotten, so a long-abandoned channel can remind
+/// the mods again
+const STALE_AFTER: Duration = Duration::from_secs(24 * 3600);
+/// Channels beyond this are not tracked, so a raid can't grow the map unbounded
+const MAX_PENDING: usize = 1000;
+const ACK_EMOJI: char = '✅';
+
+/// A questioned user's messages that no staff member has replied to yet
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    since: Instant,
+    user: serenity::UserId,
+    last_message: serenity::MessageId,
+    bumped: bool,
+}
+
+/// Questioning channels waiting on a staff reply
+#[derive(Default, Clone)]
+pub struct QuestioningBumps {
+    pending: Arc<DashMap<serenity::ChannelId, Pending>>,
+    /// Channels that may hold open sessions, so other channels skip the session lookup
+    channels: Arc<DashSet<serenity::ChannelId>>,
+}
+
+impl QuestioningBumps {
+    /// Start out watching every channel with an open session
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, Error> {
+        let bumps = Self::default();
+        for x in QuestioningSessions::find()
+            .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+            .all(db)
+            .await?
+        {
+            bumps.channels.insert(x.channel_id.into());
+        }
+        Ok(bumps)
+    }
+
+    /// Look for questioned users in `channel` once a session opens there
+    pub fn watch(&self, channel: serenity::ChannelId) {
+        self.channels.insert(channel);
+    }
+
+    /// Stop looking at `channel` once its sessions are closed
+    pub fn unwatch(&self, channel: serenity::ChannelId) {
+        self.channels.remove(&channel);
+        self.pending.remove(&channel);
+    }
+
+    /// Note a questioned user's message, returning when the wait started if this message
+    /// started it
+    fn user_posted(
+        &self,
+        channel: serenity::ChannelId,
+        user: serenity::UserId,
+        message: serenity::MessageId,
+    ) -> Option<Instant> {
+        if let Some(mut x) = self.pending.get_mut(&channel) {
+            x.last_message = message;
+            return None;
+        }
+        if self.pending.len() >= MAX_PENDING {
+            self.clean();
+            if self.pending.len() >= MAX_PENDING {
+                return None;
+            }
+        }
+        let since = Instant::now();
+        self.pending.insert(
+            channel,
+            Pending {
+                since,
+                user,
+                last_message: message,
+                bumped: false,
+            },
+        );
+        Some(since)
+    }
+
+    /// End the wait in `channel`, returning the last message that went unanswered
+    fn staff_posted(&self, channel: serenity::ChannelId) -> Option<serenity::MessageId> {
+        self.pending.remove(&channel).map(|(_, x)| x.last_message)
+    }
+
+    /// Mark the wait that started at `since` as bumped, returning who is waiting if it still
+    /// needs a bump
+    fn take_due(&self, channel: serenity::ChannelId, since: Instant) -> Option<serenity::UserId> {
+        let mut pending = self.pending.get_mut(&channel)?;
+        if pending.since != since || pending.bumped {
+            return None;
+        }
+        pending.bumped = true;
+        Some(pending.user)
+    }
+
+    /// Remove stale waits, returning how many were removed
+    pub fn clean(&self) -> usize {
+        let mut removed = 0;
+        self.pending.retain(|_, x| {
+            let stale = x.since.elapsed() >= STALE_AFTER;
+            removed += usize::from(stale);
+            !stale
+        });
+        removed
+    }
+}
+
+#[derive(FromQueryResult)]
+struct BumpData {
+    mod_role: ids::DbRoleId,
+    questioning_bump_minutes: i32,
+}
+
+/// Track replies in questioning channels, reminding the mods about users left waiting and
+/// acknowledging the user once staff reply
+#[instrument(skip_all, err)]
+pub async fn message_sent(
+    message: &serenity::Message,
+    guild: serenity::GuildId,
+    reference: EventReference<'_>,
+) -> Result<(), Error> {
+    let (ctx, data) = (reference.0, reference.3);
+    let features = data.features_for(guild);
+    if message.author.bot
+        || !(features.questioning_bumps || features.questioning_acks)
+        || !data
+            .questioning_bumps
+            .channels
+            .contains(&message.channel_id)
+    {
+        return Ok(());
+    }
+    let sessions = super::user_screening::open_sessions(&data.db, message.channel_id).await?;
+    if sessions.is_empty() {
+        return Ok(());
+    }
+
+    // Anyone else who can see a questioning channel is staff
+    if !sessions
+        .iter()
+        .any(|x| serenity::UserId::from(x.user_id) == message.author.id)
+    {
+        if let Some(last) = data.questioning_bumps.staff_posted(message.channel_id) {
+            if features.questioning_acks {
+                message
+                    .channel_id
+                    .create_reaction(ctx, last, serenity::ReactionType::Unicode(ACK_EMOJI.into()))
+                    .await?;
+            }
+        }
+        return Ok(());
+    }
+
+    let Some(since) =
+        data.questioning_bumps
+            .user_posted(message.channel_id, message.author.id, message.id)
+    else {
+        return Ok(());
+    };
+    if !features.questioning_bumps {
+        return Ok(());
+    }
+    let Some(server_data) = Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::ModRole)
+        .column(servers::Column::QuestioningBumpMinutes)
+        .into_model::<BumpData>()
+        .one(&data.db)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let (ctx, db, health, bumps) = (
+        ctx.clone(),
+        data.db.clone(),
+        data.config_health.clone(),
+        data.questioning_bumps.clone(),
+    );
+    let channel = message.channel_id;
+    tokio::spawn(async move {
+        let minutes = u64::try_from(server_data.questioning_bump_minutes).unwrap_or(1);
+        tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        let Some(user) = bumps.take_due(channel, since) else {
+            return Ok(());
+        };
+        bump(
+            &ctx,
+            &db,
+            &health,
+            guild,
+            channel,
+            user,
+            server_data.mod_role.into(),
+            minutes,
+        )
+        .await
+    });
+    Ok(())
+}
+
+/// Remind the mods that `user` is still waiting in `channel`
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, err)]
+async fn bump(
+    ctx: &serenity::Context,
+    db: &DatabaseConnection,
+    health: &super::config_health::ConfigHealth,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    user: serenity::UserId,
+    mod_role: serenity::RoleId,
+    minutes: u64,
+) -> Result<(), Error> {
+    let Some(mod_channel) = super::route_mod_channel(db, health, guild, None).await? else {
+        return Ok(());
+    };
+    let msg = format!(
+        "{}, user {} has been waiting {}m in questioning: {}",
+        mod_role.mention(),
+        user.mention(),
+        minutes,
+        channel.mention()
+    );
+    if super::quiet_hours::hold(db, guild, mod_channel, None, &msg).await? {
+        return Ok(());
+    }
+    mod_channel
+        .send_message(ctx, |f| {
+            f.content(msg)
+                .allowed_mentions(|f| f.empty_users().roles(vec![mod_role]))
+        })
+        .await?;
+    info!(
+        "Reminded mods of guild '{}' about user '{}' waiting in questioning",
+        guild, user
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNEL: serenity::ChannelId = serenity::ChannelId(1);
+    const USER: serenity::UserId = serenity::UserId(2);
+
+    #[test]
+    fn only_the_first_unanswered_message_starts_a_wait() {
+        let bumps = QuestioningBumps::default();
+        let since = bumps.user_posted(CHANNEL, USER, serenity::MessageId(3));
+        assert!(since.is_some());
+        assert!(bumps
+            .user_posted(CHANNEL, USER, serenity::MessageId(4))
+            .is_none());
+        assert_eq!(bumps.staff_posted(CHANNEL), Some(serenity::MessageId(4)));
+        assert_eq!(bumps.staff_posted(CHANNEL), None);
+    }
+
+    #[test]
+    fn waits_are_bumped_once() {
+        let bumps = QuestioningBumps::default();
+        let since = bumps
+            .user_posted(CHANNEL, USER, serenity::MessageId(3))
+            .unwrap();
+        assert_eq!(bumps.take_due(CHANNEL, since), Some(USER));
+        assert_eq!(bumps.take_due(CHANNEL, since), None);
+        bumps.user_posted(CHANNEL, USER, serenity::MessageId(4));
+        assert_eq!(bumps.take_due(CHANNEL, since), None);
+    }
+
+    #[test]
+    fn answered_waits_are_not_bumped() {
+        let bumps = QuestioningBumps::default();
+        let since = bumps
+            .user_posted(CHANNEL, USER, serenity::MessageId(3))
+            .unwrap();
+        bumps.staff_posted(CHANNEL);
+        assert_eq!(bumps.take_due(CHANNEL, since), None);
+        // A new wait after the reply gets its own timer
+        let next = bumps
+            .user_posted(CHANNEL, USER, serenity::MessageId(5))
+            .unwrap();
+        assert_eq!(bumps.take_due(CHANNEL, since), None);
+        assert_eq!(bumps.take_due(CHANNEL, next), Some(USER));
+    }
+
+    #[test]
+    fn stale_waits_are_cleaned() {
+        let bumps = QuestioningBumps::default();
+        bumps.pending.insert(
+            CHANNEL,
+            Pending {
+                since: Instant::now() - STALE_AFTER,
+                user: USER,
+                last_message: serenity::MessageId(3),
+                bumped: true,
+            },
+        );
+        bumps.user_posted(serenity::ChannelId(4), USER, serenity::MessageId(5));
+        assert_eq!(bumps.clean(), 1);
+        assert_eq!(bumps.staff_posted(CHANNEL), None);
+    }
+
+    #[test]
+    fn waits_are_bounded() {
+        let bumps = QuestioningBumps::default();
+        for i in 0..MAX_PENDING as u64 {
+            bumps.user_posted(serenity::ChannelId(i + 10), USER, serenity::MessageId(1));
+        }
+        assert!(bumps
+            .user_posted(CHANNEL, USER, serenity::MessageId(3))
+            .is_none());
+        assert_eq!(bumps.pending.len(), MAX_PENDING);
+    }
+}
@@ -0,0 +1,215 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Hold routine mod notices during a server's quiet hours and post them as a digest afterwards
+
+use super::{t, Error};
+use crate::entities::{prelude::*, *};
+use chrono::Timelike;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use tracing::{error, info};
+
+const DIGEST_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+
+/// Whether `minute` past midnight is inside the window from `start` up to `end`, which wraps
+/// past midnight when `end` comes first; equal ends mean no window at all
+pub const fn in_window(minute: i32, start: i32, end: i32) -> bool {
+    if start <= end {
+        start <= minute && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+/// Render minutes past midnight as `HH:MM`
+pub fn format_minutes(minutes: i32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Parse `HH:MM` into minutes past midnight
+pub fn parse_minutes(text: &str) -> Option<i32> {
+    let (hours, minutes) = text.trim().split_once(':')?;
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    ((0..24).contains(&hours) && (0..60).contains(&minutes)).then_some(hours * 60 + minutes)
+}
+
+#[derive(FromQueryResult)]
+struct QuietHoursData {
+    quiet_hours_start: Option<i32>,
+    quiet_hours_end: Option<i32>,
+    quiet_hours_timezone: Option<String>,
+}
+
+impl QuietHoursData {
+    fn is_quiet(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let (Some(start), Some(end)) = (self.quiet_hours_start, self.quiet_hours_end) else {
+            return false;
+        };
+        let tz = self
+            .quiet_hours_timezone
+            .as_deref()
+            .and_then(super::timezones::parse_tz)
+            .unwrap_or(chrono_tz::UTC);
+        let local = now.with_timezone(&tz);
+        #[allow(clippy::cast_possible_wrap)]
+        let minute = (local.hour() * 60 + local.minute()) as i32;
+        in_window(minute, start, end)
+    }
+}
+
+async fn is_quiet(db: &DatabaseConnection, guild: serenity::GuildId) -> Result<bool, Error> {
+    Ok(Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::QuietHoursStart)
+        .column(servers::Column::QuietHoursEnd)
+        .column(servers::Column::QuietHoursTimezone)
+        .into_model::<QuietHoursData>()
+        .one(db)
+        .await?
+        .is_some_and(|x| x.is_quiet(chrono::Utc::now())))
+}
+
+/// Routine notices can wait for the digest; anything without a purpose, such as moderation
+/// actions and configuration warnings, is always posted straight away
+const fn can_wait(purpose: Option<guild_log_channels::Purpose>) -> bool {
+    matches!(
+        purpose,
+        Some(guild_log_channels::Purpose::JoinAlerts | guild_log_channels::Purpose::FilterNotices)
+    )
+}
+
+/// Queue `msg` for `channel` if `guild` is in quiet hours, returning whether it was held
+pub async fn hold(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    purpose: Option<guild_log_channels::Purpose>,
+    msg: &str,
+) -> Result<bool, Error> {
+    if !can_wait(purpose) || !is_quiet(db, guild).await? {
+        return Ok(false);
+    }
+    HeldNotices::insert(held_notices::ActiveModel {
+        id: ActiveValue::NotSet,
+        guild_id: ActiveValue::Set(guild.into()),
+        channel_id: ActiveValue::Set(channel.into()),
+        content: ActiveValue::Set(msg.to_owned()),
+        held_at: ActiveValue::Set(chrono::Utc::now()),
+    })
+    .exec(db)
+    .await?;
+    Ok(true)
+}
+
+/// Post held notices once their server's quiet hours are over
+pub async fn schedule_digests(ctx: serenity::Context, db: DatabaseConnection) {
+    loop {
+        tokio::time::sleep(DIGEST_INTERVAL).await;
+        if let Err(e) = post_digests(&ctx, &db).await {
+            error!("Failed to post quiet hours digests: {}", e);
+        }
+    }
+}
+
+async fn post_digests(ctx: &serenity::Context, db: &DatabaseConnection) -> Result<(), Error> {
+    let held = HeldNotices::find()
+        .order_by_asc(held_notices::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .into_group_map_by(|x| serenity::GuildId::from(x.guild_id));
+
+    for (guild, notices) in held {
+        if is_quiet(db, guild).await? {
+            continue;
+        }
+        // Claim each notice before posting it, so it can't end up in two digests
+        let mut claimed = vec![];
+        for i in notices {
+            if HeldNotices::delete_by_id(i.id)
+                .exec(db)
+                .await?
+                .rows_affected
+                == 1
+            {
+                claimed.push(i);
+            }
+        }
+        if claimed.is_empty() {
+            continue;
+        }
+        for (channel, notices) in claimed
+            .into_iter()
+            .into_group_map_by(|x| serenity::ChannelId::from(x.channel_id))
+        {
+            let lines = notices
+                .iter()
+                .map(|x| format!("<t:{}:t> {}", x.held_at.timestamp(), x.content))
+                .collect::<Vec<_>>();
+            let pages = super::chunk_lines(&lines, MAX_EMBED_DESCRIPTION_LENGTH);
+            for (i, page) in pages.iter().enumerate() {
+                // A missing channel would otherwise hold the digest back forever
+                _ = t(channel
+                    .send_message(ctx, |f| {
+                        f.embed(|f| {
+                            f.title(format!("During quiet hours ({})", lines.len()))
+                                .description(page)
+                                .footer(|f| f.text(format!("Page {} of {}", i + 1, pages.len())))
+                        })
+                        .allowed_mentions(|f| f.empty_users())
+                    })
+                    .await);
+            }
+        }
+        info!("Posted quiet hours digest in guild '{}'", guild);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_can_cross_midnight() {
+        let (night, day) = ((22 * 60, 7 * 60), (9 * 60, 17 * 60));
+        for (minute, in_night, in_day) in [
+            (23 * 60, true, false),
+            (0, true, false),
+            (7 * 60 - 1, true, false),
+            (7 * 60, false, false),
+            (12 * 60, false, true),
+            (17 * 60, false, false),
+            (22 * 60, true, false),
+        ] {
+            assert_eq!(in_window(minute, night.0, night.1), in_night, "{minute}");
+            assert_eq!(in_window(minute, day.0, day.1), in_day, "{minute}");
+        }
+        assert!(!in_window(60, 60, 60));
+    }
+
+    #[test]
+    fn times_round_trip() {
+        assert_eq!(parse_minutes(" 07:30 "), Some(450));
+        assert_eq!(format_minutes(450), "07:30");
+        assert_eq!(parse_minutes("24:00"), None);
+        assert_eq!(parse_minutes("7"), None);
+    }
+}
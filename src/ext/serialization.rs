@@ -0,0 +1,273 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Codecs for the blobs stored in `servers` columns
+//!
+//! Every read and write of these columns should go through here, so format changes happen in one
+//! place and the golden tests below catch anything that would break existing databases.
+
+use super::entry_modal::{EntryModals, ModalStructure, StoredModals};
+use super::triggers::{StoredTrigger, Trigger};
+use super::Error;
+use image_hasher::ImageHash;
+use std::collections::HashMap;
+
+/// Entry modals are stored as JSON in `entry_modal_json`, as a map of language codes to modals,
+/// or as the bare modal they were before languages while there's only the default one
+pub(super) fn encode_modals(modals: &EntryModals) -> Result<String, Error> {
+    Ok(match modals.as_single() {
+        Some(x) => serde_json::to_string(x)?,
+        None => serde_json::to_string(&modals.0)?,
+    })
+}
+
+pub(super) fn decode_modals(raw: &str) -> Result<EntryModals, Error> {
+    Ok(serde_json::from_str::<StoredModals>(raw)?.into())
+}
+
+/// Entry modals were previously stored as named-map MessagePack in `entry_modal`
+pub(super) fn decode_legacy_modal(raw: &[u8]) -> Result<ModalStructure, Error> {
+    Ok(rmp_serde::from_slice(raw)?)
+}
+
+/// Triggers are stored as compact MessagePack in `triggers`, and were bare values before they
+/// had flags
+pub(super) fn encode_triggers(triggers: &HashMap<String, Trigger>) -> Result<Vec<u8>, Error> {
+    Ok(rmp_serde::to_vec(triggers)?)
+}
+
+pub(super) fn decode_triggers(raw: &[u8]) -> Result<HashMap<String, Trigger>, Error> {
+    let stored: HashMap<String, StoredTrigger> = rmp_serde::from_slice(raw)?;
+    Ok(stored.into_iter().map(|(k, v)| (k, v.into())).collect())
+}
+
+/// Blocked images are stored in `blocked_images` as their hashes' raw bytes, back to back
+pub(super) fn encode_blocked_images<'a>(
+    hashes: impl IntoIterator<Item = &'a ImageHash>,
+) -> Vec<u8> {
+    fedbot_core::blocklist::encode(hashes)
+}
+
+pub(super) fn decode_blocked_images(raw: &[u8]) -> Result<Vec<ImageHash>, Error> {
+    fedbot_core::blocklist::decode(raw)
+        .map_err(|e| super::FedBotError::new(format!("{e:?}")).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::entry_modal::{LanguageModal, ModalInput, DEFAULT_LANGUAGE};
+    use super::super::triggers::ReplyMode;
+    use super::*;
+    use poise::serenity_prelude as serenity;
+
+    // Written by the release that introduced each format; never regenerate these
+    const LEGACY_MODAL: &[u8] = include_bytes!("../../fixtures/serialization/entry_modal.msgpack");
+    const MODAL: &str = include_str!("../../fixtures/serialization/entry_modal.json");
+    const LANGUAGE_MODALS: &str =
+        include_str!("../../fixtures/serialization/entry_modal_languages.json");
+    const LEGACY_TRIGGERS: &[u8] = include_bytes!("../../fixtures/serialization/triggers.msgpack");
+    const MOD_ONLY_TRIGGERS: &[u8] =
+        include_bytes!("../../fixtures/serialization/triggers_mod_only.msgpack");
+    const TRIGGERS: &[u8] =
+        include_bytes!("../../fixtures/serialization/triggers_reply_mode.msgpack");
+    const BLOCKED_IMAGES: &[u8] = include_bytes!("../../fixtures/serialization/blocked_images.bin");
+
+    fn expected_modal() -> ModalStructure {
+        ModalStructure(vec![
+            ModalInput {
+                max: Some(500),
+                min: Some(10),
+                label: "Why do you want to join?".to_owned(),
+                placeholder: Some("Tell us a bit about yourself".to_owned()),
+                required: true,
+                style: serenity::InputTextStyle::Paragraph,
+            },
+            ModalInput {
+                max: None,
+                min: None,
+                label: "Where did you find us?".to_owned(),
+                placeholder: None,
+                required: false,
+                style: serenity::InputTextStyle::Short,
+            },
+        ])
+    }
+
+    fn expected_language_modals() -> EntryModals {
+        let mut modals = EntryModals::single(expected_modal());
+        modals.0.insert(
+            "fr".to_owned(),
+            LanguageModal {
+                label: "Français".to_owned(),
+                inputs: ModalStructure(vec![ModalInput {
+                    max: None,
+                    min: None,
+                    label: "Pourquoi nous rejoindre ?".to_owned(),
+                    placeholder: None,
+                    required: true,
+                    style: serenity::InputTextStyle::Paragraph,
+                }]),
+            },
+        );
+        modals
+    }
+
+    fn trigger(value: &str, is_mod_only: bool) -> Trigger {
+        Trigger {
+            value: value.to_owned(),
+            is_mod_only,
+            reply_mode: ReplyMode::Reply,
+        }
+    }
+
+    fn expected_legacy_triggers() -> HashMap<String, Trigger> {
+        HashMap::from([
+            (
+                "rules".to_owned(),
+                trigger("Read the rules, {user}!", false),
+            ),
+            ("welcome".to_owned(), trigger("Welcome to {server}", false)),
+        ])
+    }
+
+    fn expected_mod_only_triggers() -> HashMap<String, Trigger> {
+        HashMap::from([
+            (
+                "rules".to_owned(),
+                trigger("Read the rules, {user}!", false),
+            ),
+            (
+                "banmacro".to_owned(),
+                trigger("Banned for {server} rule 1", true),
+            ),
+        ])
+    }
+
+    fn expected_triggers() -> HashMap<String, Trigger> {
+        HashMap::from([
+            (
+                "rules".to_owned(),
+                Trigger {
+                    reply_mode: ReplyMode::Channel,
+                    ..trigger("Read the rules, {user}!", false)
+                },
+            ),
+            (
+                "banmacro".to_owned(),
+                Trigger {
+                    reply_mode: ReplyMode::ReplyNoPing,
+                    ..trigger("Banned for {server} rule 1", true)
+                },
+            ),
+            ("welcome".to_owned(), trigger("Welcome to {server}", false)),
+        ])
+    }
+
+    fn expected_hashes() -> Vec<ImageHash> {
+        [
+            [0, 1, 2, 3, 4, 5, 6, 7],
+            [255, 254, 253, 252, 251, 250, 249, 248],
+        ]
+        .iter()
+        .map(|x| ImageHash::from_bytes(x).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn legacy_modal_fixture_decodes() {
+        assert_eq!(decode_legacy_modal(LEGACY_MODAL).unwrap(), expected_modal());
+    }
+
+    #[test]
+    fn modal_fixture_decodes() {
+        let modals = decode_modals(MODAL).unwrap();
+        assert_eq!(modals, EntryModals::single(expected_modal()));
+        assert!(modals.0.contains_key(DEFAULT_LANGUAGE));
+    }
+
+    #[test]
+    fn language_modals_fixture_decodes() {
+        assert_eq!(
+            decode_modals(LANGUAGE_MODALS).unwrap(),
+            expected_language_modals()
+        );
+    }
+
+    #[test]
+    fn legacy_triggers_fixture_decodes() {
+        assert_eq!(
+            decode_triggers(LEGACY_TRIGGERS).unwrap(),
+            expected_legacy_triggers()
+        );
+    }
+
+    #[test]
+    fn mod_only_triggers_fixture_decodes() {
+        assert_eq!(
+            decode_triggers(MOD_ONLY_TRIGGERS).unwrap(),
+            expected_mod_only_triggers()
+        );
+    }
+
+    #[test]
+    fn triggers_fixture_decodes() {
+        assert_eq!(decode_triggers(TRIGGERS).unwrap(), expected_triggers());
+    }
+
+    #[test]
+    fn blocked_images_fixture_decodes() {
+        assert_eq!(
+            decode_blocked_images(BLOCKED_IMAGES).unwrap(),
+            expected_hashes()
+        );
+    }
+
+    #[test]
+    fn modal_round_trips() {
+        let modals = EntryModals::single(expected_modal());
+        let encoded = encode_modals(&modals).unwrap();
+        // Older releases can still read a lone default modal
+        assert_eq!(encoded, MODAL);
+        assert_eq!(decode_modals(&encoded).unwrap(), modals);
+    }
+
+    #[test]
+    fn language_modals_round_trip() {
+        let modals = expected_language_modals();
+        assert_eq!(
+            decode_modals(&encode_modals(&modals).unwrap()).unwrap(),
+            modals
+        );
+    }
+
+    #[test]
+    fn triggers_round_trip() {
+        let triggers = expected_triggers();
+        assert_eq!(
+            decode_triggers(&encode_triggers(&triggers).unwrap()).unwrap(),
+            triggers
+        );
+    }
+
+    #[test]
+    fn blocked_images_round_trip() {
+        let hashes = expected_hashes();
+        assert_eq!(
+            decode_blocked_images(&encode_blocked_images(&hashes)).unwrap(),
+            hashes
+        );
+    }
+}
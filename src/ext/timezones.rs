@@ -0,0 +1,182 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{Context, Error};
+use crate::entities::{prelude::*, *};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use sea_orm::*;
+use tracing::{info, instrument};
+
+#[allow(clippy::unused_async)]
+pub async fn tz_autocomplete<'a>(
+    _ctx: Context<'a>,
+    partial: &'a str,
+) -> impl Iterator<Item = poise::AutocompleteChoice<String>> + 'a {
+    let partial_matcher = partial.to_lowercase().replace('_', " ");
+    let mut all_tzs = TZ_VARIANTS
+        .iter()
+        .map(|x| poise::AutocompleteChoice {
+            name: x.name().to_owned().replace('_', " "),
+            value: x.name().to_owned(),
+        })
+        .filter_map(|x| {
+            let lower_name = x.name.to_lowercase();
+            if lower_name.contains(&partial_matcher) {
+                Some((x, lower_name))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if !partial_matcher.is_empty() {
+        all_tzs.sort_by_key(|x| {
+            if x.1 == partial_matcher {
+                0
+            } else {
+                x.1.find(&partial_matcher).unwrap_or(usize::MAX)
+            }
+        });
+    }
+    all_tzs.into_iter().map(|x| x.0).take(25)
+}
+
+/// Parse an IANA timezone name, as given by autocomplete or typed with spaces
+pub fn parse_tz(name: &str) -> Option<Tz> {
+    name.trim().replace(' ', "_").parse().ok()
+}
+
+fn cache_timezone(ctx: Context<'_>, tz: Option<Tz>) {
+    if let Ok(mut x) = ctx.data().user_timezones.write() {
+        x.insert(ctx.author().id, tz);
+    }
+}
+
+/// The invoking user's stored timezone, cached after the first lookup
+pub async fn user_timezone(ctx: Context<'_>) -> Result<Option<Tz>, Error> {
+    if let Some(x) = ctx
+        .data()
+        .user_timezones
+        .read()
+        .ok()
+        .and_then(|x| x.get(&ctx.author().id).copied())
+    {
+        return Ok(x);
+    }
+
+    let tz = UserPreferences::find_by_id(ctx.author().id)
+        .one(&ctx.data().db)
+        .await?
+        .and_then(|x| x.timezone)
+        .and_then(|x| parse_tz(&x));
+    cache_timezone(ctx, tz);
+    Ok(tz)
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(
+    slash_command,
+    subcommands("set_timezone", "get_timezone", "clear_timezone")
+)]
+pub async fn timezone(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Set your default timezone for /timestamp
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, rename = "set")]
+async fn set_timezone(
+    ctx: Context<'_>,
+    #[autocomplete = "tz_autocomplete"] tz: String,
+) -> Result<(), Error> {
+    let Some(parsed) = parse_tz(&tz) else {
+        ctx.send(|f| {
+            f.content(format!(
+                "Unknown timezone `{tz}`. Pick one of the suggested timezones."
+            ))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+
+    UserPreferences::insert(user_preferences::ActiveModel {
+        user_id: ActiveValue::Set(ctx.author().id.into()),
+        timezone: ActiveValue::Set(Some(parsed.name().to_owned())),
+    })
+    .on_conflict(
+        sea_query::OnConflict::column(user_preferences::Column::UserId)
+            .update_column(user_preferences::Column::Timezone)
+            .to_owned(),
+    )
+    .exec(&ctx.data().db)
+    .await?;
+    cache_timezone(ctx, Some(parsed));
+
+    info!(
+        "User '{}' set their timezone to '{}'",
+        ctx.author().tag(),
+        parsed.name()
+    );
+    ctx.send(|f| {
+        f.content(format!("Your timezone is now `{}`.", parsed.name()))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Show your default timezone
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, rename = "get")]
+async fn get_timezone(ctx: Context<'_>) -> Result<(), Error> {
+    let content = match user_timezone(ctx).await? {
+        Some(x) => format!("Your timezone is `{}`.", x.name()),
+        None => format!(
+            "You haven't set a timezone. Use {} to set one.",
+            super::mention_command(ctx, "timezone set").await?
+        ),
+    };
+    ctx.send(|f| {
+        f.content(content)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Remove your default timezone
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, rename = "clear")]
+async fn clear_timezone(ctx: Context<'_>) -> Result<(), Error> {
+    UserPreferences::update_many()
+        .col_expr(
+            user_preferences::Column::Timezone,
+            sea_query::Expr::value(Option::<String>::None),
+        )
+        .filter(user_preferences::Column::UserId.eq(ids::DbUserId::from(ctx.author().id)))
+        .exec(&ctx.data().db)
+        .await?;
+    cache_timezone(ctx, None);
+
+    info!("User '{}' cleared their timezone", ctx.author().tag());
+    ctx.send(|f| {
+        f.content("Your timezone has been cleared.")
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
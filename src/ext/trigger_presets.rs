@@ -0,0 +1,494 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Named snapshots of a server's triggers, which allied servers can apply once shared
+
+use super::triggers::{trigger_problem, Trigger};
+use super::{Context, Error};
+use crate::{
+    check_admin,
+    entities::{prelude::*, *},
+    require_profile,
+};
+use fedbot_core::triggers::is_valid_trigger_name;
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use sea_orm::*;
+use std::collections::HashMap;
+use tracing::{info, instrument};
+
+const MAX_PRESET_NAME_LENGTH: usize = 32;
+const MAX_AUTOCOMPLETE_OPTIONS: usize = 25;
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+
+/// How a preset is combined with the triggers a server already has
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ApplyMode {
+    #[default]
+    #[name = "Merge, keeping existing triggers"]
+    Merge,
+    #[name = "Replace all triggers"]
+    Replace,
+}
+
+/// Write `preset` into `current`, returning the names skipped because the server already has a
+/// different trigger by that name
+fn apply_preset_to(
+    current: &mut HashMap<String, Trigger>,
+    preset: HashMap<String, Trigger>,
+    mode: ApplyMode,
+) -> Vec<String> {
+    if mode == ApplyMode::Replace {
+        *current = preset;
+        return vec![];
+    }
+    let mut collisions = vec![];
+    for (name, trigger) in preset {
+        match current.get(&name) {
+            Some(x) if *x != trigger => collisions.push(name),
+            Some(_) => {}
+            None => {
+                current.insert(name, trigger);
+            }
+        }
+    }
+    collisions.sort();
+    collisions
+}
+
+/// Every trigger in `preset` that couldn't be set by hand, with the reason
+fn preset_problems(preset: &HashMap<String, Trigger>) -> Vec<String> {
+    preset
+        .iter()
+        .filter_map(|(name, x)| trigger_problem(name, &x.value).map(|y| format!("`!{name}`: {y}")))
+        .sorted()
+        .collect()
+}
+
+fn valid_preset_name(name: &str) -> bool {
+    name.chars().count() <= MAX_PRESET_NAME_LENGTH && is_valid_trigger_name(name)
+}
+
+/// Presets `guild` can see: its own and every shared one
+fn visible_to(guild: serenity::GuildId) -> Condition {
+    Condition::any()
+        .add(trigger_presets::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .add(trigger_presets::Column::Shared.eq(true))
+}
+
+async fn preset_autocomplete(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild) = ctx.guild_id() else {
+        return vec![];
+    };
+    let partial = partial.to_lowercase();
+    TriggerPresets::find()
+        .filter(visible_to(guild))
+        .filter(trigger_presets::Column::Name.starts_with(&partial))
+        .order_by_asc(trigger_presets::Column::Name)
+        .limit(MAX_AUTOCOMPLETE_OPTIONS as u64)
+        .all(&ctx.data().db)
+        .await
+        .map(|x| x.into_iter().map(|y| y.name).collect())
+        .unwrap_or_default()
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), Error> {
+    let content = content.into();
+    ctx.send(|f| {
+        f.content(content)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("save_preset", "list_presets", "apply_preset", "share_preset")
+)]
+pub async fn preset(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Save this server's triggers as a preset, overwriting an earlier save of the same name
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "save")]
+async fn save_preset(
+    ctx: Context<'_>,
+    #[description = "Preset name (letters, numbers and underscores)"] name: String,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let profile = require_profile!(ctx);
+
+    let name = name.to_lowercase();
+    if !valid_preset_name(&name) {
+        return reply(
+            ctx,
+            format!(
+                "Preset names can only use letters, numbers and underscores, up to {MAX_PRESET_NAME_LENGTH} characters."
+            ),
+        )
+        .await;
+    }
+
+    let triggers = match &profile.triggers {
+        Some(x) => super::serialization::decode_triggers(x)?,
+        None => HashMap::new(),
+    };
+    if triggers.is_empty() {
+        return reply(ctx, "This server has no triggers to save.").await;
+    }
+
+    let existing = TriggerPresets::find_by_id(name.clone())
+        .one(&ctx.data().db)
+        .await?;
+    if existing
+        .as_ref()
+        .is_some_and(|x| serenity::GuildId::from(x.guild_id) != guild)
+    {
+        return reply(
+            ctx,
+            format!("Another server already has a preset named `{name}`."),
+        )
+        .await;
+    }
+
+    let encoded = super::serialization::encode_triggers(&triggers)?;
+    let now = chrono::Utc::now();
+    if let Some(x) = existing {
+        let mut model: trigger_presets::ActiveModel = x.into();
+        model.triggers = ActiveValue::Set(encoded);
+        model.updated_at = ActiveValue::Set(now);
+        model.update(&ctx.data().db).await?;
+    } else {
+        TriggerPresets::insert(trigger_presets::ActiveModel {
+            name: ActiveValue::Set(name.clone()),
+            guild_id: ActiveValue::Set(guild.into()),
+            triggers: ActiveValue::Set(encoded),
+            shared: ActiveValue::Set(false),
+            updated_at: ActiveValue::Set(now),
+        })
+        .exec(&ctx.data().db)
+        .await?;
+    }
+
+    info!(
+        "User '{}' saved {} triggers of guild '{}' as preset '{}'",
+        ctx.author().tag(),
+        triggers.len(),
+        guild,
+        name
+    );
+    super::config_audit(
+        ctx,
+        guild,
+        "Trigger preset saved",
+        vec![
+            ("Preset".to_owned(), name.clone()),
+            ("Triggers".to_owned(), triggers.len().to_string()),
+        ],
+    )
+    .await?;
+    reply(
+        ctx,
+        format!("Saved {} trigger(s) as preset `{name}`.", triggers.len()),
+    )
+    .await
+}
+
+/// List the presets this server saved and the ones other servers shared
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn list_presets(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let presets = TriggerPresets::find()
+        .filter(visible_to(guild))
+        .order_by_asc(trigger_presets::Column::Name)
+        .all(&ctx.data().db)
+        .await?;
+    if presets.is_empty() {
+        return reply(ctx, "No trigger presets available.").await;
+    }
+
+    let lines = presets
+        .iter()
+        .map(|x| {
+            let owner = serenity::GuildId::from(x.guild_id);
+            let count = super::serialization::decode_triggers(&x.triggers).map_or(0, |y| y.len());
+            let source = if owner == guild {
+                if x.shared {
+                    "saved here, shared".to_owned()
+                } else {
+                    "saved here".to_owned()
+                }
+            } else {
+                format!(
+                    "from {}",
+                    owner.name(ctx).unwrap_or_else(|| owner.to_string())
+                )
+            };
+            format!(
+                "`{}`: {} trigger(s), {}, updated <t:{}:R>",
+                x.name,
+                count,
+                source,
+                x.updated_at.timestamp()
+            )
+        })
+        .collect::<Vec<_>>();
+    for (index, i) in super::chunk_lines(&lines, MAX_EMBED_DESCRIPTION_LENGTH)
+        .into_iter()
+        .enumerate()
+    {
+        ctx.send(|f| {
+            f.embed(|f| {
+                if index == 0 {
+                    f.title("Trigger presets");
+                }
+                f.description(i)
+            })
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Write a preset's triggers into this server's triggers
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "apply")]
+async fn apply_preset(
+    ctx: Context<'_>,
+    #[autocomplete = "preset_autocomplete"] name: String,
+    #[description = "Whether to keep this server's other triggers (defaults to merging)"]
+    mode: Option<ApplyMode>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let profile = require_profile!(ctx);
+    let mode = mode.unwrap_or_default();
+
+    let name = name.to_lowercase();
+    let Some(preset) = TriggerPresets::find_by_id(name.clone())
+        .filter(visible_to(guild))
+        .one(&ctx.data().db)
+        .await?
+    else {
+        return reply(ctx, format!("No preset named `{name}`.")).await;
+    };
+    let preset_triggers = super::serialization::decode_triggers(&preset.triggers)?;
+
+    let problems = preset_problems(&preset_triggers);
+    if !problems.is_empty() {
+        return reply(
+            ctx,
+            super::triggers::fit_message(format!(
+                "Preset `{name}` can't be applied:\n{}",
+                problems.iter().map(|x| format!("- {x}")).format("\n")
+            )),
+        )
+        .await;
+    }
+
+    let mut triggers = match &profile.triggers {
+        Some(x) => super::serialization::decode_triggers(x)?,
+        None => HashMap::new(),
+    };
+    let before = triggers.clone();
+    let collisions = apply_preset_to(&mut triggers, preset_triggers, mode);
+    let added = triggers
+        .iter()
+        .filter(|(k, v)| before.get(*k) != Some(*v))
+        .count();
+    let removed = before.keys().filter(|x| !triggers.contains_key(*x)).count();
+
+    if triggers != before {
+        let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+        model.id = ActiveValue::Unchanged(guild.into());
+        model.triggers = ActiveValue::Set(Some(super::serialization::encode_triggers(&triggers)?));
+        model.update(&ctx.data().db).await?;
+
+        ctx.data().triggers.write().await.insert(guild, triggers);
+
+        info!(
+            "User '{}' applied trigger preset '{}' to guild '{}' ({:?})",
+            ctx.author().tag(),
+            name,
+            guild,
+            mode
+        );
+        super::config_audit(
+            ctx,
+            guild,
+            "Trigger preset applied",
+            vec![
+                ("Preset".to_owned(), name.clone()),
+                ("Mode".to_owned(), mode.name().to_owned()),
+                ("Added/updated".to_owned(), added.to_string()),
+                ("Removed".to_owned(), removed.to_string()),
+            ],
+        )
+        .await?;
+    }
+
+    let mut summary =
+        format!("Applied preset `{name}`: {added} trigger(s) added or updated, {removed} removed.");
+    if !collisions.is_empty() {
+        summary.push_str(&format!(
+            "\nKept this server's own version of {}.",
+            collisions.iter().map(|x| format!("`!{x}`")).join(", ")
+        ));
+    }
+    reply(ctx, super::triggers::fit_message(summary)).await
+}
+
+/// Let other servers see and apply one of this server's presets
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "share")]
+async fn share_preset(
+    ctx: Context<'_>,
+    #[autocomplete = "preset_autocomplete"] name: String,
+    #[description = "Whether other servers can use it (defaults to sharing)"] shared: Option<bool>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let shared = shared.unwrap_or(true);
+    let name = name.to_lowercase();
+    let Some(preset) = TriggerPresets::find_by_id(name.clone())
+        .filter(trigger_presets::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .one(&ctx.data().db)
+        .await?
+    else {
+        return reply(ctx, format!("This server has no preset named `{name}`.")).await;
+    };
+
+    let mut model: trigger_presets::ActiveModel = preset.into();
+    model.shared = ActiveValue::Set(shared);
+    model.update(&ctx.data().db).await?;
+
+    info!(
+        "User '{}' {} trigger preset '{}' of guild '{}'",
+        ctx.author().tag(),
+        if shared { "shared" } else { "unshared" },
+        name,
+        guild
+    );
+    super::config_audit(
+        ctx,
+        guild,
+        "Trigger preset sharing changed",
+        vec![
+            ("Preset".to_owned(), name.clone()),
+            ("Shared".to_owned(), shared.to_string()),
+        ],
+    )
+    .await?;
+    reply(
+        ctx,
+        if shared {
+            format!("Other servers can now apply preset `{name}`.")
+        } else {
+            format!("Preset `{name}` is now only visible to this server.")
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::triggers::ReplyMode;
+    use super::*;
+
+    fn trigger(value: &str) -> Trigger {
+        Trigger {
+            value: value.to_owned(),
+            is_mod_only: false,
+            reply_mode: ReplyMode::Reply,
+        }
+    }
+
+    fn map(entries: &[(&str, &str)]) -> HashMap<String, Trigger> {
+        entries
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), trigger(v)))
+            .collect()
+    }
+
+    #[test]
+    fn merging_keeps_existing_triggers() {
+        let mut current = map(&[("rules", "Ours"), ("faq", "Same")]);
+        let collisions = apply_preset_to(
+            &mut current,
+            map(&[("rules", "Theirs"), ("faq", "Same"), ("links", "New")]),
+            ApplyMode::Merge,
+        );
+        assert_eq!(collisions, ["rules"]);
+        assert_eq!(
+            current,
+            map(&[("rules", "Ours"), ("faq", "Same"), ("links", "New")])
+        );
+    }
+
+    #[test]
+    fn replacing_drops_other_triggers() {
+        let mut current = map(&[("rules", "Ours"), ("old", "Gone")]);
+        let collisions = apply_preset_to(
+            &mut current,
+            map(&[("rules", "Theirs")]),
+            ApplyMode::Replace,
+        );
+        assert!(collisions.is_empty());
+        assert_eq!(current, map(&[("rules", "Theirs")]));
+    }
+
+    #[test]
+    fn oversized_values_are_reported() {
+        let long = "a".repeat(2001);
+        let problems = preset_problems(&map(&[("long", &long), ("ok", "Fine"), ("empty", " ")]));
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("`!empty`"));
+        assert!(problems[1].starts_with("`!long`"));
+    }
+
+    #[test]
+    fn preset_names_are_short_words() {
+        assert!(valid_preset_name("allied_rules"));
+        assert!(!valid_preset_name("allied rules"));
+        assert!(!valid_preset_name(""));
+        assert!(!valid_preset_name(&"a".repeat(MAX_PRESET_NAME_LENGTH + 1)));
+    }
+}
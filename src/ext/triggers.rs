@@ -14,25 +14,157 @@
    limitations under the License.
 */
 
-use super::ContainBytes;
+use super::PermissionTier;
 use crate::{
     check_admin,
     entities::{prelude::*, *},
+    require_profile,
 };
+use fedbot_core::triggers::{is_valid_trigger_name, trigger_names};
 use itertools::Itertools;
-use lazy_static::lazy_static;
 use poise::serenity_prelude as serenity;
 use poise::Modal;
-use regex::Regex;
 use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use serenity::Mentionable;
 use std::collections::HashMap;
 use tracing::{info, instrument};
 
-lazy_static! {
-    static ref TRIGGERS: Regex = Regex::new(r"(?:^|\s)!(\w+)").unwrap();
+const MAX_TRIGGERS_PER_MESSAGE: usize = 4;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// How a trigger's response is posted
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum ReplyMode {
+    #[default]
+    #[name = "Reply and ping"]
+    Reply,
+    #[name = "Reply without pinging"]
+    ReplyNoPing,
+    #[name = "Separate message"]
+    Channel,
 }
 
-const MAX_TRIGGERS_PER_MESSAGE: usize = 4;
+impl ReplyMode {
+    const fn description(self) -> &'static str {
+        match self {
+            Self::Reply => "reply",
+            Self::ReplyNoPing => "reply without ping",
+            Self::Channel => "separate message",
+        }
+    }
+}
+
+/// A trigger's response, who can fire it and how it's posted
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub value: String,
+    #[serde(default)]
+    pub is_mod_only: bool,
+    #[serde(default)]
+    pub reply_mode: ReplyMode,
+}
+
+/// Triggers were stored as bare values before they had any flags
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum StoredTrigger {
+    Legacy(String),
+    Current(Trigger),
+}
+
+impl From<StoredTrigger> for Trigger {
+    fn from(x: StoredTrigger) -> Self {
+        match x {
+            StoredTrigger::Legacy(value) => Self {
+                value,
+                is_mod_only: false,
+                reply_mode: ReplyMode::default(),
+            },
+            StoredTrigger::Current(x) => x,
+        }
+    }
+}
+
+/// Cut `text` down to a single message, for values stored before lengths were checked
+/// or pushed over the limit by `{user}`/`{server}`
+pub fn fit_message(text: String) -> String {
+    if text.chars().count() > MAX_MESSAGE_LENGTH {
+        format!(
+            "{}…",
+            text.chars()
+                .take(MAX_MESSAGE_LENGTH - 1)
+                .collect::<String>()
+        )
+    } else {
+        text
+    }
+}
+
+/// Why a trigger named `name` can't respond with `value`, if it can't
+pub(super) fn trigger_problem(name: &str, value: &str) -> Option<String> {
+    if !is_valid_trigger_name(name) {
+        return Some("Invalid trigger name.".to_owned());
+    }
+    if value.trim().is_empty() {
+        return Some("Trigger value cannot be empty.".to_owned());
+    }
+    let length = value.chars().count();
+    if length > MAX_MESSAGE_LENGTH {
+        return Some(format!(
+            "Trigger values must fit in one message ({MAX_MESSAGE_LENGTH} characters), but this one is {length} characters."
+        ));
+    }
+    None
+}
+
+/// Substitute `{user}` and `{server}` in a template
+pub fn render_template(value: &str, user: &serenity::User, guild_name: &str) -> String {
+    value
+        .replace("{user}", &user.mention().to_string())
+        .replace("{server}", guild_name)
+}
+
+/// Substitute `{user}` and `{server}` in a trigger value
+pub fn render_trigger_value(value: &str, message: &serenity::Message, guild_name: &str) -> String {
+    render_template(value, &message.author, guild_name)
+}
+
+/// Whether the author of `message` may fire mod-only triggers
+async fn author_is_mod(
+    message: &serenity::Message,
+    guild: serenity::GuildId,
+    reference: super::EventReference<'_>,
+) -> Result<bool, super::Error> {
+    let Some(profile) = Servers::find_by_id(guild).one(&reference.3.db).await? else {
+        return Ok(false);
+    };
+    let member = guild.member(reference.0, message.author.id).await?;
+    Ok(super::has_mod_role(
+        &reference.3.db,
+        guild,
+        profile.mod_role.into(),
+        &member.roles,
+    )
+    .await?
+        || member.permissions(reference.0)?.administrator())
+}
+
+/// Whether the invoker can see mod-only triggers
+async fn invoker_is_mod(
+    ctx: super::Context<'_>,
+    guild: serenity::GuildId,
+) -> Result<bool, super::Error> {
+    let Some(profile) = Servers::find_by_id(guild).one(&ctx.data().db).await? else {
+        return Ok(false);
+    };
+    let mod_roles = super::mod_roles(&ctx.data().db, guild, profile.mod_role.into()).await?;
+    Ok(super::resolve_tier(ctx, guild, &mod_roles, None)
+        .await?
+        .is_some_and(|x| x >= PermissionTier::Mod))
+}
 
 #[instrument(skip_all, err)]
 pub async fn fire_triggers(
@@ -40,36 +172,53 @@ pub async fn fire_triggers(
     guild: serenity::GuildId,
     reference: super::EventReference<'_>,
 ) -> Result<bool, super::Error> {
-    if reference
-        .3
-        .trigger_cooldown
-        .on_cooldown(message.author.id)
-        .await
-    {
+    if reference.3.trigger_cooldown.on_cooldown(message.author.id) {
         return Ok(false);
     }
 
     if let Some(triggers_map) = reference.3.triggers.read().await.get(&guild) {
-        for i in TRIGGERS
-            .captures_iter(&message.content)
-            .take(MAX_TRIGGERS_PER_MESSAGE)
-        {
-            if let Some(trigger_text) = triggers_map.get(
-                i.get(1)
-                    .ok_or(super::FedBotError::new("malformed trigger"))?
-                    .as_str()
-                    .to_lowercase()
-                    .as_str(),
-            ) {
-                message.reply(reference.0, trigger_text).await?;
+        let guild_name = guild.name(reference.0).unwrap_or_default();
+        // Only looked up once a mod-only trigger matches, so most messages never fetch the author
+        let mut is_mod = None;
+        for name in trigger_names(&message.content).take(MAX_TRIGGERS_PER_MESSAGE) {
+            if let Some(trigger) = triggers_map.get(&name) {
+                if trigger.is_mod_only {
+                    let allowed = match is_mod {
+                        Some(x) => x,
+                        None => *is_mod.insert(author_is_mod(message, guild, reference).await?),
+                    };
+                    if !allowed {
+                        continue;
+                    }
+                }
+                let content =
+                    fit_message(render_trigger_value(&trigger.value, message, &guild_name));
+                message
+                    .channel_id
+                    .send_message(reference.0, |f| {
+                        if trigger.reply_mode != ReplyMode::Channel {
+                            f.reference_message(message);
+                        }
+                        f.content(content)
+                            // Only users can be pinged, so triggers can't re-broadcast @everyone or roles
+                            .allowed_mentions(|f| {
+                                f.replied_user(trigger.reply_mode == ReplyMode::Reply)
+                                    .parse(serenity::ParseValue::Users)
+                            })
+                    })
+                    .await?;
+                reference
+                    .3
+                    .events
+                    .publish(super::events::BotEvent::TriggerFired {
+                        guild,
+                        channel: message.channel_id,
+                        name,
+                    });
             }
         }
     }
-    reference
-        .3
-        .trigger_cooldown
-        .activate(message.author.id)
-        .await;
+    reference.3.trigger_cooldown.activate(message.author.id);
     Ok(false)
 }
 
@@ -88,9 +237,18 @@ pub async fn triggers(ctx: super::Context<'_>) -> Result<(), super::Error> {
         .id;
 
     if let Some(triggers_map) = ctx.data().triggers.read().await.get(&guild) {
+        let show_mod_only =
+            triggers_map.values().any(|x| x.is_mod_only) && invoker_is_mod(ctx, guild).await?;
         let commands = triggers_map
-            .keys()
-            .map(|x| format!("!{x}"))
+            .iter()
+            .filter(|(_, x)| show_mod_only || !x.is_mod_only)
+            .map(|(name, x)| {
+                format!(
+                    "{}!{name} ({})",
+                    if x.is_mod_only { "🔒 " } else { "" },
+                    x.reply_mode.description()
+                )
+            })
             .format("\n")
             .to_string();
         if !commands.is_empty() {
@@ -101,7 +259,7 @@ pub async fn triggers(ctx: super::Context<'_>) -> Result<(), super::Error> {
 
     ctx.send(|f| {
         f.content("No triggers in guild.")
-            .ephemeral(ctx.data().is_ephemeral)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
     Ok(())
@@ -111,23 +269,18 @@ pub async fn triggers(ctx: super::Context<'_>) -> Result<(), super::Error> {
 #[instrument(skip_all, err)]
 #[poise::command(
     slash_command,
-    subcommands("set_trigger", "remove_trigger"),
+    subcommands(
+        "set_trigger",
+        "remove_trigger",
+        "test_trigger",
+        "super::trigger_presets::preset"
+    ),
     guild_only
 )]
 pub async fn trigger(_ctx: super::Context<'_>) -> Result<(), super::Error> {
     Ok(())
 }
 
-fn check_trigger_name(name: &str) -> Result<bool, super::Error> {
-    Ok(name
-        == TRIGGERS
-            .captures(&format!("!{name}"))
-            .ok_or(super::FedBotError::new("malformed trigger"))?
-            .get(1)
-            .ok_or(super::FedBotError::new("malformed trigger"))?
-            .as_str())
-}
-
 #[derive(Modal)]
 #[name = "Add Value"]
 struct TriggerValueModal {
@@ -136,6 +289,32 @@ struct TriggerValueModal {
     value: String,
 }
 
+pub async fn trigger_autocomplete(ctx: super::Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild) = ctx.guild_id() else {
+        return vec![];
+    };
+    let partial_matcher = partial.to_lowercase();
+    let is_mod = invoker_is_mod(ctx, guild).await.unwrap_or(false);
+    let mut matches = ctx
+        .data()
+        .triggers
+        .read()
+        .await
+        .get(&guild)
+        .map(|x| {
+            x.iter()
+                .filter(|(name, trigger)| {
+                    name.contains(&partial_matcher) && (is_mod || !trigger.is_mod_only)
+                })
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    matches.sort_by_key(|x| (!x.starts_with(&partial_matcher), x.clone()));
+    matches.truncate(25);
+    matches
+}
+
 /// Add/update a trigger
 #[instrument(skip_all, err)]
 #[poise::command(slash_command, guild_only, rename = "set")]
@@ -143,6 +322,10 @@ pub async fn set_trigger(
     ctx: super::Context<'_>,
     name: String,
     #[description = "Leave empty to use a modal for multiline text"] value: Option<String>,
+    #[description = "Only fire for mods and hide from everyone else's trigger list"]
+    mod_only: Option<bool>,
+    #[description = "Whether to reply to the message that fired it (defaults to replying with a ping)"]
+    reply_mode: Option<ReplyMode>,
 ) -> Result<(), super::Error> {
     let modal_ctx: super::ApplicationContext;
     if let super::Context::Application(inner_ctx) = ctx {
@@ -158,6 +341,8 @@ pub async fn set_trigger(
 
     check_admin!(ctx, guild);
 
+    let raw_commands = require_profile!(ctx);
+
     let value = if let Some(x) = value {
         x
     } else {
@@ -169,24 +354,15 @@ pub async fn set_trigger(
 
     let name = name.to_lowercase();
 
-    if !check_trigger_name(&name).unwrap_or(false) {
+    if let Some(problem) = trigger_problem(&name, &value) {
         ctx.send(|f| {
-            f.content("Invalid trigger name.")
-                .ephemeral(ctx.data().is_ephemeral)
+            f.content(problem)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
     }
 
-    let raw_commands: GuildTriggers = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::Triggers)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-
     info!(
         "User '{}#{}' added/updated trigger '{}'",
         ctx.author().name,
@@ -195,29 +371,53 @@ pub async fn set_trigger(
     );
 
     let mut triggers = match raw_commands.triggers {
-        Some(x) => rmp_serde::from_slice(&x)?,
+        Some(x) => super::serialization::decode_triggers(&x)?,
         None => HashMap::new(),
     };
-    triggers.insert(name.clone(), value.clone());
+    // Updating a value keeps its existing options unless new ones are given
+    let is_mod_only =
+        mod_only.unwrap_or_else(|| triggers.get(&name).is_some_and(|x| x.is_mod_only));
+    let reply_mode = reply_mode
+        .or_else(|| triggers.get(&name).map(|x| x.reply_mode))
+        .unwrap_or_default();
+    let trigger = Trigger {
+        value: value.clone(),
+        is_mod_only,
+        reply_mode,
+    };
+    triggers.insert(name.clone(), trigger.clone());
 
     let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
-    model.id = ActiveValue::Unchanged(guild.as_u64().repack());
-    model.triggers = ActiveValue::Set(Some(rmp_serde::to_vec(&triggers)?));
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.triggers = ActiveValue::Set(Some(super::serialization::encode_triggers(&triggers)?));
     model.update(&ctx.data().db).await?;
 
+    super::config_audit(
+        ctx,
+        guild,
+        "Trigger added/updated",
+        vec![
+            ("Trigger".to_owned(), format!("!{name}")),
+            ("Value".to_owned(), value),
+            ("Mod only".to_owned(), is_mod_only.to_string()),
+            ("Reply mode".to_owned(), reply_mode.description().to_owned()),
+        ],
+    )
+    .await?;
+
     let mut mem_cache = ctx.data().triggers.write().await;
     if let Some(x) = mem_cache.get_mut(&guild) {
-        x.insert(name, value);
+        x.insert(name, trigger);
     } else {
         let mut new_map = HashMap::new();
-        new_map.insert(name, value);
+        new_map.insert(name, trigger);
         mem_cache.insert(guild, new_map);
     }
     drop(mem_cache);
 
     ctx.send(|f| {
         f.content("Added trigger!")
-            .ephemeral(ctx.data().is_ephemeral)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
 
@@ -227,7 +427,10 @@ pub async fn set_trigger(
 /// Remove a trigger
 #[instrument(skip_all, err)]
 #[poise::command(slash_command, guild_only, rename = "remove")]
-pub async fn remove_trigger(ctx: super::Context<'_>, name: String) -> Result<(), super::Error> {
+pub async fn remove_trigger(
+    ctx: super::Context<'_>,
+    #[autocomplete = "trigger_autocomplete"] name: String,
+) -> Result<(), super::Error> {
     let guild = ctx
         .guild()
         .ok_or(super::FedBotError::new("command not in guild"))?
@@ -237,23 +440,30 @@ pub async fn remove_trigger(ctx: super::Context<'_>, name: String) -> Result<(),
 
     let name = name.to_lowercase();
 
-    if !check_trigger_name(&name).unwrap_or(false) {
+    if !is_valid_trigger_name(&name) {
         ctx.send(|f| {
             f.content("Invalid trigger name.")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
     }
 
-    let raw_commands: GuildTriggers = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::Triggers)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
+    let raw_commands = require_profile!(ctx);
+
+    let mut triggers: HashMap<String, Trigger> = match raw_commands.triggers {
+        Some(x) => super::serialization::decode_triggers(&x)?,
+        None => HashMap::new(),
+    };
+
+    if triggers.remove(&name).is_none() {
+        ctx.send(|f| {
+            f.content(format!("No trigger named `{name}`."))
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
 
     info!(
         "User '{}#{}' removed trigger '{}'",
@@ -262,31 +472,79 @@ pub async fn remove_trigger(ctx: super::Context<'_>, name: String) -> Result<(),
         name.as_str()
     );
 
-    let mut triggers: HashMap<String, String> = match raw_commands.triggers {
-        Some(x) => rmp_serde::from_slice(&x)?,
-        None => return Err(super::FedBotError::new("no triggers to remove").into()),
-    };
-
-    triggers.remove(&name);
-
     let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
-    model.id = ActiveValue::Unchanged(guild.as_u64().repack());
-    model.triggers = ActiveValue::Set(Some(rmp_serde::to_vec(&triggers)?));
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.triggers = ActiveValue::Set(Some(super::serialization::encode_triggers(&triggers)?));
     model.update(&ctx.data().db).await?;
 
+    super::config_audit(
+        ctx,
+        guild,
+        "Trigger removed",
+        vec![("Trigger".to_owned(), format!("!{name}"))],
+    )
+    .await?;
+
     if let Some(x) = ctx.data().triggers.write().await.get_mut(&guild) {
         x.remove(&name);
     }
 
     ctx.send(|f| {
         f.content("Removed trigger!")
-            .ephemeral(ctx.data().is_ephemeral)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
     })
     .await?;
 
     Ok(())
 }
 
+/// Preview a trigger's response
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, rename = "test")]
+pub async fn test_trigger(
+    ctx: super::Context<'_>,
+    #[autocomplete = "trigger_autocomplete"] name: String,
+) -> Result<(), super::Error> {
+    let guild = ctx
+        .guild()
+        .ok_or(super::FedBotError::new("command not in guild"))?;
+
+    let name = name.to_lowercase();
+
+    let trigger = ctx
+        .data()
+        .triggers
+        .read()
+        .await
+        .get(&guild.id)
+        .and_then(|x| x.get(&name).cloned());
+    // Mod-only triggers are hidden from everyone else, so don't reveal them here either
+    let trigger = match trigger {
+        Some(x) if x.is_mod_only && !invoker_is_mod(ctx, guild.id).await? => None,
+        x => x,
+    };
+
+    if let Some(x) = trigger {
+        ctx.send(|f| {
+            f.content(fit_message(render_template(
+                &x.value,
+                ctx.author(),
+                &guild.name,
+            )))
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    } else {
+        ctx.send(|f| {
+            f.content(format!("No trigger named `{name}`."))
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
 #[instrument(skip_all, err)]
 pub async fn add_guild_triggers(
     guild: &serenity::Guild,
@@ -297,7 +555,7 @@ pub async fn add_guild_triggers(
         return Ok(()); // For now
     }
 
-    let raw_commands: GuildTriggers = Servers::find_by_id(guild.id.as_u64().repack())
+    let raw_commands: GuildTriggers = Servers::find_by_id(guild.id)
         .select_only()
         .column(servers::Column::Id)
         .column(servers::Column::Triggers)
@@ -307,13 +565,44 @@ pub async fn add_guild_triggers(
         .ok_or(super::FedBotError::new("Failed to find query"))?;
 
     if let Some(trigger_binary) = raw_commands.triggers {
-        reference
-            .3
-            .triggers
-            .write()
-            .await
-            .insert(guild.id, rmp_serde::from_slice(&trigger_binary)?);
+        reference.3.triggers.write().await.insert(
+            guild.id,
+            super::serialization::decode_triggers(&trigger_binary)?,
+        );
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_values_are_unchanged() {
+        let text = "a".repeat(MAX_MESSAGE_LENGTH);
+        assert_eq!(fit_message(text.clone()), text);
+    }
+
+    #[test]
+    fn trigger_problems_check_name_then_value() {
+        assert_eq!(trigger_problem("rules", "Read the rules"), None);
+        assert_eq!(
+            trigger_problem("two words", "").as_deref(),
+            Some("Invalid trigger name.")
+        );
+        assert_eq!(
+            trigger_problem("rules", "  ").as_deref(),
+            Some("Trigger value cannot be empty.")
+        );
+        assert!(trigger_problem("rules", &"a".repeat(MAX_MESSAGE_LENGTH)).is_none());
+        assert!(trigger_problem("rules", &"a".repeat(MAX_MESSAGE_LENGTH + 1)).is_some());
+    }
+
+    #[test]
+    fn long_values_are_truncated_with_ellipsis() {
+        let fitted = fit_message("é".repeat(MAX_MESSAGE_LENGTH + 10));
+        assert_eq!(fitted.chars().count(), MAX_MESSAGE_LENGTH);
+        assert!(fitted.ends_with('…'));
+    }
+}
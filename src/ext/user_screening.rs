@@ -1,10 +1,10 @@
 use std::borrow::Cow;
 
-use super::ContainBytes;
-use super::{t, Context, Error};
+use super::{t, Context, Error, PermissionTier};
 use crate::{
-    check_mod_role,
+    check_tier,
     entities::{prelude::*, *},
+    require_profile,
 };
 use itertools::Itertools;
 use poise::serenity_prelude as serenity;
@@ -13,22 +13,48 @@ use serenity::utils::parse_role;
 use serenity::Mentionable;
 use tracing::instrument;
 
-#[derive(FromQueryResult)]
-struct AcceptUserServerData {
-    questioning_category: i64,
-    questioning_role: i64,
-    mod_channel: i64,
-    main_channel: i64,
-    member_role: i64,
-    mod_role: i64,
+const MAX_CHANNEL_SLUG_LENGTH: usize = 90;
+const MAX_THREAD_NAME_LENGTH: usize = 100;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+const DEFAULT_REASON: &str = "not specified";
+/// Group intros carry a Roles embed per user plus the Questioning embed, and Discord allows 10
+const MAX_GROUP_SIZE: usize = 9;
+
+/// Build a channel-safe slug from a user's name, since Discord silently strips invalid characters
+fn user_slug(user: &serenity::User, max_len: usize) -> String {
+    let name = if user.discriminator == 0 {
+        user.name.clone()
+    } else {
+        format!("{}{:04}", user.name, user.discriminator)
+    };
+    let mut slug = name
+        .to_lowercase()
+        .chars()
+        .map(|x| if x.is_ascii_alphanumeric() { x } else { '-' })
+        .dedup_by(|x, y| *x == '-' && *y == '-')
+        .take(max_len)
+        .collect::<String>();
+    slug = slug.trim_matches('-').to_owned();
+    if slug.is_empty() {
+        "user".to_owned()
+    } else {
+        slug
+    }
 }
 
-#[derive(FromQueryResult)]
-struct QuestionUserServerData {
-    questioning_category: i64,
-    questioning_role: i64,
-    member_role: i64,
-    mod_role: i64,
+/// Questioning channels are found again by their `-{user_id}` suffix
+fn questioning_channel_name(user: &serenity::User) -> String {
+    format!("{}-{}", user_slug(user, MAX_CHANNEL_SLUG_LENGTH), user.id)
+}
+
+/// Group channels are named after their first user plus a count of the others
+fn group_channel_name(first: &serenity::User, others: usize) -> String {
+    format!(
+        "{}-plus-{}-{}",
+        user_slug(first, MAX_CHANNEL_SLUG_LENGTH),
+        others,
+        first.id
+    )
 }
 
 #[instrument(skip_all, err)]
@@ -37,14 +63,354 @@ pub async fn alert_new_user(
     guild: serenity::GuildId,
     reference: super::EventReference<'_>,
 ) -> Result<(), super::Error> {
-    super::mod_log(
-        reference.0,
+    let Some(channel) = super::mod_channel(
         reference.3,
         guild,
-        None,
-        format!("User {} joined", member.mention()),
+        Some(guild_log_channels::Purpose::JoinAlerts),
     )
-    .await?;
+    .await?
+    else {
+        return Ok(());
+    };
+    // Held alerts lose their buttons, but /accept and /question still work from the digest
+    if super::quiet_hours::hold(
+        &reference.3.db,
+        guild,
+        channel,
+        Some(guild_log_channels::Purpose::JoinAlerts),
+        &format!("User {} joined", member.mention()),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    channel
+        .send_message(reference.0, |f| {
+            f.content(format!("User {} joined", member.mention()))
+                .allowed_mentions(|f| f.empty_users())
+                .components(|f| join_alert_buttons(f, member.user.id, false))
+        })
+        .await?;
+    Ok(())
+}
+
+const JOIN_ALERT_PREFIX: &str = "joinAlert-";
+
+/// Buttons on a join alert, whose custom IDs carry the joined user so they work across restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JoinAction {
+    Accept,
+    Question,
+    Ignore,
+}
+
+impl JoinAction {
+    const ALL: [Self; 3] = [Self::Accept, Self::Question, Self::Ignore];
+
+    const fn id(self) -> &'static str {
+        match self {
+            Self::Accept => "accept",
+            Self::Question => "question",
+            Self::Ignore => "ignore",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Accept => "Accept",
+            Self::Question => "Question",
+            Self::Ignore => "Ignore",
+        }
+    }
+
+    const fn style(self) -> serenity::ButtonStyle {
+        match self {
+            Self::Accept => serenity::ButtonStyle::Success,
+            Self::Question => serenity::ButtonStyle::Danger,
+            Self::Ignore => serenity::ButtonStyle::Secondary,
+        }
+    }
+
+    const fn outcome(self) -> &'static str {
+        match self {
+            Self::Accept => "accepted",
+            Self::Question => "sent to questioning",
+            Self::Ignore => "ignored",
+        }
+    }
+
+    fn custom_id(self, user: serenity::UserId) -> String {
+        format!("{JOIN_ALERT_PREFIX}{}-{}", self.id(), user)
+    }
+
+    fn parse(custom_id: &str) -> Option<(Self, serenity::UserId)> {
+        let (action, user) = custom_id.strip_prefix(JOIN_ALERT_PREFIX)?.split_once('-')?;
+        let action = Self::ALL.into_iter().find(|x| x.id() == action)?;
+        Some((action, serenity::UserId(user.parse().ok()?)))
+    }
+}
+
+fn join_alert_buttons(
+    f: &mut serenity::CreateComponents,
+    user: serenity::UserId,
+    disabled: bool,
+) -> &mut serenity::CreateComponents {
+    f.create_action_row(|f| {
+        for action in JoinAction::ALL {
+            f.create_button(|f| {
+                f.custom_id(action.custom_id(user))
+                    .label(action.label())
+                    .style(action.style())
+                    .disabled(disabled)
+            });
+        }
+        f
+    })
+}
+
+/// Accept, question or dismiss a newly joined user from the buttons on their join alert
+#[instrument(skip_all, err)]
+pub async fn handle_join_alert(
+    interaction: &serenity::MessageComponentInteraction,
+    reference: super::EventReference<'_>,
+) -> Result<(), Error> {
+    let Some((action, user)) = JoinAction::parse(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+    let (Some(guild), Some(member)) = (interaction.guild_id, interaction.member.as_ref()) else {
+        return Ok(());
+    };
+    let (ctx, data) = (reference.0, reference.3);
+    let Some(server_data) = Servers::find_by_id(guild).one(&data.db).await? else {
+        return Ok(());
+    };
+
+    let refusal =
+        if !super::has_mod_role(&data.db, guild, server_data.mod_role.into(), &member.roles).await?
+        {
+            Some("Only mods can handle join alerts.")
+        } else if action != JoinAction::Ignore && guild.member(ctx, user).await.is_err() {
+            Some("User is no longer in the server.")
+        } else {
+            None
+        };
+    if let Some(x) = refusal {
+        interaction
+            .create_interaction_response(ctx, |f| {
+                f.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|f| f.content(x).ephemeral(true))
+            })
+            .await?;
+        return Ok(());
+    }
+    interaction
+        .create_interaction_response(ctx, |f| {
+            f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+
+    let user = user.to_user(ctx).await?;
+    let refusal = match action {
+        JoinAction::Accept => matches!(
+            accept_user(ctx, data, guild, &user, member.user.id, &server_data, None).await?,
+            AcceptOutcome::AlreadyAccepted
+        )
+        .then_some("User already is accepted!"),
+        JoinAction::Question if user.bot => Some("Cannot send a bot to questioning."),
+        JoinAction::Question => (!question_user(
+            ctx,
+            data,
+            guild,
+            &user,
+            member.user.id,
+            DEFAULT_REASON,
+            &server_data,
+        )
+        .await?)
+            .then_some("User is already in questioning!"),
+        JoinAction::Ignore => None,
+    };
+    if let Some(x) = refusal {
+        interaction
+            .create_followup_message(ctx, |f| f.content(x).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    interaction
+        .message
+        .clone()
+        .edit(ctx, |f| {
+            f.content(format!(
+                "{} ({} by {})",
+                interaction.message.content,
+                action.outcome(),
+                member.mention()
+            ))
+            .components(|f| join_alert_buttons(f, user.id, true))
+        })
+        .await?;
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct QuestioningData {
+    questioning_category: ids::DbChannelId,
+    questioning_role: ids::DbRoleId,
+}
+
+/// The open questioning sessions in `channel`, which hold more than one user for group
+/// questioning, in the order they were opened
+pub(super) async fn open_sessions(
+    db: &DatabaseConnection,
+    channel: serenity::ChannelId,
+) -> Result<Vec<questioning_sessions::Model>, Error> {
+    Ok(QuestioningSessions::find()
+        .filter(questioning_sessions::Column::ChannelId.eq(ids::DbChannelId::from(channel)))
+        .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+        .order_by_asc(questioning_sessions::Column::Id)
+        .all(db)
+        .await?)
+}
+
+/// Mark the open sessions in `channel` as finished, returning them
+async fn close_sessions(
+    db: &DatabaseConnection,
+    channel: serenity::ChannelId,
+    status: questioning_sessions::Status,
+) -> Result<Vec<questioning_sessions::Model>, Error> {
+    let sessions = open_sessions(db, channel).await?;
+    QuestioningSessions::update_many()
+        .col_expr(
+            questioning_sessions::Column::Status,
+            sea_query::Expr::value(status),
+        )
+        .filter(questioning_sessions::Column::ChannelId.eq(ids::DbChannelId::from(channel)))
+        .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+        .exec(db)
+        .await?;
+    Ok(sessions)
+}
+
+/// Mark only `user`'s open session in `channel` as finished
+async fn close_user_session(
+    db: &DatabaseConnection,
+    channel: serenity::ChannelId,
+    user: serenity::UserId,
+    status: questioning_sessions::Status,
+) -> Result<(), Error> {
+    QuestioningSessions::update_many()
+        .col_expr(
+            questioning_sessions::Column::Status,
+            sea_query::Expr::value(status),
+        )
+        .filter(questioning_sessions::Column::ChannelId.eq(ids::DbChannelId::from(channel)))
+        .filter(questioning_sessions::Column::UserId.eq(ids::DbUserId::from(user)))
+        .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Whether anyone other than `user` is still being questioned in `channel`
+async fn others_in_channel(
+    db: &DatabaseConnection,
+    channel: serenity::ChannelId,
+    user: serenity::UserId,
+) -> Result<bool, Error> {
+    Ok(open_sessions(db, channel)
+        .await?
+        .iter()
+        .any(|x| serenity::UserId::from(x.user_id) != user))
+}
+
+/// The questioning channel `user` is in, found through their open session or, for channels
+/// opened before sessions were recorded, by the `-{user_id}` suffix
+pub(super) async fn find_questioning_channel(
+    ctx: &serenity::Context,
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+    questioning_category: serenity::ChannelId,
+    user: serenity::UserId,
+) -> Result<Option<serenity::GuildChannel>, Error> {
+    let session_channel = QuestioningSessions::find()
+        .filter(questioning_sessions::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .filter(questioning_sessions::Column::UserId.eq(ids::DbUserId::from(user)))
+        .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+        .order_by_desc(questioning_sessions::Column::OpenedAt)
+        .one(db)
+        .await?
+        .map(|x| serenity::ChannelId::from(x.channel_id));
+    Ok(guild.channels(ctx).await?.into_values().find(|x| {
+        x.parent_id == Some(questioning_category)
+            && session_channel.map_or_else(|| x.name.ends_with(&format!("-{user}")), |y| x.id == y)
+    }))
+}
+
+/// Warn mods when a questioning channel is deleted while its user is still in questioning
+#[instrument(skip_all, err)]
+pub async fn questioning_channel_deleted(
+    channel: &serenity::GuildChannel,
+    reference: super::EventReference<'_>,
+) -> Result<(), super::Error> {
+    let Some(server_data) = Servers::find_by_id(channel.guild_id)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::QuestioningCategory)
+        .column(servers::Column::QuestioningRole)
+        .into_model::<QuestioningData>()
+        .one(&reference.3.db)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    if channel.parent_id != Some(server_data.questioning_category.into()) {
+        return Ok(());
+    }
+    // accept/return close their session first, so only manual deletions are still open
+    let mut users = close_sessions(
+        &reference.3.db,
+        channel.id,
+        questioning_sessions::Status::Deleted,
+    )
+    .await?
+    .into_iter()
+    .map(|x| serenity::UserId::from(x.user_id))
+    .collect::<Vec<_>>();
+    reference.3.questioning_bumps.unwatch(channel.id);
+    if users.is_empty() {
+        users.extend(
+            channel
+                .name
+                .rsplit('-')
+                .next()
+                .and_then(|x| x.parse::<u64>().ok())
+                .map(serenity::UserId),
+        );
+    }
+
+    // accept/return remove the questioning role before deleting the channel
+    let questioning_role = serenity::RoleId::from(server_data.questioning_role);
+    for user in users {
+        let Ok(member) = channel.guild_id.member(reference.0, user).await else {
+            continue;
+        };
+        if member.roles.contains(&questioning_role) {
+            super::mod_log(
+                reference.0,
+                reference.3,
+                channel.guild_id,
+                None,
+                format!(
+                    "Warning: questioning channel `{}` was deleted, but {} is still in questioning",
+                    channel.name,
+                    user.mention()
+                ),
+            )
+            .await?;
+        }
+    }
     Ok(())
 }
 
@@ -56,39 +422,67 @@ pub async fn accept(ctx: Context<'_>, user: serenity::User) -> Result<(), Error>
         .guild_id()
         .ok_or(super::FedBotError::new("command called outside server"))?;
 
-    let server_data: AcceptUserServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::QuestioningCategory)
-        .column(servers::Column::QuestioningRole)
-        .column(servers::Column::ModChannel)
-        .column(servers::Column::MainChannel)
-        .column(servers::Column::MemberRole)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (questioning_category, questioning_role, mod_channel, main_channel, member_role, mod_role) = (
-        serenity::ChannelId(server_data.questioning_category.repack()),
-        serenity::RoleId(server_data.questioning_role.repack()),
-        serenity::ChannelId(server_data.mod_channel.repack()),
-        serenity::ChannelId(server_data.main_channel.repack()),
-        serenity::RoleId(server_data.member_role.repack()),
-        serenity::RoleId(server_data.mod_role.repack()),
-    );
+    let server_data = require_profile!(ctx);
 
-    check_mod_role!(ctx, guild, mod_role);
+    check_tier!(ctx, guild, PermissionTier::Helper, &server_data);
 
     crate::defer!(ctx);
 
+    let content = match accept_user(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild,
+        &user,
+        ctx.author().id,
+        &server_data,
+        Some(ctx.channel_id()),
+    )
+    .await?
+    {
+        AcceptOutcome::AlreadyAccepted => "User already is accepted!",
+        AcceptOutcome::Accepted {
+            channel_archived: true,
+        } => return Ok(()),
+        AcceptOutcome::Accepted {
+            channel_archived: false,
+        } => "Accepted user!",
+    };
+    ctx.send(|f| {
+        f.content(content)
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+enum AcceptOutcome {
+    AlreadyAccepted,
+    /// `channel_archived` is set when the questioning channel `accept_user` was run from is gone
+    Accepted {
+        channel_archived: bool,
+    },
+}
+
+/// Give `user` the member role, welcome them, and close their questioning channel if they have one
+async fn accept_user(
+    ctx: &serenity::Context,
+    data: &super::Data,
+    guild: serenity::GuildId,
+    user: &serenity::User,
+    moderator: serenity::UserId,
+    server_data: &servers::Model,
+    invoked_in: Option<serenity::ChannelId>,
+) -> Result<AcceptOutcome, Error> {
+    let (questioning_category, questioning_role, mod_channel, main_channel, member_role) = (
+        serenity::ChannelId::from(server_data.questioning_category),
+        serenity::RoleId::from(server_data.questioning_role),
+        serenity::ChannelId::from(server_data.mod_channel),
+        serenity::ChannelId::from(server_data.main_channel),
+        serenity::RoleId::from(server_data.member_role),
+    );
+
     if user.has_role(ctx, guild, member_role).await? {
-        ctx.send(|f| {
-            f.content("User already is accepted!")
-                .ephemeral(ctx.data().is_ephemeral)
-        })
-        .await?;
-        return Ok(());
+        return Ok(AcceptOutcome::AlreadyAccepted);
     }
 
     let mut member = guild.member(ctx, user.id).await?;
@@ -97,32 +491,50 @@ pub async fn accept(ctx: Context<'_>, user: serenity::User) -> Result<(), Error>
     let guild_name = guild
         .name(ctx)
         .ok_or(super::FedBotError::new("cannot get guild name"))?;
+    let welcome = match &server_data.welcome_message_template {
+        Some(x) => {
+            super::triggers::fit_message(super::triggers::render_template(x, user, &guild_name))
+        }
+        None => format!(
+            "Welcome to {}, {}. Everyone say hi!",
+            guild_name,
+            user.mention()
+        ),
+    };
     main_channel
         .send_message(ctx, |f| {
-            f.content(format!(
-                "Welcome to {}, {}. Everyone say hi!",
-                guild_name,
-                user.mention()
-            ))
+            f.content(welcome)
+                // Only the new member can be pinged, however the template is written
+                .allowed_mentions(|f| f.users([user.id]))
         })
         .await?;
 
-    let mut send_response = true;
+    let mut channel_archived = false;
     if user.has_role(ctx, guild, questioning_role).await? {
         member.remove_role(ctx, questioning_role).await?;
-        if let Some(channel) = guild.channels(ctx).await?.into_values().find(|x| {
-            x.parent_id == Some(questioning_category)
-                && x.name.ends_with(&format!("-{}", member.user.id))
-        }) {
-            if channel.id == ctx.channel_id() {
-                send_response = false;
+        if let Some(channel) =
+            find_questioning_channel(ctx, &data.db, guild, questioning_category, user.id).await?
+        {
+            // The channel is about to be deleted, so confirm with a plain message instead
+            if Some(channel.id) == invoked_in
+                && !others_in_channel(&data.db, channel.id, user.id).await?
+            {
+                channel_archived = true;
+                channel
+                    .send_message(ctx, |f| {
+                        f.content(format!("Accepted {}. Archiving channel…", user.mention()))
+                            .allowed_mentions(|f| f.empty_users())
+                    })
+                    .await?;
             }
             clear_questioning(
                 ctx,
+                data,
                 questioning_category,
                 mod_channel,
                 Some(member),
                 channel,
+                questioning_sessions::Status::Accepted,
             )
             .await?;
         } else {
@@ -130,30 +542,18 @@ pub async fn accept(ctx: Context<'_>, user: serenity::User) -> Result<(), Error>
         }
     }
 
-    super::mod_log(
-        ctx.serenity_context(),
-        ctx.data(),
-        guild,
-        None,
-        format!(
-            "User {} accepted by mod {}",
-            user.id.mention(),
-            ctx.author().mention()
-        ),
-    )
-    .await?;
-    if send_response {
-        ctx.send(|f| {
-            f.content("Accepted user!")
-                .ephemeral(ctx.data().is_ephemeral)
-        })
-        .await?;
-    }
-    Ok(())
+    data.events
+        .publish(super::events::BotEvent::MemberAccepted {
+            guild,
+            user: user.id,
+            moderator,
+        });
+    Ok(AcceptOutcome::Accepted { channel_archived })
 }
 
 struct LoggedMessage {
-    filenames: Vec<String>,
+    attachments: Vec<serenity::Attachment>,
+    skipped: Vec<String>,
     content: String,
     timestamp: serenity::Timestamp,
     author: (String, String, String),
@@ -161,6 +561,31 @@ struct LoggedMessage {
 
 const MAX_TOTAL_EMBED_LENGTH: usize = 6000;
 const MAX_EMBEDS_PER_MESSAGE: usize = 5;
+const MAX_UPLOAD_SIZE: u64 = 25 * 1024 * 1024; // Discord's upload limit per message
+const MAX_FILES_PER_MESSAGE: usize = 10;
+
+/// Split a message's attachments (by size) into groups that each fit in one upload, in order,
+/// leaving out files too large to upload at all
+fn upload_groups(sizes: &[u64]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut total = 0;
+    for (i, &x) in sizes.iter().enumerate() {
+        if x > MAX_UPLOAD_SIZE {
+            continue;
+        }
+        match groups.last_mut() {
+            Some(group) if group.len() < MAX_FILES_PER_MESSAGE && x <= MAX_UPLOAD_SIZE - total => {
+                group.push(i);
+                total += x;
+            }
+            _ => {
+                groups.push(vec![i]);
+                total = x;
+            }
+        }
+    }
+    groups
+}
 
 #[instrument(skip_all, err)]
 #[poise::command(slash_command, guild_only)]
@@ -169,31 +594,27 @@ pub async fn purge_questioning(ctx: Context<'_>) -> Result<(), Error> {
         .guild_id()
         .ok_or(super::FedBotError::new("command called outside server"))?;
 
-    let server_data: AcceptUserServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::QuestioningCategory)
-        .column(servers::Column::QuestioningRole)
-        .column(servers::Column::ModChannel)
-        .column(servers::Column::MainChannel)
-        .column(servers::Column::MemberRole)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (questioning_category, mod_channel, mod_role) = (
-        serenity::ChannelId(server_data.questioning_category.repack()),
-        serenity::ChannelId(server_data.mod_channel.repack()),
-        serenity::RoleId(server_data.mod_role.repack()),
+    let server_data = require_profile!(ctx);
+    let (questioning_category, mod_channel) = (
+        serenity::ChannelId::from(server_data.questioning_category),
+        serenity::ChannelId::from(server_data.mod_channel),
     );
 
-    check_mod_role!(ctx, guild, mod_role);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
 
     crate::defer!(ctx);
 
     if let serenity::Channel::Guild(x) = ctx.channel_id().to_channel(ctx).await? {
-        clear_questioning(ctx, questioning_category, mod_channel, None, x).await?;
+        clear_questioning(
+            ctx.serenity_context(),
+            ctx.data(),
+            questioning_category,
+            mod_channel,
+            None,
+            x,
+            questioning_sessions::Status::Purged,
+        )
+        .await?;
     } else {
         return Err(super::FedBotError::new("channel is not a guild channel").into());
     }
@@ -203,33 +624,64 @@ pub async fn purge_questioning(ctx: Context<'_>) -> Result<(), Error> {
 
 #[allow(clippy::too_many_lines)]
 async fn clear_questioning(
-    ctx: Context<'_>,
+    ctx: &serenity::Context,
+    data: &super::Data,
     questioning_category: serenity::ChannelId,
     questioning_log_channel: serenity::ChannelId,
     member: Option<serenity::Member>,
     channel: serenity::GuildChannel,
+    status: questioning_sessions::Status,
 ) -> Result<(), Error> {
     let mut messages = channel.messages(ctx, |f| f).await?;
+    let sessions = open_sessions(&data.db, channel.id).await?;
 
     if let Some(mut member) = member {
-        if let Some(i) = messages
+        let snapshot = sessions
             .iter()
-            .find(|x| x.author.id == ctx.framework().bot_id)
+            .find(|x| serenity::UserId::from(x.user_id) == member.user.id)
+            .and_then(|x| x.roles_json.as_deref());
+        let roles = if let Some(x) = snapshot {
+            serde_json::from_str::<Vec<u64>>(x)?
+                .into_iter()
+                .map(serenity::RoleId)
+                .collect()
+        } else {
+            // Older sessions only kept their snapshot in the intro; skip later bot messages such
+            // as accept's confirmation
+            messages
+                .iter()
+                .filter(|x| x.author.id == data.bot_id)
+                .find_map(|x| {
+                    x.embeds
+                        .first()
+                        .filter(|y| y.title == Some("Roles".to_owned()))
+                })
+                .and_then(|x| x.description.as_ref())
+                .map(|x| {
+                    x.split(' ')
+                        .filter_map(parse_role)
+                        .map(serenity::RoleId)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+        if !roles.is_empty() {
+            member.add_roles(ctx, roles.as_slice()).await?;
+        }
+
+        // The rest of a group is still being questioned, so only this user leaves the channel
+        if sessions
+            .iter()
+            .any(|x| serenity::UserId::from(x.user_id) != member.user.id)
         {
-            if let Some(embed) = i.embeds.get(0) {
-                if embed.title == Some("Roles".to_owned()) {
-                    if let Some(roles) = embed.description.as_ref().map(|x| {
-                        x.split(' ')
-                            .filter_map(parse_role)
-                            .map(serenity::RoleId)
-                            .collect::<Vec<_>>()
-                    }) {
-                        if !roles.is_empty() {
-                            member.add_roles(ctx, roles.as_slice()).await?;
-                        }
-                    }
-                }
-            }
+            channel
+                .delete_permission(
+                    ctx,
+                    serenity::PermissionOverwriteType::Member(member.user.id),
+                )
+                .await?;
+            close_user_session(&data.db, channel.id, member.user.id, status).await?;
+            return Ok(());
         }
 
         channel
@@ -249,17 +701,35 @@ async fn clear_questioning(
         .first()
         .ok_or(super::FedBotError::new("cannot get first message"))?;
     let start_time = first_message.timestamp.unix_timestamp();
-    let questioned_user = serenity::UserId(
-        super::USER
-            .captures(first_message.content.as_str())
-            .ok_or(super::FedBotError::new("cannot get user in question(ing)"))?
-            .get(1)
-            .ok_or(super::FedBotError::new("malformed regex"))?
-            .as_str()
-            .parse()?,
-    )
-    .to_user(ctx)
-    .await?;
+    let session = sessions.first();
+    let participants = if let Some(x) = session {
+        // Group members who already left still belong in the log header
+        QuestioningSessions::find()
+            .filter(questioning_sessions::Column::ChannelId.eq(x.channel_id))
+            .filter(questioning_sessions::Column::OpenedAt.eq(x.opened_at))
+            .order_by_asc(questioning_sessions::Column::Id)
+            .all(&data.db)
+            .await?
+            .into_iter()
+            .map(|x| serenity::UserId::from(x.user_id))
+            .collect::<Vec<_>>()
+    } else {
+        // Channels opened before sessions were recorded only name the user in their first message
+        vec![serenity::UserId(
+            super::USER
+                .captures(first_message.content.as_str())
+                .ok_or(super::FedBotError::new("cannot get user in question(ing)"))?
+                .get(1)
+                .ok_or(super::FedBotError::new("malformed regex"))?
+                .as_str()
+                .parse()?,
+        )]
+    };
+    let questioned_user = participants
+        .first()
+        .ok_or(super::FedBotError::new("questioning session has no users"))?
+        .to_user(ctx)
+        .await?;
 
     let log_thread = questioning_log_channel
         .create_public_thread(
@@ -267,89 +737,132 @@ async fn clear_questioning(
             questioning_log_channel
                 .send_message(ctx, |f| {
                     f.content(format!(
-                        "Log from {} channel with {} on <t:{}:f>",
+                        "Log from {} channel with {} on <t:{}:f>{}",
                         questioning_category.mention(),
-                        questioned_user.mention(),
-                        start_time
+                        participants.iter().map(Mentionable::mention).format(", "),
+                        start_time,
+                        session
+                            .map(|x| format!(" (reason: {})", x.reason))
+                            .unwrap_or_default()
                     ))
                 })
                 .await?
                 .id,
             |f| {
+                let suffix = format!("-{}-{}", questioned_user.id, start_time);
                 f.name(format!(
-                    "{}{}-{}-{}",
-                    &questioned_user.name,
-                    questioned_user.discriminator,
-                    questioned_user.id,
-                    start_time
+                    "{}{}",
+                    user_slug(
+                        &questioned_user,
+                        MAX_THREAD_NAME_LENGTH.saturating_sub(suffix.len())
+                    ),
+                    suffix
                 ))
             },
         )
         .await?;
 
     let mut messages_vec = vec![];
-    let mut attachments_vec = vec![];
     let mut total_length = 0;
+    let (mut batch_size, mut batch_files) = (0, 0);
 
     for i in messages {
-        if total_length > MAX_TOTAL_EMBED_LENGTH || messages_vec.len() > MAX_EMBEDS_PER_MESSAGE {
-            send_logged_messages(ctx, log_thread.id, attachments_vec, messages_vec).await?;
-            attachments_vec = vec![];
-            messages_vec = vec![];
-            total_length = 0;
+        let mut groups = upload_groups(&i.attachments.iter().map(|x| x.size).collect::<Vec<_>>());
+        if groups.is_empty() {
+            groups.push(vec![]);
         }
+        let skipped = i
+            .attachments
+            .iter()
+            .filter(|x| x.size > MAX_UPLOAD_SIZE)
+            .map(|x| x.filename.clone())
+            .collect::<Vec<_>>();
+        let mut files = i.attachments.into_iter().map(Some).collect::<Vec<_>>();
+        let author = (
+            i.author.face(),
+            i.author.tag(),
+            format!("https://discordapp.com/users/{}", i.author.id),
+        );
+        // Files that don't fit in one upload carry on in the following batches under the same author
+        let mut body = Some((i.content, skipped));
+        for group in groups {
+            let attachments = group
+                .into_iter()
+                .filter_map(|x| files[x].take())
+                .collect::<Vec<_>>();
+            let this_size = attachments.iter().map(|x| x.size).sum::<u64>();
 
-        for j in &i.attachments {
-            if let Ok(x) = t(ctx.data().reqwest.get(&j.url).send().await) {
-                if let Ok(y) = t(x.bytes().await) {
-                    attachments_vec.push(serenity::AttachmentType::Bytes {
-                        data: Cow::Owned(y.to_vec()),
-                        filename: j.filename.clone(),
-                    });
-                }
+            // Attachments are only downloaded when their batch is sent, so one batch is held at a time
+            if total_length > MAX_TOTAL_EMBED_LENGTH
+                || messages_vec.len() > MAX_EMBEDS_PER_MESSAGE
+                || batch_size + this_size > MAX_UPLOAD_SIZE
+                || batch_files + attachments.len() > MAX_FILES_PER_MESSAGE
+            {
+                send_logged_messages(ctx, data, log_thread.id, messages_vec).await?;
+                messages_vec = vec![];
+                total_length = 0;
+                (batch_size, batch_files) = (0, 0);
             }
-        }
+            batch_size += this_size;
+            batch_files += attachments.len();
 
-        let this_message = LoggedMessage {
-            filenames: i.attachments.into_iter().map(|x| x.filename).collect(),
-            content: i.content,
-            timestamp: i.timestamp,
-            author: (
-                i.author.face(),
-                i.author.tag(),
-                format!("https://discordapp.com/users/{}", i.author.id),
-            ),
-        };
+            let (content, skipped) = body.take().unwrap_or_default();
+            let this_message = LoggedMessage {
+                attachments,
+                skipped,
+                content,
+                timestamp: i.timestamp,
+                author: author.clone(),
+            };
 
-        total_length += this_message.content.len()
-            + this_message.author.0.len()
-            + this_message.author.1.len()
-            + this_message.author.2.len();
-        messages_vec.push(this_message);
+            total_length += this_message.content.len()
+                + this_message.author.0.len()
+                + this_message.author.1.len()
+                + this_message.author.2.len();
+            messages_vec.push(this_message);
+        }
     }
     if !messages_vec.is_empty() {
-        send_logged_messages(ctx, log_thread.id, attachments_vec, messages_vec).await?;
+        send_logged_messages(ctx, data, log_thread.id, messages_vec).await?;
     }
+    close_sessions(&data.db, channel.id, status).await?;
+    data.questioning_bumps.unwatch(channel.id);
     channel.delete(ctx).await?;
 
     Ok(())
 }
 
 async fn send_logged_messages(
-    ctx: Context<'_>,
+    ctx: &serenity::Context,
+    data: &super::Data,
     log_thread: serenity::ChannelId,
-    attachments: Vec<serenity::AttachmentType<'_>>,
     messages: Vec<LoggedMessage>,
 ) -> Result<(), Error> {
+    let mut attachments = vec![];
+    for i in messages.iter().flat_map(|x| &x.attachments) {
+        if let Ok(x) = t(data.reqwest.get(&i.url).send().await) {
+            if let Ok(y) = t(x.bytes().await) {
+                attachments.push(serenity::AttachmentType::Bytes {
+                    data: Cow::Owned(y.to_vec()),
+                    filename: i.filename.clone(),
+                });
+            }
+        }
+    }
+
     log_thread
         .send_files(ctx, attachments, |f| {
             for i in messages {
                 f.add_embed(|f| {
                     f.author(|x| x.icon_url(i.author.0).name(i.author.1).url(i.author.2));
-                    for j in i.filenames {
-                        f.attachment(j);
+                    for j in i.attachments {
+                        f.attachment(j.filename);
+                    }
+                    let mut description = i.content;
+                    for j in i.skipped {
+                        description.push_str(&format!("\n*attachment too large to archive: {j}*"));
                     }
-                    f.description(i.content).timestamp(i.timestamp)
+                    f.description(description).timestamp(i.timestamp)
                 });
             }
             f.allowed_mentions(|f| f.empty_users())
@@ -371,28 +884,15 @@ pub async fn return_(ctx: Context<'_>, user: serenity::User) -> Result<(), Error
         .guild_id()
         .ok_or(super::FedBotError::new("command called outside server"))?;
 
-    let server_data: AcceptUserServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::QuestioningCategory)
-        .column(servers::Column::QuestioningRole)
-        .column(servers::Column::ModChannel)
-        .column(servers::Column::MainChannel)
-        .column(servers::Column::MemberRole)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (questioning_category, questioning_role, mod_channel, member_role, mod_role) = (
-        serenity::ChannelId(server_data.questioning_category.repack()),
-        serenity::RoleId(server_data.questioning_role.repack()),
-        serenity::ChannelId(server_data.mod_channel.repack()),
-        serenity::RoleId(server_data.member_role.repack()),
-        serenity::RoleId(server_data.mod_role.repack()),
+    let server_data = require_profile!(ctx);
+    let (questioning_category, questioning_role, mod_channel, member_role) = (
+        serenity::ChannelId::from(server_data.questioning_category),
+        serenity::RoleId::from(server_data.questioning_role),
+        serenity::ChannelId::from(server_data.mod_channel),
+        serenity::RoleId::from(server_data.member_role),
     );
 
-    check_mod_role!(ctx, guild, mod_role);
+    check_tier!(ctx, guild, PermissionTier::Helper, &server_data);
 
     crate::defer!(ctx);
 
@@ -401,7 +901,7 @@ pub async fn return_(ctx: Context<'_>, user: serenity::User) -> Result<(), Error
     {
         ctx.send(|f| {
             f.content("User is not in questioning!")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
         return Ok(());
@@ -412,41 +912,45 @@ pub async fn return_(ctx: Context<'_>, user: serenity::User) -> Result<(), Error
     member.remove_role(ctx, questioning_role).await?;
 
     let mut send_response = true;
-    if let Some(channel) = guild.channels(ctx).await?.into_values().find(|x| {
-        x.parent_id == Some(questioning_category)
-            && x.name.ends_with(&format!("-{}", member.user.id))
-    }) {
-        if channel.id == ctx.channel_id() {
+    if let Some(channel) = find_questioning_channel(
+        ctx.serenity_context(),
+        &ctx.data().db,
+        guild,
+        questioning_category,
+        user.id,
+    )
+    .await?
+    {
+        if channel.id == ctx.channel_id()
+            && !others_in_channel(&ctx.data().db, channel.id, user.id).await?
+        {
             send_response = false;
         }
         clear_questioning(
-            ctx,
+            ctx.serenity_context(),
+            ctx.data(),
             questioning_category,
             mod_channel,
             Some(member),
             channel,
+            questioning_sessions::Status::Returned,
         )
         .await?;
     } else {
         return Err(super::FedBotError::new("questioning channel not found").into());
     }
 
-    super::mod_log(
-        ctx.serenity_context(),
-        ctx.data(),
-        guild,
-        None,
-        format!(
-            "User {} returned from questioning by mod {}",
-            user.mention(),
-            ctx.author().mention()
-        ),
-    )
-    .await?;
+    ctx.data()
+        .events
+        .publish(super::events::BotEvent::MemberReturned {
+            guild,
+            user: user.id,
+            moderator: ctx.author().id,
+        });
     if send_response {
         ctx.send(|f| {
             f.content("Returned user!")
-                .ephemeral(ctx.data().is_ephemeral)
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
         })
         .await?;
     }
@@ -455,86 +959,292 @@ pub async fn return_(ctx: Context<'_>, user: serenity::User) -> Result<(), Error
 
 /// Send a user to questioning and optionally send a warning/explanation message
 #[instrument(skip_all, err)]
-#[poise::command(slash_command, context_menu_command = "Question User", guild_only)]
-pub async fn question(ctx: Context<'_>, user: serenity::User) -> Result<(), Error> {
+#[poise::command(slash_command, guild_only)]
+pub async fn question(
+    ctx: Context<'_>,
+    user: serenity::User,
+    #[description = "Why the user is being sent to questioning"] reason: Option<String>,
+) -> Result<(), Error> {
+    send_to_questioning(ctx, user, reason).await
+}
+
+/// Send a user to questioning and optionally send a warning/explanation message
+#[instrument(skip_all, err)]
+#[poise::command(context_menu_command = "Question User", guild_only)]
+pub async fn question_menu(ctx: Context<'_>, user: serenity::User) -> Result<(), Error> {
+    send_to_questioning(ctx, user, None).await
+}
+
+/// Send several coordinated users to one shared questioning channel
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+pub async fn question_group(
+    ctx: Context<'_>,
+    #[description = "Mentions of the users to question together"] users: String,
+    #[description = "Why the users are being sent to questioning"] reason: Option<String>,
+) -> Result<(), Error> {
     let guild = ctx
         .guild_id()
         .ok_or(super::FedBotError::new("command called outside server"))?;
 
-    let server_data: QuestionUserServerData = Servers::find_by_id(guild.as_u64().repack())
-        .select_only()
-        .column(servers::Column::Id)
-        .column(servers::Column::QuestioningCategory)
-        .column(servers::Column::QuestioningRole)
-        .column(servers::Column::ModChannel)
-        .column(servers::Column::MemberRole)
-        .column(servers::Column::ModRole)
-        .into_model()
-        .one(&ctx.data().db)
-        .await?
-        .ok_or(super::FedBotError::new("Failed to find query"))?;
-    let (questioning_category, questioning_role, member_role, mod_role) = (
-        serenity::ChannelId(server_data.questioning_category.repack()),
-        serenity::RoleId(server_data.questioning_role.repack()),
-        serenity::RoleId(server_data.member_role.repack()),
-        serenity::RoleId(server_data.mod_role.repack()),
-    );
+    let reason = reason.unwrap_or_else(|| DEFAULT_REASON.to_owned());
+    if reason.chars().count() > super::MAX_EMBED_FIELD_LENGTH {
+        ctx.send(|f| {
+            f.content(format!(
+                "Reason is too long (max {} characters).",
+                super::MAX_EMBED_FIELD_LENGTH
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
 
-    check_mod_role!(ctx, guild, mod_role);
+    let ids = super::USER
+        .captures_iter(&users)
+        .filter_map(|x| x.get(1)?.as_str().parse().ok())
+        .unique()
+        .map(serenity::UserId)
+        .collect::<Vec<_>>();
+    if ids.is_empty() || ids.len() > MAX_GROUP_SIZE {
+        ctx.send(|f| {
+            f.content(format!(
+                "Mention between 1 and {MAX_GROUP_SIZE} users to question together."
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let server_data = require_profile!(ctx);
+
+    check_tier!(ctx, guild, PermissionTier::Helper, &server_data);
 
     crate::defer!(ctx);
 
-    if user.has_role(ctx, guild, questioning_role).await? {
+    let mut users = vec![];
+    for i in ids {
+        users.push(i.to_user(ctx).await?);
+    }
+    if users.iter().any(|x| x.bot) {
         ctx.send(|f| {
-            f.content("User is already in questioning!")
-                .ephemeral(ctx.data().is_ephemeral)
+            f.content("Cannot send a bot to questioning.")
+                .ephemeral(true)
         })
         .await?;
         return Ok(());
     }
 
-    let mut member = guild.member(ctx, user.id).await?;
-    member.remove_role(ctx, member_role).await?;
+    let already_questioned = question_users(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild,
+        &users.iter().collect::<Vec<_>>(),
+        ctx.author().id,
+        &reason,
+        &server_data,
+    )
+    .await?;
+    ctx.send(|f| {
+        f.content(if already_questioned.is_empty() {
+            "Sent users to questioning!".to_owned()
+        } else if already_questioned.len() == users.len() {
+            "Users are already in questioning!".to_owned()
+        } else {
+            format!(
+                "Sent users to questioning, except {} who already are.",
+                already_questioned
+                    .iter()
+                    .map(Mentionable::mention)
+                    .format(", ")
+            )
+        })
+        .allowed_mentions(|f| f.empty_users())
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+async fn send_to_questioning(
+    ctx: Context<'_>,
+    user: serenity::User,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let reason = reason.unwrap_or_else(|| DEFAULT_REASON.to_owned());
+    if reason.chars().count() > super::MAX_EMBED_FIELD_LENGTH {
+        ctx.send(|f| {
+            f.content(format!(
+                "Reason is too long (max {} characters).",
+                super::MAX_EMBED_FIELD_LENGTH
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    if user.bot {
+        ctx.send(|f| {
+            f.content("Cannot send a bot to questioning.")
+                .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let server_data = require_profile!(ctx);
+
+    check_tier!(ctx, guild, PermissionTier::Helper, &server_data);
+
+    crate::defer!(ctx);
+
+    let sent = question_user(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild,
+        &user,
+        ctx.author().id,
+        &reason,
+        &server_data,
+    )
+    .await?;
+    ctx.send(|f| {
+        f.content(if sent {
+            "Sent user to questioning!"
+        } else {
+            "User is already in questioning!"
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Move `user` into a private questioning channel, returning `false` if they're already in one
+pub(super) async fn question_user(
+    ctx: &serenity::Context,
+    data: &super::Data,
+    guild: serenity::GuildId,
+    user: &serenity::User,
+    moderator: serenity::UserId,
+    reason: &str,
+    server_data: &servers::Model,
+) -> Result<bool, Error> {
+    Ok(
+        question_users(ctx, data, guild, &[user], moderator, reason, server_data)
+            .await?
+            .is_empty(),
+    )
+}
+
+/// Move `users` into one shared questioning channel, returning those already in questioning
+#[allow(clippy::too_many_lines)]
+async fn question_users(
+    ctx: &serenity::Context,
+    data: &super::Data,
+    guild: serenity::GuildId,
+    users: &[&serenity::User],
+    moderator: serenity::UserId,
+    reason: &str,
+    server_data: &servers::Model,
+) -> Result<Vec<serenity::UserId>, Error> {
+    let (questioning_category, questioning_role, member_role, mod_role, helper_role) = (
+        serenity::ChannelId::from(server_data.questioning_category),
+        serenity::RoleId::from(server_data.questioning_role),
+        serenity::RoleId::from(server_data.member_role),
+        serenity::RoleId::from(server_data.mod_role),
+        server_data.helper_role.map(serenity::RoleId::from),
+    );
+
+    let mut already_questioned = vec![];
+    let mut members = vec![];
+    for user in users {
+        if user.has_role(ctx, guild, questioning_role).await? {
+            already_questioned.push(user.id);
+            continue;
+        }
+        let mut member = guild.member(ctx, user.id).await?;
+        member.remove_role(ctx, member_role).await?;
+        members.push(member);
+    }
 
-    let roles = member.roles.clone();
+    let channel_name = match members.as_slice() {
+        [] => return Ok(already_questioned),
+        [x] => questioning_channel_name(&x.user),
+        [x, rest @ ..] => group_channel_name(&x.user, rest.len()),
+    };
+    let first_user = members[0].user.id;
+    let roles = members.iter().map(|x| x.roles.clone()).collect::<Vec<_>>();
 
-    let questioning_channel: serenity::GuildChannel;
+    let mut questioning_channel: serenity::GuildChannel;
 
+    // Group channels are always new, so a leftover single-user channel isn't shared by accident
     if let Some(channel) = guild.channels(ctx).await?.into_values().find(|x| {
-        x.parent_id == Some(questioning_category) && x.name.ends_with(&format!("-{}", user.id))
+        (members.len() == 1
+            && x.parent_id == Some(questioning_category)
+            && x.name.ends_with(&format!("-{first_user}")))
+            || x.name == channel_name
     }) {
         questioning_channel = channel;
+        if questioning_channel.parent_id != Some(questioning_category) {
+            questioning_channel
+                .edit(ctx, |f| f.category(questioning_category))
+                .await?;
+        }
     } else {
         questioning_channel = guild
             .create_channel(ctx, |f| {
                 f.category(questioning_category)
                     .kind(serenity::ChannelType::Text)
-                    .name(format!("{}{}-{}", user.name, user.discriminator, user.id))
+                    .name(channel_name)
             })
             .await?;
     }
 
-    questioning_channel
-        .create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::empty(),
-                kind: serenity::PermissionOverwriteType::Member(user.id),
-            },
-        )
-        .await?;
+    for member in &members {
+        questioning_channel
+            .create_permission(
+                ctx,
+                &serenity::PermissionOverwrite {
+                    allow: serenity::Permissions::VIEW_CHANNEL,
+                    deny: serenity::Permissions::empty(),
+                    kind: serenity::PermissionOverwriteType::Member(member.user.id),
+                },
+            )
+            .await?;
+    }
 
-    questioning_channel
-        .create_permission(
-            ctx,
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::VIEW_CHANNEL,
-                deny: serenity::Permissions::empty(),
-                kind: serenity::PermissionOverwriteType::Role(mod_role),
-            },
-        )
-        .await?;
+    for mod_role in super::mod_roles(&data.db, guild, mod_role).await? {
+        questioning_channel
+            .create_permission(
+                ctx,
+                &serenity::PermissionOverwrite {
+                    allow: serenity::Permissions::VIEW_CHANNEL,
+                    deny: serenity::Permissions::empty(),
+                    kind: serenity::PermissionOverwriteType::Role(mod_role),
+                },
+            )
+            .await?;
+    }
+
+    if let Some(helper_role) = helper_role {
+        questioning_channel
+            .create_permission(
+                ctx,
+                &serenity::PermissionOverwrite {
+                    allow: serenity::Permissions::VIEW_CHANNEL,
+                    deny: serenity::Permissions::empty(),
+                    kind: serenity::PermissionOverwriteType::Role(helper_role),
+                },
+            )
+            .await?;
+    }
 
     let default_role = serenity::RoleId(guild.0); // @everyone has the same id as the guild
     questioning_channel
@@ -548,40 +1258,173 @@ pub async fn question(ctx: Context<'_>, user: serenity::User) -> Result<(), Erro
         )
         .await?;
 
-    questioning_channel
+    let opened_at = chrono::Utc::now();
+    let mentions = members.iter().map(|x| x.mention()).join(", ");
+    let intro = questioning_channel
         .send_message(ctx, |f| {
             f.content(format!(
-                "{}, you have been sent to questioning by mod {}.",
-                user.mention(),
-                ctx.author().mention()
-            ))
-            .add_embed(|f| {
-                f.title("Roles")
-                    .author(|f| f.icon_url(member.face()).name(member.user.tag()))
-                    .description(roles.iter().map(Mentionable::mention).format(" "))
+                "{mentions}, you have been sent to questioning by mod {}.",
+                moderator.mention()
+            ));
+            for (member, roles) in members.iter().zip(&roles) {
+                f.add_embed(|f| {
+                    f.title("Roles")
+                        .author(|f| f.icon_url(member.face()).name(member.user.tag()))
+                        .description(roles.iter().map(Mentionable::mention).format(" "))
+                });
+            }
+            f.add_embed(|f| {
+                f.title("Questioning")
+                    .field("Reason", reason, false)
+                    .field("Sent by", moderator.mention(), true)
+                    .field("Opened", format!("<t:{}:f>", opened_at.timestamp()), true)
             })
         })
         .await?;
+    _ = t(intro.pin(ctx).await);
+
+    let mut sessions = vec![];
+    for (member, roles) in members.iter().zip(&roles) {
+        sessions.push(questioning_sessions::ActiveModel {
+            id: ActiveValue::NotSet,
+            guild_id: ActiveValue::Set(guild.into()),
+            user_id: ActiveValue::Set(member.user.id.into()),
+            mod_id: ActiveValue::Set(moderator.into()),
+            reason: ActiveValue::Set(reason.to_owned()),
+            opened_at: ActiveValue::Set(opened_at),
+            channel_id: ActiveValue::Set(questioning_channel.id.into()),
+            status: ActiveValue::Set(questioning_sessions::Status::Open),
+            roles_json: ActiveValue::Set(Some(serde_json::to_string(
+                &roles.iter().map(|x| x.0).collect::<Vec<_>>(),
+            )?)),
+        });
+    }
+    QuestioningSessions::insert_many(sessions)
+        .exec(&data.db)
+        .await?;
+    data.questioning_bumps.watch(questioning_channel.id);
 
-    member.remove_roles(ctx, &roles).await?;
-    member.add_role(ctx, questioning_role).await?;
+    for (member, roles) in members.iter_mut().zip(&roles) {
+        member.remove_roles(ctx, roles).await?;
+        member.add_role(ctx, questioning_role).await?;
+    }
 
-    super::mod_log(
-        ctx.serenity_context(),
-        ctx.data(),
-        guild,
-        None,
-        format!(
-            "User {} sent to questioning by mod {}",
-            user.mention(),
-            ctx.author().mention()
-        ),
-    )
-    .await?;
-    ctx.send(|f| {
-        f.content("Sent user to questioning!")
-            .ephemeral(ctx.data().is_ephemeral)
-    })
-    .await?;
+    data.events
+        .publish(super::events::BotEvent::MemberQuestioned {
+            guild,
+            users: members.iter().map(|x| x.user.id).collect(),
+            moderator,
+            reason: reason.to_owned(),
+        });
+    Ok(already_questioned)
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, subcommands("sessions"), guild_only)]
+pub async fn screening(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
+
+/// List everyone currently in questioning and why they were sent there
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn sessions(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    let lines = QuestioningSessions::find()
+        .filter(questioning_sessions::Column::GuildId.eq(ids::DbGuildId::from(guild)))
+        .filter(questioning_sessions::Column::Status.eq(questioning_sessions::Status::Open))
+        .order_by_asc(questioning_sessions::Column::OpenedAt)
+        .all(&ctx.data().db)
+        .await?
+        .into_iter()
+        .map(|x| {
+            format!(
+                "- {} in {}, sent by {} <t:{}:R>: {}",
+                serenity::UserId::from(x.user_id).mention(),
+                serenity::ChannelId::from(x.channel_id).mention(),
+                serenity::UserId::from(x.mod_id).mention(),
+                x.opened_at.timestamp(),
+                x.reason
+            )
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        ctx.send(|f| {
+            f.content("Nobody is in questioning.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    for i in super::chunk_lines(&lines, MAX_MESSAGE_LENGTH) {
+        ctx.send(|f| {
+            f.content(i)
+                .allowed_mentions(|f| f.empty_parse())
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn oversize_files_are_skipped() {
+        assert_eq!(upload_groups(&[MB, 30 * MB, 2 * MB]), [vec![0, 2]]);
+    }
+
+    #[test]
+    fn files_past_the_upload_limit_move_to_the_next_batch() {
+        assert_eq!(
+            upload_groups(&[10 * MB, 10 * MB, 10 * MB, 5 * MB]),
+            [vec![0, 1], vec![2, 3]]
+        );
+    }
+
+    #[test]
+    fn file_count_is_capped() {
+        let groups = upload_groups(&[1; MAX_FILES_PER_MESSAGE + 2]);
+        assert_eq!(
+            groups.iter().map(Vec::len).collect::<Vec<_>>(),
+            [MAX_FILES_PER_MESSAGE, 2]
+        );
+    }
+
+    #[test]
+    fn join_alert_ids_round_trip() {
+        for action in JoinAction::ALL {
+            let id = action.custom_id(serenity::UserId(123));
+            assert!(id.len() <= 100);
+            assert_eq!(
+                JoinAction::parse(&id),
+                Some((action, serenity::UserId(123)))
+            );
+        }
+        assert_eq!(JoinAction::parse("joinAlert-kick-123"), None);
+        assert_eq!(JoinAction::parse("joinAlert-accept-abc"), None);
+        assert_eq!(JoinAction::parse("vote-accept-123"), None);
+    }
+
+    #[test]
+    fn group_channels_keep_the_first_users_suffix() {
+        let mut user = serenity::User::default();
+        user.id = serenity::UserId(123);
+        user.name = "Raid Account".to_owned();
+        user.discriminator = 0;
+        assert_eq!(group_channel_name(&user, 4), "raid-account-plus-4-123");
+    }
+}
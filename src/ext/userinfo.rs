@@ -0,0 +1,203 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::{Context, Error, PermissionTier};
+use crate::{check_tier, require_profile};
+use itertools::Itertools;
+use poise::serenity_prelude as serenity;
+use serenity::Mentionable;
+use tracing::instrument;
+
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+const MAX_EMBED_FIELD_LENGTH: usize = 1024;
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+const MAX_EMBED_TOTAL_LENGTH: usize = 6000;
+
+/// Questioning log threads for `user` in `channel`, as (thread, start time), newest first
+async fn questioning_logs(
+    ctx: Context<'_>,
+    guild: serenity::GuildId,
+    channel: serenity::ChannelId,
+    user: serenity::UserId,
+) -> Result<Vec<(serenity::ChannelId, i64)>, Error> {
+    let mut threads = guild
+        .get_active_threads(ctx)
+        .await?
+        .threads
+        .into_iter()
+        .filter(|x| x.parent_id == Some(channel))
+        .collect::<Vec<_>>();
+    let mut before = None;
+    loop {
+        let archived = channel
+            .get_archived_public_threads(ctx, before, None)
+            .await?;
+        before = archived
+            .threads
+            .last()
+            .and_then(|x| x.thread_metadata)
+            .and_then(|x| x.archive_timestamp)
+            .map(|x| x.unix_timestamp().try_into())
+            .transpose()?;
+        threads.extend(archived.threads);
+        if !archived.has_more || before.is_none() {
+            break;
+        }
+    }
+
+    // Log threads are named `{slug}-{user_id}-{start_time}`
+    let marker = format!("-{user}-");
+    Ok(threads
+        .into_iter()
+        .filter_map(|x| {
+            let (_, start) = x.name.rsplit_once(&marker)?;
+            Some((x.id, start.parse().ok()?))
+        })
+        .sorted_by_key(|x| std::cmp::Reverse(x.1))
+        .collect())
+}
+
+/// Show everything the bot knows about a user
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, context_menu_command = "User Info", guild_only)]
+pub async fn userinfo(ctx: Context<'_>, user: serenity::User) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    let server_data = require_profile!(ctx);
+    check_tier!(ctx, guild, PermissionTier::Mod, &server_data);
+
+    crate::defer!(ctx);
+
+    let member = guild.member(ctx, user.id).await.ok();
+    let mut fields = vec![(
+        "Account created".to_owned(),
+        format!("<t:{}:f>", user.created_at().unix_timestamp()),
+    )];
+    if let Some(member) = &member {
+        if let Some(x) = member.joined_at {
+            fields.push(("Joined".to_owned(), format!("<t:{}:f>", x.unix_timestamp())));
+        }
+        let status = if member
+            .roles
+            .contains(&serenity::RoleId::from(server_data.questioning_role))
+        {
+            "In questioning"
+        } else if member
+            .roles
+            .contains(&serenity::RoleId::from(server_data.member_role))
+        {
+            "Member"
+        } else {
+            "Unscreened"
+        };
+        fields.push(("Screening status".to_owned(), status.to_owned()));
+        let roles = member.roles.iter().map(Mentionable::mention).join(" ");
+        fields.push((
+            format!("Roles ({})", member.roles.len()),
+            super::chunk_lines(&[roles], MAX_EMBED_FIELD_LENGTH)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| "None".to_owned()),
+        ));
+    } else {
+        fields.push(("Screening status".to_owned(), "Not in server".to_owned()));
+    }
+    let avatar_blocked = super::image_filtering::blocked_match(ctx.data(), guild, &user.face())
+        .await
+        .is_some();
+    fields.push((
+        "Avatar".to_owned(),
+        if avatar_blocked {
+            "Matches a blocked image".to_owned()
+        } else {
+            "No blocked image match".to_owned()
+        },
+    ));
+
+    // (title, description) for each embed after the overview
+    let mut sections = vec![];
+    let logs = questioning_logs(
+        ctx,
+        guild,
+        serenity::ChannelId::from(server_data.mod_channel),
+        user.id,
+    )
+    .await?;
+    if !logs.is_empty() {
+        let lines = logs
+            .iter()
+            .map(|(thread, start)| format!("{} (<t:{start}:f>)", thread.mention()))
+            .collect::<Vec<_>>();
+        for (index, i) in super::chunk_lines(&lines, MAX_EMBED_DESCRIPTION_LENGTH)
+            .into_iter()
+            .enumerate()
+        {
+            let title = if index == 0 {
+                format!("Questioning history ({})", logs.len())
+            } else {
+                "Questioning history (continued)".to_owned()
+            };
+            sections.push((title, i));
+        }
+    }
+
+    // Split embeds across messages to stay under Discord's per-message limits
+    let overview_length = user.tag().len()
+        + fields
+            .iter()
+            .map(|x| x.0.chars().count() + x.1.chars().count())
+            .sum::<usize>();
+    let mut messages = vec![vec![]];
+    let (mut current_embeds, mut current_length) = (1, overview_length);
+    for i in sections {
+        let length = i.0.chars().count() + i.1.chars().count();
+        if current_embeds == MAX_EMBEDS_PER_MESSAGE
+            || current_length + length > MAX_EMBED_TOTAL_LENGTH
+        {
+            messages.push(vec![]);
+            (current_embeds, current_length) = (0, 0);
+        }
+        current_embeds += 1;
+        current_length += length;
+        if let Some(x) = messages.last_mut() {
+            x.push(i);
+        }
+    }
+
+    for (index, sections) in messages.into_iter().enumerate() {
+        ctx.send(|f| {
+            if index == 0 {
+                f.embed(|f| {
+                    f.author(|f| f.name(user.tag()).icon_url(user.face()))
+                        .thumbnail(user.face())
+                        .description(user.mention());
+                    for (name, value) in &fields {
+                        f.field(name, value, true);
+                    }
+                    f
+                });
+            }
+            for (title, description) in sections {
+                f.embed(|f| f.title(title).description(description));
+            }
+            f.ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,632 @@
+/*
+   Copyright 2023-present CyanoJ
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! POST moderation events to a server's own HTTP endpoint
+
+use super::events::{BotEvent, Subscription};
+use super::profile_setup::diff_profile;
+use super::{Context, Error};
+use crate::entities::{prelude::*, *};
+use crate::{check_admin, require_profile};
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use sea_orm::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use tokio::sync::mpsc;
+use tracing::{instrument, warn};
+
+pub const SIGNATURE_HEADER: &str = "X-Fedbot-Signature";
+const DELIVERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const RETRIES: u32 = 2;
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+const SECRET_BYTES: usize = 32;
+/// Deliveries a server can have waiting before new ones are dropped
+const QUEUE_LENGTH: usize = 64;
+/// How long a server's delivery task waits for another event before exiting
+const WORKER_IDLE: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Whether `ip` is reachable on the public internet, so admins can't point deliveries at the bot
+/// host's own network
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(x) => is_public_v4(x),
+        IpAddr::V6(x) => {
+            if let Some(y) = x.to_ipv4_mapped() {
+                return is_public_v4(y);
+            }
+            let segments = x.segments();
+            !(x.is_unspecified()
+                || x.is_loopback()
+                || x.is_multicast()
+                // Unique local, link-local and documentation ranges
+                || segments[0] & 0xfe00 == 0xfc00
+                || segments[0] & 0xffc0 == 0xfe80
+                || (segments[0] == 0x2001 && segments[1] == 0xdb8)
+                // NAT64 addresses wrap an IPv4 address
+                || (segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                    && !is_public_v4(Ipv4Addr::from(
+                        (u32::from(segments[6]) << 16) | u32::from(segments[7]),
+                    ))))
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || octets[0] == 0
+        // Shared address space, IETF protocol assignments, benchmarking and reserved ranges
+        || (octets[0] == 100 && octets[1] & 0xc0 == 64)
+        || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+        || (octets[0] == 198 && octets[1] & 0xfe == 18)
+        || octets[0] >= 240)
+}
+
+/// Resolves hosts to their public addresses only, so a DNS change after a URL was checked can't
+/// redirect deliveries inward
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|x| is_public(x.ip()))
+                .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(super::FedBotError::new(format!(
+                    "{} has no public address",
+                    name.as_str()
+                ))
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Client for deliveries, which only connects to public addresses and doesn't follow redirects
+pub fn client() -> Result<reqwest::Client, Error> {
+    Ok(reqwest::Client::builder()
+        .dns_resolver(std::sync::Arc::new(PublicResolver))
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(DELIVERY_TIMEOUT)
+        .build()?)
+}
+
+/// Why deliveries can't be sent to `url`, if they can't
+async fn url_problem(url: &str) -> Option<&'static str> {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return Some("The webhook needs a full `http://` or `https://` URL.");
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some("The webhook needs a full `http://` or `https://` URL.");
+    }
+    let Some(host) = url.host_str() else {
+        return Some("The webhook needs a full `http://` or `https://` URL.");
+    };
+    // IPv6 hosts keep their brackets
+    let addrs = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(x) => vec![x],
+        Err(_) => match tokio::net::lookup_host((host, 0)).await {
+            Ok(x) => x.map(|y| y.ip()).collect(),
+            Err(_) => return Some("The webhook's host couldn't be resolved."),
+        },
+    };
+    if addrs.is_empty() || !addrs.into_iter().all(is_public) {
+        return Some("The webhook must point at a public address.");
+    }
+    None
+}
+
+/// Body of each delivery; IDs are strings as they don't fit in a JavaScript number
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct Payload {
+    event: &'static str,
+    guild_id: String,
+    actor: Option<String>,
+    target: Option<String>,
+    reason: Option<String>,
+    timestamp: String,
+}
+
+impl Payload {
+    fn new(
+        event: &'static str,
+        guild: serenity::GuildId,
+        actor: Option<serenity::UserId>,
+        target: Option<serenity::UserId>,
+        reason: Option<String>,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            event,
+            guild_id: guild.to_string(),
+            actor: actor.map(|x| x.to_string()),
+            target: target.map(|x| x.to_string()),
+            reason,
+            timestamp: timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// The deliveries for `event`, one per affected user; the bot is the actor for automatic actions
+fn payloads(
+    event: &BotEvent,
+    timestamp: chrono::DateTime<chrono::Utc>,
+) -> Vec<(serenity::GuildId, Payload)> {
+    match event {
+        BotEvent::MessageFiltered {
+            guild,
+            user,
+            reason,
+        } => vec![(
+            *guild,
+            Payload::new(
+                "message_filtered",
+                *guild,
+                None,
+                Some(*user),
+                Some(reason.clone()),
+                timestamp,
+            ),
+        )],
+        BotEvent::ImageBlocked { guild, user, hash } => vec![(
+            *guild,
+            Payload::new(
+                "image_blocked",
+                *guild,
+                None,
+                Some(*user),
+                Some(format!("blocked image (hash: {hash})")),
+                timestamp,
+            ),
+        )],
+        BotEvent::MemberAccepted {
+            guild,
+            user,
+            moderator,
+        } => vec![(
+            *guild,
+            Payload::new(
+                "member_accepted",
+                *guild,
+                Some(*moderator),
+                Some(*user),
+                None,
+                timestamp,
+            ),
+        )],
+        BotEvent::MemberQuestioned {
+            guild,
+            users,
+            moderator,
+            reason,
+        } => users
+            .iter()
+            .map(|x| {
+                (
+                    *guild,
+                    Payload::new(
+                        "member_questioned",
+                        *guild,
+                        Some(*moderator),
+                        Some(*x),
+                        Some(reason.clone()),
+                        timestamp,
+                    ),
+                )
+            })
+            .collect(),
+        BotEvent::MemberKicked {
+            guild,
+            user,
+            reason,
+        } => vec![(
+            *guild,
+            Payload::new(
+                "member_kicked",
+                *guild,
+                None,
+                Some(*user),
+                Some(reason.clone()),
+                timestamp,
+            ),
+        )],
+        BotEvent::MemberReturned { .. }
+        | BotEvent::TriggerFired { .. }
+        | BotEvent::FilteringDegraded { .. }
+        | BotEvent::FilteringRecovered { .. } => vec![],
+    }
+}
+
+/// HMAC-SHA256 of `body` keyed with `secret`, as sent in [`SIGNATURE_HEADER`]
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut key = [0; BLOCK_SIZE];
+    if secret.len() > BLOCK_SIZE {
+        key[..32].copy_from_slice(&Sha256::digest(secret));
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+    let pad = |x: u8| key.map(|y| y ^ x);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(body)
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize();
+    format!("sha256={outer:x}")
+}
+
+fn generate_secret() -> String {
+    rand::thread_rng()
+        .gen::<[u8; SECRET_BYTES]>()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect()
+}
+
+/// POST `payload` to `url`, retrying failed connections and server errors up to [`RETRIES`] times
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    payload: &Payload,
+) -> Result<reqwest::StatusCode, Error> {
+    if let Some(x) = url_problem(url).await {
+        return Err(super::FedBotError::new(x).into());
+    }
+    let body = serde_json::to_vec(payload)?;
+    let signature = sign(secret.as_bytes(), &body);
+    let mut attempt = 0;
+    loop {
+        let result = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        match result {
+            Ok(x) if !x.status().is_server_error() => return Ok(x.status()),
+            _ if attempt < RETRIES => {
+                attempt += 1;
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+            }
+            Ok(x) => return Ok(x.status()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[derive(FromQueryResult)]
+struct WebhookData {
+    webhook_url: Option<String>,
+    webhook_secret: Option<String>,
+}
+
+async fn webhook_for(
+    db: &DatabaseConnection,
+    guild: serenity::GuildId,
+) -> Result<Option<(String, String)>, Error> {
+    Ok(Servers::find_by_id(guild)
+        .select_only()
+        .column(servers::Column::Id)
+        .column(servers::Column::WebhookUrl)
+        .column(servers::Column::WebhookSecret)
+        .into_model::<WebhookData>()
+        .one(db)
+        .await?
+        .and_then(|x| x.webhook_url.zip(x.webhook_secret)))
+}
+
+/// Deliver events to the servers that have a webhook, each through its own queue so a slow
+/// endpoint only holds up its own server's deliveries
+pub async fn deliver_events(
+    db: DatabaseConnection,
+    client: reqwest::Client,
+    mut events: Subscription,
+) {
+    let mut queues = HashMap::new();
+    while let Some(event) = events.next().await {
+        for (guild, payload) in payloads(&event, chrono::Utc::now()) {
+            let webhook = match webhook_for(&db, guild).await {
+                Ok(Some(x)) => x,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to look up webhook for guild '{}': {}", guild, e);
+                    continue;
+                }
+            };
+            let queue = queues
+                .entry(guild)
+                .or_insert_with(|| spawn_worker(client.clone(), guild));
+            match queue.try_send((webhook, payload)) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full((_, x))) => warn!(
+                    "Webhook queue for guild '{}' is full, dropping '{}'",
+                    guild, x.event
+                ),
+                // The guild's task went idle and exited
+                Err(mpsc::error::TrySendError::Closed(x)) => {
+                    let queue = spawn_worker(client.clone(), guild);
+                    _ = queue.try_send(x);
+                    queues.insert(guild, queue);
+                }
+            }
+        }
+    }
+}
+
+type Delivery = ((String, String), Payload);
+
+/// Deliver `guild`'s events in order until it has gone [`WORKER_IDLE`] without any
+fn spawn_worker(client: reqwest::Client, guild: serenity::GuildId) -> mpsc::Sender<Delivery> {
+    let (sender, mut receiver) = mpsc::channel::<Delivery>(QUEUE_LENGTH);
+    tokio::spawn(async move {
+        while let Ok(Some(x)) = tokio::time::timeout(WORKER_IDLE, receiver.recv()).await {
+            deliver_logged(&client, guild, x).await;
+        }
+        // Anything queued while timing out is still delivered
+        receiver.close();
+        while let Ok(x) = receiver.try_recv() {
+            deliver_logged(&client, guild, x).await;
+        }
+    });
+    sender
+}
+
+async fn deliver_logged(
+    client: &reqwest::Client,
+    guild: serenity::GuildId,
+    ((url, secret), payload): Delivery,
+) {
+    match deliver(client, &url, &secret, &payload).await {
+        Ok(x) if x.is_success() => {}
+        Ok(x) => warn!(
+            "Webhook for guild '{}' answered {} to '{}'",
+            guild, x, payload.event
+        ),
+        Err(e) => warn!(
+            "Failed to deliver '{}' to webhook for guild '{}': {}",
+            payload.event, guild, e
+        ),
+    }
+}
+
+/// Blank supercommand
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only, subcommands("set", "clear", "test"))]
+pub async fn webhook(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Send filter deletions, image blocks, accepts, questionings and kicks to an external URL
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "HTTP(S) URL to POST events to"] url: String,
+    #[description = "Secret to sign deliveries with (one is generated if left empty)"]
+    secret: Option<String>,
+) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    if let Some(x) = url_problem(&url).await {
+        ctx.send(|f| f.content(x).ephemeral(true)).await?;
+        return Ok(());
+    }
+    let secret = secret.unwrap_or_else(generate_secret);
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.webhook_url = ActiveValue::Set(Some(url.clone()));
+    model.webhook_secret = ActiveValue::Set(Some(secret.clone()));
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Webhook updated", changes).await?;
+    }
+
+    // Always ephemeral, as the reply contains the secret
+    ctx.send(|f| {
+        f.content(format!(
+            "Moderation events will be sent to <{url}>.\nEach delivery has a `{SIGNATURE_HEADER}` header of `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with this secret:\n||`{secret}`||"
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Stop sending moderation events to the webhook
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let old_profile = require_profile!(ctx);
+
+    let mut model: servers::ActiveModel = sea_orm::ActiveModelTrait::default();
+    model.id = ActiveValue::Unchanged(guild.into());
+    model.webhook_url = ActiveValue::Set(None);
+    model.webhook_secret = ActiveValue::Set(None);
+    let changes = diff_profile(Some(&old_profile), &model);
+    model.update(&ctx.data().db).await?;
+
+    if !changes.is_empty() {
+        super::config_audit(ctx, guild, "Webhook removed", changes).await?;
+    }
+
+    ctx.send(|f| {
+        f.content("Moderation events will no longer be sent to a webhook.")
+            .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Send a sample event to the webhook and show how it responded
+#[instrument(skip_all, err)]
+#[poise::command(slash_command, guild_only)]
+async fn test(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx
+        .guild_id()
+        .ok_or(super::FedBotError::new("command called outside server"))?;
+
+    check_admin!(ctx, guild);
+
+    let Some((url, secret)) = webhook_for(&ctx.data().db, guild).await? else {
+        ctx.send(|f| {
+            f.content("No webhook is set up. Use `/config webhook set` first.")
+                .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+        })
+        .await?;
+        return Ok(());
+    };
+
+    crate::defer!(ctx);
+    let payload = Payload::new(
+        "test",
+        guild,
+        Some(ctx.author().id),
+        None,
+        Some("test delivery".to_owned()),
+        chrono::Utc::now(),
+    );
+    let result = deliver(&ctx.data().webhook_client, &url, &secret, &payload).await;
+    ctx.send(|f| {
+        f.content(match result {
+            Ok(x) => format!("<{url}> responded with `{x}`."),
+            Err(e) => format!("Could not deliver to <{url}>: {e}"),
+        })
+        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn only_public_addresses_are_allowed() {
+        for i in [
+            "93.184.216.34",
+            "2606:2800:220:1:248:1893:25c8:1946",
+            "64:ff9b::5db8:d822",
+        ] {
+            assert!(is_public(i.parse().unwrap()), "{i}");
+        }
+        for i in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public(i.parse().unwrap()), "{i}");
+        }
+    }
+
+    #[tokio::test]
+    async fn internal_urls_are_refused() {
+        assert!(url_problem("http://127.0.0.1:8080/hook").await.is_some());
+        assert!(url_problem("http://[::1]/hook").await.is_some());
+        assert!(url_problem("http://localhost/hook").await.is_some());
+        assert!(url_problem("ftp://93.184.216.34/hook").await.is_some());
+        assert_eq!(url_problem("https://93.184.216.34/hook").await, None);
+    }
+
+    #[test]
+    fn signatures_match_rfc_4231() {
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Keys longer than a block are hashed first
+        assert_eq!(
+            sign(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            "sha256=60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn questioning_several_users_sends_one_payload_each() {
+        let timestamp = chrono::Utc.timestamp_opt(1_688_000_000, 0).unwrap();
+        let sent = payloads(
+            &BotEvent::MemberQuestioned {
+                guild: serenity::GuildId(1),
+                users: vec![serenity::UserId(2), serenity::UserId(3)],
+                moderator: serenity::UserId(4),
+                reason: "raid".to_owned(),
+            },
+            timestamp,
+        );
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&sent[1].1).unwrap(),
+            r#"{"event":"member_questioned","guild_id":"1","actor":"4","target":"3","reason":"raid","timestamp":"2023-06-29T00:53:20Z"}"#
+        );
+        assert!(payloads(
+            &BotEvent::TriggerFired {
+                guild: serenity::GuildId(1),
+                channel: serenity::ChannelId(2),
+                name: "rules".to_owned(),
+            },
+            timestamp
+        )
+        .is_empty());
+    }
+}
@@ -14,20 +14,16 @@
    limitations under the License.
 */
 
-#![feature(
-    async_closure,
-    is_some_and,
-    fs_try_exists,
-    path_file_prefix,
-    iter_array_chunks,
-    hash_drain_filter
-)]
+#![feature(async_closure, is_some_and, fs_try_exists, path_file_prefix)]
 #![allow(clippy::wildcard_imports)]
 
 use dunce::canonicalize;
 use entities::prelude::*;
+use ext::filter_followups::FilterFollowups;
+use ext::questioning_bumps::QuestioningBumps;
 use ext::TriggerCooldown;
 use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache};
+use migration::{Migrator, MigratorTrait};
 use poise::serenity_prelude as serenity;
 use poise::Event;
 use poise::PrefixFrameworkOptions;
@@ -35,7 +31,7 @@ use reqwest::Client;
 use reqwest_middleware::ClientBuilder;
 use sea_orm::*;
 use tokio::sync::RwLock;
-use tracing::{error, instrument, log::LevelFilter, Level};
+use tracing::{debug, error, instrument, log::LevelFilter, warn, Level};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 
 use std::collections::HashMap;
@@ -52,6 +48,7 @@ use self::ext::{
 const EPHEMERAL_MESSAGES: bool = true;
 const DB_FILE: &str = "test.db";
 const DB_MEM_PAGES: isize = 12_500; // Pages are normally 4096 bytes each
+const MESSAGE_CACHE_SIZE: usize = 100; // Per channel
 
 #[instrument(skip_all, err)]
 async fn dispatch_events<'a>(
@@ -61,32 +58,84 @@ async fn dispatch_events<'a>(
     data: &'a Data,
 ) -> Result<(), Error> {
     let reference = (ctx, event, system, data);
+    if let Event::GuildCreate {
+        guild,
+        is_new: true,
+    } = event
+    {
+        if !data.allowlist.allows(guild.id) {
+            return ext::allowlist::refuse_guild(ctx, guild, data.bot_id).await;
+        }
+    }
+    if ext::allowlist::event_guild(event).is_some_and(|x| !data.allowlist.allows(x)) {
+        return Ok(());
+    }
     match event {
-        Event::Message { new_message } => {
-            if !new_message.is_own(ctx) {
-                if let Some(guild) = new_message.guild_id {
-                    let _ = ext::profanity_checks::filter_message(
+        Event::Message { new_message } if new_message.author.id != data.bot_id => {
+            if let Some(guild) = new_message.guild_id {
+                let features = data.features_for(guild);
+                if ext::message_limits::enforce_limits(
+                    new_message.into(),
+                    guild,
+                    new_message.channel_id,
+                    new_message.id,
+                    &new_message.author,
+                    reference,
+                )
+                .await?
+                {
+                    return Ok(());
+                }
+                let filter = if features.profanity_filter
+                    && ext::profanity_checks::filter_message(
                         new_message,
+                        guild,
                         new_message.channel_id,
                         new_message.id,
                         &new_message.author,
+                        ext::MessageOrigin::Sent,
                         reference,
                     )
                     .await?
-                        || ext::image_filtering::filter_message(
-                            new_message,
-                            guild,
-                            new_message.channel_id,
-                            new_message.id,
-                            &new_message.author,
-                            reference,
-                        )
-                        .await?
-                        || ext::triggers::fire_triggers(new_message, guild, reference).await?;
+                {
+                    Some("profanity")
+                } else if features.image_filter
+                    && ext::image_filtering::filter_message(
+                        new_message,
+                        guild,
+                        new_message.channel_id,
+                        new_message.id,
+                        &new_message.author,
+                        ext::MessageOrigin::Sent,
+                        reference,
+                    )
+                    .await?
+                {
+                    Some("image")
+                } else {
+                    None
+                };
+                if features.screening {
+                    ext::first_messages::check_message(new_message, guild, filter, reference)
+                        .await?;
+                }
+                if filter.is_none() {
+                    _ = ext::t(
+                        ext::questioning_bumps::message_sent(new_message, guild, reference).await,
+                    );
                 }
+                let _ = filter.is_some()
+                    || ext::filter_followups::answer_followup(new_message, guild, reference)
+                        .await?
+                    || (features.triggers
+                        && ext::triggers::fire_triggers(new_message, guild, reference).await?);
             }
         }
-        Event::MessageUpdate { event, .. } => {
+        Event::MessageUpdate {
+            old_if_available,
+            event,
+            ..
+        } => {
             // Message event may be partial so we may have to ask for more info
             let author: &serenity::User;
             let author_guard: serenity::User;
@@ -97,32 +146,52 @@ async fn dispatch_events<'a>(
                 author = &author_guard;
             }
 
-            if author.id != ctx.cache.current_user_id() {
+            if author.id != data.bot_id {
                 if let Some(guild) = event.guild_id {
-                    let _ = ext::profanity_checks::filter_message(
-                        event,
+                    ext::profanity_reviews::message_edited(event.id, reference).await?;
+                    let features = data.features_for(guild);
+                    let origin = ext::MessageOrigin::Edited {
+                        before: old_if_available.as_ref().map(|x| x.content.as_str()),
+                        after: event.content.as_deref(),
+                    };
+                    let _ = ext::message_limits::enforce_limits(
+                        event.into(),
+                        guild,
                         event.channel_id,
                         event.id,
                         author,
                         reference,
                     )
                     .await?
-                        || ext::image_filtering::filter_message(
-                            event,
-                            guild,
-                            event.channel_id,
-                            event.id,
-                            author,
-                            reference,
-                        )
-                        .await?;
+                        || (features.profanity_filter
+                            && ext::profanity_checks::filter_message(
+                                event,
+                                guild,
+                                event.channel_id,
+                                event.id,
+                                author,
+                                origin,
+                                reference,
+                            )
+                            .await?)
+                        || (features.image_filter
+                            && ext::image_filtering::filter_message(
+                                event,
+                                guild,
+                                event.channel_id,
+                                event.id,
+                                author,
+                                origin,
+                                reference,
+                            )
+                            .await?);
                 }
             }
         }
         Event::GuildStickersUpdate {
             guild_id,
             current_state,
-        } => {
+        } if data.features_for(*guild_id).image_filter => {
             ext::image_filtering::filter_stickers(
                 current_state
                     .clone()
@@ -136,7 +205,7 @@ async fn dispatch_events<'a>(
         Event::GuildEmojisUpdate {
             guild_id,
             current_state,
-        } => {
+        } if data.features_for(*guild_id).image_filter => {
             ext::image_filtering::filter_emojis(
                 current_state
                     .clone()
@@ -150,38 +219,137 @@ async fn dispatch_events<'a>(
         Event::GuildCreate { guild, is_new } => {
             prompt_guild_setup(guild, *is_new, reference).await?;
             // Fires on startup too
+            ext::profile_setup::add_guild_settings(guild, *is_new, reference).await?;
+            ext::config_health::validate_guild(
+                reference.0,
+                &reference.3.db,
+                &reference.3.config_health,
+                reference.3.bot_id,
+                guild.id,
+            )
+            .await?;
             ext::triggers::add_guild_triggers(guild, *is_new, reference).await?;
             if !*is_new {
                 ext::entry_modal::display_entry_modal(reference.0, reference.3, guild.id).await?;
             }
         }
         Event::GuildMemberAddition { new_member } => {
-            ext::user_screening::alert_new_user(new_member, new_member.guild_id, reference).await?;
-            ext::image_filtering::filter_member(new_member, new_member.guild_id, reference).await?;
+            let features = data.features_for(new_member.guild_id);
+            if features.screening
+                && !ext::member_history::restore_returning_member(
+                    new_member,
+                    new_member.guild_id,
+                    reference,
+                )
+                .await?
+            {
+                ext::user_screening::alert_new_user(new_member, new_member.guild_id, reference)
+                    .await?;
+            }
+            if features.image_filter {
+                ext::image_filtering::filter_member(new_member, new_member.guild_id, reference)
+                    .await?;
+            }
+        }
+        Event::GuildMemberRemoval {
+            guild_id,
+            member_data_if_available: Some(member),
+            ..
+        } => {
+            ext::member_history::record_departure(*guild_id, member, reference).await?;
         }
-        Event::GuildMemberUpdate { new, .. } => {
-            ext::image_filtering::filter_member(new, new.guild_id, reference).await?;
+        Event::GuildMemberUpdate {
+            old_if_available,
+            new,
+        } => {
+            let features = data.features_for(new.guild_id);
+            if features.screening {
+                ext::first_messages::member_updated(old_if_available.as_ref(), new, reference)
+                    .await?;
+            }
+            if features.image_filter {
+                ext::image_filtering::filter_member(new, new.guild_id, reference).await?;
+            }
         }
         Event::GuildUpdate {
-            new_but_incomplete, ..
+            old_data_if_available,
+            new_but_incomplete,
         } => {
-            ext::image_filtering::filter_server(
+            // The cache has already been updated by now, so the old name comes from the event
+            ext::profanity_checks::filter_server_name(
+                old_data_if_available.as_ref().map(|x| x.name.as_str()),
                 new_but_incomplete,
-                new_but_incomplete.id,
                 reference,
             )
             .await?;
+            if data.features_for(new_but_incomplete.id).image_filter {
+                ext::image_filtering::filter_server(
+                    new_but_incomplete,
+                    new_but_incomplete.id,
+                    reference,
+                )
+                .await?;
+            }
         }
         Event::Ready { .. } => {
             set_db_pragmas(reference).await?;
+            ext::entry_modal::migrate_entry_modals(&reference.3.db).await?;
+            ext::polls::reschedule_polls(reference.0.http.clone(), &reference.3.db).await?;
             tokio::spawn(clean_trigger_cooldowns(
                 reference.3.trigger_cooldown.clone(),
             ));
         }
+        Event::MessageDelete {
+            channel_id,
+            deleted_message_id,
+            guild_id: Some(guild),
+        } => {
+            ext::profanity_reviews::messages_deleted(&[*deleted_message_id], reference).await?;
+            ext::entry_modal::messages_deleted(
+                *guild,
+                *channel_id,
+                &[*deleted_message_id],
+                reference,
+            )
+            .await?;
+        }
+        Event::MessageDeleteBulk {
+            channel_id,
+            multiple_deleted_messages_ids,
+            guild_id: Some(guild),
+        } => {
+            ext::profanity_reviews::messages_deleted(multiple_deleted_messages_ids, reference)
+                .await?;
+            ext::entry_modal::messages_deleted(
+                *guild,
+                *channel_id,
+                multiple_deleted_messages_ids,
+                reference,
+            )
+            .await?;
+        }
+        Event::ChannelDelete { channel } => {
+            ext::user_screening::questioning_channel_deleted(channel, reference).await?;
+        }
         Event::ReactionAdd { add_reaction } => {
-            if let Some(guild) = add_reaction.guild_id {
+            if let Some(guild) = add_reaction
+                .guild_id
+                .filter(|x| data.features_for(*x).image_filter)
+            {
                 ext::image_filtering::filter_reaction(add_reaction, guild, reference).await?;
             }
+            ext::polls::record_vote(add_reaction, true, reference).await?;
+        }
+        Event::ReactionRemove { removed_reaction } => {
+            ext::polls::record_vote(removed_reaction, false, reference).await?;
+        }
+        Event::InteractionCreate {
+            interaction: serenity::Interaction::MessageComponent(interaction),
+        } => {
+            ext::polls::send_vote_log(interaction, reference).await?;
+            ext::user_screening::handle_join_alert(interaction, reference).await?;
+            ext::profanity_reviews::handle_review(interaction, reference).await?;
+            ext::false_positives::handle_buttons(interaction, reference).await?;
         }
         _ => (),
     }
@@ -189,11 +357,47 @@ async fn dispatch_events<'a>(
 }
 
 const CLEANING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+const CLEANING_WARN_THRESHOLD: usize = 10000; // More than this per cycle suggests a leak or unusual load
 
 async fn clean_trigger_cooldowns(cooldown: TriggerCooldown) {
     loop {
         tokio::time::sleep(CLEANING_INTERVAL).await;
-        cooldown.clean().await;
+        let count = cooldown.clean();
+        debug!("Cleaned {} expired trigger cooldowns", count);
+        if count > CLEANING_WARN_THRESHOLD {
+            warn!(
+                "Cleaned {} expired trigger cooldowns in one cycle, which is more than expected",
+                count
+            );
+        }
+    }
+}
+
+async fn clean_filter_followups(followups: FilterFollowups) {
+    loop {
+        tokio::time::sleep(CLEANING_INTERVAL).await;
+        let count = followups.clean();
+        debug!("Cleaned {} expired filter follow-up watches", count);
+        if count > CLEANING_WARN_THRESHOLD {
+            warn!(
+                "Cleaned {} expired filter follow-up watches in one cycle, which is more than expected",
+                count
+            );
+        }
+    }
+}
+
+async fn clean_questioning_bumps(bumps: QuestioningBumps) {
+    loop {
+        tokio::time::sleep(CLEANING_INTERVAL).await;
+        let count = bumps.clean();
+        debug!("Cleaned {} stale questioning waits", count);
+        if count > CLEANING_WARN_THRESHOLD {
+            warn!(
+                "Cleaned {} stale questioning waits in one cycle, which is more than expected",
+                count
+            );
+        }
     }
 }
 
@@ -208,7 +412,7 @@ async fn prompt_guild_setup(
         return Ok(());
     }
 
-    get_alert_channel(guild, reference).await?.send_message(reference.0, |f| f.content(
+    get_alert_channel(guild, reference.3.bot_id).await?.send_message(reference.0, |f| f.content(
         concat!(
         "Thank you for adding FedBot to your server!\n",
         "To set up FedBot, please run `/profiles init`. (NOTE: you must have Administrator permissions to run this command.)\n",
@@ -258,7 +462,7 @@ async fn on_error(err: FrameworkError<'_>) {
             _ = t(ctx
                 .send(|f| {
                     f.content("Sorry, an error occured.")
-                        .ephemeral(ctx.data().is_ephemeral)
+                        .ephemeral(ctx.data().is_ephemeral_for(ctx.guild_id()))
                 })
                 .await);
         }
@@ -275,13 +479,37 @@ async fn on_error(err: FrameworkError<'_>) {
     }
 }
 
-#[tokio::main]
-#[instrument(skip_all, err)]
-async fn main() -> Result<(), Error> {
-    let exe_path = canonicalize(Path::new(&std::env::current_exe()?))?;
-    ext::profanity_checks::init_statics();
+enum Subcommand {
+    Run,
+    Migrate,
+    RegisterCommands(Option<serenity::GuildId>),
+    Check,
+}
 
-    let (non_blocking, _guard) = tracing_appender::non_blocking(RollingFileAppender::new(
+const USAGE: &str = "usage: fedbot [run | migrate | register-commands [--guild <id>] | check]";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Subcommand, Error> {
+    let subcommand = match args.next().as_deref() {
+        None | Some("run") => Subcommand::Run,
+        Some("migrate") => Subcommand::Migrate,
+        Some("register-commands") => match args.next().as_deref() {
+            None => Subcommand::RegisterCommands(None),
+            Some("--guild") => Subcommand::RegisterCommands(Some(serenity::GuildId(
+                args.next().ok_or(FedBotError::new(USAGE))?.parse()?,
+            ))),
+            Some(_) => return Err(FedBotError::new(USAGE).into()),
+        },
+        Some("check") => Subcommand::Check,
+        Some(_) => return Err(FedBotError::new(USAGE).into()),
+    };
+    if args.next().is_some() {
+        return Err(FedBotError::new(USAGE).into());
+    }
+    Ok(subcommand)
+}
+
+fn init_logging(exe_path: &Path) -> Result<tracing_appender::non_blocking::WorkerGuard, Error> {
+    let (non_blocking, guard) = tracing_appender::non_blocking(RollingFileAppender::new(
         Rotation::NEVER,
         exe_path
             .parent()
@@ -300,15 +528,21 @@ async fn main() -> Result<(), Error> {
         .with_writer(non_blocking)
         .with_ansi(false)
         .init();
+    Ok(guard)
+}
 
-    dotenv::from_path(&exe_path.with_file_name(".env"))?;
-
-    let db_path = exe_path
+fn db_path(exe_path: &Path) -> Result<String, Error> {
+    Ok(exe_path
         .with_file_name(DB_FILE)
         .as_os_str()
         .to_str()
         .ok_or(FedBotError::new("cannot locate exe file"))?
-        .to_owned();
+        .to_owned())
+}
+
+/// Connection options for the database next to the exe, creating it if it doesn't exist
+async fn db_options(exe_path: &Path) -> Result<ConnectOptions, Error> {
+    let db_path = db_path(exe_path)?;
 
     let mut db_options = ConnectOptions::new(format!("sqlite://{}?mode=rwc", &db_path));
     db_options.sqlx_logging_level(LevelFilter::Debug);
@@ -316,71 +550,363 @@ async fn main() -> Result<(), Error> {
     if !fs::try_exists(&db_path)? {
         let bootstrap_db = Database::connect(db_options.clone()).await?;
         // Add other tables as they are added to SCHEMA
-        let tables = vec![DbBackend::Sqlite
-            .build(&Schema::new(DbBackend::Sqlite).create_table_from_entity(Servers))];
+        let schema = Schema::new(DbBackend::Sqlite);
+        let tables = vec![
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(Servers)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(ModSubscriptions)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(Polls)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(PollVotes)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(UserPreferences)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(BlockedHashes)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(QuestioningSessions)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(ApiTokens)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(ScreeningSubmissions)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(MemberHistory)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(GuildModRoles)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(GuildLogChannels)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(HeldNotices)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(FirstMessages)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(ProfanityReviews)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(AllowedGuilds)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(WhitelistedHashes)),
+            DbBackend::Sqlite.build(&schema.create_table_from_entity(TriggerPresets)),
+        ];
         for i in tables {
             bootstrap_db.query_one(i).await?;
         }
+        // The tables above already match the latest schema, so nothing is left to migrate
+        Migrator::install(&bootstrap_db).await?;
+        let now = chrono::Utc::now().timestamp();
+        for i in Migrator::migrations() {
+            bootstrap_db
+                .execute(Statement::from_sql_and_values(
+                    DbBackend::Sqlite,
+                    r"INSERT INTO seaql_migrations (version, applied_at) VALUES (?, ?)",
+                    [i.name().into(), now.into()],
+                ))
+                .await?;
+        }
         drop(bootstrap_db);
     }
+    Ok(db_options)
+}
+
+fn token() -> Result<String, Error> {
+    Ok(std::env::var("DISCORD_FEDBOT_TOKEN")?)
+}
+
+fn commands() -> Vec<poise::Command<Data, Error>> {
+    vec![
+        ext::assorted::test(),
+        ext::assorted::timestamp(),
+        ext::timezones::timezone(),
+        ext::assorted::purgeto(),
+        ext::assorted::pirate_emoji(),
+        ext::profile_setup::profile(),
+        ext::profile_setup::config(),
+        ext::profile_setup::serverinfo(),
+        ext::userinfo::userinfo(),
+        ext::user_screening::accept(),
+        ext::user_screening::return_(),
+        ext::user_screening::question(),
+        ext::user_screening::question_menu(),
+        ext::user_screening::question_group(),
+        ext::user_screening::purge_questioning(),
+        ext::user_screening::screening(),
+        ext::entry_modal::send_entry_form(),
+        ext::image_filtering::block_msg(),
+        ext::image_filtering::check_msg(),
+        ext::image_filtering::block_pfp(),
+        ext::image_filtering::block_server(),
+        ext::image_filtering::blocklist(),
+        ext::message_limits::limits(),
+        ext::assorted::move_(),
+        ext::assorted::minesweeper(),
+        ext::polls::poll(),
+        ext::polls::close_poll_menu(),
+        ext::assorted::invite(),
+        ext::assorted::whoami(),
+        ext::triggers::trigger(),
+        ext::triggers::triggers(),
+        ext::backup::botbackup(),
+        ext::notifications::notifyme(),
+        ext::api::apitoken(),
+        ext::profanity_checks::filter(),
+        ext::owner::owner(),
+        ext::filter_stats::botstats(),
+        ext::allowlist::allowguild(),
+    ]
+}
+
+async fn register_commands(
+    http: impl AsRef<serenity::Http>,
+    commands: &[poise::Command<Data, Error>],
+    guild: Option<serenity::GuildId>,
+) -> Result<(), Error> {
+    if let Some(x) = guild {
+        poise::builtins::register_in_guild(http, commands, x).await?;
+    } else {
+        poise::builtins::register_globally(http, commands).await?;
+    }
+    Ok(())
+}
 
+async fn run(db_options: ConnectOptions) -> Result<(), Error> {
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![
-                ext::assorted::test(),
-                ext::assorted::timestamp(),
-                ext::assorted::purgeto(),
-                ext::assorted::pirate_emoji(),
-                ext::profile_setup::profile(),
-                ext::user_screening::accept(),
-                ext::user_screening::return_(),
-                ext::user_screening::question(),
-                ext::user_screening::purge_questioning(),
-                ext::image_filtering::block_msg(),
-                ext::image_filtering::block_pfp(),
-                ext::image_filtering::block_server(),
-                ext::assorted::move_(),
-                ext::assorted::minesweeper(),
-                ext::assorted::poll(),
-                ext::assorted::invite(),
-                ext::triggers::trigger(),
-                ext::triggers::triggers(),
-            ],
+            commands: commands(),
             event_handler: |ctx, event, system, data| {
                 Box::pin(async move { dispatch_events(ctx, event, system, data).await })
             },
             on_error: |err| Box::pin(async move { on_error(err).await }),
+            // Commands don't pass through the event handler, so unlisted servers are refused here
+            command_check: Some(|ctx| {
+                Box::pin(async move {
+                    Ok(match ctx.guild_id() {
+                        Some(x) => ctx.data().allowlist.allows(x),
+                        None => true,
+                    })
+                })
+            }),
             prefix_options: PrefixFrameworkOptions {
                 prefix: None,
                 ..Default::default()
             },
             ..Default::default()
         })
-        .token(std::env::var("DISCORD_FEDBOT_TOKEN")?)
+        .token(token()?)
         .intents(serenity::GatewayIntents::all())
+        // Keep recent messages around so filtered edits can be reported with their original content
+        .client_settings(|f| f.cache_settings(|f| f.max_messages(MESSAGE_CACHE_SIZE)))
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
-                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                register_commands(ctx, &framework.options().commands, None).await?;
+                let db = Database::connect(db_options).await?;
+                // Setup only runs once, unlike Ready which fires again on reconnect
+                tokio::spawn(ext::api::serve(db.clone()));
+                let events = ext::events::EventBus::default();
+                let config_health = ext::config_health::ConfigHealth::default();
+                tokio::spawn(ext::events::log_notices(
+                    ctx.clone(),
+                    db.clone(),
+                    config_health.clone(),
+                    events.subscribe(),
+                ));
+                let reqwest = ClientBuilder::new(Client::new())
+                    .with(Cache(HttpCache {
+                        mode: CacheMode::Default,
+                        manager: CACacheManager::default(),
+                        options: None,
+                    }))
+                    .build();
+                let hasher = image_hasher::HasherConfig::new()
+                    .hash_size(ext::HASH_BYTES.into(), ext::HASH_BYTES.into())
+                    .to_hasher();
+                let safe_images = ext::image_filtering::load_safe_images(&reqwest, &hasher).await;
+                let webhook_client = ext::webhooks::client()?;
+                tokio::spawn(ext::webhooks::deliver_events(
+                    db.clone(),
+                    webhook_client.clone(),
+                    events.subscribe(),
+                ));
+                let allowlist = ext::allowlist::Allowlist::load(&db).await?;
+                tokio::spawn(ext::allowlist::enforce(ctx.clone(), allowlist.clone()));
+                tokio::spawn(ext::quiet_hours::schedule_digests(ctx.clone(), db.clone()));
+                let questioning_bumps = QuestioningBumps::load(&db).await?;
+                tokio::spawn(clean_questioning_bumps(questioning_bumps.clone()));
+                let filter_followups = FilterFollowups::default();
+                tokio::spawn(clean_filter_followups(filter_followups.clone()));
+                let filter_stats = ext::filter_stats::FilterStats::default();
+                tokio::spawn(ext::filter_stats::schedule_stats_log(filter_stats.clone()));
+                tokio::spawn(ext::config_health::schedule_validation(
+                    ctx.clone(),
+                    db.clone(),
+                    config_health.clone(),
+                    ctx.cache.current_user().id,
+                ));
+                tokio::spawn(ext::hash_expiry::schedule_cleanup(
+                    ctx.clone(),
+                    db.clone(),
+                    config_health.clone(),
+                ));
+                tokio::spawn(ext::backup::schedule_backups(db.clone()));
                 Ok(Data {
-                    login_time: None,
+                    bot_id: ctx.cache.current_user().id,
                     is_ephemeral: EPHEMERAL_MESSAGES,
                     // users: HashMap::new(),
-                    db: Database::connect(db_options).await?,
-                    reqwest: ClientBuilder::new(Client::new())
-                        .with(Cache(HttpCache {
-                            mode: CacheMode::Default,
-                            manager: CACacheManager::default(),
-                            options: None,
-                        }))
-                        .build(),
-                    hasher: image_hasher::HasherConfig::new()
-                        .hash_size(ext::HASH_BYTES.into(), ext::HASH_BYTES.into())
-                        .to_hasher(),
+                    db,
+                    reqwest,
+                    webhook_client,
+                    hasher,
                     triggers: RwLock::new(HashMap::new()),
                     trigger_cooldown: TriggerCooldown::default(),
+                    ephemeral_overrides: std::sync::RwLock::new(HashMap::new()),
+                    linked_image_filters: std::sync::RwLock::new(HashMap::new()),
+                    message_limits: std::sync::RwLock::new(HashMap::new()),
+                    feature_toggles: std::sync::RwLock::new(HashMap::new()),
+                    first_message_thresholds: std::sync::RwLock::new(HashMap::new()),
+                    nsfw_policies: std::sync::RwLock::new(HashMap::new()),
+                    nsfw_channels: ext::image_filtering::NsfwChannels::default(),
+                    trusted_members: ext::first_messages::TrustedMembers::default(),
+                    user_timezones: std::sync::RwLock::new(HashMap::new()),
+                    safe_images: RwLock::new(safe_images),
+                    hash_matches: ext::image_filtering::HashMatchTracker::default(),
+                    mod_notifier: ext::notifications::ModNotifier::default(),
+                    config_health,
+                    entry_forms: ext::entry_modal::EntryForms::default(),
+                    filter_followups,
+                    questioning_bumps,
+                    filter_stats,
+                    circuit_breakers: ext::circuit_breaker::CircuitBreakers::default(),
+                    events,
+                    allowlist,
                 })
             })
         });
     framework.run().await?;
     Ok(())
 }
+
+async fn migrate(db_options: ConnectOptions) -> Result<(), Error> {
+    let db = Database::connect(db_options).await?;
+    // Migrations don't expose their names, so compare what's recorded before and after
+    let before = Migrator::get_migration_models(&db)
+        .await?
+        .into_iter()
+        .map(|x| x.version)
+        .collect::<Vec<_>>();
+    Migrator::up(&db, None).await?;
+    let new = Migrator::get_migration_models(&db)
+        .await?
+        .into_iter()
+        .map(|x| x.version)
+        .filter(|x| !before.contains(x))
+        .collect::<Vec<_>>();
+    if new.is_empty() {
+        println!("Database is up to date.");
+    }
+    for i in new {
+        println!("Applied migration {i}");
+    }
+    Ok(())
+}
+
+/// Register slash commands over HTTP without connecting to the gateway
+async fn register_commands_only(guild: Option<serenity::GuildId>) -> Result<(), Error> {
+    let http = serenity::Http::new(&token()?);
+    http.set_application_id(http.get_current_application_info().await?.id.0);
+    register_commands(&http, &commands(), guild).await?;
+    if let Some(x) = guild {
+        println!("Registered commands in guild {x}.");
+    } else {
+        println!("Registered commands globally (this may take a while to propagate).");
+    }
+    Ok(())
+}
+
+/// Discord tokens are three dot-separated base64 segments, the first encoding the bot's user ID
+fn check_token_format(token: &str) -> Result<(), String> {
+    use base64::Engine;
+
+    let segments = token.split('.').collect::<Vec<_>>();
+    if segments.len() != 3 || segments.iter().any(|x| x.is_empty()) {
+        return Err("expected three dot-separated segments".to_owned());
+    }
+    let id = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(segments[0].trim_end_matches('='))
+        .ok()
+        .and_then(|x| String::from_utf8(x).ok())
+        .filter(|x| !x.is_empty() && x.chars().all(|y| y.is_ascii_digit()));
+    if id.is_none() {
+        return Err("first segment does not encode a user ID".to_owned());
+    }
+    Ok(())
+}
+
+const WORD_LISTS: [&str; 4] = [
+    "banned_chars.txt",
+    "replace_chars.txt",
+    "allowlist.txt",
+    "blocklist.txt",
+];
+
+async fn check(exe_path: &Path) -> bool {
+    let mut ok = true;
+
+    match token()
+        .map_err(|e| e.to_string())
+        .and_then(|x| check_token_format(&x))
+    {
+        Ok(()) => println!("[ok] Discord token"),
+        Err(e) => {
+            ok = false;
+            println!("[FAIL] Discord token: {e}");
+        }
+    }
+
+    // Opened read-only so checking never creates or changes the database
+    let db_check = async {
+        let db_path = db_path(exe_path)?;
+        if !exe_path.with_file_name(DB_FILE).is_file() {
+            return Err(FedBotError::new(format!("{db_path} not found")).into());
+        }
+        Database::connect(format!("sqlite://{db_path}?mode=ro"))
+            .await?
+            .execute(Statement::from_string(
+                DbBackend::Sqlite,
+                r"SELECT 1".to_owned(),
+            ))
+            .await?;
+        Ok::<(), Error>(())
+    };
+    match db_check.await {
+        Ok(()) => println!("[ok] Database connection"),
+        Err(e) => {
+            ok = false;
+            println!("[FAIL] Database connection: {e}");
+        }
+    }
+
+    // The profanity filter falls back to its defaults when these are missing
+    for i in WORD_LISTS {
+        if exe_path.with_file_name(i).is_file() {
+            println!("[ok] Word list {i}");
+        } else {
+            println!("[warn] Word list {i} not found");
+        }
+    }
+
+    ok
+}
+
+#[tokio::main]
+#[instrument(skip_all, err)]
+async fn main() -> Result<(), Error> {
+    let subcommand = parse_args(std::env::args().skip(1))?;
+
+    let exe_path = canonicalize(Path::new(&std::env::current_exe()?))?;
+    let _guard = init_logging(&exe_path)?;
+
+    let env = dotenv::from_path(exe_path.with_file_name(".env"));
+
+    if let Subcommand::Check = subcommand {
+        if let Err(e) = env {
+            println!("[FAIL] Loading .env: {e}");
+        }
+        if !check(&exe_path).await {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    env?;
+
+    match subcommand {
+        Subcommand::Run => {
+            ext::profanity_checks::init_statics();
+            run(db_options(&exe_path).await?).await
+        }
+        Subcommand::Migrate => migrate(db_options(&exe_path).await?).await,
+        Subcommand::RegisterCommands(guild) => register_commands_only(guild).await,
+        Subcommand::Check => Ok(()),
+    }
+}